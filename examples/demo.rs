@@ -8,7 +8,7 @@ use clap::{Parser, Subcommand};
 use matc::{
     certmanager::{self, FileCertManager},
     clusters::{self, defs::{CLUSTER_DOOR_LOCK_CMD_ID_GETUSER, CLUSTER_ID_DOOR_LOCK}},
-    controller, discover, messages, onboarding, tlv, transport,
+    controller, devman, discover, messages, onboarding, tlv, transport,
 };
 
 const DEFAULT_FABRIC: u64 = 0x110;
@@ -16,6 +16,7 @@ const DEFAULT_LOCAL_ADDRESS: &str = "0.0.0.0:5555";
 const DEFAULT_CERT_PATH: &str = "./pem";
 
 const DEFAULT_DEVICE_ADDRESS: &str = "192.168.5.108:5540";
+const DEFAULT_DATA_DIR: &str = "./matter-data";
 #[derive(Parser, Debug)]
 #[command()]
 struct Cli {
@@ -44,6 +45,47 @@ enum Commands {
         device_id: u64,
         pin: u32,
     },
+    /// Commission device and store it under friendly name in device manager registry
+    CommissionNamed {
+        #[clap(long)]
+        #[arg(default_value_t = DEFAULT_DATA_DIR.to_string())]
+        data_dir: String,
+
+        /// used only when data directory is created
+        #[clap(long)]
+        #[arg(default_value_t = DEFAULT_FABRIC)]
+        fabric_id: u64,
+
+        /// used only when data directory is created
+        #[clap(long)]
+        #[arg(default_value_t = 100)]
+        controller_id: u64,
+
+        /// used only when data directory is created
+        #[clap(long)]
+        #[arg(default_value_t=DEFAULT_LOCAL_ADDRESS.to_string())]
+        local_address: String,
+
+        name: String,
+        device_address: String,
+        device_id: u64,
+        pin: u32,
+    },
+    /// Run command on device commissioned with commission-named
+    CommandNamed {
+        #[clap(long)]
+        #[arg(global = true, default_value_t = DEFAULT_DATA_DIR.to_string())]
+        data_dir: String,
+
+        #[clap(long)]
+        #[arg(global = true, default_value_t = 1)]
+        endpoint: u16,
+
+        name: String,
+
+        #[command(subcommand)]
+        command: CommandCommand,
+    },
     ListSupportedClusters {
         #[clap(long)]
         #[arg(default_value_t=DEFAULT_LOCAL_ADDRESS.to_string())]
@@ -135,6 +177,10 @@ enum CommandCommand {
     InvokeCommandRemoveFabric {
         index: u8,
     },
+    Identify {
+        #[arg(default_value_t = 10)]
+        seconds: u16,
+    },
     ListSupportedClusters {
         endpoint: u16,
     },
@@ -157,6 +203,19 @@ enum CommandCommand {
         #[arg(default_value_t = 200)]
         timeout: u16,
     },
+    RevokeCommissioning {},
+    /// Subscribe to attribute and print reports until Ctrl-C
+    Subscribe {
+        endpoint: u16,
+        cluster: u32,
+        attr: u32,
+
+        #[arg(default_value_t = 1)]
+        min_interval: u16,
+
+        #[arg(default_value_t = 30)]
+        max_interval: u16,
+    },
     MonitorDoorState{},
     Test2{},
 }
@@ -207,21 +266,57 @@ fn commission(
         let controller = controller::Controller::new(&cm, &transport, cm.get_fabric_id()).unwrap();
         let connection = transport.create_connection(device_address).await;
         let con = controller
-            .commission(&connection, pin, device_id, controller_id)
+            .commission_step_by_step(&connection, pin, device_id, controller_id, |step, elapsed| {
+                println!("{} done in {:.2}sec", step, elapsed.as_secs_f32())
+            })
             .await
             .unwrap();
         println!("commissioning ok. now list supported clusters (endpoint 0):");
-        let resptlv = con.read_request2(0, 0x1d, 1).await.unwrap();
-        if let tlv::TlvItemValue::List(l) = resptlv {
-            for c in l {
-                if let tlv::TlvItemValue::Int(v) = c.value {
-                    println!("{:?}", clusters::names::get_cluster_name(v as u32));
-                }
-            }
+        for (_, name) in con.list_clusters(0).await.unwrap() {
+            println!("{:?}", name);
         }
     });
 }
 
+fn commission_named(
+    data_dir: &str,
+    config: devman::ManagerConfig,
+    name: &str,
+    device_address: &str,
+    device_id: u64,
+    pin: u32,
+) {
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .unwrap();
+
+    runtime.block_on(async {
+        // create only on first use - any other load error must not overwrite existing config
+        let dm = if std::path::Path::new(data_dir).join("config.json").exists() {
+            devman::DeviceManager::load(data_dir).await.unwrap()
+        } else {
+            println!("creating device manager in {}", data_dir);
+            devman::DeviceManager::create(data_dir, config).await.unwrap()
+        };
+        dm.commission(device_address, pin, device_id, name).await.unwrap();
+        println!("commissioning ok. device stored as {:?}", name);
+    });
+}
+
+fn command_named(command: CommandCommand, data_dir: &str, name: &str, endpoint: u16) {
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .unwrap();
+
+    runtime.block_on(async {
+        let dm = devman::DeviceManager::load(data_dir).await.unwrap();
+        let mut connection = dm.connect_by_name(name).await.unwrap();
+        run_command(command, &mut connection, endpoint).await;
+    });
+}
+
 async fn progress(duration: Duration) {
     tokio::spawn(async move {
         let start_time = time::SystemTime::now();
@@ -260,8 +355,7 @@ fn discover_cmd(discover: DiscoverCommand, timeout: u64, cert_path: String) {
             let device_str = if let Some(device_id) = device_id {
                 let cm: Arc<dyn certmanager::CertManager> = certmanager::FileCertManager::load(&cert_path).unwrap();
                 let fabric = matc::fabric::Fabric::new(cm.get_fabric_id(), 1, &cm.get_ca_public_key().unwrap(), &cm.get_ipk_epoch_key());
-                let c = fabric.compressed().unwrap();
-                Some(format!("{}-{:016X}", hex::encode(c).to_uppercase(), device_id))
+                Some(discover::operational_instance_label(fabric.compressed_id().unwrap(), device_id))
             } else {
                 None
             };
@@ -330,20 +424,11 @@ async fn bridge_info(connection: &mut controller::Connection) {
                         println!("  attr 0x{:x} {}: {:?}", n, bridged_device_attr_name_from_id(n), out);
                     }
                 }
-                let supported_clusters = connection
-                    .read_request2(v as u16, clusters::defs::CLUSTER_ID_DESCRIPTOR,
-                    clusters::defs::CLUSTER_DESCRIPTOR_ATTR_ID_SERVERLIST)
-                    .await
-                    .unwrap();
                 println!("  Supported clusters:");
-                if let tlv::TlvItemValue::List(l) = supported_clusters {
-                    for c in l {
-                        if let tlv::TlvItemValue::Int(v) = c.value {
-                            match clusters::names::get_cluster_name(v as u32) {
-                                Some(v) => println!("    {}", v),
-                                None => println!(".   unknown cluster - id 0x{:x}", v),
-                            }
-                        }
+                for (id, name) in connection.list_clusters(v as u16).await.unwrap() {
+                    match name {
+                        Some(name) => println!("    {}", name),
+                        None => println!(".   unknown cluster - id 0x{:x}", id),
                     }
                 }
                 let taglist = connection
@@ -859,212 +944,239 @@ fn command_cmd(
         .unwrap();
 
     runtime.block_on(async {
-        {
         let mut connection =
             create_connection(local_address, device_address, device_id, controller_id, cert_path)
                 .await
                 .unwrap();
+        run_command(command, &mut connection, endpoint).await;
+    });
+}
 
-        match command {
-            CommandCommand::Read {
-                endpoint,
-                cluster,
-                attr,
-            } => {
-                let res = connection
-                    .read_request(endpoint, cluster, attr)
-                    .await
-                    .unwrap();
-                res.tlv.dump(1);
-            }
-            CommandCommand::InvokeCommandOn {} => {
-                let res = connection.invoke_request(endpoint, 0x6, 1, &[]).await.unwrap();
-                res.tlv.dump(1);
-            }
-            CommandCommand::InvokeCommandOff {} => {
-                let res = connection.invoke_request(endpoint, 0x6, 0, &[]).await.unwrap();
-                res.tlv.dump(1);
-            }
-            CommandCommand::InvokeCommandMoveToLevel { level } => {
-                let tlv = tlv::TlvItemEnc {
-                    tag: 0,
-                    value: tlv::TlvItemValueEnc::StructInvisible(vec![
-                        (0, tlv::TlvItemValueEnc::UInt8(level)).into(),
-                        (1, tlv::TlvItemValueEnc::UInt16(10)).into(), // transition time
-                        (2, tlv::TlvItemValueEnc::UInt8(0)).into(), // options mask
-                        (3, tlv::TlvItemValueEnc::UInt8(0)).into(), // options override
-                    ]),
-                }
-                .encode()
+async fn run_command(command: CommandCommand, connection: &mut controller::Connection, endpoint: u16) {
+    match command {
+        CommandCommand::Read {
+            endpoint,
+            cluster,
+            attr,
+        } => {
+            let res = connection
+                .read_request(endpoint, cluster, attr)
+                .await
                 .unwrap();
-                let res = connection
-                    .invoke_request(
-                        endpoint,
-                        clusters::defs::CLUSTER_ID_LEVEL_CONTROL,
-                        clusters::defs::CLUSTER_LEVEL_CONTROL_CMD_ID_MOVETOLEVEL,
-                        &tlv,
-                    )
-                    .await
-                    .unwrap();
-                res.tlv.dump(1);
-            }
-            CommandCommand::InvokeCommandMoveToHue { hue } => {
-                let tlv = tlv::TlvItemEnc {
-                    tag: 0,
-                    value: tlv::TlvItemValueEnc::StructInvisible(vec![
-                        (0, tlv::TlvItemValueEnc::UInt8(hue)).into(),
-                        (1, tlv::TlvItemValueEnc::UInt8(0)).into(), // direction
-                        (2, tlv::TlvItemValueEnc::UInt16(10)).into(), // time
-                        (3, tlv::TlvItemValueEnc::UInt8(0)).into(), // options mask
-                        (4, tlv::TlvItemValueEnc::UInt8(0)).into(), // options override
-                    ]),
-                }
-                .encode()
+            res.tlv.dump(1);
+        }
+        CommandCommand::InvokeCommandOn {} => {
+            let res = connection.invoke_request(endpoint, 0x6, 1, &[]).await.unwrap();
+            res.tlv.dump(1);
+        }
+        CommandCommand::InvokeCommandOff {} => {
+            let res = connection.invoke_request(endpoint, 0x6, 0, &[]).await.unwrap();
+            res.tlv.dump(1);
+        }
+        CommandCommand::Identify { seconds } => {
+            connection.identify(endpoint, seconds).await.unwrap();
+        }
+        CommandCommand::InvokeCommandMoveToLevel { level } => {
+            let tlv = tlv::CommandParams::new()
+                .u8(0, level)
+                .u16(1, 10) // transition time
+                .u8(2, 0) // options mask
+                .u8(3, 0) // options override
+                .build()
                 .unwrap();
-                let res = connection
-                    .invoke_request(
-                        endpoint,
-                        clusters::defs::CLUSTER_ID_COLOR_CONTROL,
-                        clusters::defs::CLUSTER_COLOR_CONTROL_CMD_ID_MOVETOHUE,
-                        &tlv,
-                    )
-                    .await
-                    .unwrap();
-                res.tlv.dump(1);
-            }
-            CommandCommand::InvokeCommandUpdateFabricLabel { label } => {
-                let tlv = tlv::TlvItemEnc {
-                    tag: 0,
-                    value: tlv::TlvItemValueEnc::String(label),
-                }
-                .encode()
+            let res = connection
+                .invoke_request(
+                    endpoint,
+                    clusters::defs::CLUSTER_ID_LEVEL_CONTROL,
+                    clusters::defs::CLUSTER_LEVEL_CONTROL_CMD_ID_MOVETOLEVEL,
+                    &tlv,
+                )
+                .await
                 .unwrap();
-                let res = connection.invoke_request(0, 0x3e, 9, &tlv).await.unwrap();
-                res.tlv.dump(1);
+            res.tlv.dump(1);
+        }
+        CommandCommand::InvokeCommandMoveToHue { hue } => {
+            let tlv = tlv::CommandParams::new()
+                .u8(0, hue)
+                .u8(1, 0) // direction
+                .u16(2, 10) // time
+                .u8(3, 0) // options mask
+                .u8(4, 0) // options override
+                .build()
+                .unwrap();
+            let res = connection
+                .invoke_request(
+                    endpoint,
+                    clusters::defs::CLUSTER_ID_COLOR_CONTROL,
+                    clusters::defs::CLUSTER_COLOR_CONTROL_CMD_ID_MOVETOHUE,
+                    &tlv,
+                )
+                .await
+                .unwrap();
+            res.tlv.dump(1);
+        }
+        CommandCommand::InvokeCommandUpdateFabricLabel { label } => {
+            connection.update_fabric_label(&label).await.unwrap();
+        }
+        CommandCommand::InvokeCommandRemoveFabric { index } => {
+            let tlv = tlv::TlvItemEnc {
+                tag: 0,
+                value: tlv::TlvItemValueEnc::UInt8(index),
             }
-            CommandCommand::InvokeCommandRemoveFabric { index } => {
-                let tlv = tlv::TlvItemEnc {
-                    tag: 0,
-                    value: tlv::TlvItemValueEnc::UInt8(index),
+            .encode()
+            .unwrap();
+            let res = connection.invoke_request(0, 0x3e, 0xa, &tlv).await.unwrap();
+            res.tlv.dump(1);
+        }
+        CommandCommand::ListSupportedClusters { endpoint } => {
+            for (id, name) in connection.list_clusters(endpoint).await.unwrap() {
+                match name {
+                    Some(name) => println!("{}", name),
+                    None => println!("unknown cluster - id 0x{:x}", id),
                 }
-                .encode()
-                .unwrap();
-                let res = connection.invoke_request(0, 0x3e, 0xa, &tlv).await.unwrap();
-                res.tlv.dump(1);
             }
-            CommandCommand::ListSupportedClusters { endpoint } => {
-                let resptlv = connection.read_request2(endpoint, 0x1d, 1).await.unwrap();
-                if let tlv::TlvItemValue::List(l) = resptlv {
-                    for c in l {
-                        if let tlv::TlvItemValue::Int(v) = c.value {
-                            match clusters::names::get_cluster_name(v as u32) {
-                                Some(v) => println!("{}", v),
-                                None => println!("unknown cluster - id 0x{:x}", v),
-                            }
+        }
+        CommandCommand::ListSupportedClusters2 { endpoint } => {
+            let resptlv = connection.read_request(endpoint, 0x1d, 1).await.unwrap();
+            let r = resptlv.tlv.get(&[1]).unwrap();
+            if let tlv::TlvItemValue::List(l) = r {
+                for r in l {
+                    let v = r.get(&[1, 2]);
+                    if let Some(tlv::TlvItemValue::Int(v)) = v {
+                        match clusters::names::get_cluster_name(*v as u32) {
+                            Some(v) => println!("{}", v),
+                            None => println!("unknown cluster - id 0x{:x}", v),
                         }
                     }
                 }
             }
-            CommandCommand::ListSupportedClusters2 { endpoint } => {
-                let resptlv = connection.read_request(endpoint, 0x1d, 1).await.unwrap();
-                let r = resptlv.tlv.get(&[1]).unwrap();
-                if let tlv::TlvItemValue::List(l) = r {
-                    for r in l {
-                        let v = r.get(&[1, 2]);
-                        if let Some(tlv::TlvItemValue::Int(v)) = v {
-                            match clusters::names::get_cluster_name(*v as u32) {
-                                Some(v) => println!("{}", v),
-                                None => println!("unknown cluster - id 0x{:x}", v),
-                            }
-                        }
+        }
+        CommandCommand::ListParts {} => {
+            let resptlv = connection
+                .read_request2(
+                    0,
+                    clusters::defs::CLUSTER_ID_DESCRIPTOR,
+                    clusters::defs::CLUSTER_DESCRIPTOR_ATTR_ID_PARTSLIST,
+                )
+                .await
+                .unwrap();
+            println!("{:?}", resptlv);
+            if let tlv::TlvItemValue::List(l) = resptlv {
+                for c in l {
+                    if let tlv::TlvItemValue::Int(v) = c.value {
+                        println!("{}", v);
                     }
                 }
             }
-            CommandCommand::ListParts {} => {
-                let resptlv = connection
-                    .read_request2(
-                        0,
-                        clusters::defs::CLUSTER_ID_DESCRIPTOR,
-                        clusters::defs::CLUSTER_DESCRIPTOR_ATTR_ID_PARTSLIST,
-                    )
-                    .await
-                    .unwrap();
-                println!("{:?}", resptlv);
-                if let tlv::TlvItemValue::List(l) = resptlv {
-                    for c in l {
-                        if let tlv::TlvItemValue::Int(v) = c.value {
-                            println!("{}", v);
-                        }
+        }
+        CommandCommand::ListBridgedDevices {} => {
+            bridge_info(connection).await;
+        }
+        CommandCommand::ListAttributes {} => {
+            all_attributes(connection).await;
+        }
+        CommandCommand::ListDevices {} => {
+            list_devices(connection).await;
+        }
+        CommandCommand::StartCommissioning { pin, iterations, discriminator, timeout } => {
+            let mut salt = [0; 32];
+            rand::RngCore::fill_bytes(&mut rand::thread_rng(), &mut salt);
+            let key = &matc::controller::pin_to_passcode(pin).unwrap();
+            let data = matc::spake2p::Engine::create_passcode_verifier(key, &salt, iterations);
+            let tlv = tlv::TlvItemEnc {
+                        tag: 0,
+                        value: tlv::TlvItemValueEnc::StructInvisible(vec![
+                                (0, tlv::TlvItemValueEnc::UInt16(timeout)).into(),
+                                (1, tlv::TlvItemValueEnc::OctetString(data)).into(),
+                                (2, tlv::TlvItemValueEnc::UInt16(discriminator)).into(),
+                                (3, tlv::TlvItemValueEnc::UInt32(iterations)).into(),
+                                (4, tlv::TlvItemValueEnc::OctetString(salt.to_vec())).into(),
+                        ]),
                     }
-                }
+                    .encode()
+                    .unwrap();
+            let res = connection.invoke_request_timed(0, clusters::defs::CLUSTER_ID_ADMINISTRATOR_COMMISSIONING, clusters::defs::CLUSTER_ADMINISTRATOR_COMMISSIONING_CMD_ID_OPENCOMMISSIONINGWINDOW, &tlv, 6000).await.unwrap();
+            log::debug!("start commissioning response: {:?}", res);
+            if res.protocol_header.protocol_id != messages::ProtocolMessageHeader::PROTOCOL_ID_INTERACTION
+                || res.protocol_header.opcode != messages::ProtocolMessageHeader::INTERACTION_OPCODE_INVOKE_RESP
+            {
+                panic!("unexpected response {:?}", res);
             }
-            CommandCommand::ListBridgedDevices {} => {
-                bridge_info(&mut connection).await;
+            let (_common_status, status) = messages::parse_im_invoke_resp(&res.tlv).unwrap();
+            match status {
+                0 => log::info!("start commissioning status: success"),
+                2 => log::info!("start commissioning status: busy(2)"),
+                3 => log::info!("start commissioning status: pake error(3)"),
+                4 => log::info!("start commissioning status: window not open(4)"),
+                _ => log::info!("start commissioning status: {}", status),
             }
-            CommandCommand::ListAttributes {} => {
-                all_attributes(&mut connection).await;
+        },
+        CommandCommand::RevokeCommissioning {} => {
+            if connection.revoke_commissioning().await.unwrap() {
+                log::info!("commissioning window closed");
+            } else {
+                log::info!("commissioning window was not open");
             }
-            CommandCommand::ListDevices {} => {
-                list_devices(&mut connection).await;
+        }
+        CommandCommand::Subscribe { endpoint, cluster, attr, min_interval, max_interval } => {
+            fn print_reports(reports: &[matc::im::AttributeReport]) {
+                let now = time::SystemTime::now()
+                    .duration_since(time::UNIX_EPOCH)
+                    .unwrap_or_default();
+                for r in reports {
+                    println!(
+                        "{:.3} {}/0x{:x}/0x{:x}: {:?}",
+                        now.as_secs_f64(),
+                        r.path.endpoint.unwrap_or_default(),
+                        r.path.cluster.unwrap_or_default(),
+                        r.path.attribute.unwrap_or_default(),
+                        r.data
+                    );
+                }
             }
-            CommandCommand::StartCommissioning { pin, iterations, discriminator, timeout } => {
-                let mut salt = [0; 32];
-                rand::RngCore::fill_bytes(&mut rand::thread_rng(), &mut salt);
-                let key = &matc::controller::pin_to_passcode(pin).unwrap();
-                let data = matc::spake2p::Engine::create_passcode_verifier(key, &salt, iterations);
-                let tlv = tlv::TlvItemEnc {
-                            tag: 0,
-                            value: tlv::TlvItemValueEnc::StructInvisible(vec![
-                                    (0, tlv::TlvItemValueEnc::UInt16(timeout)).into(),
-                                    (1, tlv::TlvItemValueEnc::OctetString(data)).into(),
-                                    (2, tlv::TlvItemValueEnc::UInt16(discriminator)).into(),
-                                    (3, tlv::TlvItemValueEnc::UInt32(iterations)).into(),
-                                    (4, tlv::TlvItemValueEnc::OctetString(salt.to_vec())).into(),
-                            ]),
+            let mut sub = connection
+                .subscribe_attrs_with_intervals(Some(endpoint), Some(cluster), Some(attr), false, min_interval, max_interval)
+                .await
+                .unwrap();
+            println!("subscription {} max interval {}s", sub.subscription_id, sub.max_interval);
+            print_reports(&sub.priming_attribute_reports);
+            loop {
+                tokio::select! {
+                    update = sub.next() => match update {
+                        Some(update) => print_reports(&update.attribute_reports),
+                        None => {
+                            println!("connection closed");
+                            break;
                         }
-                        .encode()
-                        .unwrap();
-                let res = connection.invoke_request_timed(0, clusters::defs::CLUSTER_ID_ADMINISTRATOR_COMMISSIONING, clusters::defs::CLUSTER_ADMINISTRATOR_COMMISSIONING_CMD_ID_OPENCOMMISSIONINGWINDOW, &tlv, 6000).await.unwrap();
-                log::debug!("start commissioning response: {:?}", res);
-                if res.protocol_header.protocol_id != messages::ProtocolMessageHeader::PROTOCOL_ID_INTERACTION
-                    || res.protocol_header.opcode != messages::ProtocolMessageHeader::INTERACTION_OPCODE_INVOKE_RESP
-                {
-                    panic!("unexpected response {:?}", res);
+                    },
+                    _ = tokio::signal::ctrl_c() => break,
                 }
-                let (_common_status, status) = messages::parse_im_invoke_resp(&res.tlv).unwrap();
-                match status {
-                    0 => log::info!("start commissioning status: success"),
-                    2 => log::info!("start commissioning status: busy(2)"),
-                    3 => log::info!("start commissioning status: pake error(3)"),
-                    4 => log::info!("start commissioning status: window not open(4)"),
-                    _ => log::info!("start commissioning status: {}", status),
-                }
-            },
-            CommandCommand::MonitorDoorState{} => {
-                fn print_door_state_events(events: &[matc::im::EventReport]) {
-                    for ev in events {
-                        if let Some(data) = &ev.data {
-                            println!("status: {:?}", clusters::codec::door_lock::decode_door_state_change_event(data));
-                        }
+            }
+            connection.close_session().await.unwrap();
+        }
+        CommandCommand::MonitorDoorState{} => {
+            fn print_door_state_events(events: &[matc::im::EventReport]) {
+                for ev in events {
+                    if let Some(data) = &ev.data {
+                        println!("status: {:?}", clusters::codec::door_lock::decode_door_state_change_event(data));
                     }
                 }
-                let mut sub = connection.subscribe_events(Some(1), Some(0x101), Some(1), false).await.unwrap();
-                print_door_state_events(&sub.priming_event_reports);
-                while let Some(update) = sub.next().await {
-                    print_door_state_events(&update.event_reports);
-                }
             }
-            CommandCommand::Test2{} => {
-                let tlv = clusters::codec::door_lock::encode_get_user(1).unwrap();
-                let res = connection.invoke_request(1, CLUSTER_ID_DOOR_LOCK, CLUSTER_DOOR_LOCK_CMD_ID_GETUSER, &tlv).await.unwrap();
-                let tlv = res.tlv.get(&[1, 0, 0, 1]).unwrap();
-                let dec = clusters::codec::door_lock::decode_get_user_response(tlv).unwrap();
-                println!("decoded get user response: {:?}", dec);
-
+            let mut sub = connection.subscribe_events(Some(1), Some(0x101), Some(1), false).await.unwrap();
+            print_door_state_events(&sub.priming_event_reports);
+            while let Some(update) = sub.next().await {
+                print_door_state_events(&update.event_reports);
             }
         }
+        CommandCommand::Test2{} => {
+            let tlv = clusters::codec::door_lock::encode_get_user(1).unwrap();
+            let res = connection.invoke_request(1, CLUSTER_ID_DOOR_LOCK, CLUSTER_DOOR_LOCK_CMD_ID_GETUSER, &tlv).await.unwrap();
+            let tlv = res.tlv.get(&[1, 0, 0, 1]).unwrap();
+            let dec = clusters::codec::door_lock::decode_get_user_response(tlv).unwrap();
+            println!("decoded get user response: {:?}", dec);
+
         }
-    });
+    }
 }
 
 fn main() {
@@ -1105,6 +1217,27 @@ fn main() {
                 &cert_path,
             );
         }
+        Commands::CommissionNamed {
+            data_dir,
+            fabric_id,
+            controller_id,
+            local_address,
+            name,
+            device_address,
+            device_id,
+            pin,
+        } => {
+            let config = devman::ManagerConfig::new(fabric_id, controller_id, &local_address);
+            commission_named(&data_dir, config, &name, &device_address, device_id, pin);
+        }
+        Commands::CommandNamed {
+            data_dir,
+            endpoint,
+            name,
+            command,
+        } => {
+            command_named(command, &data_dir, &name, endpoint);
+        }
         Commands::CaBootstrap { fabric_id } => {
             let cm = FileCertManager::new(fabric_id, &cert_path);
             cm.bootstrap().unwrap();
@@ -1135,15 +1268,10 @@ fn main() {
                 )
                 .await
                 .unwrap();
-                let resptlv = connection.read_request2(endpoint, 0x1d, 1).await.unwrap();
-                if let tlv::TlvItemValue::List(l) = resptlv {
-                    for c in l {
-                        if let tlv::TlvItemValue::Int(v) = c.value {
-                            match clusters::names::get_cluster_name(v as u32) {
-                                Some(v) => println!("{}", v),
-                                None => println!("unknown cluster - id 0x{:x}", v),
-                            }
-                        }
+                for (id, name) in connection.list_clusters(endpoint).await.unwrap() {
+                    match name {
+                        Some(name) => println!("{}", name),
+                        None => println!("unknown cluster - id 0x{:x}", id),
                     }
                 }
             });
@@ -289,11 +289,7 @@ async fn main() -> Result<()> {
 
     match cli.command {
         Commands::Init { fabric_id, controller_id, local_address } => {
-            let config = ManagerConfig {
-                fabric_id,
-                controller_id,
-                local_address,
-            };
+            let config = ManagerConfig::new(fabric_id, controller_id, &local_address);
             DeviceManager::create(data_dir, config).await?;
             println!("Device manager initialized in {}", data_dir);
         }
@@ -421,10 +417,14 @@ async fn main() -> Result<()> {
             if devices.is_empty() {
                 println!("No devices registered.");
             } else {
-                println!("{:<10} {:<25} Name", "Node ID", "Address");
-                println!("{}", "-".repeat(60));
+                println!("{:<10} {:<25} {:<12} {:<10} Name", "Node ID", "Address", "VID:PID", "Endpoints");
+                println!("{}", "-".repeat(80));
                 for d in devices {
-                    println!("{:<10} {:<25} {}", d.node_id, d.address, d.name);
+                    let vid_pid = match (d.vendor_id, d.product_id) {
+                        (Some(v), Some(p)) => format!("{:04x}:{:04x}", v, p),
+                        _ => "-".to_owned(),
+                    };
+                    println!("{:<10} {:<25} {:<12} {:<10} {}", d.node_id, d.address, vid_pid, d.endpoints.len(), d.name);
                 }
             }
         }
@@ -13,6 +13,15 @@ struct Cli {
     #[clap(long)]
     #[arg(default_value_t = false)]
     compact: bool,
+
+    /// Discover only on this interface (name or address)
+    #[clap(long)]
+    interface: Option<String>,
+
+    /// Print network interfaces and exit
+    #[clap(long)]
+    #[arg(default_value_t = false)]
+    list_interfaces: bool,
 }
 
 
@@ -51,37 +60,6 @@ async fn print_service(target: &str, mdns: &matc::mdns2::MdnsService) -> Result<
     Ok(())
 }
 
-/*async fn request_missing_info(target: &str, mdns: &matc::mdns2::MdnsService) {
-    //mdns.active_lookup(target, matc::mdns::TYPE_TXT).await;
-    mdns.active_lookup(target, matc::mdns::TYPE_SRV).await;
-    let txt_recors = mdns.lookup(target, matc::mdns::TYPE_TXT).await;
-    if txt_recors.is_empty() {
-        log::debug!("No TXT record found for {}, sending active query", target);
-        mdns.active_lookup(target, matc::mdns::TYPE_TXT).await;
-    }
-    
-    let srv_records = mdns.lookup(target, matc::mdns::TYPE_SRV).await;
-    if srv_records.is_empty() {
-        log::debug!("No SRV record found for {}, sending active query", target);
-        mdns.active_lookup(target, matc::mdns::TYPE_SRV).await;
-        return;
-    }
-    for srv_rr in srv_records {
-        log::debug!(". SRV record: {:?}", srv_rr.target);
-        let srv_target = match srv_rr.data {
-            matc::mdns::RRData::SRV { ref target, .. } => target,
-            _ => continue,
-        };
-        let a_records = mdns.lookup(srv_target, matc::mdns::TYPE_A).await;
-        let aaaa_records = mdns.lookup(srv_target, matc::mdns::TYPE_AAAA).await;
-        if a_records.is_empty() && aaaa_records.is_empty() {
-            log::debug!("No A/AAAA record found for {}, sending active query", srv_target);
-            mdns.active_lookup(srv_target, matc::mdns::TYPE_A).await;
-            mdns.active_lookup(srv_target, matc::mdns::TYPE_AAAA).await;
-        }
-    }
-}*/
-
 #[tokio::main]
 async fn main() {
     let cli = Cli::parse();
@@ -96,7 +74,15 @@ async fn main() {
         .format_timestamp(Some(env_logger::TimestampPrecision::Millis))
         .init();
 
-    let mdns = matc::mdns2::MdnsService::new().await.unwrap();
+    if cli.list_interfaces {
+        for iface in matc::transport::list_interfaces().unwrap() {
+            println!("{:<16} index: {:<4} ipv4: {:?} ipv6: {:?}",
+                     iface.name, iface.index.map(|i| i.to_string()).unwrap_or_default(), iface.ipv4, iface.ipv6);
+        }
+        return;
+    }
+
+    let mdns = matc::mdns2::MdnsService::new_on(cli.interface.as_deref()).await.unwrap();
     let mut receiver = mdns.subscribe();
     mdns.add_query("_matter._tcp.local", 0xff, Duration::from_secs(10)).await;
     mdns.add_query("_matterc._udp.local", 0xff, Duration::from_secs(10)).await;
@@ -120,12 +106,9 @@ async fn main() {
                         continue;
                     }
                 };
-                match cache.insert(matter_info.instance.clone(), true) {
-                    Some(_) => {
-                        log::debug!("Already seen instance {}, skipping", matter_info.instance);
-                        continue;
-                    }
-                    None => {}
+                if cache.insert(matter_info.instance.clone(), true).is_some() {
+                    log::debug!("Already seen instance {}, skipping", matter_info.instance);
+                    continue;
                 }
                 if cli.compact {
                     matter_info.print_compact();
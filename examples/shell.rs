@@ -427,7 +427,7 @@ async fn cmd_init(args: &[String], data_dir: &str) -> Result<()> {
         }
         i += 1;
     }
-    let config = ManagerConfig { fabric_id, controller_id, local_address };
+    let config = ManagerConfig::new(fabric_id, controller_id, &local_address);
     DeviceManager::create(data_dir, config).await?;
     println!("Device manager initialized in '{}'.", data_dir);
     Ok(())
@@ -837,8 +837,8 @@ async fn cmd_discover(args: &[String]) -> Result<()> {
     } else {
         println!("Found {} device(s):", infos.len());
         for info in infos {
-            let ip_port = info.ips.first()
-                .map(|ip| format!("{}:{}", ip, info.port.unwrap_or(5540)))
+            let ip_port = info.scoped_addrs(matc::discover::DEFAULT_PORT).first()
+                .map(|a| a.to_string())
                 .unwrap_or_else(|| "?".to_string());
             println!("  {} — {} (disc: {:?})", ip_port, info.instance,
                      info.discriminator.as_deref().unwrap_or("?"));
@@ -25,11 +25,7 @@ async fn main() -> Result<()> {
             devman
         } else {
             println!("No existing config found, performing first-time setup...");
-            let config = ManagerConfig {
-                fabric_id: FABRIC_ID,
-                controller_id: CONTROLLER_ID,
-                local_address: LOCAL_ADDRESS.to_string(),
-            };
+            let config = ManagerConfig::new(FABRIC_ID, CONTROLLER_ID, LOCAL_ADDRESS);
             DeviceManager::create(DATA_DIR, config).await
         }
     }?;
@@ -0,0 +1,8 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    let _ = matc::tlv::decode_tlv(data);
+    let _ = matc::tlv::decode_tlv_strict(data);
+});
@@ -17,6 +17,7 @@ const RECEIVE_TIMEOUT: Duration = Duration::from_secs(1);
 const EXCHANGE_CHANNEL_CAPACITY: usize = 8;
 const SUBSCRIPTION_CHANNEL_CAPACITY: usize = 16;
 const PARTIAL_REPORT_MAX_AGE: Duration = Duration::from_secs(60);
+const ACK_POLL_INTERVAL: Duration = Duration::from_millis(50);
 
 struct UnackedMessage {
     data: Vec<u8>,
@@ -28,8 +29,9 @@ struct UnackedMessage {
 /// Deadline for the first retransmission of a freshly sent message,
 /// per the peer's MRP intervals (spec 4.12 backoff, index 0).
 fn initial_retransmit_deadline(conn: &dyn ConnectionTrait) -> tokio::time::Instant {
-    let base = mrp::base_interval(&conn.mrp_params(), conn.last_received_elapsed());
-    tokio::time::Instant::now() + mrp::backoff_interval(base, 0)
+    let params = conn.mrp_params();
+    let base = mrp::base_interval(&params, conn.last_received_elapsed());
+    tokio::time::Instant::now() + mrp::backoff_interval(base, 0, params.backoff_jitter_percent)
 }
 
 struct ReadLoopState {
@@ -144,8 +146,13 @@ impl ActiveConnection {
         // makes Subscription::next() return None.
         self.subscriptions.lock().unwrap().clear();
 
-        // Install the new session.
+        // Install the new session; the old one's local id is no longer used.
+        let old_session_id = self.session_holder.lock().unwrap().my_session_id;
+        let new_session_id = new_session.my_session_id;
         *self.session_holder.lock().unwrap() = Arc::new(new_session);
+        if old_session_id != new_session_id {
+            self.transport_conn.release_session_id(old_session_id);
+        }
         let new_session_arc = self.session_holder.lock().unwrap().clone();
 
         // Spawn a fresh read loop on the same transport connection.
@@ -168,18 +175,25 @@ impl ActiveConnection {
         Ok(())
     }
 
-    async fn send_internal(&self, exchange_id: u16, data: &[u8]) -> Result<()> {
+    /// Encode and send message, returning its message counter.
+    async fn send_internal(&self, exchange_id: u16, data: &[u8]) -> Result<u32> {
         let session = self.session_holder.lock().unwrap().clone();
         let encoded = session.encode_message(data)?;
+        let (header, _) = messages::MessageHeader::decode(&encoded)?;
         self.track_sent(&encoded, Some(exchange_id)).await;
         if let Err(e) = self.transport_conn.send(&encoded).await {
             log::debug!("error sending message on exchange {}: {:?}", exchange_id, e);
-            if let Ok((header, _)) = messages::MessageHeader::decode(&encoded) {
-                self.unacked.lock().await.remove(&header.message_counter);
-            }
+            self.unacked.lock().await.remove(&header.message_counter);
             return Err(e);
         }
-        Ok(())
+        Ok(header.message_counter)
+    }
+
+    /// Encode and send message without tracking it for retransmission.
+    pub(crate) async fn send_unreliable(&self, data: &[u8]) -> Result<()> {
+        let session = self.session_holder.lock().unwrap().clone();
+        let encoded = session.encode_message(data)?;
+        self.transport_conn.send(&encoded).await
     }
 
     /// Open a logical exchange: messages received for this exchange id are
@@ -197,6 +211,39 @@ impl ActiveConnection {
         exchange.recv().await
     }
 
+    /// Send message which expects no response and wait only until the peer acknowledges it.
+    /// On reliable transports returns as soon as the message is sent.
+    /// Fails when retransmissions are exhausted without acknowledgement.
+    pub(crate) async fn send_wait_ack(&self, exchange_id: u16, data: &[u8]) -> Result<()> {
+        let mut exchange = self.open_exchange(exchange_id);
+        let counter = self.send_internal(exchange_id, data).await?;
+        if self.transport_conn.is_reliable() {
+            return Ok(());
+        }
+        loop {
+            {
+                // retransmit give-up removes the exchange before the unacked entry,
+                // both under the unacked lock
+                let unacked = self.unacked.lock().await;
+                if !unacked.contains_key(&counter) {
+                    if self.pending_exchanges.lock().unwrap().contains_key(&exchange_id) {
+                        return Ok(());
+                    }
+                    anyhow::bail!("message {} not acknowledged", counter);
+                }
+            }
+            match tokio::time::timeout(ACK_POLL_INTERVAL, exchange.recv()).await {
+                Ok(Ok(msg)) => log::debug!(
+                    "unexpected message on exchange {} opcode {}",
+                    exchange_id,
+                    msg.protocol_header.opcode
+                ),
+                Ok(Err(_)) => anyhow::bail!("message {} not acknowledged", counter),
+                Err(_) => {}
+            }
+        }
+    }
+
     /// Register a subscription id; decoded updates for it are delivered to the
     /// returned receiver instead of the raw event channel.
     pub(crate) fn register_subscription(&self, id: u32) -> mpsc::Receiver<im::ReportUpdate> {
@@ -263,7 +310,7 @@ pub(crate) struct Exchange<'a> {
 
 impl Exchange<'_> {
     pub(crate) async fn send(&self, data: &[u8]) -> Result<()> {
-        self.conn.send_internal(self.id, data).await
+        self.conn.send_internal(self.id, data).await.map(|_| ())
     }
 
     pub(crate) async fn recv(&mut self) -> Result<Message> {
@@ -525,10 +572,8 @@ async fn check_retransmit(
         let mut unacked_lock = unacked.lock().await;
         let mut to_remove = Vec::new();
         let now = tokio::time::Instant::now();
-        let base = mrp::base_interval(
-            &transport_conn.mrp_params(),
-            transport_conn.last_received_elapsed(),
-        );
+        let params = transport_conn.mrp_params();
+        let base = mrp::base_interval(&params, transport_conn.last_received_elapsed());
 
         for (counter, msg) in unacked_lock.iter_mut() {
             if msg.next_retransmit > now {
@@ -552,7 +597,7 @@ async fn check_retransmit(
                     msg.transmissions + 1
                 );
                 to_retransmit.push(msg.data.clone());
-                msg.next_retransmit = now + mrp::backoff_interval(base, msg.transmissions);
+                msg.next_retransmit = now + mrp::backoff_interval(base, msg.transmissions, params.backoff_jitter_percent);
                 msg.transmissions += 1;
             }
         }
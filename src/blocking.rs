@@ -0,0 +1,141 @@
+//! Synchronous facade for callers without async runtime (requires `blocking` feature).
+//!
+//! [BlockingController] owns a Tokio runtime and wraps [Controller](crate::controller::Controller).
+//! Connections it returns run their requests on the same runtime, so every call simply blocks
+//! until the async operation completes. This is a thin wrapper - for anything not covered here use
+//! [BlockingConnection::block_on] with the async api.
+//!
+//! Do not use from within an async context; blocking calls there panic.
+//!
+//! ```no_run
+//! # use std::sync::Arc;
+//! # use anyhow::Result;
+//! # use matc::{blocking::BlockingController, certmanager, clusters};
+//! # fn main() -> Result<()> {
+//! let cm: Arc<dyn certmanager::CertManager> = certmanager::FileCertManager::load("./pem")?;
+//! let controller = BlockingController::new(&cm, "0.0.0.0:5555", 1000)?;
+//! let connection = controller.auth_sigma("192.168.5.70:5540", 300, 100)?;
+//! connection.invoke_request(1, clusters::defs::CLUSTER_ID_ON_OFF, clusters::defs::CLUSTER_ON_OFF_CMD_ID_ON, &[])?;
+//! let on = connection.read_request2(1, clusters::defs::CLUSTER_ID_ON_OFF, clusters::defs::CLUSTER_ON_OFF_ATTR_ID_ONOFF)?;
+//! println!("on: {:?}", on);
+//! # Ok(())
+//! # }
+//! ```
+
+use std::sync::Arc;
+
+use anyhow::Result;
+
+use crate::{certmanager, controller, messages::Message, tlv::TlvItemValue, transport};
+
+/// Blocking wrapper of [Controller](controller::Controller) with its own transport and runtime.
+pub struct BlockingController {
+    runtime: Arc<tokio::runtime::Runtime>,
+    transport: Arc<transport::Transport>,
+    controller: Arc<controller::Controller>,
+}
+
+impl BlockingController {
+    /// Create runtime, bind transport to `local_address` and create controller for `fabric_id`.
+    pub fn new(
+        certmanager: &Arc<dyn certmanager::CertManager>,
+        local_address: &str,
+        fabric_id: u64,
+    ) -> Result<Self> {
+        let runtime = tokio::runtime::Builder::new_multi_thread()
+            .worker_threads(1)
+            .enable_all()
+            .build()?;
+        let transport = runtime.block_on(transport::Transport::new(local_address))?;
+        let controller = controller::Controller::new(certmanager, &transport, fabric_id)?;
+        Ok(Self {
+            runtime: Arc::new(runtime),
+            transport,
+            controller,
+        })
+    }
+
+    /// Commission device at `address` - see [Controller::commission](controller::Controller::commission).
+    pub fn commission(
+        &self,
+        address: &str,
+        pin: u32,
+        node_id: u64,
+        controller_id: u64,
+    ) -> Result<BlockingConnection> {
+        let connection = self.runtime.block_on(async {
+            let connection = self.transport.create_connection(address).await;
+            self.controller
+                .commission(&connection, pin, node_id, controller_id)
+                .await
+        })?;
+        Ok(self.wrap(connection))
+    }
+
+    /// Authenticate commissioned device at `address` - see [Controller::auth_sigma](controller::Controller::auth_sigma).
+    pub fn auth_sigma(
+        &self,
+        address: &str,
+        node_id: u64,
+        controller_id: u64,
+    ) -> Result<BlockingConnection> {
+        let connection = self.runtime.block_on(async {
+            let connection = self.transport.create_connection(address).await;
+            self.controller
+                .auth_sigma(&connection, node_id, controller_id)
+                .await
+        })?;
+        Ok(self.wrap(connection))
+    }
+
+    fn wrap(&self, connection: controller::Connection) -> BlockingConnection {
+        BlockingConnection {
+            runtime: self.runtime.clone(),
+            connection,
+        }
+    }
+}
+
+/// Blocking wrapper of authenticated [Connection](controller::Connection).
+pub struct BlockingConnection {
+    runtime: Arc<tokio::runtime::Runtime>,
+    connection: controller::Connection,
+}
+
+impl BlockingConnection {
+    /// See [Connection::read_request](controller::Connection::read_request).
+    pub fn read_request(&self, endpoint: u16, cluster: u32, attr: u32) -> Result<Message> {
+        self.runtime
+            .block_on(self.connection.read_request(endpoint, cluster, attr))
+    }
+
+    /// See [Connection::read_request2](controller::Connection::read_request2).
+    pub fn read_request2(&self, endpoint: u16, cluster: u32, attr: u32) -> Result<TlvItemValue> {
+        self.runtime
+            .block_on(self.connection.read_request2(endpoint, cluster, attr))
+    }
+
+    /// See [Connection::invoke_request](controller::Connection::invoke_request).
+    pub fn invoke_request(
+        &self,
+        endpoint: u16,
+        cluster: u32,
+        command: u32,
+        payload: &[u8],
+    ) -> Result<Message> {
+        self.runtime.block_on(
+            self.connection
+                .invoke_request(endpoint, cluster, command, payload),
+        )
+    }
+
+    /// Run any async operation, typically one using [BlockingConnection::connection].
+    pub fn block_on<F: std::future::Future>(&self, future: F) -> F::Output {
+        self.runtime.block_on(future)
+    }
+
+    /// Underlying async connection.
+    pub fn connection(&self) -> &controller::Connection {
+        &self.connection
+    }
+}
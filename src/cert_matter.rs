@@ -9,7 +9,7 @@ use x509_cert::{
 
 use crate::{
     tlv::{self, TlvBuffer},
-    util::cryptoutil,
+    util::{asn1, cryptoutil},
 };
 
 fn decode_dn_value(dn: &x509_cert::der::Any) -> Result<u64> {
@@ -51,7 +51,17 @@ fn extract_extension(cert: &x509_cert::TbsCertificate, oid: &str) -> Result<Vec<
 pub fn get_subject_node_id_from_x509(fname: &str) -> Result<u64> {
     let cert_file = std::fs::read_to_string(fname)?;
     let cert = x509_cert::Certificate::from_pem(cert_file)?;
-    for extra in cert.tbs_certificate.subject.0 {
+    subject_node_id(&cert)
+}
+
+/// Subject node id of x509 certificate in DER format.
+pub fn get_subject_node_id_from_x509_bytes(bytes: &[u8]) -> Result<u64> {
+    let cert = x509_cert::Certificate::from_der(bytes)?;
+    subject_node_id(&cert)
+}
+
+fn subject_node_id(cert: &CertificateInner) -> Result<u64> {
+    for extra in &cert.tbs_certificate.subject.0 {
         for e2 in extra.0.as_slice() {
             if e2.oid == const_oid::ObjectIdentifier::new_unwrap("1.3.6.1.4.1.37244.1.1") {
                 return decode_dn_value(&e2.value);
@@ -61,6 +71,204 @@ pub fn get_subject_node_id_from_x509(fname: &str) -> Result<u64> {
     Err(anyhow::anyhow!("matter subject/node not found in x509"))
 }
 
+/// Subject node id (matter-node-id DN attribute) of certificate in matter TLV format, e.g. a NOC
+/// read back from device.
+pub fn get_subject_node_id(matter_cert: &[u8]) -> Result<u64> {
+    let cert = tlv::decode_tlv(matter_cert)?;
+    cert.get_int(&[6, 17])
+        .context("matter subject/node not found in certificate")
+}
+
+/// Seconds between Unix epoch and Matter epoch (2000-01-01 00:00:00 UTC).
+pub const MATTER_EPOCH_UNIX_SECS: u64 = 946684800;
+
+/// Matter TLV tags of distinguished name attributes. Tags 1-16 are standard X.520
+/// string attributes (+0x80 when encoded as PrintableString).
+pub const DN_TAG_COMMON_NAME: u8 = 1;
+pub const DN_TAG_NODE_ID: u8 = 17;
+pub const DN_TAG_FIRMWARE_SIGNING_ID: u8 = 18;
+pub const DN_TAG_ICAC_ID: u8 = 19;
+pub const DN_TAG_RCAC_ID: u8 = 20;
+pub const DN_TAG_FABRIC_ID: u8 = 21;
+pub const DN_TAG_NOC_CAT: u8 = 22;
+
+/// Value of distinguished name attribute.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DnValue {
+    /// Matter specific attribute (node id, fabric id, CAT, ...).
+    Int(u64),
+    /// Standard X.520 attribute.
+    String(String),
+}
+
+/// Distinguished name attribute with its matter TLV tag (see `DN_TAG_*`).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DnAttribute {
+    pub tag: u8,
+    pub value: DnValue,
+}
+
+/// Certificate in matter TLV format decoded into its fields, e.g. NOC, ICAC or RCAC
+/// read back from a device.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MatterCert {
+    pub serial_number: Vec<u8>,
+    /// 1 = ecdsa-with-sha256
+    pub signature_algorithm: u8,
+    pub issuer: Vec<DnAttribute>,
+    /// Matter epoch seconds, see [MATTER_EPOCH_UNIX_SECS].
+    pub not_before: u32,
+    /// Matter epoch seconds; 0 means no well-defined expiration.
+    pub not_after: u32,
+    pub subject: Vec<DnAttribute>,
+    /// 1 = EC public key
+    pub public_key_algorithm: u8,
+    /// 1 = prime256v1
+    pub elliptic_curve: u8,
+    /// Uncompressed SEC1 public key.
+    pub public_key: Vec<u8>,
+    pub is_ca: bool,
+    pub path_len_constraint: Option<u8>,
+    /// KeyUsage bits in X.509 order (digitalSignature = 0x01, keyCertSign = 0x20, ...).
+    pub key_usage: Option<u16>,
+    /// ExtendedKeyUsage purposes (1 = server auth, 2 = client auth, ...).
+    pub extended_key_usage: Vec<u8>,
+    pub subject_key_id: Option<Vec<u8>>,
+    pub authority_key_id: Option<Vec<u8>>,
+    /// Tags of extensions present (1 = basic constraints ... 6 = future extension), in
+    /// certificate order. X.509 extensions are rebuilt in this order.
+    pub extension_order: Vec<u8>,
+    /// DER encoded X.509 extensions carried as is (future-extension, tag 6).
+    pub future_extensions: Vec<Vec<u8>>,
+    /// ECDSA signature as r || s.
+    pub signature: Vec<u8>,
+}
+
+fn decode_dn(cert: &tlv::TlvItem, tag: u8) -> Result<Vec<DnAttribute>> {
+    let Some(tlv::TlvItemValue::List(attrs)) = cert.get(&[tag]) else {
+        anyhow::bail!("matter cert: distinguished name {} missing", tag);
+    };
+    attrs
+        .iter()
+        .map(|a| {
+            let value = match &a.value {
+                tlv::TlvItemValue::Int(v) => DnValue::Int(*v),
+                tlv::TlvItemValue::String(v) => DnValue::String(v.clone()),
+                other => {
+                    anyhow::bail!("matter cert: unexpected DN attribute {} {:?}", a.tag, other)
+                }
+            };
+            Ok(DnAttribute { tag: a.tag, value })
+        })
+        .collect()
+}
+
+fn dn_int(dn: &[DnAttribute], tag: u8) -> Option<u64> {
+    dn.iter().find_map(|a| match a.value {
+        DnValue::Int(v) if a.tag == tag => Some(v),
+        _ => None,
+    })
+}
+
+impl MatterCert {
+    /// Decode certificate in matter TLV format.
+    pub fn decode(matter_cert: &[u8]) -> Result<Self> {
+        let cert = tlv::decode_tlv(matter_cert)?;
+        let u8_field = |tag: u8, name: &str| {
+            cert.get_u8(&[tag])
+                .with_context(|| format!("matter cert: {} missing", name))
+        };
+        let extended_key_usage = match cert.get(&[10, 3]) {
+            Some(tlv::TlvItemValue::List(items)) => items
+                .iter()
+                .filter_map(|i| match i.value {
+                    tlv::TlvItemValue::Int(v) => Some(v as u8),
+                    _ => None,
+                })
+                .collect(),
+            _ => Vec::new(),
+        };
+        let mut extension_order = Vec::new();
+        let mut future_extensions = Vec::new();
+        if let Some(tlv::TlvItemValue::List(items)) = cert.get(&[10]) {
+            for ext in items {
+                extension_order.push(ext.tag);
+                if let tlv::TlvItemValue::OctetString(v) = &ext.value {
+                    if ext.tag == 6 {
+                        future_extensions.push(v.clone());
+                    }
+                }
+            }
+        }
+        Ok(Self {
+            serial_number: cert
+                .get_octet_string_owned(&[1])
+                .context("matter cert: serial missing")?,
+            signature_algorithm: u8_field(2, "signature algorithm")?,
+            issuer: decode_dn(&cert, 3)?,
+            not_before: cert
+                .get_int(&[4])
+                .context("matter cert: not_before missing")? as u32,
+            not_after: cert
+                .get_int(&[5])
+                .context("matter cert: not_after missing")? as u32,
+            subject: decode_dn(&cert, 6)?,
+            public_key_algorithm: u8_field(7, "public key algorithm")?,
+            elliptic_curve: u8_field(8, "elliptic curve")?,
+            public_key: cert
+                .get_octet_string_owned(&[9])
+                .context("matter cert: public key missing")?,
+            is_ca: cert.get_bool(&[10, 1, 1]).unwrap_or(false),
+            path_len_constraint: cert.get_u8(&[10, 1, 2]),
+            key_usage: cert.get_int(&[10, 2]).map(|v| v as u16),
+            extended_key_usage,
+            subject_key_id: cert.get_octet_string_owned(&[10, 4]),
+            authority_key_id: cert.get_octet_string_owned(&[10, 5]),
+            extension_order,
+            future_extensions,
+            signature: cert
+                .get_octet_string_owned(&[11])
+                .context("matter cert: signature missing")?,
+        })
+    }
+
+    /// Node id of the subject (NOC).
+    pub fn subject_node_id(&self) -> Option<u64> {
+        dn_int(&self.subject, DN_TAG_NODE_ID)
+    }
+
+    /// Fabric id of the subject.
+    pub fn subject_fabric_id(&self) -> Option<u64> {
+        dn_int(&self.subject, DN_TAG_FABRIC_ID)
+    }
+
+    /// CASE Authenticated Tags of the subject (NOC).
+    pub fn subject_case_auth_tags(&self) -> Vec<u32> {
+        self.subject
+            .iter()
+            .filter_map(|a| match a.value {
+                DnValue::Int(v) if a.tag == DN_TAG_NOC_CAT => Some(v as u32),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Id of the CA (RCAC or ICAC) which issued the certificate.
+    pub fn issuer_ca_id(&self) -> Option<u64> {
+        dn_int(&self.issuer, DN_TAG_ICAC_ID).or_else(|| dn_int(&self.issuer, DN_TAG_RCAC_ID))
+    }
+
+    /// Validity start as seconds since Unix epoch.
+    pub fn not_before_unix(&self) -> u64 {
+        MATTER_EPOCH_UNIX_SECS + self.not_before as u64
+    }
+
+    /// Validity end as seconds since Unix epoch; None when the certificate does not expire.
+    pub fn not_after_unix(&self) -> Option<u64> {
+        (self.not_after != 0).then(|| MATTER_EPOCH_UNIX_SECS + self.not_after as u64)
+    }
+}
+
 /// Convert certificate in PEM file to matter format
 /// PEM file must contain x509 certificate compatible with matter
 pub fn convert_x509_to_matter(fname: &str, ca_pubkey: &[u8]) -> Result<Vec<u8>> {
@@ -88,12 +296,12 @@ fn convert_x509_to_matter_int(cert: &CertificateInner, ca_pubkey: &[u8]) -> Resu
     let not_before = cert.tbs_certificate.validity.not_before;
     enc.write_uint32(
         4,
-        (not_before.to_unix_duration().as_secs() - 946684800) as u32,
+        (not_before.to_unix_duration().as_secs() - MATTER_EPOCH_UNIX_SECS) as u32,
     )?;
     let not_after = cert.tbs_certificate.validity.not_after;
     enc.write_uint32(
         5,
-        (not_after.to_unix_duration().as_secs() - 946684800) as u32,
+        (not_after.to_unix_duration().as_secs() - MATTER_EPOCH_UNIX_SECS) as u32,
     )?;
 
     enc.write_list(6)?; // subject
@@ -183,3 +391,311 @@ fn convert_x509_to_matter_int(cert: &CertificateInner, ca_pubkey: &[u8]) -> Resu
     enc.write_struct_end()?;
     Ok(enc.data)
 }
+
+/// Convert certificate in matter TLV format back to X509/DER.
+/// Distinguished names and extensions are rebuilt from the decoded [MatterCert]; the
+/// result carries the original signature, which verifies over the rebuilt TBS.
+/// Attributes or extensions without X.509 mapping are an error.
+pub fn convert_matter_to_x509(matter_cert: &[u8]) -> Result<Vec<u8>> {
+    let cert = MatterCert::decode(matter_cert)?;
+    let tbs = crate::cert_x509::encode_tbs(&cert)?;
+    let sig = ecdsa::Signature::<NistP256>::from_slice(&cert.signature)?;
+
+    let mut encoder = asn1::Encoder::new();
+    encoder.start_seq(0x30)?;
+    encoder.write_raw(&tbs);
+    encoder.start_seq(0x30)?; //signature algorithm
+    encoder.write_oid(crate::cert_x509::OID_SIG_ECDSA_WITH_SHA256)?;
+    encoder.end_seq();
+    let mut sig_bits = vec![0];
+    sig_bits.extend_from_slice(sig.to_der().as_bytes());
+    encoder.write_octet_string_with_tag(0x3, &sig_bits)?;
+    encoder.end_seq();
+    Ok(encoder.encode())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decode_matter_cert() -> Result<()> {
+        let ca_secret = p256::SecretKey::random(&mut rand::thread_rng());
+        let ca_public = ca_secret.public_key().to_sec1_bytes();
+        let node_secret = p256::SecretKey::random(&mut rand::thread_rng());
+        let node_public = node_secret.public_key().to_sec1_bytes();
+
+        let x509 =
+            crate::cert_x509::encode_x509(&node_public, 0x1111, 0x1234, 0x5678, &ca_secret, false)?;
+        let noc = MatterCert::decode(&convert_x509_bytes_to_matter(&x509, &ca_public)?)?;
+        assert_eq!(noc.signature_algorithm, 1);
+        assert_eq!(
+            noc.issuer,
+            vec![DnAttribute {
+                tag: DN_TAG_RCAC_ID,
+                value: DnValue::Int(0x5678)
+            }]
+        );
+        assert_eq!(noc.issuer_ca_id(), Some(0x5678));
+        assert_eq!(noc.subject_node_id(), Some(0x1111));
+        assert_eq!(noc.subject_fabric_id(), Some(0x1234));
+        assert!(noc.subject_case_auth_tags().is_empty());
+        assert_eq!(noc.public_key, node_public.to_vec());
+        assert!(!noc.is_ca);
+        assert_eq!(noc.key_usage, Some(0x01));
+        assert_eq!(noc.extended_key_usage, vec![2, 1]);
+        assert_eq!(noc.subject_key_id, Some(cryptoutil::sha1_enc(&node_public)));
+        assert_eq!(noc.authority_key_id, Some(cryptoutil::sha1_enc(&ca_public)));
+        assert_eq!(noc.signature.len(), 64);
+        assert_eq!(
+            noc.not_after_unix().unwrap() - noc.not_before_unix(),
+            60 * 60 * 24 * 100
+        );
+
+        let x509 = crate::cert_x509::encode_x509(&ca_public, 0x5678, 0, 0x5678, &ca_secret, true)?;
+        let root = MatterCert::decode(&convert_x509_bytes_to_matter(&x509, &ca_public)?)?;
+        assert!(root.is_ca);
+        assert_eq!(root.key_usage, Some(0x60));
+        assert!(root.extended_key_usage.is_empty());
+        assert_eq!(
+            root.subject,
+            vec![DnAttribute {
+                tag: DN_TAG_RCAC_ID,
+                value: DnValue::Int(0x5678)
+            }]
+        );
+        assert_eq!(root.subject_node_id(), None);
+
+        assert!(MatterCert::decode(&[0x15, 0x18]).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_convert_matter_to_x509_roundtrip() -> Result<()> {
+        let ca_secret = p256::SecretKey::random(&mut rand::thread_rng());
+        let ca_public = ca_secret.public_key().to_sec1_bytes();
+        for is_ca in [false, true] {
+            let node_secret = p256::SecretKey::random(&mut rand::thread_rng());
+            let node_public = node_secret.public_key().to_sec1_bytes();
+            let x509 =
+                crate::cert_x509::encode_x509(&node_public, 1111, 1234, 5678, &ca_secret, is_ca)?;
+            let matter = convert_x509_bytes_to_matter(&x509, &ca_public)?;
+            let back = convert_matter_to_x509(&matter)?;
+            assert_eq!(back, x509, "x509 -> matter -> x509 (is_ca={})", is_ca);
+            assert_eq!(convert_x509_bytes_to_matter(&back, &ca_public)?, matter);
+        }
+        assert!(convert_matter_to_x509(&[0x15, 0x18]).is_err());
+        Ok(())
+    }
+
+    /// Certificate of another issuer: X.509 built with x509-cert independently of this
+    /// library's encoder and the matching matter TLV written by hand.
+    struct ForeignCert {
+        x509: Vec<u8>,
+        matter: Vec<u8>,
+    }
+
+    fn foreign_noc(
+        issuer: &[(u8, &str, u64)],
+        subject: &[(u8, &str, u64)],
+        not_after: Option<u32>,
+        issuer_key: &p256::SecretKey,
+    ) -> Result<ForeignCert> {
+        use x509_cert::der::asn1::{Any, BitString, GeneralizedTime, OctetString, SetOfVec, UtcTime};
+        use x509_cert::der::{DateTime, Encode, Tag};
+        use x509_cert::ext::pkix;
+
+        const NOT_BEFORE: u32 = 700_000_000;
+        let oid = const_oid::ObjectIdentifier::new_unwrap;
+        let name = |attrs: &[(u8, &str, u64)]| -> Result<x509_cert::name::Name> {
+            let mut rdns = Vec::new();
+            for (_, o, v) in attrs {
+                let digits = if *o == "1.3.6.1.4.1.37244.1.6" { 8 } else { 16 };
+                let value = format!("{:0>width$X}", v, width = digits);
+                rdns.push(x509_cert::name::RelativeDistinguishedName(SetOfVec::try_from(vec![
+                    x509_cert::attr::AttributeTypeAndValue {
+                        oid: oid(o),
+                        value: Any::new(Tag::Utf8String, value.as_bytes())?,
+                    },
+                ])?));
+            }
+            Ok(x509_cert::name::RdnSequence(rdns))
+        };
+        let ext = |id: &str, critical: bool, value: Vec<u8>| -> Result<x509_cert::ext::Extension> {
+            Ok(x509_cert::ext::Extension {
+                extn_id: oid(id),
+                critical,
+                extn_value: OctetString::new(value)?,
+            })
+        };
+
+        let node_secret = p256::SecretKey::random(&mut rand::thread_rng());
+        let node_public = node_secret.public_key().to_sec1_bytes();
+        let ski = cryptoutil::sha1_enc(&node_public);
+        let aki = cryptoutil::sha1_enc(&issuer_key.public_key().to_sec1_bytes());
+        let time = |secs: u32| {
+            std::time::Duration::from_secs(MATTER_EPOCH_UNIX_SECS + secs as u64)
+        };
+        let sig_alg = x509_cert::spki::AlgorithmIdentifierOwned {
+            oid: oid(crate::cert_x509::OID_SIG_ECDSA_WITH_SHA256),
+            parameters: None,
+        };
+        let tbs = x509_cert::TbsCertificate {
+            version: x509_cert::Version::V3,
+            serial_number: x509_cert::serial_number::SerialNumber::new(&[0x4e, 0x21, 0x7a])?,
+            signature: sig_alg.clone(),
+            issuer: name(issuer)?,
+            validity: x509_cert::time::Validity {
+                not_before: x509_cert::time::Time::UtcTime(UtcTime::from_unix_duration(time(NOT_BEFORE))?),
+                not_after: match not_after {
+                    Some(secs) => x509_cert::time::Time::UtcTime(UtcTime::from_unix_duration(time(secs))?),
+                    None => x509_cert::time::Time::GeneralTime(GeneralizedTime::from_date_time(
+                        DateTime::new(9999, 12, 31, 23, 59, 59)?,
+                    )),
+                },
+            },
+            subject: name(subject)?,
+            subject_public_key_info: x509_cert::spki::SubjectPublicKeyInfoOwned {
+                algorithm: x509_cert::spki::AlgorithmIdentifierOwned {
+                    oid: oid("1.2.840.10045.2.1"),
+                    parameters: Some(Any::from(&oid("1.2.840.10045.3.1.7"))),
+                },
+                subject_public_key: BitString::from_bytes(&node_public)?,
+            },
+            issuer_unique_id: None,
+            subject_unique_id: None,
+            extensions: Some(vec![
+                ext(
+                    crate::cert_x509::OID_CE_BASIC_CONSTRAINTS,
+                    true,
+                    pkix::BasicConstraints { ca: false, path_len_constraint: None }.to_der()?,
+                )?,
+                ext(
+                    crate::cert_x509::OID_CE_KEY_USAGE,
+                    true,
+                    pkix::KeyUsage(pkix::KeyUsages::DigitalSignature.into()).to_der()?,
+                )?,
+                ext(
+                    crate::cert_x509::OID_CE_EXT_KEU_USAGE,
+                    true,
+                    pkix::ExtendedKeyUsage(vec![oid("1.3.6.1.5.5.7.3.2"), oid("1.3.6.1.5.5.7.3.1")])
+                        .to_der()?,
+                )?,
+                ext(
+                    crate::cert_x509::OID_CE_SUBJECT_KEY_IDENTIFIER,
+                    false,
+                    pkix::SubjectKeyIdentifier(OctetString::new(ski.clone())?).to_der()?,
+                )?,
+                ext(
+                    crate::cert_x509::OID_CE_AUTHORITY_KEY_IDENTIFIER,
+                    false,
+                    pkix::AuthorityKeyIdentifier {
+                        key_identifier: Some(OctetString::new(aki.clone())?),
+                        authority_cert_issuer: None,
+                        authority_cert_serial_number: None,
+                    }
+                    .to_der()?,
+                )?,
+            ]),
+        };
+        let key = ecdsa::SigningKey::<NistP256>::from(issuer_key);
+        let sig: ecdsa::Signature<NistP256> = ecdsa::signature::Signer::sign(&key, &tbs.to_der()?);
+        let x509 = x509_cert::Certificate {
+            tbs_certificate: tbs,
+            signature_algorithm: sig_alg,
+            signature: BitString::from_bytes(sig.to_der().as_bytes())?,
+        }
+        .to_der()?;
+
+        let mut m = TlvBuffer::new();
+        m.write_anon_struct()?;
+        m.write_octetstring(1, &[0x4e, 0x21, 0x7a])?;
+        m.write_uint8(2, 1)?;
+        for (tag, dn) in [(3, issuer), (6, subject)] {
+            m.write_list(tag)?;
+            for (t, _, v) in dn {
+                if *t == DN_TAG_NOC_CAT {
+                    m.write_uint32(*t, *v as u32)?;
+                } else {
+                    m.write_uint64(*t, *v)?;
+                }
+            }
+            m.write_struct_end()?;
+        }
+        m.write_uint32(4, NOT_BEFORE)?;
+        m.write_uint32(5, not_after.unwrap_or(0))?;
+        m.write_uint8(7, 1)?;
+        m.write_uint8(8, 1)?;
+        m.write_octetstring(9, &node_public)?;
+        m.write_list(10)?;
+        m.write_struct(1)?;
+        m.write_bool(1, false)?;
+        m.write_struct_end()?;
+        m.write_uint8(2, 0x01)?;
+        m.write_array(3)?;
+        m.write_uint8_notag(2)?;
+        m.write_uint8_notag(1)?;
+        m.write_struct_end()?;
+        m.write_octetstring(4, &ski)?;
+        m.write_octetstring(5, &aki)?;
+        m.write_struct_end()?;
+        m.write_octetstring(11, &sig.to_bytes())?;
+        m.write_struct_end()?;
+        Ok(ForeignCert { x509, matter: m.data })
+    }
+
+    fn assert_converts(cert: &ForeignCert, issuer_key: &p256::SecretKey) -> Result<()> {
+        use x509_cert::der::Encode;
+        let back = convert_matter_to_x509(&cert.matter)?;
+        assert_eq!(back, cert.x509);
+        let parsed = x509_cert::Certificate::from_der(&back)?;
+        let sig = ecdsa::Signature::<NistP256>::from_der(parsed.signature.raw_bytes())?;
+        let verifying_key = ecdsa::VerifyingKey::from(issuer_key.public_key());
+        ecdsa::signature::Verifier::verify(&verifying_key, &parsed.tbs_certificate.to_der()?, &sig)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_convert_icac_issued_noc() -> Result<()> {
+        const ICAC: &str = "1.3.6.1.4.1.37244.1.3";
+        const NODE: &str = "1.3.6.1.4.1.37244.1.1";
+        const FABRIC: &str = "1.3.6.1.4.1.37244.1.5";
+        let icac_key = p256::SecretKey::random(&mut rand::thread_rng());
+        let cert = foreign_noc(
+            &[(DN_TAG_ICAC_ID, ICAC, 0xCACACACA00000001), (DN_TAG_FABRIC_ID, FABRIC, 0xFAB000000000001D)],
+            &[(DN_TAG_NODE_ID, NODE, 0xDEDEDEDE00010001), (DN_TAG_FABRIC_ID, FABRIC, 0xFAB000000000001D)],
+            Some(1_000_000_000),
+            &icac_key,
+        )?;
+        assert_eq!(MatterCert::decode(&cert.matter)?.issuer_ca_id(), Some(0xCACACACA00000001));
+        assert_converts(&cert, &icac_key)
+    }
+
+    #[test]
+    fn test_convert_noc_with_cats() -> Result<()> {
+        const RCAC: &str = "1.3.6.1.4.1.37244.1.4";
+        const NODE: &str = "1.3.6.1.4.1.37244.1.1";
+        const FABRIC: &str = "1.3.6.1.4.1.37244.1.5";
+        const CAT: &str = "1.3.6.1.4.1.37244.1.6";
+        let root_key = p256::SecretKey::random(&mut rand::thread_rng());
+        let cert = foreign_noc(
+            &[(DN_TAG_RCAC_ID, RCAC, 0xCACACACA00000001)],
+            &[
+                (DN_TAG_NODE_ID, NODE, 0xDEDEDEDE00010001),
+                (DN_TAG_FABRIC_ID, FABRIC, 0xFAB000000000001D),
+                (DN_TAG_NOC_CAT, CAT, 0xABCD0002),
+                (DN_TAG_NOC_CAT, CAT, 0xABCE0018),
+            ],
+            None,
+            &root_key,
+        )?;
+        assert_eq!(MatterCert::decode(&cert.matter)?.subject_case_auth_tags(), vec![0xABCD0002, 0xABCE0018]);
+        assert_converts(&cert, &root_key)?;
+
+        // attribute without X.509 mapping is reported, not dropped
+        let mut m = MatterCert::decode(&cert.matter)?;
+        m.subject.push(DnAttribute { tag: 40, value: DnValue::Int(1) });
+        assert!(crate::cert_x509::encode_tbs(&m).is_err());
+        Ok(())
+    }
+}
@@ -3,7 +3,6 @@
 use byteorder::WriteBytesExt;
 use std::time::{Duration, SystemTime};
 
-use crate::tlv;
 use crate::util::asn1;
 use crate::util::cryptoutil;
 use anyhow::{Context, Result};
@@ -34,7 +33,7 @@ const OID_MATTER_DN_NODE: &str = "1.3.6.1.4.1.37244.1.1";
 const OID_MATTER_DN_CA: &str = "1.3.6.1.4.1.37244.1.4";
 const OID_MATTER_DN_FABRIC: &str = "1.3.6.1.4.1.37244.1.5";
 
-const OID_SIG_ECDSA_WITH_SHA256: &str = "1.2.840.10045.4.3.2";
+pub(crate) const OID_SIG_ECDSA_WITH_SHA256: &str = "1.2.840.10045.4.3.2";
 
 pub(crate) const OID_CE_SUBJECT_KEY_IDENTIFIER: &str = "2.5.29.14";
 pub(crate) const OID_CE_KEY_USAGE: &str = "2.5.29.15";
@@ -52,39 +51,153 @@ fn add_rdn(encoder: &mut asn1::Encoder, oid: &str, id: u64) -> Result<()> {
     Ok(())
 }
 
-fn epoch2000_to_x509_time(secs: u32) -> Result<String> {
+/// X.509 attribute type and ASN.1 string tag of standard DN attribute with matter tag 1-16.
+fn x520_attribute(tag: u8) -> Option<(&'static str, u8)> {
+    // PrintableString variant has 0x80 added to the tag
+    let printable = tag & 0x80 != 0;
+    let string_tag = if printable { 0x13 } else { 0x0c };
+    let oid = match tag & 0x7f {
+        1 => "2.5.4.3",   // common name
+        2 => "2.5.4.4",   // surname
+        3 => "2.5.4.5",   // serial number
+        4 => "2.5.4.6",   // country
+        5 => "2.5.4.7",   // locality
+        6 => "2.5.4.8",   // state or province
+        7 => "2.5.4.10",  // organization
+        8 => "2.5.4.11",  // organizational unit
+        9 => "2.5.4.12",  // title
+        10 => "2.5.4.41", // name
+        11 => "2.5.4.42", // given name
+        12 => "2.5.4.43", // initials
+        13 => "2.5.4.44", // generation qualifier
+        14 => "2.5.4.46", // dn qualifier
+        15 => "2.5.4.65", // pseudonym
+        16 if !printable => return Some(("0.9.2342.19200300.100.1.25", 0x16)), // domain component, IA5String
+        _ => return None,
+    };
+    Some((oid, string_tag))
+}
+
+/// X.509 attribute type and hex digit count of matter specific DN attribute.
+fn matter_attribute(tag: u8) -> Option<(&'static str, usize)> {
+    use crate::cert_matter::{
+        DN_TAG_FABRIC_ID, DN_TAG_FIRMWARE_SIGNING_ID, DN_TAG_ICAC_ID, DN_TAG_NODE_ID,
+        DN_TAG_NOC_CAT, DN_TAG_RCAC_ID,
+    };
+    match tag {
+        DN_TAG_NODE_ID => Some((OID_MATTER_DN_NODE, 16)),
+        DN_TAG_FIRMWARE_SIGNING_ID => Some(("1.3.6.1.4.1.37244.1.2", 16)),
+        DN_TAG_ICAC_ID => Some(("1.3.6.1.4.1.37244.1.3", 16)),
+        DN_TAG_RCAC_ID => Some((OID_MATTER_DN_CA, 16)),
+        DN_TAG_FABRIC_ID => Some((OID_MATTER_DN_FABRIC, 16)),
+        DN_TAG_NOC_CAT => Some(("1.3.6.1.4.1.37244.1.6", 8)),
+        _ => None,
+    }
+}
+
+/// Distinguished name - one RDN per attribute, in matter certificate order.
+fn encode_dn(encoder: &mut asn1::Encoder, dn: &[crate::cert_matter::DnAttribute]) -> Result<()> {
+    use crate::cert_matter::DnValue;
+    encoder.start_seq(0x30)?;
+    for attr in dn {
+        encoder.start_seq(0x31)?; //rdn
+        encoder.start_seq(0x30)?; //atv
+        match &attr.value {
+            DnValue::Int(v) => {
+                let (oid, digits) = matter_attribute(attr.tag)
+                    .with_context(|| format!("matter cert: unsupported DN attribute {}", attr.tag))?;
+                encoder.write_oid(oid)?;
+                encoder.write_string(&format!("{:0>width$X}", v, width = digits))?;
+            }
+            DnValue::String(v) => {
+                let (oid, string_tag) = x520_attribute(attr.tag)
+                    .with_context(|| format!("matter cert: unsupported DN attribute {}", attr.tag))?;
+                if v.len() >= 0x80 {
+                    anyhow::bail!("matter cert: DN attribute {} too long", attr.tag);
+                }
+                encoder.write_oid(oid)?;
+                encoder.write_string_with_tag(string_tag, v)?;
+            }
+        }
+        encoder.end_seq();
+        encoder.end_seq();
+    }
+    encoder.end_seq();
+    Ok(())
+}
+
+/// UTCTime through 2049, GeneralizedTime after; `no_expiry` is the 99991231235959Z marker.
+fn encode_time(encoder: &mut asn1::Encoder, secs: u32, no_expiry: bool) -> Result<()> {
+    if no_expiry {
+        encoder.write_string_with_tag(0x18, "99991231235959Z")?;
+        return Ok(());
+    }
     let st = SystemTime::UNIX_EPOCH
-        .checked_add(Duration::from_secs(946684800 + secs as u64))
+        .checked_add(Duration::from_secs(
+            crate::cert_matter::MATTER_EPOCH_UNIX_SECS + secs as u64,
+        ))
         .context("certificate time out of range")?;
-    systemtime_to_x509_time(st)
+    match x509_cert::der::asn1::UtcTime::from_system_time(st) {
+        Ok(_) => encoder.write_string_with_tag(0x17, &systemtime_to_x509_time(st)?)?,
+        Err(_) => {
+            let t = x509_cert::der::asn1::GeneralizedTime::from_system_time(st)?;
+            let mut v = Vec::new();
+            x509_cert::der::EncodeValue::encode_value(&t, &mut v)?;
+            encoder.write_string_with_tag(0x18, std::str::from_utf8(&v)?)?;
+        }
+    }
+    Ok(())
+}
+
+/// KeyUsage BIT STRING; matter bit n (digitalSignature = 0x01) is X.509 named bit n.
+fn key_usage_bits(usage: u16) -> Vec<u8> {
+    if usage == 0 {
+        return vec![0x03, 0x01, 0x00];
+    }
+    let highest = 15 - usage.leading_zeros() as usize;
+    let mut bytes = vec![0u8; highest / 8 + 1];
+    for bit in 0..=highest {
+        if usage & (1 << bit) != 0 {
+            bytes[bit / 8] |= 0x80 >> (bit % 8);
+        }
+    }
+    let mut out = vec![0x03, bytes.len() as u8 + 1, (7 - highest % 8) as u8];
+    out.extend(bytes);
+    out
+}
+
+fn key_purpose_oid(purpose: u8) -> Option<&'static str> {
+    match purpose {
+        1 => Some("1.3.6.1.5.5.7.3.1"), // server auth
+        2 => Some("1.3.6.1.5.5.7.3.2"), // client auth
+        3 => Some("1.3.6.1.5.5.7.3.3"), // code signing
+        4 => Some("1.3.6.1.5.5.7.3.4"), // email protection
+        5 => Some("1.3.6.1.5.5.7.3.8"), // time stamping
+        6 => Some("1.3.6.1.5.5.7.3.9"), // OCSP signing
+        _ => None,
+    }
 }
 
 /// Reconstruct the X.509 TBSCertificate DER from a certificate in Matter TLV format.
 /// Matter certificate signatures are computed over the X.509 TBS, so this is needed to
 /// verify a Matter certificate against its issuer's public key.
-/// Only certificates produced by [encode_x509] (this library's own CA) round-trip exactly;
-/// certificates issued by other stacks may use encodings this reconstruction does not cover,
-/// in which case signature verification will fail.
+/// Distinguished names and extensions are rebuilt in certificate order per the Matter
+/// certificate encoding rules; attributes or extensions which can't be mapped are an error.
 pub(crate) fn matter_cert_to_x509_tbs(matter_cert: &[u8]) -> Result<Vec<u8>> {
-    let cert = tlv::decode_tlv(matter_cert)?;
-    let serial = cert
-        .get_octet_string(&[1])
-        .context("matter cert: serial missing")?;
-    let issuer_ca_id = cert
-        .get_int(&[3, 20])
-        .context("matter cert: issuer ca id missing")?;
-    let not_before = cert.get_int(&[4]).context("matter cert: not_before missing")? as u32;
-    let not_after = cert.get_int(&[5]).context("matter cert: not_after missing")? as u32;
-    let public_key = cert
-        .get_octet_string(&[9])
-        .context("matter cert: public key missing")?;
-    let is_ca = cert.get_bool(&[10, 1, 1]).unwrap_or(false);
-    let subject_key_id = cert
-        .get_octet_string(&[10, 4])
-        .context("matter cert: subject key id missing")?;
-    let authority_key_id = cert
-        .get_octet_string(&[10, 5])
-        .context("matter cert: authority key id missing")?;
+    encode_tbs(&crate::cert_matter::MatterCert::decode(matter_cert)?)
+}
+
+pub(crate) fn encode_tbs(cert: &crate::cert_matter::MatterCert) -> Result<Vec<u8>> {
+    if cert.signature_algorithm != 1 {
+        anyhow::bail!("matter cert: unsupported signature algorithm {}", cert.signature_algorithm);
+    }
+    if cert.public_key_algorithm != 1 || cert.elliptic_curve != 1 {
+        anyhow::bail!(
+            "matter cert: unsupported public key algorithm {}/{}",
+            cert.public_key_algorithm,
+            cert.elliptic_curve
+        );
+    }
 
     let mut encoder = asn1::Encoder::new();
     encoder.start_seq(0x30)?;
@@ -93,38 +206,20 @@ pub(crate) fn matter_cert_to_x509_tbs(matter_cert: &[u8]) -> Result<Vec<u8>> {
     encoder.write_int(2)?; // version
     encoder.end_seq();
 
-    encoder.write_octet_string_with_tag(0x2, serial)?; // serial INTEGER content bytes
+    encoder.write_octet_string_with_tag(0x2, &cert.serial_number)?; // serial INTEGER content bytes
 
     encoder.start_seq(0x30)?; //signature algorithm
     encoder.write_oid(OID_SIG_ECDSA_WITH_SHA256)?;
     encoder.end_seq();
 
-    encoder.start_seq(0x30)?; //issuer
-    add_rdn(&mut encoder, OID_MATTER_DN_CA, issuer_ca_id)?;
-    encoder.end_seq();
+    encode_dn(&mut encoder, &cert.issuer)?;
 
     encoder.start_seq(0x30)?; //validity
-    encoder.write_string_with_tag(0x17, &epoch2000_to_x509_time(not_before)?)?;
-    encoder.write_string_with_tag(0x17, &epoch2000_to_x509_time(not_after)?)?;
+    encode_time(&mut encoder, cert.not_before, false)?;
+    encode_time(&mut encoder, cert.not_after, cert.not_after == 0)?;
     encoder.end_seq();
 
-    encoder.start_seq(0x30)?; //subject
-    if is_ca {
-        let subject_ca_id = cert
-            .get_int(&[6, 20])
-            .context("matter cert: subject ca id missing")?;
-        add_rdn(&mut encoder, OID_MATTER_DN_CA, subject_ca_id)?;
-    } else {
-        let node_id = cert
-            .get_int(&[6, 17])
-            .context("matter cert: subject node id missing")?;
-        let fabric_id = cert
-            .get_int(&[6, 21])
-            .context("matter cert: subject fabric id missing")?;
-        add_rdn(&mut encoder, OID_MATTER_DN_NODE, node_id)?;
-        add_rdn(&mut encoder, OID_MATTER_DN_FABRIC, fabric_id)?;
-    }
-    encoder.end_seq();
+    encode_dn(&mut encoder, &cert.subject)?;
 
     encoder.start_seq(0x30)?; //subject key info
     encoder.start_seq(0x30)?; //algorithm
@@ -132,65 +227,64 @@ pub(crate) fn matter_cert_to_x509_tbs(matter_cert: &[u8]) -> Result<Vec<u8>> {
     encoder.write_oid("1.2.840.10045.3.1.7")?;
     encoder.end_seq();
     let mut pk2 = vec![0u8];
-    pk2.extend_from_slice(public_key);
+    pk2.extend_from_slice(&cert.public_key);
     encoder.write_octet_string_with_tag(0x3, &pk2)?;
     encoder.end_seq();
 
-    let subjectkeyidasn = {
-        let mut encoder = asn1::Encoder::new();
-        encoder.write_octet_string(subject_key_id)?;
-        encoder.encode()
-    };
-
-    let authoritykey_sha1_asn = {
-        let mut encoder = asn1::Encoder::new();
-        encoder.start_seq(0x30)?;
-        encoder.write_octet_string_with_tag(0x80, authority_key_id)?;
-        encoder.encode()
-    };
-
     encoder.start_seq(0xa3)?;
     encoder.start_seq(0x30)?;
-    if is_ca {
-        add_ext(
-            &mut encoder,
-            OID_CE_BASIC_CONSTRAINTS,
-            true,
-            &[0x30, 0x03, 0x01, 0x01, 0xFF],
-        )?;
-        add_ext(
-            &mut encoder,
-            OID_CE_KEY_USAGE,
-            true,
-            &[0x03, 0x02, 0x01, 0x06],
-        )?;
-    } else {
-        add_ext(&mut encoder, OID_CE_BASIC_CONSTRAINTS, true, &[0x30, 0x00])?;
-        add_ext(
-            &mut encoder,
-            OID_CE_KEY_USAGE,
-            true,
-            &[0x03, 0x02, 0x07, 0x80],
-        )?;
-        let mut ext_ku_encoder = asn1::Encoder::new();
-        ext_ku_encoder.start_seq(0x30)?;
-        ext_ku_encoder.write_oid("1.3.6.1.5.5.7.3.2")?; // client-auth
-        ext_ku_encoder.write_oid("1.3.6.1.5.5.7.3.1")?; // server-auth
-        let ext_ku_bytes = ext_ku_encoder.encode();
-        add_ext(&mut encoder, OID_CE_EXT_KEU_USAGE, true, &ext_ku_bytes)?;
+    let mut future = cert.future_extensions.iter();
+    for ext in &cert.extension_order {
+        match ext {
+            1 => {
+                let mut bc = asn1::Encoder::new();
+                bc.start_seq(0x30)?;
+                if cert.is_ca {
+                    bc.write_bool(true)?;
+                }
+                if let Some(len) = cert.path_len_constraint {
+                    bc.write_int(len as u32)?;
+                }
+                add_ext(&mut encoder, OID_CE_BASIC_CONSTRAINTS, true, &bc.encode())?;
+            }
+            2 => {
+                let usage = cert.key_usage.context("matter cert: key usage missing")?;
+                add_ext(&mut encoder, OID_CE_KEY_USAGE, true, &key_usage_bits(usage))?;
+            }
+            3 => {
+                let mut eku = asn1::Encoder::new();
+                eku.start_seq(0x30)?;
+                for purpose in &cert.extended_key_usage {
+                    eku.write_oid(key_purpose_oid(*purpose).with_context(|| {
+                        format!("matter cert: unsupported key purpose {}", purpose)
+                    })?)?;
+                }
+                add_ext(&mut encoder, OID_CE_EXT_KEU_USAGE, true, &eku.encode())?;
+            }
+            4 => {
+                let mut ski = asn1::Encoder::new();
+                ski.write_octet_string(
+                    cert.subject_key_id
+                        .as_ref()
+                        .context("matter cert: subject key id missing")?,
+                )?;
+                add_ext(&mut encoder, OID_CE_SUBJECT_KEY_IDENTIFIER, false, &ski.encode())?;
+            }
+            5 => {
+                let mut aki = asn1::Encoder::new();
+                aki.start_seq(0x30)?;
+                aki.write_octet_string_with_tag(
+                    0x80,
+                    cert.authority_key_id
+                        .as_ref()
+                        .context("matter cert: authority key id missing")?,
+                )?;
+                add_ext(&mut encoder, OID_CE_AUTHORITY_KEY_IDENTIFIER, false, &aki.encode())?;
+            }
+            6 => encoder.write_raw(future.next().context("matter cert: future extension missing")?),
+            other => anyhow::bail!("matter cert: unsupported extension {}", other),
+        }
     }
-    add_ext(
-        &mut encoder,
-        OID_CE_SUBJECT_KEY_IDENTIFIER,
-        false,
-        &subjectkeyidasn,
-    )?;
-    add_ext(
-        &mut encoder,
-        OID_CE_AUTHORITY_KEY_IDENTIFIER,
-        false,
-        &authoritykey_sha1_asn,
-    )?;
     encoder.end_seq();
     encoder.end_seq();
     encoder.end_seq();
@@ -198,7 +292,36 @@ pub(crate) fn matter_cert_to_x509_tbs(matter_cert: &[u8]) -> Result<Vec<u8>> {
     Ok(encoder.encode())
 }
 
-/// Create matter compatible certificate in x509 format.
+/// Source of current time for certificate issuance and timestamps.
+pub trait Clock: Send + Sync {
+    fn now(&self) -> SystemTime;
+}
+
+/// [Clock] reading system time.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> SystemTime {
+        SystemTime::now()
+    }
+}
+
+/// [Clock] always returning the same time. Use a time in the past to issue certificates
+/// accepted by devices whose clock lags behind, or any fixed time for reproducible output.
+#[derive(Debug, Clone, Copy)]
+pub struct FixedClock(pub SystemTime);
+
+impl Clock for FixedClock {
+    fn now(&self) -> SystemTime {
+        self.0
+    }
+}
+
+/// Validity period of certificates created by [encode_x509].
+pub const CERT_VALIDITY: Duration = Duration::from_secs(60 * 60 * 24 * 100);
+
+/// Create matter compatible certificate in x509 format, valid from now for [CERT_VALIDITY].
 pub fn encode_x509(
     node_public_key: &[u8],
     node_id: u64,
@@ -206,6 +329,29 @@ pub fn encode_x509(
     ca_id: u64,
     ca_private: &p256::SecretKey,
     ca: bool,
+) -> Result<Vec<u8>> {
+    encode_x509_at(
+        node_public_key,
+        node_id,
+        fabric_id,
+        ca_id,
+        ca_private,
+        ca,
+        SystemTime::now(),
+    )
+}
+
+/// Create matter compatible certificate in x509 format valid from `not_before` for [CERT_VALIDITY].
+/// Pass a time in the past to tolerate devices whose clock lags behind, or a fixed time
+/// to get reproducible output (signatures are deterministic).
+pub fn encode_x509_at(
+    node_public_key: &[u8],
+    node_id: u64,
+    fabric_id: u64,
+    ca_id: u64,
+    ca_private: &p256::SecretKey,
+    ca: bool,
+    not_before: SystemTime,
 ) -> Result<Vec<u8>> {
     let mut encoder = asn1::Encoder::new();
     encoder.start_seq(0x30)?;
@@ -227,10 +373,9 @@ pub fn encode_x509(
 
     encoder.start_seq(0x30)?; //validity
 
-    let now = SystemTime::now();
-    encoder.write_string_with_tag(0x17, &systemtime_to_x509_time(now)?)?;
-    let not_after = now
-        .checked_add(Duration::from_secs(60 * 60 * 24 * 100))
+    encoder.write_string_with_tag(0x17, &systemtime_to_x509_time(not_before)?)?;
+    let not_after = not_before
+        .checked_add(CERT_VALIDITY)
         .context("time continuity error")?;
     encoder.write_string_with_tag(0x17, &systemtime_to_x509_time(not_after)?)?;
     encoder.end_seq();
@@ -382,7 +527,7 @@ mod tests {
             let expected_tbs = der_element(&x509[header..]);
             assert_eq!(tbs, expected_tbs, "reconstructed TBS must match original (is_ca={})", is_ca);
 
-            let cert_tlv = tlv::decode_tlv(&matter)?;
+            let cert_tlv = crate::tlv::decode_tlv(&matter)?;
             let sig = cert_tlv.get_octet_string(&[11]).unwrap();
             let verifying_key =
                 ecdsa::VerifyingKey::from(p256::PublicKey::from_sec1_bytes(&ca_public)?);
@@ -391,4 +536,27 @@ mod tests {
         }
         Ok(())
     }
+
+    #[test]
+    fn test_encode_x509_at_fixed_time() -> Result<()> {
+        let ca_secret = p256::SecretKey::from_slice(&[7u8; 32])?;
+        let ca_public = ca_secret.public_key().to_sec1_bytes();
+        // 2024-01-01 00:00:00 UTC
+        let not_before = SystemTime::UNIX_EPOCH + Duration::from_secs(1704067200);
+        let x509 = encode_x509_at(&ca_public, 1, 2, 1, &ca_secret, false, not_before)?;
+        assert_eq!(
+            x509,
+            encode_x509_at(&ca_public, 1, 2, 1, &ca_secret, false, not_before)?,
+            "same input and time must give same certificate"
+        );
+
+        let matter = crate::cert_matter::convert_x509_bytes_to_matter(&x509, &ca_public)?;
+        let cert = crate::cert_matter::MatterCert::decode(&matter)?;
+        assert_eq!(cert.not_before_unix(), 1704067200);
+        assert_eq!(
+            cert.not_after_unix(),
+            Some(1704067200 + CERT_VALIDITY.as_secs())
+        );
+        Ok(())
+    }
 }
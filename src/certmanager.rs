@@ -15,6 +15,11 @@ pub trait CertManager: Send + Sync {
     fn get_user_key(&self, id: u64) -> Result<p256::SecretKey>;
     fn get_fabric_id(&self) -> u64;
     fn get_ipk_epoch_key(&self) -> Vec<u8>;
+    /// Start of validity of certificates issued with this CA, e.g. NOC pushed to device
+    /// during commissioning.
+    fn cert_not_before(&self) -> std::time::SystemTime {
+        std::time::SystemTime::now()
+    }
 }
 
 /// Example implementation of [CertManager] trait.
@@ -23,6 +28,7 @@ pub struct FileCertManager {
     fabric_id: u64,
     ipk_epoch_key: Vec<u8>,
     path: String,
+    clock: Arc<dyn cert_x509::Clock>,
 }
 
 #[derive(Serialize, Deserialize)]
@@ -33,11 +39,17 @@ struct Metadata {
 
 impl FileCertManager {
     pub fn new(fabric_id: u64, path: &str) -> Arc<Self> {
+        Self::new_with_clock(fabric_id, path, Arc::new(cert_x509::SystemClock))
+    }
+
+    /// As [FileCertManager::new], certificates are issued with validity starting at `clock` time.
+    pub fn new_with_clock(fabric_id: u64, path: &str, clock: Arc<dyn cert_x509::Clock>) -> Arc<Self> {
         let ipk_epoch_key: [u8; 16] = rand::random();
         Arc::new(Self {
             fabric_id,
             ipk_epoch_key: ipk_epoch_key.to_vec(),
             path: path.to_owned(),
+            clock,
         })
     }
 
@@ -47,6 +59,11 @@ impl FileCertManager {
     /// Falls back to legacy `metadata.pem` (fabric_id only, hardcoded IPK)
     /// so identities bootstrapped before this change keep working.
     pub fn load(path: &str) -> Result<Arc<Self>> {
+        Self::load_with_clock(path, Arc::new(cert_x509::SystemClock))
+    }
+
+    /// As [FileCertManager::load], certificates are issued with validity starting at `clock` time.
+    pub fn load_with_clock(path: &str, clock: Arc<dyn cert_x509::Clock>) -> Result<Arc<Self>> {
         let json_path = format!("{}/metadata.json", path);
         let pem_path = format!("{}/metadata.pem", path);
 
@@ -71,6 +88,7 @@ impl FileCertManager {
             fabric_id,
             ipk_epoch_key,
             path: path.to_owned(),
+            clock,
         }))
     }
 
@@ -121,13 +139,14 @@ impl FileCertManager {
         std::fs::write(self.ca_key_fname(), pem::encode(&pem).as_bytes())?;
         let node_public_key = secret_key.public_key().to_sec1_bytes();
 
-        let x509 = cert_x509::encode_x509(
+        let x509 = cert_x509::encode_x509_at(
             &node_public_key,
             CA_NODE_ID,
             self.fabric_id,
             CA_NODE_ID,
             &secret_key,
             true,
+            self.clock.now(),
         )?;
         cryptoutil::write_pem("CERTIFICATE", &x509, &self.ca_cert_fname())?;
         let metadata = Metadata {
@@ -151,13 +170,14 @@ impl FileCertManager {
         std::fs::write(self.user_key_fname(id), pem::encode(&pem).as_bytes())?;
         let node_public_key = secret_key.public_key().to_sec1_bytes();
 
-        let x509 = cert_x509::encode_x509(
+        let x509 = cert_x509::encode_x509_at(
             &node_public_key,
             id,
             self.fabric_id,
             CA_NODE_ID,
             &ca_private,
             false,
+            self.clock.now(),
         )?;
         cryptoutil::write_pem("CERTIFICATE", &x509, &self.user_cert_fname(id))?;
         Ok(())
@@ -192,4 +212,57 @@ impl CertManager for FileCertManager {
     fn get_ipk_epoch_key(&self) -> Vec<u8> {
         self.ipk_epoch_key.clone()
     }
+
+    fn cert_not_before(&self) -> std::time::SystemTime {
+        self.clock.now()
+    }
+}
+
+/// Bootstrapped [FileCertManager] for fabric 1000 in its own temp directory,
+/// which is removed when this is dropped.
+#[cfg(test)]
+pub(crate) struct TestCertManager {
+    pub cm: Arc<FileCertManager>,
+    dir: std::path::PathBuf,
+}
+
+#[cfg(test)]
+impl TestCertManager {
+    pub const FABRIC_ID: u64 = 1000;
+
+    /// `name` keeps directories of concurrently running tests apart.
+    /// Each of `users` gets an operational certificate.
+    pub fn new(name: &str, users: &[u64]) -> Self {
+        Self::with_clock(name, users, Arc::new(cert_x509::SystemClock))
+    }
+
+    pub fn with_clock(name: &str, users: &[u64], clock: Arc<dyn cert_x509::Clock>) -> Self {
+        let dir = std::env::temp_dir().join(format!("matc_test_{}", name));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let pem = dir.join("pem").to_str().unwrap().to_owned();
+        let cm = FileCertManager::new_with_clock(Self::FABRIC_ID, &pem, clock.clone());
+        cm.bootstrap().unwrap();
+        for id in users {
+            cm.create_user(*id).unwrap();
+        }
+        let cm = FileCertManager::load_with_clock(&pem, clock).unwrap();
+        Self { cm, dir }
+    }
+
+    pub fn fabric(&self) -> crate::fabric::Fabric {
+        crate::fabric::Fabric::new(
+            Self::FABRIC_ID,
+            1,
+            &self.cm.get_ca_public_key().unwrap(),
+            &self.cm.get_ipk_epoch_key(),
+        )
+    }
+}
+
+#[cfg(test)]
+impl Drop for TestCertManager {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.dir);
+    }
 }
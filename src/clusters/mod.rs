@@ -4,4 +4,6 @@ pub mod defs;
 pub mod names;
 pub mod dt_names;
 mod helpers;
+pub mod typed;
+#[allow(clippy::vec_init_then_push)]
 pub mod codec;
\ No newline at end of file
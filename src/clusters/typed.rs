@@ -0,0 +1,1196 @@
+//! Hand-written typed views over the generated codecs.
+//!
+//! Generated decoders follow the cluster XML exactly; the types here add what
+//! the XML leaves out (e.g. fabric index of fabric-scoped entries) or combine
+//! several attributes into one value.
+
+use anyhow::{Context, Result};
+
+use std::collections::{BTreeMap, HashMap};
+use std::net::{Ipv4Addr, Ipv6Addr};
+
+use crate::clusters::codec::{
+    acl_cluster, admin_commissioning_cluster as ac, basic_information_cluster as bi,
+    color_control::colorcapabilities, diagnostics_general, diagnostics_thread as dt,
+    diagnostics_wifi as dw, occupancy_sensing, on_off,
+    operational_credential_cluster, power_source_cluster as ps,
+};
+use crate::clusters::defs;
+use crate::im;
+use crate::tlv::{self, TlvItemValue};
+
+/// Context tag of the FabricIndex field in fabric-scoped structs.
+const FABRIC_INDEX_TAG: u8 = 0xfe;
+
+/// Entry of the Access Control cluster ACL attribute.
+#[derive(Debug, serde::Serialize)]
+pub struct AclEntry {
+    pub privilege: Option<acl_cluster::AccessControlEntryPrivilege>,
+    pub auth_mode: Option<acl_cluster::AccessControlEntryAuthMode>,
+    /// Subjects (node ids / CATs / group ids); empty means any subject
+    pub subjects: Vec<u64>,
+    /// Targets; empty means the whole node
+    pub targets: Vec<acl_cluster::AccessControlTarget>,
+    /// Fabric owning the entry. Differs from own fabric only in unfiltered reads.
+    pub fabric_index: Option<u8>,
+}
+
+/// Decode ACL attribute value into entries including their fabric index.
+pub fn decode_acl_entries(inp: &TlvItemValue) -> Result<Vec<AclEntry>> {
+    let TlvItemValue::List(items) = inp else {
+        anyhow::bail!("ACL attribute is not a list");
+    };
+    let entries = acl_cluster::decode_acl(inp)?;
+    Ok(entries
+        .into_iter()
+        .zip(items.iter())
+        .map(|(e, item)| AclEntry {
+            privilege: e.privilege,
+            auth_mode: e.auth_mode,
+            subjects: e.subjects.unwrap_or_default(),
+            targets: e.targets.unwrap_or_default(),
+            fabric_index: fabric_index(item),
+        })
+        .collect())
+}
+
+fn fabric_index(item: &tlv::TlvItem) -> Option<u8> {
+    item.get_u8(&[FABRIC_INDEX_TAG])
+}
+
+/// Entry of the Operational Credentials NOCs attribute.
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct NocEntry {
+    /// Node operational certificate in matter TLV format.
+    pub noc: Vec<u8>,
+    /// Intermediate certificate, if the fabric uses one.
+    pub icac: Option<Vec<u8>>,
+    pub fabric_index: Option<u8>,
+}
+
+impl NocEntry {
+    /// Node id the certificate was issued for.
+    pub fn node_id(&self) -> Result<u64> {
+        crate::cert_matter::get_subject_node_id(&self.noc)
+    }
+
+    /// Decode the node operational certificate.
+    pub fn noc_cert(&self) -> Result<crate::cert_matter::MatterCert> {
+        crate::cert_matter::MatterCert::decode(&self.noc)
+    }
+
+    /// Decode the intermediate certificate, if present.
+    pub fn icac_cert(&self) -> Result<Option<crate::cert_matter::MatterCert>> {
+        self.icac
+            .as_deref()
+            .map(crate::cert_matter::MatterCert::decode)
+            .transpose()
+    }
+}
+
+/// Decode NOCs attribute value into entries including their fabric index.
+pub fn decode_nocs(inp: &TlvItemValue) -> Result<Vec<NocEntry>> {
+    let TlvItemValue::List(items) = inp else {
+        anyhow::bail!("NOCs attribute is not a list");
+    };
+    items
+        .iter()
+        .map(|item| {
+            Ok(NocEntry {
+                noc: item
+                    .get_octet_string_owned(&[1])
+                    .ok_or_else(|| anyhow::anyhow!("NOC missing in NOCs entry"))?,
+                icac: item.get_octet_string_owned(&[2]),
+                fabric_index: fabric_index(item),
+            })
+        })
+        .collect()
+}
+
+/// Entry of the Operational Credentials TrustedRootCertificates attribute.
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct TrustedRoot {
+    /// Root certificate in matter TLV format.
+    pub cert: Vec<u8>,
+}
+
+impl TrustedRoot {
+    /// Decode the root certificate.
+    pub fn decode(&self) -> Result<crate::cert_matter::MatterCert> {
+        crate::cert_matter::MatterCert::decode(&self.cert)
+    }
+
+    /// Public key of the root (uncompressed SEC1 point).
+    pub fn public_key(&self) -> Result<Vec<u8>> {
+        Ok(self.decode()?.public_key)
+    }
+}
+
+/// True when one of `roots` has public key `ca_public_key`, i.e. the device trusts
+/// our CA and CASE can succeed. Roots which can't be decoded are skipped.
+pub fn shares_root(roots: &[TrustedRoot], ca_public_key: &[u8]) -> bool {
+    roots.iter().any(|root| match root.public_key() {
+        Ok(key) => key == ca_public_key,
+        Err(e) => {
+            log::debug!("skipping undecodable trusted root: {:?}", e);
+            false
+        }
+    })
+}
+
+/// Decode TrustedRootCertificates attribute value.
+pub fn decode_trusted_roots(inp: &TlvItemValue) -> Result<Vec<TrustedRoot>> {
+    Ok(
+        operational_credential_cluster::decode_trusted_root_certificates(inp)?
+            .into_iter()
+            .map(|cert| TrustedRoot { cert })
+            .collect(),
+    )
+}
+
+/// Entry of the General Diagnostics NetworkInterfaces attribute.
+///
+/// The generated [diagnostics_general::NetworkInterface] maps hardware and IP
+/// addresses to integers; here they are decoded from their octet strings.
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+pub struct NetworkInterfaceInfo {
+    pub name: String,
+    pub is_operational: bool,
+    /// MAC (6 bytes) or Thread extended address (8 bytes)
+    pub hardware_address: Vec<u8>,
+    pub ipv4_addresses: Vec<Ipv4Addr>,
+    pub ipv6_addresses: Vec<Ipv6Addr>,
+    pub interface_type: Option<diagnostics_general::InterfaceType>,
+}
+
+/// Decode NetworkInterfaces attribute value.
+pub fn decode_network_interfaces(inp: &TlvItemValue) -> Result<Vec<NetworkInterfaceInfo>> {
+    let TlvItemValue::List(items) = inp else {
+        anyhow::bail!("NetworkInterfaces attribute is not a list");
+    };
+    let mut res = Vec::new();
+    for item in items {
+        res.push(NetworkInterfaceInfo {
+            name: item.get_string_owned(&[0]).unwrap_or_default(),
+            is_operational: item.get_bool(&[1]).unwrap_or(false),
+            hardware_address: item.get_octet_string_owned(&[4]).unwrap_or_default(),
+            ipv4_addresses: octet_strings(item, 5)
+                .filter_map(|b| <[u8; 4]>::try_from(b).ok().map(Ipv4Addr::from))
+                .collect(),
+            ipv6_addresses: octet_strings(item, 6)
+                .filter_map(|b| <[u8; 16]>::try_from(b).ok().map(Ipv6Addr::from))
+                .collect(),
+            interface_type: item
+                .get_int(&[7])
+                .and_then(|v| diagnostics_general::InterfaceType::from_u8(v as u8)),
+        });
+    }
+    Ok(res)
+}
+
+/// Octet strings of the list stored under `tag`; other element types are skipped.
+fn octet_strings(item: &tlv::TlvItem, tag: u8) -> impl Iterator<Item = &[u8]> {
+    let list = match item.get(&[tag]) {
+        Some(TlvItemValue::List(l)) => l.as_slice(),
+        _ => &[],
+    };
+    list.iter().filter_map(|e| match &e.value {
+        TlvItemValue::OctetString(b) => Some(b.as_slice()),
+        _ => None,
+    })
+}
+
+/// Names of the bits set in `value`, using a (mask, name) table such as
+/// [FieldKind::Bitmap](crate::clusters::codec::FieldKind::Bitmap) bits.
+/// Set bits missing from the table are ignored.
+pub fn bitmap_flags(value: u64, bits: &[(u32, &'static str)]) -> Vec<&'static str> {
+    bits.iter()
+        .filter(|(mask, _)| *mask != 0 && value & (*mask as u64) == *mask as u64)
+        .map(|(_, name)| *name)
+        .collect()
+}
+
+/// Color Control ColorCapabilities attribute decoded into flags.
+#[derive(Debug, Clone, Copy, Default, PartialEq, serde::Serialize)]
+pub struct ColorCapabilities {
+    pub hue_sat: bool,
+    pub enhanced_hue: bool,
+    pub color_loop: bool,
+    pub xy: bool,
+    pub color_temp: bool,
+}
+
+/// Decode ColorCapabilities bitmap.
+pub fn decode_color_capabilities(bits: u16) -> ColorCapabilities {
+    let has = |mask: u8| bits & mask as u16 != 0;
+    ColorCapabilities {
+        hue_sat: has(colorcapabilities::HUE_SATURATION),
+        enhanced_hue: has(colorcapabilities::ENHANCED_HUE),
+        color_loop: has(colorcapabilities::COLOR_LOOP),
+        xy: has(colorcapabilities::XY),
+        color_temp: has(colorcapabilities::COLOR_TEMPERATURE),
+    }
+}
+
+/// Decode attribute `id` from `attrs` with `decode`; `None` when the attribute is missing
+/// or does not decode.
+fn attr<T>(
+    attrs: &HashMap<u32, TlvItemValue>,
+    id: u32,
+    decode: fn(&TlvItemValue) -> Result<T>,
+) -> Option<T> {
+    attrs.get(&id).and_then(|v| decode(v).ok())
+}
+
+/// Selected Basic Information attributes, as shown in device detail views.
+/// Every field is optional: attributes missing from the report, or with an
+/// unexpected type, are `None`.
+#[derive(Debug, Clone, Default, PartialEq, serde::Serialize)]
+pub struct BasicInfo {
+    pub vendor_name: Option<String>,
+    pub vendor_id: Option<u16>,
+    pub product_name: Option<String>,
+    pub product_id: Option<u16>,
+    pub node_label: Option<String>,
+    pub hardware_version: Option<u16>,
+    pub hardware_version_string: Option<String>,
+    pub software_version: Option<u32>,
+    pub software_version_string: Option<String>,
+    pub product_url: Option<String>,
+    pub serial_number: Option<String>,
+    pub unique_id: Option<String>,
+}
+
+impl BasicInfo {
+    /// Build from attribute values keyed by attribute id, e.g. the result of
+    /// [crate::controller::Connection::read_cluster] on the Basic Information cluster.
+    pub fn from_attributes(attrs: &HashMap<u32, TlvItemValue>) -> Self {
+        BasicInfo {
+            vendor_name: attr(attrs, defs::CLUSTER_BASIC_INFORMATION_ATTR_ID_VENDORNAME, bi::decode_vendor_name),
+            vendor_id: attr(attrs, defs::CLUSTER_BASIC_INFORMATION_ATTR_ID_VENDORID, bi::decode_vendor_id),
+            product_name: attr(attrs, defs::CLUSTER_BASIC_INFORMATION_ATTR_ID_PRODUCTNAME, bi::decode_product_name),
+            product_id: attr(attrs, defs::CLUSTER_BASIC_INFORMATION_ATTR_ID_PRODUCTID, bi::decode_product_id),
+            node_label: attr(attrs, defs::CLUSTER_BASIC_INFORMATION_ATTR_ID_NODELABEL, bi::decode_node_label),
+            hardware_version: attr(attrs, defs::CLUSTER_BASIC_INFORMATION_ATTR_ID_HARDWAREVERSION, bi::decode_hardware_version),
+            hardware_version_string: attr(attrs, defs::CLUSTER_BASIC_INFORMATION_ATTR_ID_HARDWAREVERSIONSTRING, bi::decode_hardware_version_string),
+            software_version: attr(attrs, defs::CLUSTER_BASIC_INFORMATION_ATTR_ID_SOFTWAREVERSION, bi::decode_software_version),
+            software_version_string: attr(attrs, defs::CLUSTER_BASIC_INFORMATION_ATTR_ID_SOFTWAREVERSIONSTRING, bi::decode_software_version_string),
+            product_url: attr(attrs, defs::CLUSTER_BASIC_INFORMATION_ATTR_ID_PRODUCTURL, bi::decode_product_url),
+            serial_number: attr(attrs, defs::CLUSTER_BASIC_INFORMATION_ATTR_ID_SERIALNUMBER, bi::decode_serial_number),
+            unique_id: attr(attrs, defs::CLUSTER_BASIC_INFORMATION_ATTR_ID_UNIQUEID, bi::decode_unique_id),
+        }
+    }
+}
+
+/// Thread Network Diagnostics summary: mesh membership, role and radio neighborhood.
+///
+/// Scalar fields are `None` when missing from the report or null (interface not attached).
+#[derive(Debug, Default, serde::Serialize)]
+pub struct ThreadDiagnostics {
+    pub channel: Option<u16>,
+    pub routing_role: Option<dt::RoutingRole>,
+    pub network_name: Option<String>,
+    pub pan_id: Option<u16>,
+    pub extended_pan_id: Option<u64>,
+    pub partition_id: Option<u32>,
+    pub leader_router_id: Option<u8>,
+    pub ext_address: Option<u64>,
+    pub rloc16: Option<u16>,
+    pub active_network_faults: Vec<dt::NetworkFault>,
+    pub neighbors: Vec<dt::NeighborTable>,
+    pub routes: Vec<dt::RouteTable>,
+}
+
+impl ThreadDiagnostics {
+    /// Build from attribute values keyed by attribute id, e.g. the result of
+    /// [crate::controller::Connection::read_cluster] on the Thread Network Diagnostics cluster.
+    pub fn from_attributes(attrs: &HashMap<u32, TlvItemValue>) -> Self {
+        ThreadDiagnostics {
+            channel: attr(attrs, defs::CLUSTER_THREAD_NETWORK_DIAGNOSTICS_ATTR_ID_CHANNEL, dt::decode_channel).flatten(),
+            routing_role: attr(attrs, defs::CLUSTER_THREAD_NETWORK_DIAGNOSTICS_ATTR_ID_ROUTINGROLE, dt::decode_routing_role).flatten(),
+            network_name: attr(attrs, defs::CLUSTER_THREAD_NETWORK_DIAGNOSTICS_ATTR_ID_NETWORKNAME, dt::decode_network_name).flatten(),
+            pan_id: attr(attrs, defs::CLUSTER_THREAD_NETWORK_DIAGNOSTICS_ATTR_ID_PANID, dt::decode_pan_id).flatten(),
+            extended_pan_id: attr(attrs, defs::CLUSTER_THREAD_NETWORK_DIAGNOSTICS_ATTR_ID_EXTENDEDPANID, dt::decode_extended_pan_id).flatten(),
+            partition_id: attr(attrs, defs::CLUSTER_THREAD_NETWORK_DIAGNOSTICS_ATTR_ID_PARTITIONID, dt::decode_partition_id).flatten(),
+            leader_router_id: attr(attrs, defs::CLUSTER_THREAD_NETWORK_DIAGNOSTICS_ATTR_ID_LEADERROUTERID, dt::decode_leader_router_id).flatten(),
+            ext_address: attr(attrs, defs::CLUSTER_THREAD_NETWORK_DIAGNOSTICS_ATTR_ID_EXTADDRESS, dt::decode_ext_address).flatten(),
+            rloc16: attr(attrs, defs::CLUSTER_THREAD_NETWORK_DIAGNOSTICS_ATTR_ID_RLOC16, dt::decode_rloc16).flatten(),
+            active_network_faults: attr(attrs, defs::CLUSTER_THREAD_NETWORK_DIAGNOSTICS_ATTR_ID_ACTIVENETWORKFAULTSLIST, dt::decode_active_network_faults_list).unwrap_or_default(),
+            neighbors: attr(attrs, defs::CLUSTER_THREAD_NETWORK_DIAGNOSTICS_ATTR_ID_NEIGHBORTABLE, dt::decode_neighbor_table).unwrap_or_default(),
+            routes: attr(attrs, defs::CLUSTER_THREAD_NETWORK_DIAGNOSTICS_ATTR_ID_ROUTETABLE, dt::decode_route_table).unwrap_or_default(),
+        }
+    }
+}
+
+/// Wi-Fi Network Diagnostics summary: access point, channel and signal strength.
+#[derive(Debug, Clone, Default, PartialEq, serde::Serialize)]
+pub struct WifiDiagnostics {
+    pub bssid: Option<Vec<u8>>,
+    pub security_type: Option<dw::SecurityType>,
+    pub wifi_version: Option<dw::WiFiVersion>,
+    pub channel: Option<u16>,
+    /// Signal strength in dBm.
+    pub rssi: Option<i8>,
+    pub beacon_lost_count: Option<u32>,
+}
+
+impl WifiDiagnostics {
+    /// Build from attribute values keyed by attribute id, e.g. the result of
+    /// [crate::controller::Connection::read_cluster] on the Wi-Fi Network Diagnostics cluster.
+    pub fn from_attributes(attrs: &HashMap<u32, TlvItemValue>) -> Self {
+        WifiDiagnostics {
+            bssid: attr(attrs, defs::CLUSTER_WI_FI_NETWORK_DIAGNOSTICS_ATTR_ID_BSSID, dw::decode_bssid).flatten(),
+            security_type: attr(attrs, defs::CLUSTER_WI_FI_NETWORK_DIAGNOSTICS_ATTR_ID_SECURITYTYPE, dw::decode_security_type).flatten(),
+            wifi_version: attr(attrs, defs::CLUSTER_WI_FI_NETWORK_DIAGNOSTICS_ATTR_ID_WIFIVERSION, dw::decode_wifi_version).flatten(),
+            channel: attr(attrs, defs::CLUSTER_WI_FI_NETWORK_DIAGNOSTICS_ATTR_ID_CHANNELNUMBER, dw::decode_channel_number).flatten(),
+            rssi: attr(attrs, defs::CLUSTER_WI_FI_NETWORK_DIAGNOSTICS_ATTR_ID_RSSI, dw::decode_rssi).flatten(),
+            beacon_lost_count: attr(attrs, defs::CLUSTER_WI_FI_NETWORK_DIAGNOSTICS_ATTR_ID_BEACONLOSTCOUNT, dw::decode_beacon_lost_count).flatten(),
+        }
+    }
+}
+
+/// General Commissioning BasicCommissioningInfo: fail-safe timing limits of the device.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize)]
+pub struct BasicCommissioningInfo {
+    /// Fail-safe expiry length the device recommends for the initial ArmFailSafe.
+    pub failsafe_expiry_len: u16,
+    /// Upper bound of cumulative fail-safe time; longer ArmFailSafe is not accepted.
+    pub max_cumulative_failsafe: u16,
+}
+
+impl BasicCommissioningInfo {
+    /// Fail-safe timeout to request: `wanted` limited to the cumulative maximum,
+    /// but not below the device's recommended expiry length.
+    pub fn failsafe_timeout(&self, wanted: u16) -> u16 {
+        wanted
+            .max(self.failsafe_expiry_len)
+            .min(self.max_cumulative_failsafe)
+    }
+}
+
+/// Decode GeneralCommissioning BasicCommissioningInfo attribute.
+pub fn decode_basic_commissioning_info(inp: &TlvItemValue) -> Result<BasicCommissioningInfo> {
+    let item = tlv::TlvItem { tag: 0, value: inp.clone() };
+    Ok(BasicCommissioningInfo {
+        failsafe_expiry_len: item
+            .get_u16(&[0])
+            .ok_or_else(|| anyhow::anyhow!("FailSafeExpiryLengthSeconds missing"))?,
+        max_cumulative_failsafe: item
+            .get_u16(&[1])
+            .ok_or_else(|| anyhow::anyhow!("MaxCumulativeFailsafeSeconds missing"))?,
+    })
+}
+
+/// Administrator Commissioning window state. `admin_fabric_index` and
+/// `admin_vendor_id` are null on the device while no window is open, and also
+/// for a basic window opened outside of any fabric.
+#[derive(Debug, Clone, Default, PartialEq, serde::Serialize)]
+pub struct CommissioningWindowInfo {
+    pub status: Option<ac::CommissioningWindowStatus>,
+    pub admin_fabric_index: Option<u8>,
+    pub admin_vendor_id: Option<u16>,
+}
+
+impl CommissioningWindowInfo {
+    /// Build from attribute values keyed by attribute id, e.g. the result of
+    /// [crate::controller::Connection::read_cluster] on the Administrator Commissioning cluster.
+    pub fn from_attributes(attrs: &HashMap<u32, TlvItemValue>) -> Self {
+        let attr = |id: u32| attrs.get(&id);
+        CommissioningWindowInfo {
+            status: attr(defs::CLUSTER_ADMINISTRATOR_COMMISSIONING_ATTR_ID_WINDOWSTATUS)
+                .and_then(|v| ac::decode_window_status(v).ok()),
+            admin_fabric_index: attr(defs::CLUSTER_ADMINISTRATOR_COMMISSIONING_ATTR_ID_ADMINFABRICINDEX)
+                .and_then(|v| ac::decode_admin_fabric_index(v).ok().flatten()),
+            admin_vendor_id: attr(defs::CLUSTER_ADMINISTRATOR_COMMISSIONING_ATTR_ID_ADMINVENDORID)
+                .and_then(|v| ac::decode_admin_vendor_id(v).ok().flatten()),
+        }
+    }
+
+    /// True when an enhanced or basic commissioning window is open.
+    pub fn is_open(&self) -> bool {
+        matches!(
+            self.status,
+            Some(ac::CommissioningWindowStatus::Enhancedwindowopen)
+                | Some(ac::CommissioningWindowStatus::Basicwindowopen)
+        )
+    }
+}
+
+/// Battery state from the Power Source cluster of a battery powered device.
+#[derive(Debug, Clone, Default, PartialEq, serde::Serialize)]
+pub struct BatteryStatus {
+    pub status: Option<ps::PowerSourceStatus>,
+    pub description: Option<String>,
+    /// Remaining charge in percent, 0.0 - 100.0. Device reports it in half-percent units;
+    /// `None` when unknown (null) or out of range.
+    pub percent_remaining: Option<f32>,
+    pub charge_level: Option<ps::BatChargeLevel>,
+}
+
+impl BatteryStatus {
+    /// Build from attribute values keyed by attribute id, e.g. the result of
+    /// [crate::controller::Connection::read_cluster] on the Power Source cluster.
+    pub fn from_attributes(attrs: &HashMap<u32, TlvItemValue>) -> Self {
+        let attr = |id: u32| attrs.get(&id);
+        BatteryStatus {
+            status: attr(defs::CLUSTER_POWER_SOURCE_ATTR_ID_STATUS)
+                .and_then(|v| ps::decode_status(v).ok()),
+            description: attr(defs::CLUSTER_POWER_SOURCE_ATTR_ID_DESCRIPTION)
+                .and_then(|v| ps::decode_description(v).ok()),
+            percent_remaining: attr(defs::CLUSTER_POWER_SOURCE_ATTR_ID_BATPERCENTREMAINING)
+                .and_then(|v| ps::decode_bat_percent_remaining(v).ok().flatten())
+                .and_then(battery_percent),
+            charge_level: attr(defs::CLUSTER_POWER_SOURCE_ATTR_ID_BATCHARGELEVEL)
+                .and_then(|v| ps::decode_bat_charge_level(v).ok()),
+        }
+    }
+}
+
+/// Convert BatPercentRemaining (half-percent units, 0 - 200) to percent.
+pub fn battery_percent(half_percent: u8) -> Option<f32> {
+    if half_percent > 200 {
+        return None;
+    }
+    Some(half_percent as f32 / 2.0)
+}
+
+/// Temperature Measurement MeasuredValue (0.01 °C units) in °C.
+pub fn temperature_celsius(raw: i16) -> f32 {
+    raw as f32 / 100.0
+}
+
+/// Relative Humidity Measurement MeasuredValue (0.01 % units) in %RH.
+/// `None` for values above 100 %.
+pub fn relative_humidity_percent(raw: u16) -> Option<f32> {
+    if raw > 10000 {
+        return None;
+    }
+    Some(raw as f32 / 100.0)
+}
+
+/// Illuminance Measurement MeasuredValue in lux. Device reports
+/// `10000 * log10(lux) + 1`; 0 means too low to be measured.
+pub fn illuminance_lux(raw: u16) -> f64 {
+    if raw == 0 {
+        return 0.0;
+    }
+    10f64.powf((raw as f64 - 1.0) / 10000.0)
+}
+
+/// Nullable MeasuredValue: `None` for null, error for anything but an integer.
+fn decode_nullable_int(inp: &TlvItemValue) -> Result<Option<u64>> {
+    match inp {
+        TlvItemValue::Nil() => Ok(None),
+        TlvItemValue::Int(v) => Ok(Some(*v)),
+        _ => Err(anyhow::anyhow!("MeasuredValue: expected integer or null")),
+    }
+}
+
+/// Decode Temperature Measurement MeasuredValue in °C, `None` when null.
+pub fn decode_temperature(inp: &TlvItemValue) -> Result<Option<f32>> {
+    match decode_nullable_int(inp)? {
+        Some(v) => {
+            // signed TLV integers are kept sign-extended
+            let raw = i16::try_from(v as i64).context("MeasuredValue out of range")?;
+            Ok(Some(temperature_celsius(raw)))
+        }
+        None => Ok(None),
+    }
+}
+
+/// Decode Relative Humidity Measurement MeasuredValue in %RH, `None` when null.
+pub fn decode_relative_humidity(inp: &TlvItemValue) -> Result<Option<f32>> {
+    match decode_nullable_int(inp)? {
+        Some(v) => {
+            let raw = u16::try_from(v).context("MeasuredValue out of range")?;
+            Ok(relative_humidity_percent(raw))
+        }
+        None => Ok(None),
+    }
+}
+
+/// Decode Illuminance Measurement MeasuredValue in lux, `None` when null.
+pub fn decode_illuminance(inp: &TlvItemValue) -> Result<Option<f64>> {
+    match decode_nullable_int(inp)? {
+        Some(v) => Ok(Some(illuminance_lux(u16::try_from(v).context("MeasuredValue out of range")?))),
+        None => Ok(None),
+    }
+}
+
+/// Decode Occupancy Sensing Occupancy bitmap: true when occupied.
+pub fn decode_occupied(inp: &TlvItemValue) -> Result<bool> {
+    let bits = occupancy_sensing::decode_occupancy(inp)?;
+    Ok(bits & occupancy_sensing::occupancy::OCCUPIED != 0)
+}
+
+/// Maximum length in bytes of label and value of a UserLabel / FixedLabel entry.
+pub const MAX_LABEL_LEN: usize = 16;
+
+/// Entry of the UserLabel or FixedLabel LabelList attribute.
+///
+/// The generated label cluster codecs don't know the LabelStruct of the
+/// separate Label cluster definition and decode entries as integers.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+pub struct Label {
+    pub label: String,
+    pub value: String,
+}
+
+/// Decode UserLabel / FixedLabel LabelList attribute value.
+pub fn decode_labels(inp: &TlvItemValue) -> Result<Vec<Label>> {
+    let TlvItemValue::List(items) = inp else {
+        anyhow::bail!("LabelList: expected list, got {:?}", inp);
+    };
+    items
+        .iter()
+        .map(|item| {
+            Ok(Label {
+                label: item.get_string_owned(&[0]).context("LabelList: label missing")?,
+                value: item.get_string_owned(&[1]).context("LabelList: value missing")?,
+            })
+        })
+        .collect()
+}
+
+/// Encode UserLabel LabelList for [crate::controller::Connection::write_request].
+/// The written list replaces all labels of the endpoint.
+pub fn encode_labels(labels: &[(String, String)]) -> Result<Vec<u8>> {
+    let mut tlv = tlv::TlvBuffer::new();
+    tlv.write_array(ATTRIBUTE_DATA_TAG)?;
+    for (label, value) in labels {
+        for (name, s) in [("label", label), ("value", value)] {
+            if s.len() > MAX_LABEL_LEN {
+                anyhow::bail!("{} {:?} is {} bytes, maximum is {}", name, s, s.len(), MAX_LABEL_LEN);
+            }
+        }
+        tlv.write_anon_struct()?;
+        tlv.write_string(0, label)?;
+        tlv.write_string(1, value)?;
+        tlv.write_struct_end()?;
+    }
+    tlv.write_struct_end()?;
+    Ok(tlv.data)
+}
+
+/// Semantic tag from Descriptor TagList, labelling an endpoint within a device
+/// (e.g. left / right switch, outlet number).
+///
+/// The generated descriptor codec decodes TagList entries as integers.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+pub struct SemanticTag {
+    /// Manufacturer of a manufacturer specific namespace; None for standard namespaces.
+    pub mfg_code: Option<u16>,
+    pub namespace_id: u8,
+    pub tag: u8,
+    pub label: Option<String>,
+}
+
+/// Decode Descriptor TagList attribute value.
+pub fn decode_semantic_tags(inp: &TlvItemValue) -> Result<Vec<SemanticTag>> {
+    let TlvItemValue::List(items) = inp else {
+        anyhow::bail!("TagList: expected list, got {:?}", inp);
+    };
+    items
+        .iter()
+        .map(|item| {
+            let mfg_code = match item.get_int(&[0]) {
+                Some(v) => Some(u16::try_from(v).context("TagList: mfgCode out of range")?),
+                None => None,
+            };
+            let namespace_id = item.get_int(&[1]).context("TagList: namespaceID missing")?;
+            let tag = item.get_int(&[2]).context("TagList: tag missing")?;
+            Ok(SemanticTag {
+                mfg_code,
+                namespace_id: u8::try_from(namespace_id).context("TagList: namespaceID out of range")?,
+                tag: u8::try_from(tag).context("TagList: tag out of range")?,
+                label: item.get_string_owned(&[3]),
+            })
+        })
+        .collect()
+}
+
+/// Context tag of the attribute value in an AttributeDataIB of a write request.
+const ATTRIBUTE_DATA_TAG: u8 = 2;
+/// Highest LevelControl level; 255 is reserved for null.
+const MAX_LEVEL: u8 = 254;
+/// Highest ColorControl color temperature in mireds.
+const MAX_COLOR_TEMPERATURE_MIREDS: u16 = 0xfeff;
+
+/// Encode OnOff StartUpOnOff for [crate::controller::Connection::write_request].
+/// `None` (null) restores the on/off state from before power loss.
+pub fn encode_start_up_on_off(value: Option<on_off::StartUpOnOff>) -> Result<Vec<u8>> {
+    let mut tlv = tlv::TlvBuffer::new();
+    match value {
+        Some(v) => tlv.write_uint8(ATTRIBUTE_DATA_TAG, v.into())?,
+        None => tlv.write_null(ATTRIBUTE_DATA_TAG)?,
+    }
+    Ok(tlv.data)
+}
+
+/// Encode nullable LevelControl level (StartUpCurrentLevel, OnLevel) for
+/// [crate::controller::Connection::write_request]. `None` (null) means the previous
+/// level: from before power loss for StartUpCurrentLevel, before switching off for OnLevel.
+pub fn encode_nullable_level(value: Option<u8>) -> Result<Vec<u8>> {
+    let mut tlv = tlv::TlvBuffer::new();
+    match value {
+        Some(v) if v > MAX_LEVEL => {
+            anyhow::bail!("level {} out of range 0..={}", v, MAX_LEVEL)
+        }
+        Some(v) => tlv.write_uint8(ATTRIBUTE_DATA_TAG, v)?,
+        None => tlv.write_null(ATTRIBUTE_DATA_TAG)?,
+    }
+    Ok(tlv.data)
+}
+
+/// Encode ColorControl StartUpColorTemperatureMireds for
+/// [crate::controller::Connection::write_request]. `None` (null) keeps the color
+/// temperature from before power loss.
+pub fn encode_start_up_color_temperature(mireds: Option<u16>) -> Result<Vec<u8>> {
+    let mut tlv = tlv::TlvBuffer::new();
+    match mireds {
+        Some(v) if v > MAX_COLOR_TEMPERATURE_MIREDS => {
+            anyhow::bail!(
+                "color temperature {} mireds out of range 0..={}",
+                v,
+                MAX_COLOR_TEMPERATURE_MIREDS
+            )
+        }
+        Some(v) => tlv.write_uint16(ATTRIBUTE_DATA_TAG, v)?,
+        None => tlv.write_null(ATTRIBUTE_DATA_TAG)?,
+    }
+    Ok(tlv.data)
+}
+
+/// Name of global attribute present in every cluster.
+pub fn global_attribute_name(attr: u32) -> Option<&'static str> {
+    match attr {
+        0xfff8 => Some("GeneratedCommandList"),
+        0xfff9 => Some("AcceptedCommandList"),
+        0xfffa => Some("EventList"),
+        0xfffb => Some("AttributeList"),
+        0xfffc => Some("FeatureMap"),
+        0xfffd => Some("ClusterRevision"),
+        _ => None,
+    }
+}
+
+/// Name of attribute `attr` of `cluster`, cluster specific or global.
+pub fn attribute_name(cluster: u32, attr: u32) -> Option<&'static str> {
+    crate::clusters::codec::get_attribute_list(cluster)
+        .into_iter()
+        .find(|(id, _)| *id == attr)
+        .map(|(_, name)| name)
+        .or_else(|| global_attribute_name(attr))
+}
+
+/// JSON form of one reported attribute: its name and value decoded by the cluster
+/// codec (generic TLV form when the codec can't decode it), or the error status.
+pub fn attribute_json(cluster: u32, attr: u32, data: &im::AttributeData) -> serde_json::Value {
+    let mut out = serde_json::Map::new();
+    out.insert("name".to_owned(), attribute_name(cluster, attr).into());
+    match data {
+        im::AttributeData::Value(v) => {
+            let decoded = serde_json::from_str::<serde_json::Value>(
+                &crate::clusters::codec::decode_attribute_json(cluster, attr, v),
+            )
+            .ok()
+            .filter(|d| d.get("error").is_none());
+            out.insert("value".to_owned(), decoded.unwrap_or_else(|| v.to_json()));
+        }
+        im::AttributeData::Status { status, cluster_status } => {
+            out.insert("status".to_owned(), (*status).into());
+            if let Some(cs) = cluster_status {
+                out.insert("cluster_status".to_owned(), (*cs).into());
+            }
+        }
+    }
+    serde_json::Value::Object(out)
+}
+
+/// JSON form of one cluster instance: its name and attributes keyed by hex id.
+pub fn cluster_json(cluster: u32, attrs: &BTreeMap<u32, im::AttributeData>) -> serde_json::Value {
+    let attributes: serde_json::Map<String, serde_json::Value> = attrs
+        .iter()
+        .map(|(attr, data)| (format!("0x{:04x}", attr), attribute_json(cluster, *attr, data)))
+        .collect();
+    serde_json::json!({
+        "name": crate::clusters::names::get_cluster_name(cluster),
+        "attributes": attributes,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use acl_cluster::{AccessControlEntryAuthMode, AccessControlEntryPrivilege};
+
+    #[test]
+    fn test_decode_color_capabilities() {
+        assert_eq!(decode_color_capabilities(0), ColorCapabilities::default());
+        // color temperature only (tunable white bulb)
+        assert_eq!(
+            decode_color_capabilities(0x10),
+            ColorCapabilities { color_temp: true, ..Default::default() }
+        );
+        // extended color light: hue/sat, enhanced hue, loop, xy, temperature
+        let all = decode_color_capabilities(0x1f);
+        assert!(all.hue_sat && all.enhanced_hue && all.color_loop && all.xy && all.color_temp);
+        let c = decode_color_capabilities(0x09);
+        assert!(c.hue_sat && c.xy);
+        assert!(!c.enhanced_hue && !c.color_loop && !c.color_temp);
+    }
+
+    #[test]
+    fn test_bitmap_flags() {
+        const BITS: &[(u32, &str)] = &[(0x1, "LIGHTING"), (0x2, "DEAD_FRONT"), (0x4, "OFF_ONLY")];
+        assert_eq!(bitmap_flags(0x5, BITS), vec!["LIGHTING", "OFF_ONLY"]);
+        assert!(bitmap_flags(0, BITS).is_empty());
+        // unknown bits are ignored
+        assert_eq!(bitmap_flags(0x82, BITS), vec!["DEAD_FRONT"]);
+    }
+
+    #[test]
+    fn test_decode_acl_entries() {
+        // ACL read unfiltered from a device with two fabrics: admin entry of
+        // fabric 1 and an operate entry limited to OnOff on endpoint 1 of fabric 2.
+        let data = hex::decode(concat!(
+            "16",
+            "1524010524020236030464183404",
+            "24fe0118",
+            "15240103240202360307887766554433221118",
+            "3604152400062401011818",
+            "24fe0218",
+            "18"
+        ))
+        .unwrap();
+        let acl = tlv::decode_tlv(&data).unwrap();
+        let entries = decode_acl_entries(&acl.value).unwrap();
+        assert_eq!(entries.len(), 2);
+
+        assert_eq!(entries[0].privilege, Some(AccessControlEntryPrivilege::Administer));
+        assert_eq!(entries[0].auth_mode, Some(AccessControlEntryAuthMode::Case));
+        assert_eq!(entries[0].subjects, vec![100]);
+        assert!(entries[0].targets.is_empty());
+        assert_eq!(entries[0].fabric_index, Some(1));
+
+        assert_eq!(entries[1].privilege, Some(AccessControlEntryPrivilege::Operate));
+        assert_eq!(entries[1].subjects, vec![0x1122334455667788]);
+        assert_eq!(entries[1].targets.len(), 1);
+        assert_eq!(entries[1].targets[0].cluster, Some(6));
+        assert_eq!(entries[1].targets[0].endpoint, Some(1));
+        assert_eq!(entries[1].targets[0].device_type, None);
+        assert_eq!(entries[1].fabric_index, Some(2));
+    }
+
+    #[test]
+    fn test_decode_network_interfaces() {
+        // eth0 with one IPv4 and two IPv6 addresses, followed by a Thread
+        // interface which is down and has no addresses.
+        let data = hex::decode(concat!(
+            "16",
+            "15",
+            "2c0004657468302901",
+            "34023403",
+            "3004060242ac110002",
+            "36051004c0a8016418",
+            "3606",
+            "1010fe800000000000000042acfffe110002",
+            "1010fd000000000000000000000000000002",
+            "18",
+            "240702",
+            "18",
+            "15",
+            "2c0007746872656164302801",
+            "3004081122334455667788",
+            "360518360618240704",
+            "18",
+            "18"
+        ))
+        .unwrap();
+        let value = tlv::decode_tlv(&data).unwrap();
+        let ifaces = decode_network_interfaces(&value.value).unwrap();
+        assert_eq!(ifaces.len(), 2);
+
+        let eth = &ifaces[0];
+        assert_eq!(eth.name, "eth0");
+        assert!(eth.is_operational);
+        assert_eq!(eth.hardware_address, vec![0x02, 0x42, 0xac, 0x11, 0x00, 0x02]);
+        assert_eq!(eth.ipv4_addresses, vec![Ipv4Addr::new(192, 168, 1, 100)]);
+        assert_eq!(
+            eth.ipv6_addresses,
+            vec![
+                "fe80::42:acff:fe11:2".parse::<Ipv6Addr>().unwrap(),
+                "fd00::2".parse::<Ipv6Addr>().unwrap(),
+            ]
+        );
+        assert_eq!(eth.interface_type, Some(diagnostics_general::InterfaceType::Ethernet));
+
+        let thread = &ifaces[1];
+        assert_eq!(thread.name, "thread0");
+        assert!(!thread.is_operational);
+        assert_eq!(thread.hardware_address.len(), 8);
+        assert!(thread.ipv4_addresses.is_empty());
+        assert!(thread.ipv6_addresses.is_empty());
+        assert_eq!(thread.interface_type, Some(diagnostics_general::InterfaceType::Thread));
+    }
+
+    #[test]
+    fn test_thread_diagnostics() {
+        let mut neighbors = tlv::TlvBuffer::new();
+        neighbors.write_anon_list().unwrap();
+        neighbors.write_anon_struct().unwrap();
+        neighbors.write_uint64(0, 0x1122334455667788).unwrap();
+        neighbors.write_uint32(1, 12).unwrap();
+        neighbors.write_uint16(2, 0x0400).unwrap();
+        neighbors.write_uint8(5, 3).unwrap();
+        neighbors.write_int8(6, -60).unwrap();
+        neighbors.write_int8(7, -72).unwrap();
+        neighbors.write_bool(10, true).unwrap();
+        neighbors.write_bool(13, false).unwrap();
+        neighbors.write_struct_end().unwrap();
+        neighbors.write_struct_end().unwrap();
+
+        let mut routes = tlv::TlvBuffer::new();
+        routes.write_anon_list().unwrap();
+        routes.write_anon_struct().unwrap();
+        routes.write_uint64(0, 0xaabbccddeeff0011).unwrap();
+        routes.write_uint16(1, 0x2000).unwrap();
+        routes.write_uint8(2, 8).unwrap();
+        routes.write_uint8(3, 63).unwrap();
+        routes.write_uint8(4, 1).unwrap();
+        routes.write_bool(8, true).unwrap();
+        routes.write_bool(9, true).unwrap();
+        routes.write_struct_end().unwrap();
+        routes.write_struct_end().unwrap();
+
+        let attrs = HashMap::from([
+            (0x00, TlvItemValue::Int(15)),
+            (0x01, TlvItemValue::Int(5)),
+            (0x02, TlvItemValue::String("OpenThread".into())),
+            (0x03, TlvItemValue::Int(0x1234)),
+            (0x07, tlv::decode_tlv(&neighbors.data).unwrap().value),
+            (0x08, tlv::decode_tlv(&routes.data).unwrap().value),
+            (0x09, TlvItemValue::Nil()),
+            (0x3e, TlvItemValue::List(Vec::new())),
+        ]);
+        let diag = ThreadDiagnostics::from_attributes(&attrs);
+        assert_eq!(diag.channel, Some(15));
+        assert_eq!(diag.routing_role, Some(dt::RoutingRole::Router));
+        assert_eq!(diag.network_name.as_deref(), Some("OpenThread"));
+        assert_eq!(diag.pan_id, Some(0x1234));
+        assert_eq!(diag.partition_id, None);
+        assert_eq!(diag.rloc16, None);
+        assert!(diag.active_network_faults.is_empty());
+
+        assert_eq!(diag.neighbors.len(), 1);
+        let n = &diag.neighbors[0];
+        assert_eq!(n.ext_address, Some(0x1122334455667788));
+        assert_eq!(n.rloc16, Some(0x0400));
+        assert_eq!(n.lqi, Some(3));
+        assert_eq!(n.average_rssi, Some(-60));
+        assert_eq!(n.last_rssi, Some(-72));
+        assert_eq!(n.rx_on_when_idle, Some(true));
+        assert_eq!(n.is_child, Some(false));
+        assert_eq!(n.full_thread_device, None);
+
+        assert_eq!(diag.routes.len(), 1);
+        let r = &diag.routes[0];
+        assert_eq!(r.rloc16, Some(0x2000));
+        assert_eq!(r.router_id, Some(8));
+        assert_eq!(r.next_hop, Some(63));
+        assert_eq!(r.path_cost, Some(1));
+        assert_eq!(r.link_established, Some(true));
+    }
+
+    #[test]
+    fn test_wifi_diagnostics() {
+        let mut rssi = tlv::TlvBuffer::new();
+        rssi.write_int8(0, -48).unwrap();
+        let attrs = HashMap::from([
+            (0x00, TlvItemValue::OctetString(vec![0, 1, 2, 3, 4, 5])),
+            (0x01, TlvItemValue::Int(4)),
+            (0x03, TlvItemValue::Int(36)),
+            (0x04, tlv::decode_tlv(&rssi.data).unwrap().value),
+            (0x05, TlvItemValue::Nil()),
+        ]);
+        let diag = WifiDiagnostics::from_attributes(&attrs);
+        assert_eq!(diag.bssid, Some(vec![0, 1, 2, 3, 4, 5]));
+        assert_eq!(diag.security_type, Some(dw::SecurityType::Wpa2));
+        assert_eq!(diag.wifi_version, None);
+        assert_eq!(diag.channel, Some(36));
+        assert_eq!(diag.rssi, Some(-48));
+        assert_eq!(diag.beacon_lost_count, None);
+    }
+
+    #[test]
+    fn test_basic_info_mandatory_subset() {
+        let attrs = HashMap::from([
+            (0x00, TlvItemValue::Int(17)),
+            (0x01, TlvItemValue::String("TEST_VENDOR".into())),
+            (0x02, TlvItemValue::Int(0xfff1)),
+            (0x03, TlvItemValue::String("TEST_PRODUCT".into())),
+            (0x04, TlvItemValue::Int(0x8001)),
+            (0x05, TlvItemValue::String("".into())),
+            (0x06, TlvItemValue::String("XX".into())),
+            (0x07, TlvItemValue::Int(0)),
+            (0x08, TlvItemValue::String("TEST_VERSION".into())),
+            (0x09, TlvItemValue::Int(1)),
+            (0x0a, TlvItemValue::String("1.0".into())),
+        ]);
+        let info = BasicInfo::from_attributes(&attrs);
+        assert_eq!(info.vendor_name.as_deref(), Some("TEST_VENDOR"));
+        assert_eq!(info.vendor_id, Some(0xfff1));
+        assert_eq!(info.product_name.as_deref(), Some("TEST_PRODUCT"));
+        assert_eq!(info.product_id, Some(0x8001));
+        assert_eq!(info.node_label.as_deref(), Some(""));
+        assert_eq!(info.hardware_version, Some(0));
+        assert_eq!(info.hardware_version_string.as_deref(), Some("TEST_VERSION"));
+        assert_eq!(info.software_version, Some(1));
+        assert_eq!(info.software_version_string.as_deref(), Some("1.0"));
+        assert_eq!(info.product_url, None);
+        assert_eq!(info.serial_number, None);
+        assert_eq!(info.unique_id, None);
+
+        // wrong type for an attribute only drops that field
+        let attrs = HashMap::from([(0x09, TlvItemValue::String("1".into())), (0x02, TlvItemValue::Int(1))]);
+        let info = BasicInfo::from_attributes(&attrs);
+        assert_eq!(info.software_version, None);
+        assert_eq!(info.vendor_id, Some(1));
+    }
+
+    #[test]
+    fn test_commissioning_window_info() {
+        let closed = HashMap::from([
+            (0x00, TlvItemValue::Int(0)),
+            (0x01, TlvItemValue::Nil()),
+            (0x02, TlvItemValue::Nil()),
+        ]);
+        let info = CommissioningWindowInfo::from_attributes(&closed);
+        assert_eq!(info.status, Some(ac::CommissioningWindowStatus::Windownotopen));
+        assert!(!info.is_open());
+        assert_eq!(info.admin_fabric_index, None);
+        assert_eq!(info.admin_vendor_id, None);
+
+        let open = HashMap::from([
+            (0x00, TlvItemValue::Int(1)),
+            (0x01, TlvItemValue::Int(2)),
+            (0x02, TlvItemValue::Int(0xfff1)),
+        ]);
+        let info = CommissioningWindowInfo::from_attributes(&open);
+        assert!(info.is_open());
+        assert_eq!(info.admin_fabric_index, Some(2));
+        assert_eq!(info.admin_vendor_id, Some(0xfff1));
+
+        // unknown status value is not treated as open
+        let info = CommissioningWindowInfo::from_attributes(&HashMap::from([(0x00, TlvItemValue::Int(7))]));
+        assert_eq!(info.status, None);
+        assert!(!info.is_open());
+    }
+
+    #[test]
+    fn test_battery_status() {
+        // AttributeDataIB Data (tag 2) of Power Source attributes as a battery
+        // powered contact sensor reports them
+        let reports = [
+            (0x00, "240201"),               // Status: Active
+            (0x02, "2c020742617474657279"), // Description: "Battery"
+            (0x0c, "240297"),               // BatPercentRemaining: 151 (75.5%)
+            (0x0e, "240201"),               // BatChargeLevel: Warning
+        ];
+        let attrs: HashMap<u32, TlvItemValue> = reports
+            .iter()
+            .map(|(id, h)| (*id, tlv::decode_tlv(&hex::decode(h).unwrap()).unwrap().value))
+            .collect();
+        let battery = BatteryStatus::from_attributes(&attrs);
+        assert_eq!(battery.status, Some(ps::PowerSourceStatus::Active));
+        assert_eq!(battery.description.as_deref(), Some("Battery"));
+        assert_eq!(battery.percent_remaining, Some(75.5));
+        assert_eq!(battery.charge_level, Some(ps::BatChargeLevel::Warning));
+
+        // null percentage
+        let battery = BatteryStatus::from_attributes(&HashMap::from([(0x0c, TlvItemValue::Nil())]));
+        assert_eq!(battery.percent_remaining, None);
+        assert_eq!(battery_percent(200), Some(100.0));
+        assert_eq!(battery_percent(0), Some(0.0));
+        assert_eq!(battery_percent(201), None);
+    }
+
+    #[test]
+    fn test_sensor_units() {
+        assert_eq!(temperature_celsius(2150), 21.5);
+        assert_eq!(temperature_celsius(-1025), -10.25);
+        assert_eq!(decode_temperature(&TlvItemValue::Int(-500i64 as u64)).unwrap(), Some(-5.0));
+        assert_eq!(decode_temperature(&TlvItemValue::Nil()).unwrap(), None);
+
+        assert_eq!(relative_humidity_percent(4575), Some(45.75));
+        assert_eq!(relative_humidity_percent(10000), Some(100.0));
+        assert_eq!(relative_humidity_percent(10001), None);
+        assert_eq!(decode_relative_humidity(&TlvItemValue::Int(4575)).unwrap(), Some(45.75));
+        assert_eq!(decode_relative_humidity(&TlvItemValue::Nil()).unwrap(), None);
+        assert!(decode_relative_humidity(&TlvItemValue::Bool(true)).is_err());
+        assert!(decode_relative_humidity(&TlvItemValue::Int(0x1_0000)).is_err());
+
+        // MeasuredValue = 10000 * log10(lux) + 1
+        assert_eq!(illuminance_lux(0), 0.0);
+        assert_eq!(illuminance_lux(1), 1.0);
+        assert!((illuminance_lux(10001) - 10.0).abs() < 1e-9);
+        assert!((illuminance_lux(30001) - 1000.0).abs() < 1e-6);
+        // 10000 * log10(500) + 1 = 26990.7
+        assert!((illuminance_lux(26991) - 500.0).abs() < 0.1);
+        assert_eq!(decode_illuminance(&TlvItemValue::Nil()).unwrap(), None);
+        assert!(decode_illuminance(&TlvItemValue::String("x".into())).is_err());
+        assert!(decode_temperature(&TlvItemValue::Bool(false)).is_err());
+
+        assert!(decode_occupied(&TlvItemValue::Int(1)).unwrap());
+        assert!(!decode_occupied(&TlvItemValue::Int(0)).unwrap());
+    }
+
+    #[test]
+    fn test_decode_basic_commissioning_info() {
+        // {0: 60, 1: 900}
+        let t = tlv::decode_tlv(&[0x15, 0x24, 0x00, 0x3c, 0x25, 0x01, 0x84, 0x03, 0x18]).unwrap();
+        let info = decode_basic_commissioning_info(&t.value).unwrap();
+        assert_eq!(
+            info,
+            BasicCommissioningInfo { failsafe_expiry_len: 60, max_cumulative_failsafe: 900 }
+        );
+        assert_eq!(info.failsafe_timeout(120), 120);
+        assert_eq!(info.failsafe_timeout(10), 60);
+        assert_eq!(info.failsafe_timeout(3600), 900);
+
+        // MaxCumulativeFailsafeSeconds missing
+        let t = tlv::decode_tlv(&[0x15, 0x24, 0x00, 0x3c, 0x18]).unwrap();
+        assert!(decode_basic_commissioning_info(&t.value).is_err());
+    }
+
+    #[test]
+    fn test_shares_root() {
+        fn root(secret: &p256::SecretKey) -> TrustedRoot {
+            let public = secret.public_key().to_sec1_bytes();
+            let x509 = crate::cert_x509::encode_x509(&public, 1, 1, 1, secret, true).unwrap();
+            TrustedRoot {
+                cert: crate::cert_matter::convert_x509_bytes_to_matter(&x509, &public).unwrap(),
+            }
+        }
+        let ours = p256::SecretKey::random(&mut rand::thread_rng());
+        let ours_public = ours.public_key().to_sec1_bytes();
+        let foreign = p256::SecretKey::random(&mut rand::thread_rng());
+        assert_eq!(root(&ours).public_key().unwrap(), ours_public.to_vec());
+
+        let garbage = TrustedRoot { cert: vec![0x15, 0x18] };
+        assert!(shares_root(&[garbage.clone(), root(&ours)], &ours_public));
+        assert!(!shares_root(&[garbage, root(&foreign)], &ours_public));
+        assert!(!shares_root(&[], &ours_public));
+    }
+
+    #[test]
+    fn test_encode_start_up_attributes() {
+        // null is "previous"
+        assert_eq!(encode_start_up_on_off(None).unwrap(), [0x34, 0x02]);
+        assert_eq!(encode_nullable_level(None).unwrap(), [0x34, 0x02]);
+        assert_eq!(encode_start_up_color_temperature(None).unwrap(), [0x34, 0x02]);
+
+        assert_eq!(
+            encode_start_up_on_off(Some(on_off::StartUpOnOff::Toggle)).unwrap(),
+            [0x24, 0x02, 0x02]
+        );
+        assert_eq!(encode_nullable_level(Some(0)).unwrap(), [0x24, 0x02, 0x00]);
+        assert_eq!(encode_nullable_level(Some(254)).unwrap(), [0x24, 0x02, 0xfe]);
+        assert!(encode_nullable_level(Some(255)).is_err());
+        assert_eq!(
+            encode_start_up_color_temperature(Some(370)).unwrap(),
+            [0x25, 0x02, 0x72, 0x01]
+        );
+        assert!(encode_start_up_color_temperature(Some(0xffff)).is_err());
+    }
+
+    #[test]
+    fn test_semantic_tags() {
+        // TagList of a two gang switch endpoint: standard Common Position "Left",
+        // manufacturer specific tag with label
+        let tag_list = hex::decode(concat!(
+            "3602",
+            "15", "3400", "240108", "240200", "18",
+            "15", "2500f1ff", "240180", "240201", "2c030c706f77657220627574746f6e", "18",
+            "18"
+        ))
+        .unwrap();
+        let item = tlv::decode_tlv(&tag_list).unwrap();
+        let tags = decode_semantic_tags(&item.value).unwrap();
+        assert_eq!(
+            tags,
+            [
+                SemanticTag { mfg_code: None, namespace_id: 8, tag: 0, label: None },
+                SemanticTag {
+                    mfg_code: Some(0xfff1),
+                    namespace_id: 0x80,
+                    tag: 1,
+                    label: Some("power button".into()),
+                },
+            ]
+        );
+        assert!(decode_semantic_tags(&TlvItemValue::Int(1)).is_err());
+        // [{1: 0x100, 2: 0}] - namespaceID doesn't fit u8
+        let wide = hex::decode("3602152501000124020018").unwrap();
+        assert!(decode_semantic_tags(&tlv::decode_tlv(&wide).unwrap().value).is_err());
+    }
+
+    #[test]
+    fn test_labels_round_trip() {
+        let labels = [
+            ("room".to_owned(), "kitchen".to_owned()),
+            ("floor".to_owned(), "".to_owned()),
+        ];
+        let encoded = encode_labels(&labels).unwrap();
+        let item = tlv::decode_tlv(&encoded).unwrap();
+        assert_eq!(item.tag, 2);
+        let decoded = decode_labels(&item.value).unwrap();
+        assert_eq!(
+            decoded,
+            [
+                Label { label: "room".into(), value: "kitchen".into() },
+                Label { label: "floor".into(), value: "".into() },
+            ]
+        );
+
+        // empty list clears labels
+        let item = tlv::decode_tlv(&encode_labels(&[]).unwrap()).unwrap();
+        assert!(decode_labels(&item.value).unwrap().is_empty());
+
+        let max = "x".repeat(MAX_LABEL_LEN);
+        assert!(encode_labels(&[(max.clone(), max.clone())]).is_ok());
+        assert!(encode_labels(&[(max.clone() + "x", "v".into())]).is_err());
+        assert!(encode_labels(&[("l".into(), max + "x")]).is_err());
+        assert!(decode_labels(&TlvItemValue::Int(1)).is_err());
+    }
+
+    #[test]
+    fn test_decode_nocs() {
+        // NOC with subject {node id: 0x1234, fabric id: 1}; other fields omitted
+        let mut cert = tlv::TlvBuffer::new();
+        cert.write_anon_struct().unwrap();
+        cert.write_list(6).unwrap();
+        cert.write_uint64(17, 0x1234).unwrap();
+        cert.write_uint64(21, 1).unwrap();
+        cert.write_struct_end().unwrap();
+        cert.write_struct_end().unwrap();
+
+        let mut t = tlv::TlvBuffer::new();
+        t.write_array(0).unwrap();
+        t.write_anon_struct().unwrap();
+        t.write_octetstring(1, &cert.data).unwrap();
+        t.write_uint8(FABRIC_INDEX_TAG, 3).unwrap();
+        t.write_struct_end().unwrap();
+        t.write_struct_end().unwrap();
+        let value = tlv::decode_tlv(&t.data).unwrap();
+
+        let nocs = decode_nocs(&value.value).unwrap();
+        assert_eq!(nocs.len(), 1);
+        assert_eq!(nocs[0].noc, cert.data);
+        assert_eq!(nocs[0].icac, None);
+        assert_eq!(nocs[0].fabric_index, Some(3));
+        assert_eq!(nocs[0].node_id().unwrap(), 0x1234);
+
+        assert!(decode_nocs(&TlvItemValue::Int(1)).is_err());
+    }
+}
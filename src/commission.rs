@@ -1,8 +1,12 @@
+use std::time::Duration;
+
 use anyhow::{Context, Result};
 use rand::RngCore;
 
 use crate::{
-    cert_matter, cert_x509, certmanager, controller::auth_sigma, fabric::Fabric, messages,
+    cert_matter, cert_x509, certmanager,
+    clusters::codec::general_commissioning_cluster::CommissioningError,
+    controller::auth_sigma, fabric::Fabric, messages,
     retransmit, session, tlv, transport::ConnectionTrait,
 };
 
@@ -10,6 +14,15 @@ const CLUSTER_OPERATIONAL_CREDENTIALS: u32 = 0x3e;
 const CMD_OPERATIONAL_CREDENTIALS_ADDTRUSTEDROOTCERTIFICATE: u32 = 0xb;
 const CMD_OPERATIONAL_CREDENTIALS_ADDNOC: u32 = 0x6;
 const CMD_OPERATIONAL_CSRREQUEST: u32 = 0x4;
+const ATTR_OPERATIONAL_CREDENTIALS_TRUSTEDROOTCERTIFICATES: u32 = 0x4;
+/// AdminVendorId sent in AddNOC.
+const ADMIN_VENDOR_ID: u16 = 101;
+/// Maximum length of NOCValue / ICACValue in AddNOC (matter TLV certificate).
+const MAX_CERT_LENGTH: usize = 400;
+/// Length of IPKValue in AddNOC (epoch key).
+const IPK_LENGTH: usize = 16;
+/// Highest operational node id (spec 2.5.5.1); ids above are group/temporary/reserved.
+const MAX_OPERATIONAL_NODE_ID: u64 = 0xFFFF_FFEF_FFFF_FFFF;
 //const CMD_OPERATIONAL_ATTESTATION_REQUEST: u32 = 0x0;
 //const CMD_OPERATIONAL_CERTCHAIN_REQUEST: u32 = 0x2;
 
@@ -17,6 +30,11 @@ const CLUSTER_GENERAL_COMMISSIONING: u32 = 0x30;
 const CMD_GENERAL_COMMISSIONING_ARMFAILSAFE: u32 = 0;
 //const CMD_GENERAL_COMMISSIONING_SETREGULATORYCONFIG: u32 = 2;
 const CMD_GENERAL_COMMISSIONING_COMMISSIONINGCOMPLETE: u32 = 4;
+const ATTR_GENERAL_COMMISSIONING_BASICCOMMISSIONINGINFO: u32 = 1;
+/// Fail-safe timeout requested by ArmFailSafe, limited by device's BasicCommissioningInfo.
+const FAILSAFE_TIMEOUT_SECS: u16 = 60;
+/// How long cancelled commissioning waits for the device to confirm fail-safe disarm.
+const DISARM_TIMEOUT: Duration = Duration::from_secs(5);
 
 #[cfg(feature = "ble")]
 const CLUSTER_NETWORK_COMMISSIONING: u32 = 0x31;
@@ -39,6 +57,123 @@ pub enum NetworkCreds {
     AlreadyOnNetwork,
 }
 
+/// CommissioningComplete rejected by the device with a non-zero ErrorCode.
+/// Detect with `err.downcast_ref::<matc::CommissioningCompleteFailed>()`.
+#[derive(Debug, Clone)]
+pub struct CommissioningCompleteFailed {
+    /// Raw ErrorCode from CommissioningCompleteResponse.
+    pub code: u64,
+    /// DebugText from the response when device provided any.
+    pub debug_text: Option<String>,
+}
+
+impl CommissioningCompleteFailed {
+    pub(crate) fn new(code: u64, debug_text: Option<String>) -> Self {
+        Self { code, debug_text }
+    }
+
+    /// Decoded ErrorCode; None for values unknown to this library.
+    pub fn error(&self) -> Option<CommissioningError> {
+        u8::try_from(self.code).ok().and_then(CommissioningError::from_u8)
+    }
+
+    /// Fail-safe expired before CommissioningComplete arrived; the device rolled
+    /// back everything provisioned so far and commissioning has to start over.
+    pub fn is_no_fail_safe(&self) -> bool {
+        self.error() == Some(CommissioningError::Nofailsafe)
+    }
+}
+
+impl std::fmt::Display for CommissioningCompleteFailed {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.is_no_fail_safe() {
+            write!(
+                f,
+                "CommissioningComplete failed: NoFailSafe - fail-safe expired and device rolled back; \
+                 re-arm fail-safe and retry faster"
+            )?;
+        } else {
+            write!(f, "CommissioningComplete failed with status {}", self.code)?;
+            if let Some(e) = self.error() {
+                write!(f, " ({:?})", e)?;
+            }
+        }
+        match &self.debug_text {
+            Some(text) => write!(f, ": {}", text),
+            None => Ok(()),
+        }
+    }
+}
+impl std::error::Error for CommissioningCompleteFailed {}
+
+/// Operation was aborted through its `CancellationToken`.
+/// Detect with `err.downcast_ref::<matc::Cancelled>()`.
+#[derive(Debug, Clone, Copy)]
+pub struct Cancelled;
+
+impl std::fmt::Display for Cancelled {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "cancelled")
+    }
+}
+impl std::error::Error for Cancelled {}
+
+/// Steps of commissioning in the order they run. Reported as they finish by
+/// [Controller::commission_step_by_step](crate::controller::Controller::commission_step_by_step).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CommissioningStep {
+    /// PASE session established using passcode.
+    Pase,
+    /// Fail-safe armed.
+    ArmFailSafe,
+    /// Device's certificate signing request received.
+    Csr,
+    /// Our root certificate installed on device.
+    AddTrustedRoot,
+    /// Device's operational certificate installed.
+    AddNoc,
+    /// CASE session established with new operational credentials.
+    Sigma,
+    /// CommissioningComplete accepted - device is commissioned.
+    CommissioningComplete,
+}
+
+impl CommissioningStep {
+    /// All steps in order.
+    pub const ALL: [CommissioningStep; 7] = [
+        CommissioningStep::Pase,
+        CommissioningStep::ArmFailSafe,
+        CommissioningStep::Csr,
+        CommissioningStep::AddTrustedRoot,
+        CommissioningStep::AddNoc,
+        CommissioningStep::Sigma,
+        CommissioningStep::CommissioningComplete,
+    ];
+
+    /// Step following this one, None after the last one.
+    pub fn next(self) -> Option<Self> {
+        let pos = Self::ALL.iter().position(|s| *s == self)?;
+        Self::ALL.get(pos + 1).copied()
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            CommissioningStep::Pase => "Pase",
+            CommissioningStep::ArmFailSafe => "ArmFailSafe",
+            CommissioningStep::Csr => "Csr",
+            CommissioningStep::AddTrustedRoot => "AddTrustedRoot",
+            CommissioningStep::AddNoc => "AddNoc",
+            CommissioningStep::Sigma => "Sigma",
+            CommissioningStep::CommissioningComplete => "CommissioningComplete",
+        }
+    }
+}
+
+impl std::fmt::Display for CommissioningStep {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.name())
+    }
+}
 
 /*async fn run_attestation(
     retrctx: &mut retransmit::RetrContext<'_>,
@@ -97,14 +232,69 @@ pub enum NetworkCreds {
     Ok(())
 }*/
 
+/// Our root certificate in matter encoding, as pushed by AddTrustedRootCertificate.
+fn root_cert_matter(cm: &dyn certmanager::CertManager) -> Result<Vec<u8>> {
+    let ca_pubkey = cm.get_ca_key()?.public_key().to_sec1_bytes();
+    let ca_cert = cm.get_ca_cert()?;
+    cert_matter::convert_x509_bytes_to_matter(&ca_cert, &ca_pubkey)
+}
+
+/// Read single attribute of endpoint 0 and return its value.
+async fn read_attribute(
+    retrctx: &mut retransmit::RetrContext<'_>,
+    cluster: u32,
+    attribute: u32,
+    exchange_id: u16,
+) -> Result<tlv::TlvItemValue> {
+    let req = messages::im_read_request(0, cluster, attribute, exchange_id)?;
+    retrctx.send(&req).await?;
+    let resp = retrctx.get_next_message().await?;
+    resp.tlv
+        .get(&[1, 0, 1, 2])
+        .cloned()
+        .context("attribute missing in response")
+}
+
+/// Read device's TrustedRootCertificates and check whether `root` is among them.
+async fn trusts_root(
+    retrctx: &mut retransmit::RetrContext<'_>,
+    root: &[u8],
+    exchange_id: u16,
+) -> Result<bool> {
+    let roots = read_attribute(
+        retrctx,
+        CLUSTER_OPERATIONAL_CREDENTIALS,
+        ATTR_OPERATIONAL_CREDENTIALS_TRUSTEDROOTCERTIFICATES,
+        exchange_id,
+    )
+    .await
+    .context("TrustedRootCertificates")?;
+    Ok(match &roots {
+        tlv::TlvItemValue::List(items) => items
+            .iter()
+            .any(|i| matches!(&i.value, tlv::TlvItemValue::OctetString(c) if c == root)),
+        _ => false,
+    })
+}
+
+/// Check over PASE session whether device already trusts our root certificate,
+/// i.e. it was commissioned into this fabric before.
+pub(crate) async fn already_commissioned(
+    connection: &dyn ConnectionTrait,
+    session: &session::Session,
+    cm: &dyn certmanager::CertManager,
+) -> Result<bool> {
+    let root = root_cert_matter(cm)?;
+    let mut retrctx = retransmit::RetrContext::new(connection, session);
+    trusts_root(&mut retrctx, &root, rand::random()).await
+}
+
 async fn push_ca_cert(
     retrcrx: &mut retransmit::RetrContext<'_>,
     cm: &dyn certmanager::CertManager,
     exchange_id: u16,
 ) -> Result<()> {
-    let ca_pubkey = cm.get_ca_key()?.public_key().to_sec1_bytes();
-    let ca_cert = cm.get_ca_cert()?;
-    let mcert = cert_matter::convert_x509_bytes_to_matter(&ca_cert, &ca_pubkey)?;
+    let mcert = root_cert_matter(cm)?;
     log::debug!("AddTrustedRootCertificate: matter cert TLV ({} bytes): {}", mcert.len(),
         mcert.iter().map(|b| format!("{:02x}", b)).collect::<Vec<_>>().join(""));
     let mut tlv = tlv::TlvBuffer::new();
@@ -154,16 +344,58 @@ fn noc_status_to_str(status: u64) -> &'static str {
     }
 }
 
-async fn push_device_cert(
-    retrcrx: &mut retransmit::RetrContext<'_>,
+/// Check ids used during commissioning.
+/// `node_id` is the device's operational id - it goes into the NOC subject.
+/// `controller_id` is our own node id - it becomes CaseAdminSubject, the node granted
+/// Administer privilege in the device's ACL. Both must be distinct operational node ids.
+fn validate_node_ids(node_id: u64, controller_id: u64) -> Result<()> {
+    for (name, id) in [("node_id", node_id), ("controller_id", controller_id)] {
+        if id == 0 || id > MAX_OPERATIONAL_NODE_ID {
+            anyhow::bail!("{} {:#x} is not an operational node id", name, id);
+        }
+    }
+    if node_id == controller_id {
+        anyhow::bail!("device node_id and controller_id must differ (both {})", node_id);
+    }
+    Ok(())
+}
+
+/// AddNOC command fields:
+/// 0 NOCValue (device cert, subject carries device node id), 1 ICACValue (optional, omitted
+/// when NOC is signed directly by root), 2 IPKValue, 3 CaseAdminSubject (controller node id),
+/// 4 AdminVendorId.
+/// Field lengths are checked here; device would reject them only with an opaque InvalidNOC.
+fn addnoc_payload(noc: &[u8], icac: Option<&[u8]>, ipk: &[u8], controller_id: u64) -> Result<Vec<u8>> {
+    if noc.is_empty() || noc.len() > MAX_CERT_LENGTH {
+        anyhow::bail!("AddNOC: NOC length {} not in 1..={}", noc.len(), MAX_CERT_LENGTH);
+    }
+    if let Some(icac) = icac {
+        if icac.is_empty() || icac.len() > MAX_CERT_LENGTH {
+            anyhow::bail!("AddNOC: ICAC length {} not in 1..={}", icac.len(), MAX_CERT_LENGTH);
+        }
+    }
+    if ipk.len() != IPK_LENGTH {
+        anyhow::bail!("AddNOC: IPK length {} must be {}", ipk.len(), IPK_LENGTH);
+    }
+    let mut tlv = tlv::TlvBuffer::new();
+    tlv.write_octetstring(0, noc)?;
+    if let Some(icac) = icac {
+        tlv.write_octetstring(1, icac)?;
+    }
+    tlv.write_octetstring(2, ipk)?;
+    tlv.write_uint64(3, controller_id)?;
+    tlv.write_uint16(4, ADMIN_VENDOR_ID)?;
+    Ok(tlv.data)
+}
+
+/// Issue NOC in matter TLV format for `node_id` with the public key of `csrd`,
+/// signed by our CA, valid from [certmanager::CertManager::cert_not_before].
+pub(crate) fn issue_noc(
     cm: &dyn certmanager::CertManager,
-    csrd: x509_cert::request::CertReq,
+    csrd: &x509_cert::request::CertReq,
     node_id: u64,
-    controller_id: u64,
-    fabric: &Fabric,
-    exchange_id: u16,
-) -> Result<()> {
-    let ca_id = fabric.ca_id;
+    ca_id: u64,
+) -> Result<Vec<u8>> {
     let ca_pubkey = cm.get_ca_key()?.public_key().to_sec1_bytes();
     let node_public_key = csrd
         .info
@@ -172,26 +404,35 @@ async fn push_device_cert(
         .as_bytes()
         .context("can't extract pubkey from csr")?;
     let ca_private = cm.get_ca_key()?;
-    let noc_x509 = cert_x509::encode_x509(
+    let noc_x509 = cert_x509::encode_x509_at(
         node_public_key,
         node_id,
         cm.get_fabric_id(),
         ca_id,
         &ca_private,
         false,
+        cm.cert_not_before(),
     )?;
-    let noc = cert_matter::convert_x509_bytes_to_matter(&noc_x509, &ca_pubkey)?;
-    let mut tlv = tlv::TlvBuffer::new();
-    tlv.write_octetstring(0, &noc)?;
-    tlv.write_octetstring(2, &fabric.ipk_epoch_key)?;
-    tlv.write_uint64(3, controller_id)?;
-    tlv.write_uint16(4, 101)?;
+    cert_matter::convert_x509_bytes_to_matter(&noc_x509, &ca_pubkey)
+}
+
+async fn push_device_cert(
+    retrcrx: &mut retransmit::RetrContext<'_>,
+    cm: &dyn certmanager::CertManager,
+    csrd: x509_cert::request::CertReq,
+    node_id: u64,
+    controller_id: u64,
+    fabric: &Fabric,
+    exchange_id: u16,
+) -> Result<()> {
+    let noc = issue_noc(cm, &csrd, node_id, fabric.ca_id)?;
+    let payload = addnoc_payload(&noc, None, &fabric.ipk_epoch_key, controller_id)?;
     let t1 = messages::im_invoke_request(
         0,
         CLUSTER_OPERATIONAL_CREDENTIALS,
         CMD_OPERATIONAL_CREDENTIALS_ADDNOC,
         exchange_id,
-        &tlv.data,
+        &payload,
         false,
     )?;
     retrcrx.send(&t1).await?;
@@ -232,23 +473,53 @@ async fn send_csr(
         .tlv
         .get_octet_string(&[1, 0, 0, 1, 0])
         .context("csr tlv missing")?;
-    let csr_t = tlv::decode_tlv(csr_tlve).context("csr tlv can't decode")?;
+    decode_nocsr_elements(csr_tlve)
+}
+
+/// Extract CSR from NOCSRElements of CSRResponse.
+pub(crate) fn decode_nocsr_elements(elements: &[u8]) -> Result<x509_cert::request::CertReq> {
+    let csr_t = tlv::decode_tlv(elements).context("csr tlv can't decode")?;
     let csr = csr_t
         .get_octet_string(&[1])
         .context("csr tlv in tlv missing")?;
-    let csrd = x509_cert::request::CertReq::try_from(csr)?;
-    Ok(csrd)
+    Ok(x509_cert::request::CertReq::try_from(csr)?)
 }
 
+#[cfg(feature = "ble")]
 async fn commissioning_complete(
     connection: &dyn ConnectionTrait,
     cm: &dyn certmanager::CertManager,
     node_id: u64,
     controller_id: u64,
     fabric: &Fabric,
+) -> Result<session::Session> {
+    let ses = case_session(connection, cm, node_id, controller_id, fabric).await?;
+    send_commissioning_complete(connection, &ses).await?;
+    Ok(ses)
+}
+
+async fn case_session(
+    connection: &dyn ConnectionTrait,
+    cm: &dyn certmanager::CertManager,
+    node_id: u64,
+    controller_id: u64,
+    fabric: &Fabric,
 ) -> Result<session::Session> {
     // resumption ignored for now - we do not support resumption on connections used for commissioning
     let (ses, _resumption) = auth_sigma(connection, fabric, cm, node_id, controller_id).await?;
+    Ok(ses)
+}
+
+/// CommissioningComplete is accepted by device only over CASE session established
+/// with the freshly installed NOC - never over PASE.
+async fn send_commissioning_complete(
+    connection: &dyn ConnectionTrait,
+    ses: &session::Session,
+) -> Result<()> {
+    if !ses.is_case() {
+        anyhow::bail!("CommissioningComplete must be sent over CASE session");
+    }
+    log::info!("sending CommissioningComplete over CASE session {}", ses.session_id);
     let t1 = messages::im_invoke_request(
         0,
         CLUSTER_GENERAL_COMMISSIONING,
@@ -257,7 +528,7 @@ async fn commissioning_complete(
         &[],
         false,
     )?;
-    let mut retrctx = retransmit::RetrContext::new(connection, &ses);
+    let mut retrctx = retransmit::RetrContext::new(connection, ses);
 
     retrctx.send(&t1).await?;
     let resp = retrctx.get_next_message().await?;
@@ -267,37 +538,137 @@ async fn commissioning_complete(
             .context("can't get status from CommissioningCompleteResponse")?
     };
     if comresp_status != 0 {
-        return Err(anyhow::anyhow!(
-            "CommissioningComplete failed with status {}",
-            comresp_status
-        ));
+        let debug_text = resp
+            .tlv
+            .get_string_owned(&[1, 0, 0, 1, 1])
+            .filter(|t| !t.is_empty());
+        return Err(CommissioningCompleteFailed {
+            code: comresp_status,
+            debug_text,
+        }
+        .into());
     }
-    Ok(ses)
+    Ok(())
 }
 
-pub(crate) async fn commission(
+/// Run commissioning steps following PASE one by one, calling `progress` with each
+/// finished step and time it took.
+///
+/// Certificates are provisioned over the PASE `session`. CommissioningComplete then
+/// requires new CASE session using the installed NOC, so PASE can't be reused for it;
+/// the device closes PASE itself once commissioning completes.
+///
+/// When `cancel` fires commissioning is aborted with [Cancelled]. The fail-safe is then
+/// disarmed over the PASE `session`, so the device rolls back what was provisioned
+/// right away instead of when the fail-safe expires.
+#[allow(clippy::too_many_arguments)]
+pub(crate) async fn commission_with_progress(
     connection: &dyn ConnectionTrait,
     session: &mut session::Session,
     fabric: &Fabric,
     cm: &dyn certmanager::CertManager,
     node_id: u64,
     controller_id: u64,
+    progress: &mut (dyn FnMut(CommissioningStep, Duration) + Send),
+    cancel: Option<&tokio_util::sync::CancellationToken>,
 ) -> Result<session::Session> {
+    let Some(cancel) = cancel else {
+        return provision(connection, session, fabric, cm, node_id, controller_id, progress).await;
+    };
+    tokio::select! {
+        res = provision(connection, session, fabric, cm, node_id, controller_id, progress) => return res,
+        _ = cancel.cancelled() => {}
+    }
+    log::info!("commissioning cancelled, disarming fail-safe");
+    match tokio::time::timeout(DISARM_TIMEOUT, disarm_failsafe(connection, session)).await {
+        Ok(Ok(())) => {}
+        Ok(Err(e)) => log::debug!("fail-safe disarm failed: {:?}", e),
+        Err(_) => log::debug!("fail-safe disarm not confirmed within {:?}", DISARM_TIMEOUT),
+    }
+    Err(Cancelled.into())
+}
+
+async fn provision(
+    connection: &dyn ConnectionTrait,
+    session: &mut session::Session,
+    fabric: &Fabric,
+    cm: &dyn certmanager::CertManager,
+    node_id: u64,
+    controller_id: u64,
+    progress: &mut (dyn FnMut(CommissioningStep, Duration) + Send),
+) -> Result<session::Session> {
+    validate_node_ids(node_id, controller_id)?;
     // node operational credentials procedure
     let mut retrctx = retransmit::RetrContext::new(connection, session);
     let base: u16 = rand::random();
+    let mut csrd = None;
+    let mut case = None;
 
-    arm_failsafe(&mut retrctx, 60, base).await?;
+    log::debug!("provisioning over PASE session {}", session.session_id);
 
-    let csrd = send_csr(&mut retrctx, base.wrapping_add(1)).await?;
-
-    push_ca_cert(&mut retrctx, cm, base.wrapping_add(2)).await?;
-
-    push_device_cert(&mut retrctx, cm, csrd, node_id, controller_id, fabric, base.wrapping_add(3)).await?;
+    let mut step = CommissioningStep::ArmFailSafe;
+    loop {
+        let started = tokio::time::Instant::now();
+        match step {
+            // established by caller before provisioning starts
+            CommissioningStep::Pase => {}
+            CommissioningStep::ArmFailSafe => {
+                let timeout = failsafe_timeout(&mut retrctx, base.wrapping_add(4)).await;
+                arm_failsafe(&mut retrctx, timeout, base).await?;
+            }
+            CommissioningStep::Csr => {
+                csrd = Some(send_csr(&mut retrctx, base.wrapping_add(1)).await?);
+            }
+            CommissioningStep::AddTrustedRoot => {
+                push_ca_cert(&mut retrctx, cm, base.wrapping_add(2)).await?;
+            }
+            CommissioningStep::AddNoc => {
+                let csrd = csrd.take().context("CSR not received")?;
+                push_device_cert(&mut retrctx, cm, csrd, node_id, controller_id, fabric, base.wrapping_add(3)).await?;
+            }
+            CommissioningStep::Sigma => {
+                log::info!("NOC installed, establishing CASE session for CommissioningComplete");
+                case = Some(case_session(connection, cm, node_id, controller_id, fabric).await?);
+            }
+            CommissioningStep::CommissioningComplete => {
+                let ses = case.as_ref().context("CASE session not established")?;
+                send_commissioning_complete(connection, ses).await?;
+            }
+        }
+        progress(step, started.elapsed());
+        match step.next() {
+            Some(next) => step = next,
+            None => break,
+        }
+    }
+    case.context("CASE session not established")
+}
 
-    let ses = commissioning_complete(connection, cm, node_id, controller_id, fabric).await?;
+/// ArmFailSafe with zero expiry: device rolls back provisioning done under the fail-safe.
+/// Succeeds without effect when no fail-safe is armed.
+async fn disarm_failsafe(connection: &dyn ConnectionTrait, session: &session::Session) -> Result<()> {
+    let mut retrctx = retransmit::RetrContext::new(connection, session);
+    arm_failsafe(&mut retrctx, 0, rand::random()).await
+}
 
-    Ok(ses)
+/// Pick ArmFailSafe timeout valid for the device from its BasicCommissioningInfo.
+/// Falls back to the default when the attribute can't be read.
+async fn failsafe_timeout(retrctx: &mut retransmit::RetrContext<'_>, exchange_id: u16) -> u16 {
+    let info = read_attribute(
+        retrctx,
+        CLUSTER_GENERAL_COMMISSIONING,
+        ATTR_GENERAL_COMMISSIONING_BASICCOMMISSIONINGINFO,
+        exchange_id,
+    )
+    .await
+    .and_then(|v| crate::clusters::typed::decode_basic_commissioning_info(&v));
+    match info {
+        Ok(info) => info.failsafe_timeout(FAILSAFE_TIMEOUT_SECS),
+        Err(e) => {
+            log::debug!("BasicCommissioningInfo not available ({:?}), using default fail-safe", e);
+            FAILSAFE_TIMEOUT_SECS
+        }
+    }
 }
 
 async fn arm_failsafe(
@@ -460,6 +831,7 @@ pub(crate) async fn commission_ble_phase(
     controller_id: u64,
     network_creds: &NetworkCreds,
 ) -> Result<()> {
+    validate_node_ids(node_id, controller_id)?;
     let mut retrctx = retransmit::RetrContext::new(ble_connection, pase_session);
     let base: u16 = rand::random();
     let e_arm      = base;
@@ -472,8 +844,9 @@ pub(crate) async fn commission_ble_phase(
     let e_net1     = base.wrapping_add(8);
     let e_net2     = base.wrapping_add(9);
 
-    arm_failsafe(&mut retrctx, 60, e_arm).await.context("ArmFailSafe")?;
-    log::debug!("Failsafe armed for 60 seconds");
+    let timeout = failsafe_timeout(&mut retrctx, base.wrapping_add(10)).await;
+    arm_failsafe(&mut retrctx, timeout, e_arm).await.context("ArmFailSafe")?;
+    log::debug!("Failsafe armed for {} seconds", timeout);
 
     //set_regulatory_config(&mut retrctx, e_reg).await.context("SetRegulatoryConfig")?;
     //log::debug!("Regulatory configuration set");
@@ -518,3 +891,501 @@ pub(crate) async fn commissioning_complete_udp(
 ) -> Result<session::Session> {
     commissioning_complete(udp_connection, cm, node_id, controller_id, fabric).await
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::device_messages::{
+        im_invoke_response_data, im_invoke_response_status, im_report_data, AttrReport,
+    };
+    use std::time::Duration;
+
+    // Device stub answering the single read request with a prepared ReportData.
+    struct ReplyConn {
+        reply: std::sync::Mutex<Option<Vec<u8>>>,
+        exchange_id: u16,
+    }
+
+    #[async_trait::async_trait]
+    impl ConnectionTrait for ReplyConn {
+        async fn send(&self, _data: &[u8]) -> Result<()> {
+            Ok(())
+        }
+        async fn receive(&self, _timeout: Duration) -> Result<Vec<u8>> {
+            let reply = self.reply.lock().unwrap().take().context("no more replies")?;
+            let report = im_report_data(
+                self.exchange_id,
+                &[AttrReport::Data {
+                    endpoint: 0,
+                    cluster: CLUSTER_OPERATIONAL_CREDENTIALS,
+                    attribute: ATTR_OPERATIONAL_CREDENTIALS_TRUSTEDROOTCERTIFICATES,
+                    value_tlv: reply,
+                }],
+                -1,
+                None,
+                false,
+            )?;
+            session::Session::new().encode_message(&report)
+        }
+        fn is_reliable(&self) -> bool {
+            true
+        }
+    }
+
+    fn roots_tlv(roots: &[&[u8]]) -> Vec<u8> {
+        let mut t = tlv::TlvBuffer::new();
+        t.write_array(2).unwrap();
+        for r in roots {
+            t.write_octetstring_notag(r).unwrap();
+        }
+        t.write_struct_end().unwrap();
+        t.data
+    }
+
+    async fn check(roots: &[&[u8]], ours: &[u8]) -> bool {
+        let conn = ReplyConn {
+            reply: std::sync::Mutex::new(Some(roots_tlv(roots))),
+            exchange_id: 7,
+        };
+        let session = session::Session::new();
+        let mut retrctx = retransmit::RetrContext::new(&conn, &session);
+        trusts_root(&mut retrctx, ours, 7).await.unwrap()
+    }
+
+    // Device stub answering provisioning commands over unsecured PASE stand-in session.
+    // CASE is not answered, so commissioning stops at Sigma step.
+    struct ProvisioningConn {
+        replies: std::sync::Mutex<std::collections::VecDeque<Vec<u8>>>,
+        operational_key: p256::SecretKey,
+    }
+
+    impl ProvisioningConn {
+        fn reply(&self, data: &[u8]) -> Result<Option<Vec<u8>>> {
+            let msg = messages::Message::decode(&session::Session::new().decode_message(data)?)?;
+            let exchange = msg.protocol_header.exchange_id;
+            let ack = msg.message_header.message_counter as i64;
+            if msg.protocol_header.protocol_id != messages::ProtocolMessageHeader::PROTOCOL_ID_INTERACTION {
+                return Ok(None);
+            }
+            if msg.protocol_header.opcode == messages::ProtocolMessageHeader::INTERACTION_OPCODE_READ_REQ {
+                let mut info = tlv::TlvBuffer::new();
+                info.write_struct(2)?;
+                info.write_uint16(0, 60)?;
+                info.write_uint16(1, 900)?;
+                info.write_struct_end()?;
+                let report = im_report_data(
+                    exchange,
+                    &[AttrReport::Data {
+                        endpoint: 0,
+                        cluster: CLUSTER_GENERAL_COMMISSIONING,
+                        attribute: ATTR_GENERAL_COMMISSIONING_BASICCOMMISSIONINGINFO,
+                        value_tlv: info.data,
+                    }],
+                    ack,
+                    None,
+                    false,
+                )?;
+                return Ok(Some(report));
+            }
+            let command = msg.tlv.get_int(&[2, 0, 0, 2]).context("command id missing")? as u32;
+            let mut fields = tlv::TlvBuffer::new();
+            let (cluster, response) = match command {
+                CMD_GENERAL_COMMISSIONING_ARMFAILSAFE => {
+                    fields.write_uint8(0, 0)?;
+                    (CLUSTER_GENERAL_COMMISSIONING, 1)
+                }
+                CMD_OPERATIONAL_CSRREQUEST => {
+                    let mut nocsr = tlv::TlvBuffer::new();
+                    nocsr.write_anon_struct()?;
+                    nocsr.write_octetstring(1, &crate::device::commissioning::generate_csr(&self.operational_key)?)?;
+                    nocsr.write_octetstring(2, &[0; 32])?;
+                    nocsr.write_struct_end()?;
+                    fields.write_octetstring(0, &nocsr.data)?;
+                    fields.write_octetstring(1, &[0; 64])?;
+                    (CLUSTER_OPERATIONAL_CREDENTIALS, 5)
+                }
+                CMD_OPERATIONAL_CREDENTIALS_ADDTRUSTEDROOTCERTIFICATE => {
+                    return Ok(Some(im_invoke_response_status(
+                        exchange,
+                        0,
+                        CLUSTER_OPERATIONAL_CREDENTIALS,
+                        command,
+                        0,
+                        ack,
+                    )?));
+                }
+                CMD_OPERATIONAL_CREDENTIALS_ADDNOC => {
+                    fields.write_uint8(0, 0)?;
+                    (CLUSTER_OPERATIONAL_CREDENTIALS, 8)
+                }
+                _ => anyhow::bail!("unexpected command {}", command),
+            };
+            Ok(Some(im_invoke_response_data(exchange, 0, cluster, response, &fields.data, ack)?))
+        }
+    }
+
+    #[async_trait::async_trait]
+    impl ConnectionTrait for ProvisioningConn {
+        async fn send(&self, data: &[u8]) -> Result<()> {
+            if let Some(reply) = self.reply(data)? {
+                let reply = session::Session::new().encode_message(&reply)?;
+                self.replies.lock().unwrap().push_back(reply);
+            }
+            Ok(())
+        }
+        async fn receive(&self, _timeout: Duration) -> Result<Vec<u8>> {
+            self.replies.lock().unwrap().pop_front().context("no more replies")
+        }
+        fn is_reliable(&self) -> bool {
+            true
+        }
+    }
+
+    // Provisioning device which never answers CSRRequest; records ArmFailSafe expiry values.
+    struct StallingConn {
+        inner: ProvisioningConn,
+        failsafe_expiry: std::sync::Mutex<Vec<u64>>,
+    }
+
+    #[async_trait::async_trait]
+    impl ConnectionTrait for StallingConn {
+        async fn send(&self, data: &[u8]) -> Result<()> {
+            let msg = messages::Message::decode(&session::Session::new().decode_message(data)?)?;
+            match msg.tlv.get_int(&[2, 0, 0, 2]).map(|c| c as u32) {
+                Some(CMD_OPERATIONAL_CSRREQUEST) => return Ok(()),
+                Some(CMD_GENERAL_COMMISSIONING_ARMFAILSAFE) => {
+                    let expiry = msg.tlv.get_int(&[2, 0, 1, 0]).context("expiry missing")?;
+                    self.failsafe_expiry.lock().unwrap().push(expiry);
+                }
+                _ => {}
+            }
+            self.inner.send(data).await
+        }
+        async fn receive(&self, timeout: Duration) -> Result<Vec<u8>> {
+            let reply = self.inner.replies.lock().unwrap().pop_front();
+            match reply {
+                Some(reply) => Ok(reply),
+                None => {
+                    tokio::time::sleep(timeout).await;
+                    anyhow::bail!("receive timeout")
+                }
+            }
+        }
+        fn is_reliable(&self) -> bool {
+            true
+        }
+    }
+
+    // Device side of a CASE session: answers CommissioningComplete and records requests.
+    struct CaseConn {
+        device: session::Session,
+        error_code: u8,
+        received: std::sync::Mutex<Vec<messages::Message>>,
+        replies: std::sync::Mutex<std::collections::VecDeque<Vec<u8>>>,
+    }
+
+    #[async_trait::async_trait]
+    impl ConnectionTrait for CaseConn {
+        async fn send(&self, data: &[u8]) -> Result<()> {
+            let msg = messages::Message::decode(&self.device.decode_message(data)?)?;
+            let mut fields = tlv::TlvBuffer::new();
+            fields.write_uint8(0, self.error_code)?;
+            fields.write_string(1, "")?;
+            let reply = im_invoke_response_data(
+                msg.protocol_header.exchange_id,
+                0,
+                CLUSTER_GENERAL_COMMISSIONING,
+                5, // CommissioningCompleteResponse
+                &fields.data,
+                msg.message_header.message_counter as i64,
+            )?;
+            self.replies.lock().unwrap().push_back(self.device.encode_message(&reply)?);
+            self.received.lock().unwrap().push(msg);
+            Ok(())
+        }
+        async fn receive(&self, _timeout: Duration) -> Result<Vec<u8>> {
+            self.replies.lock().unwrap().pop_front().context("no more replies")
+        }
+        fn is_reliable(&self) -> bool {
+            true
+        }
+    }
+
+    const CASE_I2R_KEY: [u8; 16] = [1; 16];
+    const CASE_R2I_KEY: [u8; 16] = [2; 16];
+
+    // Controller CASE session and device stub holding the matching device session.
+    fn case_pair(error_code: u8) -> (session::Session, CaseConn) {
+        let mut case = session::Session::new();
+        case.session_id = 0x2000;
+        case.my_session_id = 0x1000;
+        case.set_encrypt_key(&CASE_I2R_KEY);
+        case.set_decrypt_key(&CASE_R2I_KEY);
+        case.local_node = Some(100u64.to_le_bytes().to_vec());
+        case.remote_node = Some(0x1234u64.to_le_bytes().to_vec());
+
+        let mut device = session::Session::new();
+        device.session_id = 0x1000;
+        device.my_session_id = 0x2000;
+        device.set_encrypt_key(&CASE_R2I_KEY);
+        device.set_decrypt_key(&CASE_I2R_KEY);
+        device.local_node = case.remote_node.clone();
+        device.remote_node = case.local_node.clone();
+        let conn = CaseConn {
+            device,
+            error_code,
+            received: std::sync::Mutex::new(Vec::new()),
+            replies: std::sync::Mutex::new(std::collections::VecDeque::new()),
+        };
+        (case, conn)
+    }
+
+    #[tokio::test]
+    async fn test_commissioning_complete_over_case() {
+        let (case, conn) = case_pair(0);
+
+        send_commissioning_complete(&conn, &case).await.unwrap();
+        {
+            let received = conn.received.lock().unwrap();
+            assert_eq!(received.len(), 1);
+            // encrypted with CASE keys and addressed to the CASE session
+            assert_eq!(received[0].message_header.session_id, 0x2000);
+            assert_eq!(received[0].tlv.get_int(&[2, 0, 0, 1]), Some(CLUSTER_GENERAL_COMMISSIONING as u64));
+            assert_eq!(
+                received[0].tlv.get_int(&[2, 0, 0, 2]),
+                Some(CMD_GENERAL_COMMISSIONING_COMMISSIONINGCOMPLETE as u64)
+            );
+        }
+
+        // PASE session (no peer node id) is refused without sending anything
+        let mut pase = session::Session::new();
+        pase.session_id = 0x2001;
+        pase.set_encrypt_key(&CASE_I2R_KEY);
+        pase.set_decrypt_key(&CASE_R2I_KEY);
+        assert!(send_commissioning_complete(&conn, &pase).await.is_err());
+        assert_eq!(conn.received.lock().unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_commissioning_complete_no_fail_safe() {
+        let (case, mut conn) = case_pair(CommissioningError::Nofailsafe as u8);
+        let err = send_commissioning_complete(&conn, &case).await.unwrap_err();
+        let failed = err
+            .downcast_ref::<CommissioningCompleteFailed>()
+            .expect("typed CommissioningComplete error");
+        assert_eq!(failed.code, 3);
+        assert!(failed.is_no_fail_safe());
+        assert!(err.to_string().contains("re-arm fail-safe"), "got: {}", err);
+
+        conn.error_code = CommissioningError::Busywithotheradmin as u8;
+        let err = send_commissioning_complete(&conn, &case).await.unwrap_err();
+        let failed = err.downcast_ref::<CommissioningCompleteFailed>().unwrap();
+        assert!(!failed.is_no_fail_safe());
+        assert_eq!(
+            err.to_string(),
+            "CommissioningComplete failed with status 4 (Busywithotheradmin)"
+        );
+    }
+
+    #[test]
+    fn test_commissioning_step_order() {
+        let mut step = CommissioningStep::Pase;
+        let mut names = vec![step.name()];
+        while let Some(next) = step.next() {
+            names.push(next.name());
+            step = next;
+        }
+        assert_eq!(
+            names,
+            ["Pase", "ArmFailSafe", "Csr", "AddTrustedRoot", "AddNoc", "Sigma", "CommissioningComplete"]
+        );
+        assert_eq!(CommissioningStep::CommissioningComplete.to_string(), "CommissioningComplete");
+    }
+
+    #[tokio::test]
+    async fn test_commission_reports_steps() {
+        let certs = certmanager::TestCertManager::new("commission_steps", &[]);
+        let cm = &certs.cm;
+        let fabric = certs.fabric();
+
+        let conn = ProvisioningConn {
+            replies: std::sync::Mutex::new(std::collections::VecDeque::new()),
+            operational_key: p256::SecretKey::random(&mut rand::thread_rng()),
+        };
+        let mut session = session::Session::new();
+        let mut steps = Vec::new();
+        let res = commission_with_progress(
+            &conn,
+            &mut session,
+            &fabric,
+            cm.as_ref(),
+            0x1234,
+            100,
+            &mut |step, _elapsed| steps.push(step),
+            None,
+        )
+        .await;
+        // device never answers Sigma1
+        assert!(res.is_err());
+        assert_eq!(
+            steps,
+            [
+                CommissioningStep::ArmFailSafe,
+                CommissioningStep::Csr,
+                CommissioningStep::AddTrustedRoot,
+                CommissioningStep::AddNoc,
+            ]
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_commission_cancelled() {
+        let certs = certmanager::TestCertManager::new("commission_cancelled", &[]);
+        let cm = &certs.cm;
+        let fabric = certs.fabric();
+
+        let conn = StallingConn {
+            inner: ProvisioningConn {
+                replies: std::sync::Mutex::new(std::collections::VecDeque::new()),
+                operational_key: p256::SecretKey::random(&mut rand::thread_rng()),
+            },
+            failsafe_expiry: std::sync::Mutex::new(Vec::new()),
+        };
+        let cancel = tokio_util::sync::CancellationToken::new();
+        let trigger = cancel.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_secs(1)).await;
+            trigger.cancel();
+        });
+
+        let started = tokio::time::Instant::now();
+        let mut session = session::Session::new();
+        let mut steps = Vec::new();
+        let res = commission_with_progress(
+            &conn,
+            &mut session,
+            &fabric,
+            cm.as_ref(),
+            0x1234,
+            100,
+            &mut |step, _elapsed| steps.push(step),
+            Some(&cancel),
+        )
+        .await;
+        let Err(err) = res else {
+            panic!("commissioning must fail when cancelled");
+        };
+        assert!(err.downcast_ref::<Cancelled>().is_some(), "got: {:?}", err);
+        // aborted while waiting for CSR response, not after response timeout
+        assert!(started.elapsed() < Duration::from_secs(2));
+        assert_eq!(steps, [CommissioningStep::ArmFailSafe]);
+        // armed, then disarmed on cancel
+        assert_eq!(*conn.failsafe_expiry.lock().unwrap(), [60, 0]);
+    }
+
+    #[test]
+    fn test_issue_noc_at_injected_time() {
+        // 2023-11-14, well before now - device clock lagging behind
+        let issued = std::time::UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+        let clock = std::sync::Arc::new(cert_x509::FixedClock(issued));
+        let certs = certmanager::TestCertManager::with_clock("issue_noc_clock", &[], clock);
+        let cm = &certs.cm;
+
+        let key = p256::SecretKey::random(&mut rand::thread_rng());
+        let mut t = tlv::TlvBuffer::new();
+        t.write_anon_struct().unwrap();
+        t.write_octetstring(1, &crate::device::commissioning::generate_csr(&key).unwrap()).unwrap();
+        t.write_octetstring(2, &[0; 32]).unwrap();
+        t.write_struct_end().unwrap();
+        let csrd = decode_nocsr_elements(&t.data).unwrap();
+        let noc = cert_matter::MatterCert::decode(&issue_noc(cm.as_ref(), &csrd, 0x1234, 1).unwrap()).unwrap();
+        assert_eq!(noc.not_before_unix(), 1_700_000_000);
+        assert_eq!(
+            noc.not_after_unix(),
+            Some(1_700_000_000 + cert_x509::CERT_VALIDITY.as_secs())
+        );
+        let root = cert_matter::MatterCert::decode(&root_cert_matter(cm.as_ref()).unwrap()).unwrap();
+        assert_eq!(root.not_before_unix(), 1_700_000_000);
+    }
+
+    #[test]
+    fn test_issue_noc_for_update() {
+        let certs = certmanager::TestCertManager::new("issue_noc_for_update", &[]);
+        let cm = &certs.cm;
+
+        // NOCSRElements as sent by device in CSRResponse
+        let nocsr = |key: &p256::SecretKey| {
+            let mut t = tlv::TlvBuffer::new();
+            t.write_anon_struct().unwrap();
+            t.write_octetstring(1, &crate::device::commissioning::generate_csr(key).unwrap()).unwrap();
+            t.write_octetstring(2, &[0; 32]).unwrap();
+            t.write_struct_end().unwrap();
+            t.data
+        };
+        let old_key = p256::SecretKey::random(&mut rand::thread_rng());
+        let new_key = p256::SecretKey::random(&mut rand::thread_rng());
+        let issue = |key| {
+            let csrd = decode_nocsr_elements(&nocsr(key)).unwrap();
+            let noc = issue_noc(cm.as_ref(), &csrd, 0x1234, 1).unwrap();
+            cert_matter::MatterCert::decode(&noc).unwrap()
+        };
+        let old_noc = issue(&old_key);
+        let new_noc = issue(&new_key);
+        // same node, key swapped
+        assert_eq!(old_noc.subject, new_noc.subject);
+        assert_eq!(new_noc.public_key, new_key.public_key().to_sec1_bytes().to_vec());
+        assert_ne!(old_noc.public_key, new_noc.public_key);
+
+        let noc = issue_noc(cm.as_ref(), &decode_nocsr_elements(&nocsr(&new_key)).unwrap(), 0x1234, 1).unwrap();
+        let payload =
+            crate::clusters::codec::operational_credential_cluster::encode_update_noc(noc.clone(), None).unwrap();
+        // command fields come without enclosing struct
+        let t = tlv::decode_tlv(&[&[0x15][..], &payload, &[0x18]].concat()).unwrap();
+        assert_eq!(t.get_octet_string(&[0]), Some(noc.as_slice()));
+        assert!(t.get(&[1]).is_none(), "no ICAC in our chain");
+    }
+
+    #[test]
+    fn test_addnoc_id_mapping() {
+        validate_node_ids(0x1234, 100).unwrap();
+        assert!(validate_node_ids(100, 100).is_err());
+        assert!(validate_node_ids(0, 100).is_err());
+        assert!(validate_node_ids(0x1234, 0).is_err());
+        assert!(validate_node_ids(0xFFFF_FFFF_FFFF_0001, 100).is_err());
+
+        let payload = addnoc_payload(&[1, 2, 3], None, &[9; 16], 100).unwrap();
+        let t = tlv::decode_tlv(&payload).unwrap();
+        assert_eq!(t.get_octet_string(&[0]), Some(&[1u8, 2, 3][..]));
+        assert!(t.get(&[1]).is_none());
+        assert_eq!(t.get_octet_string(&[2]), Some(&[9u8; 16][..]));
+        assert_eq!(t.get_int(&[3]), Some(100));
+        assert_eq!(t.get_int(&[4]), Some(ADMIN_VENDOR_ID as u64));
+    }
+
+    #[test]
+    fn test_addnoc_field_lengths() {
+        let payload = addnoc_payload(&[1, 2, 3], Some(&[4, 5]), &[9; 16], 100).unwrap();
+        let t = tlv::decode_tlv(&payload).unwrap();
+        assert_eq!(t.get_octet_string(&[1]), Some(&[4u8, 5][..]));
+
+        addnoc_payload(&[1; MAX_CERT_LENGTH], Some(&[1; MAX_CERT_LENGTH]), &[9; 16], 100).unwrap();
+        let err = addnoc_payload(&[1; MAX_CERT_LENGTH + 1], None, &[9; 16], 100).unwrap_err();
+        assert_eq!(err.to_string(), "AddNOC: NOC length 401 not in 1..=400");
+        assert!(addnoc_payload(&[], None, &[9; 16], 100).is_err());
+        let err = addnoc_payload(&[1], Some(&[1; 500]), &[9; 16], 100).unwrap_err();
+        assert!(err.to_string().contains("ICAC length 500"), "got: {}", err);
+        let err = addnoc_payload(&[1], None, &[9; 15], 100).unwrap_err();
+        assert!(err.to_string().contains("IPK length 15"), "got: {}", err);
+    }
+
+    #[tokio::test]
+    async fn test_detect_already_commissioned() {
+        let ours = [0x15u8, 0x30, 0x01, 0x01, 0xaa, 0x18];
+        let other = [0x15u8, 0x30, 0x01, 0x01, 0xbb, 0x18];
+        // device commissioned into our fabric (among others)
+        assert!(check(&[&other, &ours], &ours).await);
+        // device in other fabric only / factory fresh
+        assert!(!check(&[&other], &ours).await);
+        assert!(!check(&[], &ours).await);
+    }
+}
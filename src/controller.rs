@@ -1,11 +1,11 @@
-use std::{collections::HashMap, sync::Arc, time::Duration};
+use std::{collections::{BTreeMap, HashMap}, sync::Arc, time::Duration};
 
 use crate::{
     active_connection::{ActiveConnection, Exchange},
     cert_matter, certmanager, commission, fabric, im,
     messages::{self, Message},
     retransmit, session, sigma, spake2p,
-    tlv::TlvItemValue,
+    tlv::{self, TlvItemValue},
     transport::{self, ConnectionTrait},
     util::cryptoutil,
 };
@@ -15,7 +15,6 @@ use byteorder::{LittleEndian, WriteBytesExt};
 
 pub struct Controller {
     certmanager: Arc<dyn certmanager::CertManager>,
-    #[allow(dead_code)]
     transport: Arc<transport::Transport>,
     fabric: fabric::Fabric,
     /// In-memory CASE session resumption records keyed by peer node ID.
@@ -24,11 +23,52 @@ pub struct Controller {
 
 pub struct Connection {
     active: ActiveConnection,
+    /// Descriptor ServerList per endpoint, cached for the connection lifetime.
+    server_lists: std::sync::Mutex<HashMap<u16, Vec<u32>>>,
+    /// Largest message sent; larger writes are chunked, see [Connection::set_max_message_size].
+    max_message_size: std::sync::atomic::AtomicUsize,
 }
+/// Outcome of [Controller::commission_with_result]: connection to the new device
+/// and how the device knows our fabric.
+pub struct CommissionResult {
+    /// Authenticated CASE connection to the device.
+    pub connection: Connection,
+    /// Node id the device's NOC was issued for (checked against the device).
+    pub node_id: u64,
+    /// Index the device assigned to our fabric. Fabric-scoped data on the device
+    /// (ACL entries, NOCs) carries it, and RemoveFabric takes it.
+    pub fabric_index: u8,
+}
+
+impl CommissionResult {
+    /// Read CurrentFabricIndex over freshly commissioned `connection`.
+    async fn read(connection: Connection, node_id: u64) -> Result<Self> {
+        let fabric_index =
+            crate::clusters::codec::operational_credential_cluster::read_current_fabric_index(&connection, 0)
+                .await
+                .context("reading CurrentFabricIndex")?;
+        Ok(Self {
+            connection,
+            node_id,
+            fabric_index,
+        })
+    }
+}
+
 //trait IsSync: Sync {}
 //impl IsSync for Controller {}
 
 const CA_ID: u64 = 1;
+/// Timed interaction timeout used for RevokeCommissioning.
+const REVOKE_COMMISSIONING_TIMED_TIMEOUT_MS: u16 = 5000;
+/// Maximum length of fabric label in bytes (Operational Credentials UpdateFabricLabel).
+const MAX_FABRIC_LABEL_LEN: usize = 32;
+/// Fail-safe armed while UpdateNOC rotates a device's operational key.
+const UPDATE_NOC_FAILSAFE_SECS: u16 = 60;
+/// Maximum length of Localization Configuration ActiveLocale in bytes.
+const MAX_LOCALE_LEN: usize = 35;
+/// Maximum length of Basic Information NodeLabel in bytes.
+const MAX_NODE_LABEL_LEN: usize = 32;
 
 #[derive(Debug, Clone, Copy)]
 pub struct SigmaBusy {
@@ -44,6 +84,32 @@ impl std::fmt::Display for SigmaBusy {
 }
 impl std::error::Error for SigmaBusy {}
 
+/// Variations of commissioning run by [Controller::commission_with]; defaults give
+/// plain [Controller::commission].
+#[derive(Default, Clone, Copy)]
+struct CommissionOptions<'a> {
+    /// PBKDF parameters known out of band, see [Controller::commission_with_pbkdf_params].
+    pbkdf: Option<&'a spake2p::PbkdfParams>,
+    /// Abort when fired, see [Controller::commission_cancellable].
+    cancel: Option<&'a tokio_util::sync::CancellationToken>,
+    /// Use CASE when our root is already installed, see [Controller::commission_idempotent].
+    idempotent: bool,
+}
+
+/// Run `fut`, failing with [Cancelled](commission::Cancelled) as soon as `cancel` fires.
+async fn until_cancelled<T>(
+    cancel: Option<&tokio_util::sync::CancellationToken>,
+    fut: impl std::future::Future<Output = Result<T>>,
+) -> Result<T> {
+    match cancel {
+        Some(cancel) => tokio::select! {
+            res = fut => res,
+            _ = cancel.cancelled() => Err(commission::Cancelled.into()),
+        },
+        None => fut.await,
+    }
+}
+
 impl Controller {
     pub fn new(
         certmanager: &Arc<dyn certmanager::CertManager>,
@@ -64,11 +130,24 @@ impl Controller {
         }))
     }
 
+    /// Operational certificate (x509 DER) the controller authenticates with as
+    /// `controller_id`, as issued by the certificate manager.
+    pub fn operational_cert(&self, controller_id: u64) -> Result<Vec<u8>> {
+        self.certmanager.get_user_cert(controller_id)
+    }
+
+    /// Node id in the subject of the operational certificate for `controller_id`.
+    /// This is the subject devices see for this controller, e.g. in ACL entries.
+    pub fn node_id(&self, controller_id: u64) -> Result<u64> {
+        cert_matter::get_subject_node_id_from_x509_bytes(&self.operational_cert(controller_id)?)
+    }
+
     /// commission device
     /// - authenticate using pin
     /// - push CA certificate to device
     /// - sign device's certificate
     /// - set controller id - user which can control device
+    /// - check device's NOC was issued for `node_id`
     /// - return authenticated connection which can be used to send additional commands
     pub async fn commission(
         &self,
@@ -77,19 +156,137 @@ impl Controller {
         node_id: u64,
         controller_id: u64,
     ) -> Result<Connection> {
-        let mut session = auth_spake(connection.as_ref(), pin).await?;
-        let session = commission::commission(
+        self.commission_with(connection, pin, node_id, controller_id, &mut |_, _| {}, CommissionOptions::default())
+            .await
+    }
+
+    /// Like [Controller::commission], also returning the fabric index the device
+    /// assigned to our fabric, so the device can be referenced in ACLs or removed.
+    pub async fn commission_with_result(
+        &self,
+        connection: &Arc<dyn ConnectionTrait>,
+        pin: u32,
+        node_id: u64,
+        controller_id: u64,
+    ) -> Result<CommissionResult> {
+        let connection = self.commission(connection, pin, node_id, controller_id).await?;
+        CommissionResult::read(connection, node_id).await
+    }
+
+    /// Like [Controller::commission], reporting progress. `progress` is called as each
+    /// [CommissioningStep](commission::CommissioningStep) finishes, with time the step took.
+    /// When commissioning fails the last reported step tells how far it got.
+    pub async fn commission_step_by_step<F>(
+        &self,
+        connection: &Arc<dyn ConnectionTrait>,
+        pin: u32,
+        node_id: u64,
+        controller_id: u64,
+        mut progress: F,
+    ) -> Result<Connection>
+    where
+        F: FnMut(commission::CommissioningStep, Duration) + Send,
+    {
+        self.commission_with(connection, pin, node_id, controller_id, &mut progress, CommissionOptions::default())
+            .await
+    }
+
+    /// Like [Controller::commission], aborted with [Cancelled](commission::Cancelled)
+    /// as soon as `cancel` fires, e.g. from a UI cancel button. When cancelled after PASE
+    /// the fail-safe is disarmed, so the device drops partially provisioned state at once.
+    pub async fn commission_cancellable(
+        &self,
+        connection: &Arc<dyn ConnectionTrait>,
+        pin: u32,
+        node_id: u64,
+        controller_id: u64,
+        cancel: &tokio_util::sync::CancellationToken,
+    ) -> Result<Connection> {
+        let options = CommissionOptions {
+            cancel: Some(cancel),
+            ..Default::default()
+        };
+        self.commission_with(connection, pin, node_id, controller_id, &mut |_, _| {}, options)
+            .await
+    }
+
+    /// Like [Controller::commission], for a device whose PBKDF parameters (iterations
+    /// and salt) are already known. PASE then doesn't rely on parameters sent by the
+    /// device. Parameters out of spec range are rejected.
+    pub async fn commission_with_pbkdf_params(
+        &self,
+        connection: &Arc<dyn ConnectionTrait>,
+        pin: u32,
+        params: &spake2p::PbkdfParams,
+        node_id: u64,
+        controller_id: u64,
+    ) -> Result<Connection> {
+        spake2p::validate_pbkdf_params(params.iterations, &params.salt)?;
+        let options = CommissionOptions {
+            pbkdf: Some(params),
+            ..Default::default()
+        };
+        self.commission_with(connection, pin, node_id, controller_id, &mut |_, _| {}, options)
+            .await
+    }
+
+    /// Like [Controller::commission], but safe to run again against the same device.
+    ///
+    /// After PASE the device's TrustedRootCertificates are checked. When our root is already
+    /// installed the device was commissioned into this fabric before, so certificate provisioning
+    /// (which would fail at AddNOC with FabricConflict) is skipped and CASE session is
+    /// established instead - `node_id` must then match the id the device got first time.
+    pub async fn commission_idempotent(
+        &self,
+        connection: &Arc<dyn ConnectionTrait>,
+        pin: u32,
+        node_id: u64,
+        controller_id: u64,
+    ) -> Result<Connection> {
+        let options = CommissionOptions {
+            idempotent: true,
+            ..Default::default()
+        };
+        self.commission_with(connection, pin, node_id, controller_id, &mut |_, _| {}, options)
+            .await
+    }
+
+    /// PASE, certificate provisioning and node id check shared by all `commission*` variants.
+    async fn commission_with(
+        &self,
+        connection: &Arc<dyn ConnectionTrait>,
+        pin: u32,
+        node_id: u64,
+        controller_id: u64,
+        progress: &mut (dyn FnMut(commission::CommissioningStep, Duration) + Send),
+        options: CommissionOptions<'_>,
+    ) -> Result<Connection> {
+        let cancel = options.cancel;
+        let started = tokio::time::Instant::now();
+        let mut session =
+            until_cancelled(cancel, auth_spake_with_params(connection.as_ref(), pin, options.pbkdf)).await?;
+        progress(commission::CommissioningStep::Pase, started.elapsed());
+        if options.idempotent
+            && commission::already_commissioned(connection.as_ref(), &session, self.certmanager.as_ref())
+                .await?
+        {
+            log::info!("device already commissioned into fabric, authenticating with CASE");
+            return until_cancelled(cancel, self.auth_sigma(connection, node_id, controller_id)).await;
+        }
+        let session = commission::commission_with_progress(
             connection.as_ref(),
             &mut session,
             &self.fabric,
             self.certmanager.as_ref(),
             node_id,
             controller_id,
+            progress,
+            cancel,
         )
         .await?;
-        Ok(Connection {
-            active: ActiveConnection::new(connection.clone(), session),
-        })
+        let connection = Connection::from_parts(connection.clone(), session);
+        until_cancelled(cancel, connection.verify_node_id(node_id)).await?;
+        Ok(connection)
     }
 
     /// create authenticated connection to control device
@@ -110,9 +307,92 @@ impl Controller {
         if let Some(record) = resumption {
             self.resumption.lock().await.insert(node_id, record);
         }
-        Ok(Connection {
-            active: ActiveConnection::new(connection.clone(), session),
-        })
+        Ok(Connection::from_parts(connection.clone(), session))
+    }
+
+    /// Like [Controller::auth_sigma], aborted with [Cancelled](commission::Cancelled)
+    /// as soon as `cancel` fires.
+    pub async fn auth_sigma_cancellable(
+        &self,
+        connection: &Arc<dyn ConnectionTrait>,
+        node_id: u64,
+        controller_id: u64,
+        cancel: &tokio_util::sync::CancellationToken,
+    ) -> Result<Connection> {
+        tokio::select! {
+            res = self.auth_sigma(connection, node_id, controller_id) => res,
+            _ = cancel.cancelled() => Err(commission::Cancelled.into()),
+        }
+    }
+
+    /// Rotate the operational key of the device behind `conn`. The device generates a new
+    /// key pair (CSRRequest with IsForUpdateNOC), a NOC for the same `node_id` is issued
+    /// for it and installed with UpdateNOC, then committed with CommissioningComplete.
+    /// When any step fails the fail-safe is disarmed and the device keeps its previous NOC.
+    /// CASE sessions established afterwards use the new key.
+    pub async fn update_noc(&self, conn: &Connection, node_id: u64) -> Result<()> {
+        conn.arm_fail_safe(UPDATE_NOC_FAILSAFE_SECS).await.context("update_noc")?;
+        let res = self.update_noc_armed(conn, node_id).await;
+        if res.is_err() {
+            if let Err(e) = conn.arm_fail_safe(0).await {
+                log::debug!("fail-safe disarm after failed UpdateNOC: {:?}", e);
+            }
+        }
+        res
+    }
+
+    async fn update_noc_armed(&self, conn: &Connection, node_id: u64) -> Result<()> {
+        use crate::clusters::codec::operational_credential_cluster as oc;
+        use crate::clusters::defs;
+        let nonce: [u8; 32] = rand::random();
+        let fields = conn
+            .invoke_request2(
+                0,
+                defs::CLUSTER_ID_OPERATIONAL_CREDENTIALS,
+                defs::CLUSTER_OPERATIONAL_CREDENTIALS_CMD_ID_CSRREQUEST,
+                &oc::encode_csr_request(nonce.to_vec(), Some(true))?,
+            )
+            .await
+            .context("update_noc: CSRRequest")?;
+        let elements = oc::decode_csr_response(&fields)?
+            .nocsr_elements
+            .context("CSRResponse without NOCSRElements")?;
+        let csrd = commission::decode_nocsr_elements(&elements)?;
+        let noc = commission::issue_noc(self.certmanager.as_ref(), &csrd, node_id, self.fabric.ca_id)?;
+        let fields = conn
+            .invoke_request2(
+                0,
+                defs::CLUSTER_ID_OPERATIONAL_CREDENTIALS,
+                defs::CLUSTER_OPERATIONAL_CREDENTIALS_CMD_ID_UPDATENOC,
+                &oc::encode_update_noc(noc, None)?,
+            )
+            .await
+            .context("update_noc: UpdateNOC")?;
+        let resp = oc::decode_noc_response(&fields)?;
+        match resp.status_code {
+            Some(oc::NodeOperationalCertStatus::Ok) => {}
+            other => {
+                return Err(anyhow::anyhow!(
+                    "UpdateNOC failed: {:?} {}",
+                    other,
+                    resp.debug_text.unwrap_or_default()
+                ))
+            }
+        }
+        conn.commissioning_complete().await.context("update_noc")
+    }
+
+    /// Like [Controller::auth_sigma] for a device at resolved `address`, e.g. from mDNS,
+    /// using the controller's transport. For link-local IPv6 (typical for Thread devices)
+    /// the scope id of `address` selects the interface the device is reached on.
+    pub async fn auth_sigma_addr(
+        &self,
+        address: std::net::SocketAddr,
+        node_id: u64,
+        controller_id: u64,
+    ) -> Result<Connection> {
+        let connection = self.transport.create_connection_addr(address).await;
+        self.auth_sigma(&connection, node_id, controller_id).await
     }
 
     /// Run auth_sigma with automatic BUSY retry.
@@ -187,6 +467,8 @@ impl Controller {
         retrctx.subscribe_exchange(exchange);
 
         let mut ctx = sigma::SigmaContext::new(node_id);
+        let session_id = SessionIdLease::alloc(connection.as_ref())?;
+        ctx.session_id = session_id.id;
         let ca_pubkey = self.certmanager.get_ca_key()?.public_key().to_sec1_bytes();
         sigma::sigma1_resume(&self.fabric, &mut ctx, &ca_pubkey, &record)?;
         let s1 = messages::sigma1(exchange, &ctx.sigma1_payload)?;
@@ -194,7 +476,15 @@ impl Controller {
         log::debug!("CASE resume: send Sigma1Resume exchange:{}", exchange);
         retrctx.send(&s1).await?;
 
-        let sigma2 = retrctx.get_next_message().await?;
+        let sigma2 = retrctx
+            .get_next_handshake_message(
+                exchange,
+                &[
+                    messages::ProtocolMessageHeader::OPCODE_CASE_SIGMA2,
+                    messages::ProtocolMessageHeader::OPCODE_CASE_SIGMA2_RESUME,
+                ],
+            )
+            .await?;
 
         // Responder sent a status report instead of Sigma2 / Sigma2Resume - this includes
         // Fall back to full SIGMA in all cases.
@@ -252,7 +542,8 @@ impl Controller {
 
         let mut ses = session::Session::new();
         ses.session_id = parsed.responder_session_id;
-        ses.my_session_id = ctx.session_id;
+        ses.my_session_id = session_id.keep();
+        ses.peer_mrp = use_peer_mrp(connection.as_ref(), parsed.responder_mrp);
         ses.set_decrypt_key(&keypack[16..32]);
         ses.set_encrypt_key(&keypack[..16]);
 
@@ -310,7 +601,7 @@ impl Controller {
         let btp_conn = BtpConnection::connect(peripheral).await.context("BTP connect")?;
 
         // 2. PASE
-        let mut pase_session = auth_spake(btp_conn.as_ref(), pin).await.context("PASE over BLE")?;
+        let mut pase_session = auth_spake_with_params(btp_conn.as_ref(), pin, None).await.context("PASE over BLE")?;
 
         // 3. BLE-side commissioning phase
         commission::commission_ble_phase(
@@ -350,9 +641,7 @@ impl Controller {
                 )
                 .await;
                 if let Ok(ses) = ses {
-                    return Ok(Connection {
-                        active: ActiveConnection::new(udp_conn, ses),
-                    });
+                    return Ok(Connection::from_parts(udp_conn, ses));
                 } else {
                     log::debug!("Failed to commission over UDP at {}: {:?}", address, ses.err());
                 }
@@ -371,9 +660,9 @@ impl Controller {
 
         let ca_pubkey = self.certmanager.get_ca_public_key()?;
         let fabric_tmp = fabric::Fabric::new(self.fabric.id, 0, &ca_pubkey, &self.certmanager.get_ipk_epoch_key());
-        let compressed = fabric_tmp.compressed().context("compressed fabric ID")?;
-        let instance = format!("{}-{:016X}", hex::encode_upper(&compressed), node_id);
-        let expected_target = format!("{}._matter._tcp.local.", instance);
+        let compressed = fabric_tmp.compressed_id().context("compressed fabric ID")?;
+        let instance = discover::operational_instance_name(compressed, node_id);
+        let expected_target = format!("{}.", instance);
 
         log::debug!("Discovering operational device via mDNS with target {}", expected_target);
         let (_, info) = discover::discover_one(
@@ -385,22 +674,69 @@ impl Controller {
         ).await.context(format!("operational mDNS timeout for {}", instance))?;
         log::debug!("Operational mDNS discovered device: {:?}", info);
 
-        let port = info.port.unwrap_or(5540);
         let addresses: Vec<String> = info
-            .ips
+            .scoped_addrs(discover::DEFAULT_PORT)
             .iter()
-            .map(|ip| crate::discover::addr_string(ip, port, info.scope_id))
+            .map(|a| a.to_string())
             .collect();
         log::info!("Device discovered at {}", addresses.join(", "));
         Ok(addresses)
     }
 }
 
+/// Fail unless `res` is a WriteResponse with success status for every written path.
+fn check_write_response(res: &Message) -> Result<()> {
+    if res.status_report_info.is_some() {
+        return Err(anyhow::anyhow!(
+            "write_request failed with status {:?}",
+            res.status_report_info
+        ))
+    };
+    if res.protocol_header.protocol_id
+        == messages::ProtocolMessageHeader::PROTOCOL_ID_INTERACTION
+        && res.protocol_header.opcode
+            == messages::ProtocolMessageHeader::INTERACTION_OPCODE_STATUS_RESP
+    {
+        let stat = messages::parse_status_response(res)?;
+        return Err(anyhow::anyhow!(
+            "write_request failed with status response {}",
+            stat
+        ))
+    };
+    if res.protocol_header.protocol_id
+        != messages::ProtocolMessageHeader::PROTOCOL_ID_INTERACTION
+        || res.protocol_header.opcode
+            != messages::ProtocolMessageHeader::INTERACTION_OPCODE_WRITE_RESP
+    {
+        return Err(anyhow::anyhow!(
+            "response is not expected write_resp {:?}",
+            res.protocol_header
+        ))
+    };
+    let Some(TlvItemValue::List(statuses)) = res.tlv.get(&[0]) else {
+        return Err(anyhow::anyhow!("status not found in write response"));
+    };
+    if statuses.is_empty() {
+        return Err(anyhow::anyhow!("status not found in write response"));
+    }
+    for st in statuses {
+        let stat = st.get_int(&[1, 0]).context("status not found in write response")?;
+        if stat != 0 {
+            return Err(anyhow::anyhow!("write failed with status 0x{:x}", stat));
+        }
+    }
+    Ok(())
+}
+
 /// Authenticated virtual connection can be used to send commands to device.
 impl Connection {
     /// Build a Connection from a transport-layer connection and an established session.
     pub(crate) fn from_parts(conn: Arc<dyn ConnectionTrait>, session: session::Session) -> Self {
-        Self { active: ActiveConnection::new(conn, session) }
+        Self {
+            active: ActiveConnection::new(conn, session),
+            server_lists: std::sync::Mutex::new(HashMap::new()),
+            max_message_size: std::sync::atomic::AtomicUsize::new(messages::MAX_UDP_MESSAGE_SIZE),
+        }
     }
 
     /// Read attribute from device and return parsed matter protocol response.
@@ -423,1036 +759,3176 @@ impl Connection {
         cluster: u32,
         attr: u32,
     ) -> Result<TlvItemValue> {
+        self.read_request2_filtered(endpoint, cluster, attr, true).await
+    }
+
+    /// Like [Connection::read_request2] with explicit fabricFiltered flag.
+    /// Unfiltered reads of fabric-scoped attributes (ACL, Fabrics, Bindings)
+    /// also return entries of other fabrics, which helps debugging multi-admin setups.
+    pub async fn read_request2_filtered(
+        &self,
+        endpoint: u16,
+        cluster: u32,
+        attr: u32,
+        fabric_filtered: bool,
+    ) -> Result<TlvItemValue> {
+        Ok(self.read_attribute(endpoint, cluster, attr, fabric_filtered).await?.0)
+    }
+
+    /// Like [Connection::read_request2], additionally returning DataVersion of the
+    /// cluster instance as reported by device (None if device omitted it).
+    /// Useful for attribute caches and DataVersionFilters.
+    pub async fn read_request3(
+        &self,
+        endpoint: u16,
+        cluster: u32,
+        attr: u32,
+    ) -> Result<(TlvItemValue, Option<u32>)> {
+        self.read_attribute(endpoint, cluster, attr, true).await
+    }
+
+    async fn read_attribute(
+        &self,
+        endpoint: u16,
+        cluster: u32,
+        attr: u32,
+        fabric_filtered: bool,
+    ) -> Result<(TlvItemValue, Option<u32>)> {
         let exchange: u16 = rand::random();
-        let msg = messages::im_read_request(endpoint, cluster, attr, exchange)?;
+        let msg = messages::im_read_request_path(endpoint, cluster, Some(attr), fabric_filtered, exchange)?;
         let mut ex = self.active.open_exchange(exchange);
         ex.send(&msg).await?;
         let report = self.collect_reports(&mut ex).await?;
-        let first = report
-            .attribute_reports
-            .into_iter()
-            .next()
-            .context("report data contains no attribute reports")?;
-        match first.data {
-            im::AttributeData::Value(v) => Ok(v),
+        // list attributes may arrive as full value followed by AppendItem chunks
+        let mut acc = im::ReportAccumulator::new();
+        acc.ingest(&report);
+        let (data, data_version) = match acc.attribute(endpoint, cluster, attr) {
+            Some(state) => (state.data.clone(), state.data_version),
+            None => {
+                let first = report
+                    .attribute_reports
+                    .into_iter()
+                    .next()
+                    .context("report data contains no attribute reports")?;
+                (first.data, first.data_version)
+            }
+        };
+        match data {
+            im::AttributeData::Value(v) => Ok((v, data_version)),
             im::AttributeData::Status { status, .. } => {
                 Err(anyhow::anyhow!("report data with status {}", status))
             }
         }
     }
 
-    /// Receive ReportData chunks on the exchange until the last chunk,
-    /// sending the IM StatusResponse between chunks as required, and return
-    /// the merged report. The final StatusResponse is only sent when the
-    /// device did not set SuppressResponse (e.g. subscribe priming reports).
-    async fn collect_reports(&self, exchange: &mut Exchange<'_>) -> Result<im::ReportData> {
-        let mut merged: Option<im::ReportData> = None;
-        loop {
-            let msg = exchange.recv().await?;
-            if let Some(status) = &msg.status_report_info {
-                return Err(anyhow::anyhow!(
-                    "status report while waiting for report data: {:?}",
-                    status
-                ));
-            }
-            if msg.protocol_header.protocol_id
-                != messages::ProtocolMessageHeader::PROTOCOL_ID_INTERACTION
-                || msg.protocol_header.opcode
-                    != messages::ProtocolMessageHeader::INTERACTION_OPCODE_REPORT_DATA
-            {
-                return Err(anyhow::anyhow!(
-                    "response is not expected report_data {:?}",
-                    msg.protocol_header
-                ));
-            }
-            let report = im::ReportData::parse(&msg.tlv)?;
-            let more = report.more_chunks;
-            let respond = more || !report.suppress_response;
-            match merged.as_mut() {
-                Some(m) => m.merge(report),
-                None => merged = Some(report),
-            }
-            if respond {
-                let flags = messages::im_status_flags_for(msg.protocol_header.exchange_flags);
-                let resp = messages::im_status_response(
-                    exchange.id,
-                    flags,
-                    msg.message_header.message_counter,
-                )?;
-                exchange.send(&resp).await?;
-            }
-            if !more {
-                return merged.context("no report data received");
-            }
-        }
-    }
-
-    /// Invoke command
-    pub async fn invoke_request(
+    /// Read several `(endpoint, cluster, attribute)` paths in one interaction.
+    /// Each path the device answered maps to its value or, when the device reported
+    /// AttributeStatusIB for it (e.g. UnsupportedAttribute), to the status, so one
+    /// failing path doesn't fail the others. Paths the device left out are absent.
+    pub async fn read_attributes(
         &self,
-        endpoint: u16,
-        cluster: u32,
-        command: u32,
-        payload: &[u8],
-    ) -> Result<Message> {
+        paths: &[(u16, u32, u32)],
+    ) -> Result<BTreeMap<(u16, u32, u32), im::AttributeData>> {
         let exchange: u16 = rand::random();
-        log::debug!(
-            "invoke_request exch:{} endpoint:{} cluster:{} command:{}",
-            exchange,
-            endpoint,
-            cluster,
-            command
-        );
-        let msg = messages::im_invoke_request(endpoint, cluster, command, exchange, payload, false)?;
-        self.active.request(exchange, &msg).await
+        let request: Vec<_> = paths.iter().map(|(e, c, a)| (*e, *c, Some(*a))).collect();
+        let msg = messages::im_read_request_paths(&request, true, exchange)?;
+        let mut ex = self.active.open_exchange(exchange);
+        ex.send(&msg).await?;
+        let report = self.collect_reports(&mut ex).await?;
+        let mut acc = im::ReportAccumulator::new();
+        acc.ingest(&report);
+        Ok(paths
+            .iter()
+            .filter_map(|p| Some((*p, acc.attribute(p.0, p.1, p.2)?.data.clone())))
+            .collect())
     }
 
-    /// Invoke command and return result TLV
-    pub async fn invoke_request2(
+    /// Read all attributes of one cluster on one endpoint (attribute id wildcard).
+    /// Returns attribute values keyed by attribute id; attributes reported
+    /// with an error status are skipped.
+    pub async fn read_cluster(
         &self,
         endpoint: u16,
         cluster: u32,
-        command: u32,
-        payload: &[u8],
-    ) -> Result<TlvItemValue> {
-        let res = self.invoke_request(endpoint, cluster, command, payload).await?;
-        let o = res.tlv.get(&[1, 0, 1, 1]).context("result not found")?;
-        Ok(o.clone())
+    ) -> Result<HashMap<u32, TlvItemValue>> {
+        let mut out = HashMap::new();
+        for (attr, data) in self.read_cluster_data(endpoint, cluster).await? {
+            match data {
+                im::AttributeData::Value(v) => {
+                    out.insert(attr, v);
+                }
+                im::AttributeData::Status { status, .. } => {
+                    log::debug!("read_cluster: attribute {:#x} status {}", attr, status);
+                }
+            }
+        }
+        Ok(out)
     }
 
-    pub async fn write_request(
+    /// Wildcard read of one cluster keeping per attribute error statuses.
+    async fn read_cluster_data(
         &self,
         endpoint: u16,
         cluster: u32,
-        attr: u32,
-        payload: &[u8],
-    ) -> Result<()> {
+    ) -> Result<BTreeMap<u32, im::AttributeData>> {
         let exchange: u16 = rand::random();
-        log::debug!(
-            "write_request exch:{} endpoint:{} cluster:{} attr:{}",
-            exchange,
-            endpoint,
-            cluster,
-            attr,
-        );
+        let msg = messages::im_read_request_path(endpoint, cluster, None, true, exchange)?;
+        let mut ex = self.active.open_exchange(exchange);
+        ex.send(&msg).await?;
+        let report = self.collect_reports(&mut ex).await?;
+        let mut acc = im::ReportAccumulator::new();
+        acc.ingest(&report);
+        Ok(acc
+            .attributes()
+            .filter(|((ep, cl, _), _)| *ep == endpoint && *cl == cluster)
+            .map(|((_, _, attr), state)| (*attr, state.data.clone()))
+            .collect())
+    }
 
-        let msg = messages::im_write_request(endpoint, cluster, attr, exchange, payload)?;
-        let res = self.active.request(exchange, &msg).await?;
-        if res.status_report_info.is_some() {
-            return Err(anyhow::anyhow!(
-                "write_request failed with status {:?}",
-                res.status_report_info
-            ))
-        };
-        if res.protocol_header.protocol_id
-            == messages::ProtocolMessageHeader::PROTOCOL_ID_INTERACTION
-            && res.protocol_header.opcode
-                == messages::ProtocolMessageHeader::INTERACTION_OPCODE_STATUS_RESP
-        {
-            let stat = res
-                .tlv
-                .get_int(&[0])
-                .context("status not found in status response")?;
-            res.tlv.dump(1);
-            return Err(anyhow::anyhow!(
-                "response is not expected status_resp 0x{:x}",
-                stat
-            ))
-        };
-        if res.protocol_header.protocol_id
-            != messages::ProtocolMessageHeader::PROTOCOL_ID_INTERACTION
-            || res.protocol_header.opcode
-                != messages::ProtocolMessageHeader::INTERACTION_OPCODE_WRITE_RESP
-        {
-            return Err(anyhow::anyhow!(
-                "response is not expected write_resp {:?}",
-                res.protocol_header
-            ))
-        };
-        let stat = res.tlv.get_int(&[0, 0, 1, 0]).context("status not found in write response")?;
-        if stat != 0 {
-            return Err(anyhow::anyhow!("write failed with status 0x{:x}", stat));
+    /// Read the whole data model of the node into human readable JSON:
+    /// `{"endpoints": {"<id>": {"clusters": {"0x<id>": {"name", "attributes": {"0x<id>": {"name", "value"}}}}}}}`.
+    /// Endpoints come from the Descriptor PartsList of endpoint 0, clusters from each
+    /// endpoint ServerList. Values are decoded by the cluster codecs, falling back to
+    /// [TlvItemValue::to_json]. Attributes reported with error status carry `"status"`
+    /// instead of `"value"`; endpoints or clusters which can't be read carry `"error"`.
+    pub async fn dump_device_json(&self) -> Result<serde_json::Value> {
+        let parts =
+            crate::clusters::codec::descriptor_cluster::read_parts_list(self, 0).await?;
+        let mut endpoints = serde_json::Map::new();
+        for endpoint in std::iter::once(0).chain(parts.into_iter().filter(|e| *e != 0)) {
+            let servers = match self.server_list(endpoint).await {
+                Ok(s) => s,
+                Err(e) => {
+                    endpoints.insert(endpoint.to_string(), serde_json::json!({"error": e.to_string()}));
+                    continue;
+                }
+            };
+            let mut clusters = serde_json::Map::new();
+            for cluster in servers {
+                let value = match self.read_cluster_data(endpoint, cluster).await {
+                    Ok(attrs) => crate::clusters::typed::cluster_json(cluster, &attrs),
+                    Err(e) => serde_json::json!({
+                        "name": crate::clusters::names::get_cluster_name(cluster),
+                        "error": e.to_string(),
+                    }),
+                };
+                clusters.insert(format!("0x{:04x}", cluster), value);
+            }
+            endpoints.insert(endpoint.to_string(), serde_json::json!({"clusters": clusters}));
         }
-        Ok(())
+        Ok(serde_json::json!({"endpoints": endpoints}))
     }
 
-    /// Subscribe to attribute changes. `None` path fields act as wildcards.
-    /// Set `keep_subscriptions = true` when adding a second subscription on the same
-    /// connection so the device does not cancel the first one.
-    ///
-    /// Handles the full subscribe transaction (chunked priming report, IM
-    /// StatusResponse acks, SubscribeResponse) and returns a [Subscription]
-    /// delivering decoded updates; updates are acked automatically by the
-    /// background read loop.
-    pub async fn subscribe_attrs(
-        &self,
-        endpoint: Option<u16>,
-        cluster: Option<u32>,
-        attr: Option<u32>,
-        keep_subscriptions: bool,
-    ) -> Result<Subscription> {
-        let exchange: u16 = rand::random();
-        log::debug!(
-            "subscribe_attrs exch:{} endpoint:{:?} cluster:{:?} attr:{:?} keep:{}",
-            exchange, endpoint, cluster, attr, keep_subscriptions
-        );
-        let msg = messages::im_subscribe_request_attr(endpoint, cluster, attr, exchange, keep_subscriptions)?;
-        self.subscribe_internal(exchange, &msg).await
+    /// Read installed node operational certificates (endpoint 0). Certificates stay in matter
+    /// TLV format; use [crate::clusters::typed::NocEntry::noc_cert] to inspect them.
+    pub async fn read_nocs(&self) -> Result<Vec<crate::clusters::typed::NocEntry>> {
+        let tlv = self
+            .read_request2(
+                0,
+                crate::clusters::defs::CLUSTER_ID_OPERATIONAL_CREDENTIALS,
+                crate::clusters::defs::CLUSTER_OPERATIONAL_CREDENTIALS_ATTR_ID_NOCS,
+            )
+            .await?;
+        crate::clusters::typed::decode_nocs(&tlv)
     }
 
-    /// Subscribe to events. `None` path fields act as wildcards.
-    /// See [Connection::subscribe_attrs] for transaction details.
-    pub async fn subscribe_events(
-        &self,
-        endpoint: Option<u16>,
-        cluster: Option<u32>,
-        event: Option<u32>,
-        keep_subscriptions: bool,
-    ) -> Result<Subscription> {
-        let exchange: u16 = rand::random();
-        log::debug!(
-            "subscribe_events exch:{} endpoint:{:?} cluster:{:?} event:{:?} keep:{}",
-            exchange, endpoint, cluster, event, keep_subscriptions
-        );
-        let msg = messages::im_subscribe_request_event(endpoint, cluster, event, exchange, keep_subscriptions)?;
-        self.subscribe_internal(exchange, &msg).await
+    /// Read installed trusted root certificates (endpoint 0).
+    pub async fn read_trusted_roots(&self) -> Result<Vec<crate::clusters::typed::TrustedRoot>> {
+        let tlv = self
+            .read_request2(
+                0,
+                crate::clusters::defs::CLUSTER_ID_OPERATIONAL_CREDENTIALS,
+                crate::clusters::defs::CLUSTER_OPERATIONAL_CREDENTIALS_ATTR_ID_TRUSTEDROOTCERTIFICATES,
+            )
+            .await?;
+        crate::clusters::typed::decode_trusted_roots(&tlv)
     }
 
-    async fn subscribe_internal(&self, exchange_id: u16, msg: &[u8]) -> Result<Subscription> {
-        let mut exchange = self.active.open_exchange(exchange_id);
-        exchange.send(msg).await?;
-        let priming = self.collect_reports(&mut exchange).await?;
-        let subscription_id = priming
-            .subscription_id
-            .context("priming report missing subscription id")?;
+    /// Check whether the device trusts the CA with public key `ca_public_key`
+    /// (e.g. [crate::certmanager::CertManager::get_ca_public_key]). False means
+    /// the device belongs to another fabric and CASE would fail with NoSharedTrustRoots.
+    pub async fn verify_shared_root(&self, ca_public_key: &[u8]) -> Result<bool> {
+        let roots = self.read_trusted_roots().await?;
+        Ok(crate::clusters::typed::shares_root(&roots, ca_public_key))
+    }
 
-        // Register before awaiting the SubscribeResponse so no update can be
-        // missed; the device cannot report before the transaction completes.
-        let rx = self.active.register_subscription(subscription_id);
-        let registry = self.active.subscriptions_handle();
+    /// Read the Access Control cluster ACL of the node (endpoint 0).
+    /// With `fabric_filtered` false the device may also return entries of
+    /// other fabrics; use [crate::clusters::typed::AclEntry::fabric_index] to tell them apart.
+    pub async fn read_acl(&self, fabric_filtered: bool) -> Result<Vec<crate::clusters::typed::AclEntry>> {
+        let tlv = self
+            .read_request2_filtered(
+                0,
+                crate::clusters::defs::CLUSTER_ID_ACCESS_CONTROL,
+                crate::clusters::defs::CLUSTER_ACCESS_CONTROL_ATTR_ID_ACL,
+                fabric_filtered,
+            )
+            .await?;
+        crate::clusters::typed::decode_acl_entries(&tlv)
+    }
 
-        let response = async {
-            let resp = exchange.recv().await?;
-            if resp.protocol_header.protocol_id
-                != messages::ProtocolMessageHeader::PROTOCOL_ID_INTERACTION
-                || resp.protocol_header.opcode
-                    != messages::ProtocolMessageHeader::INTERACTION_OPCODE_SUBSCRIBE_RESP
-            {
-                anyhow::bail!(
-                    "response is not expected subscribe_resp {:?}",
-                    resp.protocol_header
-                );
-            }
-            let sr = im::SubscribeResponse::parse(&resp.tlv)?;
-            if sr.subscription_id != subscription_id {
-                anyhow::bail!(
-                    "subscribe response id {} does not match priming report id {}",
-                    sr.subscription_id,
-                    subscription_id
-                );
-            }
-            Ok(sr)
-        }
-        .await;
+    /// Read General Diagnostics NetworkInterfaces (endpoint 0), e.g. to see the
+    /// operational IP addresses as reported by the device itself.
+    pub async fn network_interfaces(&self) -> Result<Vec<crate::clusters::typed::NetworkInterfaceInfo>> {
+        let tlv = self
+            .read_request2(
+                0,
+                crate::clusters::defs::CLUSTER_ID_GENERAL_DIAGNOSTICS,
+                crate::clusters::defs::CLUSTER_GENERAL_DIAGNOSTICS_ATTR_ID_NETWORKINTERFACES,
+            )
+            .await?;
+        crate::clusters::typed::decode_network_interfaces(&tlv)
+    }
 
-        match response {
-            Ok(sr) => Ok(Subscription {
-                subscription_id,
-                max_interval: sr.max_interval,
-                priming_attribute_reports: priming.attribute_reports,
-                priming_event_reports: priming.event_reports,
-                rx,
-                registry,
-            }),
-            Err(e) => {
-                registry.lock().unwrap().remove(&subscription_id);
-                Err(e)
-            }
-        }
+    /// Read Thread Network Diagnostics (endpoint 0) into [crate::clusters::typed::ThreadDiagnostics]
+    /// using a single attribute-wildcard read, e.g. to check mesh health of a Thread device.
+    pub async fn thread_diagnostics(&self) -> Result<crate::clusters::typed::ThreadDiagnostics> {
+        let attrs = self
+            .read_cluster(0, crate::clusters::defs::CLUSTER_ID_THREAD_NETWORK_DIAGNOSTICS)
+            .await?;
+        Ok(crate::clusters::typed::ThreadDiagnostics::from_attributes(&attrs))
     }
 
-    /// Cancel all subscriptions on this session by sending a SubscribeRequest with
-    /// `KeepSubscriptions = false` and no paths. The device drops all prior subscriptions.
-    pub async fn im_unsubscribe_all(&self) -> Result<Message> {
-        let exchange: u16 = rand::random();
-        log::debug!("im_unsubscribe_all exch:{}", exchange);
-        let msg = messages::im_unsubscribe_all(exchange)?;
-        self.active.request(exchange, &msg).await
+    /// Read Wi-Fi Network Diagnostics (endpoint 0) into [crate::clusters::typed::WifiDiagnostics]
+    /// using a single attribute-wildcard read.
+    pub async fn wifi_diagnostics(&self) -> Result<crate::clusters::typed::WifiDiagnostics> {
+        let attrs = self
+            .read_cluster(0, crate::clusters::defs::CLUSTER_ID_WI_FI_NETWORK_DIAGNOSTICS)
+            .await?;
+        Ok(crate::clusters::typed::WifiDiagnostics::from_attributes(&attrs))
     }
 
-    /// Enable or disable automatic IM StatusResponse replies to unsolicited
-    /// ReportData (enabled by default). Disable only when acking reports
-    /// manually via the raw message API.
-    pub fn set_auto_status_response(&self, enabled: bool) {
-        self.active.set_auto_status_response(enabled);
+    /// Read the Basic Information cluster (endpoint 0) into [crate::clusters::typed::BasicInfo]
+    /// using a single attribute-wildcard read.
+    pub async fn basic_info(&self) -> Result<crate::clusters::typed::BasicInfo> {
+        let attrs = self
+            .read_cluster(0, crate::clusters::defs::CLUSTER_ID_BASIC_INFORMATION)
+            .await?;
+        Ok(crate::clusters::typed::BasicInfo::from_attributes(&attrs))
     }
 
-    /// Invoke command with timed interaction
-    pub async fn invoke_request_timed(
+    /// Read the Power Source cluster on `endpoint` into [crate::clusters::typed::BatteryStatus]
+    /// using a single attribute-wildcard read.
+    pub async fn battery_status(
         &self,
         endpoint: u16,
-        cluster: u32,
-        command: u32,
-        payload: &[u8],
-        timeout: u16,
-    ) -> Result<Message> {
-        let exchange: u16 = rand::random();
+    ) -> Result<crate::clusters::typed::BatteryStatus> {
+        let attrs = self
+            .read_cluster(endpoint, crate::clusters::defs::CLUSTER_ID_POWER_SOURCE)
+            .await?;
+        Ok(crate::clusters::typed::BatteryStatus::from_attributes(&attrs))
+    }
 
-        // Send timed request first
-        let tr = messages::im_timed_request(exchange, timeout)?;
-        let result = self.active.request(exchange, &tr).await?;
+    /// Read Temperature Measurement MeasuredValue on `endpoint` in °C; `None` when the
+    /// sensor reports null (value unknown).
+    pub async fn temperature(&self, endpoint: u16) -> Result<Option<f32>> {
+        let tlv = self
+            .read_request2(
+                endpoint,
+                crate::clusters::defs::CLUSTER_ID_TEMPERATURE_MEASUREMENT,
+                crate::clusters::defs::CLUSTER_TEMPERATURE_MEASUREMENT_ATTR_ID_MEASUREDVALUE,
+            )
+            .await?;
+        crate::clusters::typed::decode_temperature(&tlv)
+    }
 
-        if result.protocol_header.protocol_id
-            != messages::ProtocolMessageHeader::PROTOCOL_ID_INTERACTION
-            || result.protocol_header.opcode
-                != messages::ProtocolMessageHeader::INTERACTION_OPCODE_STATUS_RESP
-        {
+    /// Read Relative Humidity Measurement MeasuredValue on `endpoint` in %RH.
+    pub async fn relative_humidity(&self, endpoint: u16) -> Result<Option<f32>> {
+        let tlv = self
+            .read_request2(
+                endpoint,
+                crate::clusters::defs::CLUSTER_ID_RELATIVE_HUMIDITY_MEASUREMENT,
+                crate::clusters::defs::CLUSTER_RELATIVE_HUMIDITY_MEASUREMENT_ATTR_ID_MEASUREDVALUE,
+            )
+            .await?;
+        crate::clusters::typed::decode_relative_humidity(&tlv)
+    }
+
+    /// Read Illuminance Measurement MeasuredValue on `endpoint` in lux.
+    pub async fn illuminance(&self, endpoint: u16) -> Result<Option<f64>> {
+        let tlv = self
+            .read_request2(
+                endpoint,
+                crate::clusters::defs::CLUSTER_ID_ILLUMINANCE_MEASUREMENT,
+                crate::clusters::defs::CLUSTER_ILLUMINANCE_MEASUREMENT_ATTR_ID_MEASUREDVALUE,
+            )
+            .await?;
+        crate::clusters::typed::decode_illuminance(&tlv)
+    }
+
+    /// Read Occupancy Sensing Occupancy on `endpoint`: true when occupied.
+    pub async fn occupied(&self, endpoint: u16) -> Result<bool> {
+        let tlv = self
+            .read_request2(
+                endpoint,
+                crate::clusters::defs::CLUSTER_ID_OCCUPANCY_SENSING,
+                crate::clusters::defs::CLUSTER_OCCUPANCY_SENSING_ATTR_ID_OCCUPANCY,
+            )
+            .await?;
+        crate::clusters::typed::decode_occupied(&tlv)
+    }
+
+    /// Read Boolean State StateValue on `endpoint`. For contact sensors true
+    /// means contact (closed).
+    pub async fn boolean_state(&self, endpoint: u16) -> Result<bool> {
+        crate::clusters::codec::boolean_state::read_state_value(self, endpoint).await
+    }
+
+    /// Read General Commissioning BasicCommissioningInfo (endpoint 0): the
+    /// recommended and maximum fail-safe times to use with ArmFailSafe.
+    pub async fn basic_commissioning_info(
+        &self,
+    ) -> Result<crate::clusters::typed::BasicCommissioningInfo> {
+        let tlv = self
+            .read_request2(
+                0,
+                crate::clusters::defs::CLUSTER_ID_GENERAL_COMMISSIONING,
+                crate::clusters::defs::CLUSTER_GENERAL_COMMISSIONING_ATTR_ID_BASICCOMMISSIONINGINFO,
+            )
+            .await?;
+        crate::clusters::typed::decode_basic_commissioning_info(&tlv)
+    }
+
+    /// Read Localization Configuration ActiveLocale (endpoint 0), e.g. "en-US".
+    /// Devices use it for user visible strings they report.
+    pub async fn active_locale(&self) -> Result<String> {
+        crate::clusters::codec::localization_configuration::read_active_locale(self, 0).await
+    }
+
+    /// Read Localization Configuration SupportedLocales (endpoint 0) - values
+    /// accepted by [Connection::set_active_locale].
+    pub async fn supported_locales(&self) -> Result<Vec<String>> {
+        crate::clusters::codec::localization_configuration::read_supported_locales(self, 0).await
+    }
+
+    /// Write Localization Configuration ActiveLocale (endpoint 0). Device rejects
+    /// locales not listed in SupportedLocales.
+    pub async fn set_active_locale(&self, locale: &str) -> Result<()> {
+        if locale.len() > MAX_LOCALE_LEN {
             return Err(anyhow::anyhow!(
-                "invoke_request_timed: unexpected response {:?}",
-                result
+                "locale is {} bytes, maximum is {}",
+                locale.len(),
+                MAX_LOCALE_LEN
             ));
         }
-        let status = result
-            .tlv
-            .get_int(&[0])
-            .context("invoke_request_timed: status not found")?;
-        if status != 0 {
+        let mut tlv = tlv::TlvBuffer::new();
+        tlv.write_string(2, locale)?;
+        self.write_request(
+            0,
+            crate::clusters::defs::CLUSTER_ID_LOCALIZATION_CONFIGURATION,
+            crate::clusters::defs::CLUSTER_LOCALIZATION_CONFIGURATION_ATTR_ID_ACTIVELOCALE,
+            &tlv.data,
+        )
+        .await
+    }
+
+    /// Read Basic Information NodeLabel (endpoint 0) - user assigned device name,
+    /// empty when not set.
+    pub async fn node_label(&self) -> Result<String> {
+        crate::clusters::codec::basic_information_cluster::read_node_label(self, 0).await
+    }
+
+    /// Write Basic Information NodeLabel (endpoint 0). The name is stored on the
+    /// device, so other controllers see it too.
+    pub async fn set_node_label(&self, label: &str) -> Result<()> {
+        if label.len() > MAX_NODE_LABEL_LEN {
             return Err(anyhow::anyhow!(
-                "invoke_request_timed: unexpected status {}",
-                status
+                "node label is {} bytes, maximum is {}",
+                label.len(),
+                MAX_NODE_LABEL_LEN
             ));
         }
+        let mut tlv = tlv::TlvBuffer::new();
+        tlv.write_string(2, label)?;
+        self.write_request(
+            0,
+            crate::clusters::defs::CLUSTER_ID_BASIC_INFORMATION,
+            crate::clusters::defs::CLUSTER_BASIC_INFORMATION_ATTR_ID_NODELABEL,
+            &tlv.data,
+        )
+        .await
+    }
 
-        log::debug!(
-            "invoke_request exch:{} endpoint:{} cluster:{} command:{}",
-            exchange,
-            endpoint,
-            cluster,
-            command
-        );
-        let msg = messages::im_invoke_request(endpoint, cluster, command, exchange, payload, true)?;
-        self.active.request(exchange, &msg).await
+    /// Read Descriptor TagList of `endpoint`: semantic tags telling apart endpoints of
+    /// the same type, e.g. buttons or outlets of a multi-gang device. Devices which
+    /// don't tag endpoints report UnsupportedAttribute.
+    pub async fn endpoint_tags(&self, endpoint: u16) -> Result<Vec<crate::clusters::typed::SemanticTag>> {
+        let tlv = self
+            .read_request2(
+                endpoint,
+                crate::clusters::defs::CLUSTER_ID_DESCRIPTOR,
+                crate::clusters::defs::CLUSTER_DESCRIPTOR_ATTR_ID_TAGLIST,
+            )
+            .await?;
+        crate::clusters::typed::decode_semantic_tags(&tlv)
     }
 
-    /// Receive next unsolicited raw message not handled elsewhere (subscription
-    /// reports are delivered decoded via [Subscription]; only reports with an
-    /// unknown subscription id and other unsolicited messages end up here).
-    /// Returns None when connection is closed. Messages may be dropped when
-    /// nobody drains this channel.
-    pub async fn recv_event(&self) -> Option<Message> {
-        self.active.recv_event().await
+    /// Read UserLabel LabelList of `endpoint`.
+    pub async fn user_labels(&self, endpoint: u16) -> Result<Vec<crate::clusters::typed::Label>> {
+        let tlv = self
+            .read_request2(
+                endpoint,
+                crate::clusters::defs::CLUSTER_ID_USER_LABEL,
+                crate::clusters::defs::CLUSTER_USER_LABEL_ATTR_ID_LABELLIST,
+            )
+            .await?;
+        crate::clusters::typed::decode_labels(&tlv)
     }
 
-    /// Try receive event without blocking.
-    pub fn try_recv_event(&self) -> Option<Message> {
-        self.active.try_recv_event()
+    /// Read FixedLabel LabelList of `endpoint` (labels set by the manufacturer).
+    pub async fn fixed_labels(&self, endpoint: u16) -> Result<Vec<crate::clusters::typed::Label>> {
+        let tlv = self
+            .read_request2(
+                endpoint,
+                crate::clusters::defs::CLUSTER_ID_FIXED_LABEL,
+                crate::clusters::defs::CLUSTER_FIXED_LABEL_ATTR_ID_LABELLIST,
+            )
+            .await?;
+        crate::clusters::typed::decode_labels(&tlv)
     }
 
-    /// Re-run CASE over the existing transport channel without tearing it down.
-    /// Stops the active read loop, runs auth_sigma (with BUSY retry), swaps the session,
-    /// and restarts the read loop -- all on the same underlying UDP channel registration.
-    pub async fn reauth(
+    /// Replace UserLabel LabelList of `endpoint` with `labels` as (label, value) pairs,
+    /// e.g. `[("room", "kitchen")]`. Labels and values are limited to 16 bytes.
+    pub async fn write_user_labels(&self, endpoint: u16, labels: &[(String, String)]) -> Result<()> {
+        let tlv = crate::clusters::typed::encode_labels(labels)?;
+        self.write_request(
+            endpoint,
+            crate::clusters::defs::CLUSTER_ID_USER_LABEL,
+            crate::clusters::defs::CLUSTER_USER_LABEL_ATTR_ID_LABELLIST,
+            &tlv,
+        )
+        .await
+    }
+
+    /// Write OnOff StartUpOnOff: state after power loss, `None` = state before it.
+    pub async fn set_start_up_on_off(
         &self,
-        controller: &Controller,
-        node_id: u64,
-        controller_id: u64,
+        endpoint: u16,
+        value: Option<crate::clusters::codec::on_off::StartUpOnOff>,
     ) -> Result<()> {
-        self.active.pause_read_loop().await;
-        let new_session = controller
-            .auth_sigma_with_busy_retry(&self.active.transport_conn, node_id, controller_id)
-            .await?;
-        self.active.reauth_with_session(new_session).await
+        let tlv = crate::clusters::typed::encode_start_up_on_off(value)?;
+        self.write_request(
+            endpoint,
+            crate::clusters::defs::CLUSTER_ID_ON_OFF,
+            crate::clusters::defs::CLUSTER_ON_OFF_ATTR_ID_STARTUPONOFF,
+            &tlv,
+        )
+        .await
     }
-}
-
-/// Active subscription created by [Connection::subscribe_attrs] or
-/// [Connection::subscribe_events]. Decoded updates are delivered via [Subscription::next];
-/// the background read loop acks them automatically. Dropping the handle stops
-/// delivery (the device-side subscription stays active until it expires or is
-/// cancelled via [Connection::im_unsubscribe_all]).
-pub struct Subscription {
-    pub subscription_id: u32,
-    /// Maximum reporting interval in seconds granted by the device.
-    pub max_interval: u16,
-    /// Attribute reports from the priming report (current values at subscribe time).
-    pub priming_attribute_reports: Vec<im::AttributeReport>,
-    /// Event reports from the priming report.
-    pub priming_event_reports: Vec<im::EventReport>,
-    rx: mpsc::Receiver<im::ReportUpdate>,
-    registry: Arc<std::sync::Mutex<HashMap<u32, mpsc::Sender<im::ReportUpdate>>>>,
-}
 
-impl Subscription {
-    /// Receive the next decoded update. Returns None when the connection is
-    /// closed or re-authenticated (the subscription is then gone; resubscribe).
-    pub async fn next(&mut self) -> Option<im::ReportUpdate> {
-        self.rx.recv().await
+    /// Write LevelControl StartUpCurrentLevel: level after power loss, `None` = level before it.
+    pub async fn set_start_up_current_level(&self, endpoint: u16, level: Option<u8>) -> Result<()> {
+        let tlv = crate::clusters::typed::encode_nullable_level(level)?;
+        self.write_request(
+            endpoint,
+            crate::clusters::defs::CLUSTER_ID_LEVEL_CONTROL,
+            crate::clusters::defs::CLUSTER_LEVEL_CONTROL_ATTR_ID_STARTUPCURRENTLEVEL,
+            &tlv,
+        )
+        .await
     }
-}
 
-impl Drop for Subscription {
-    fn drop(&mut self) {
-        self.registry.lock().unwrap().remove(&self.subscription_id);
+    /// Write LevelControl OnLevel: level when switched on, `None` = level before switching off.
+    pub async fn set_on_level(&self, endpoint: u16, level: Option<u8>) -> Result<()> {
+        let tlv = crate::clusters::typed::encode_nullable_level(level)?;
+        self.write_request(
+            endpoint,
+            crate::clusters::defs::CLUSTER_ID_LEVEL_CONTROL,
+            crate::clusters::defs::CLUSTER_LEVEL_CONTROL_ATTR_ID_ONLEVEL,
+            &tlv,
+        )
+        .await
     }
-}
 
-pub fn pin_to_passcode(pin: u32) -> Result<Vec<u8>> {
-    let mut out = Vec::new();
-    out.write_u32::<LittleEndian>(pin)?;
-    Ok(out)
-}
+    /// Write ColorControl StartUpColorTemperatureMireds: color temperature after power
+    /// loss, `None` = color temperature before it.
+    pub async fn set_start_up_color_temperature(
+        &self,
+        endpoint: u16,
+        mireds: Option<u16>,
+    ) -> Result<()> {
+        let tlv = crate::clusters::typed::encode_start_up_color_temperature(mireds)?;
+        self.write_request(
+            endpoint,
+            crate::clusters::defs::CLUSTER_ID_COLOR_CONTROL,
+            crate::clusters::defs::CLUSTER_COLOR_CONTROL_ATTR_ID_STARTUPCOLORTEMPERATUREMIREDS,
+            &tlv,
+        )
+        .await
+    }
 
-pub(crate) async fn auth_spake(connection: &dyn ConnectionTrait, pin: u32) -> Result<session::Session> {
-    let exchange = rand::random();
-    log::debug!("start auth_spake");
-    let mut session = session::Session::new();
-    session.my_session_id = 1;
-    let mut retrctx = retransmit::RetrContext::new(connection, &session);
-    // send pbkdf
-    log::debug!("send pbkdf request");
-    let pbkdf_req_protocol_message = messages::pbkdf_req(exchange)?;
-    retrctx.send(&pbkdf_req_protocol_message).await?;
+    /// Read this fabric's entry of Operational Credentials NOCs and check the
+    /// certificate was issued for `node_id`. Some devices silently ignore or
+    /// override the requested id; this catches it right after commissioning.
+    pub async fn verify_node_id(&self, node_id: u64) -> Result<()> {
+        let tlv = self
+            .read_request2(
+                0,
+                crate::clusters::defs::CLUSTER_ID_OPERATIONAL_CREDENTIALS,
+                crate::clusters::defs::CLUSTER_OPERATIONAL_CREDENTIALS_ATTR_ID_NOCS,
+            )
+            .await?;
+        let nocs = crate::clusters::typed::decode_nocs(&tlv)?;
+        let noc = nocs.first().context("device returned no NOC for our fabric")?;
+        let actual = noc.node_id()?;
+        if actual != node_id {
+            return Err(anyhow::anyhow!(
+                "device NOC has node id 0x{:x}, requested 0x{:x}",
+                actual,
+                node_id
+            ));
+        }
+        Ok(())
+    }
 
-    // get pbkdf response
-    let pbkdf_response = retrctx.get_next_message().await?;
-    if pbkdf_response.protocol_header.protocol_id
-        != messages::ProtocolMessageHeader::PROTOCOL_ID_SECURE_CHANNEL
-        || pbkdf_response.protocol_header.opcode
-            != messages::ProtocolMessageHeader::OPCODE_PBKDF_RESP
-    {
-        return Err(anyhow::anyhow!("pbkdf response not received"));
+    /// Read the Administrator Commissioning cluster (endpoint 0): whether a
+    /// commissioning window is open and which fabric/vendor opened it. Check
+    /// this before opening a window, as a device rejects a second one as busy.
+    pub async fn commissioning_window_status(
+        &self,
+    ) -> Result<crate::clusters::typed::CommissioningWindowInfo> {
+        let attrs = self
+            .read_cluster(0, crate::clusters::defs::CLUSTER_ID_ADMINISTRATOR_COMMISSIONING)
+            .await?;
+        Ok(crate::clusters::typed::CommissioningWindowInfo::from_attributes(&attrs))
     }
 
-    let iterations = pbkdf_response
-        .tlv
-        .get_int(&[4, 1])
-        .context("pbkdf_response - iterations missing")?;
-    let salt = pbkdf_response
-        .tlv
-        .get_octet_string(&[4, 2])
-        .context("pbkdf_response - salt missing")?;
-    let p_session = pbkdf_response
-        .tlv
-        .get_int(&[3])
-        .context("pbkdf_response - session missing")?;
-
-    // send pake1
-    let engine = spake2p::Engine::new()?;
-    let mut ctx = engine.start(&pin_to_passcode(pin)?, salt, iterations as u32)?;
-    log::debug!("send pake1 request");
-    let pake1_protocol_message = messages::pake1(exchange, ctx.x.as_bytes(), -1)?;
-    retrctx.send(&pake1_protocol_message).await?;
-
-    // receive pake2
-    let pake2 = retrctx.get_next_message().await?;
-    if pake2.protocol_header.protocol_id
-        != messages::ProtocolMessageHeader::PROTOCOL_ID_SECURE_CHANNEL
-        || pake2.protocol_header.opcode != messages::ProtocolMessageHeader::OPCODE_PASE_PAKE2
-    {
-        return Err(anyhow::anyhow!("pake2 not received"));
+    /// Close an open commissioning window early (Administrator Commissioning
+    /// RevokeCommissioning on endpoint 0, sent as timed invoke). Returns false
+    /// when the device reports WindowNotOpen, i.e. there was nothing to close.
+    pub async fn revoke_commissioning(&self) -> Result<bool> {
+        let res = self
+            .invoke_request_timed(
+                0,
+                crate::clusters::defs::CLUSTER_ID_ADMINISTRATOR_COMMISSIONING,
+                crate::clusters::defs::CLUSTER_ADMINISTRATOR_COMMISSIONING_CMD_ID_REVOKECOMMISSIONING,
+                &[],
+                REVOKE_COMMISSIONING_TIMED_TIMEOUT_MS,
+            )
+            .await?;
+        let (common_status, cluster_status) = messages::parse_im_invoke_resp(&res.tlv)?;
+        let window_not_open =
+            crate::clusters::codec::admin_commissioning_cluster::StatusCode::Windownotopen as u32;
+        match (common_status, cluster_status) {
+            (0, _) => Ok(true),
+            (_, s) if s == window_not_open => Ok(false),
+            (c, s) => Err(anyhow::anyhow!(
+                "revoke_commissioning failed: status {} cluster status {}",
+                messages::ImStatusCode(c as u8),
+                s
+            )),
+        }
     }
-    let pake2_pb = pake2
-        .tlv
-        .get_octet_string(&[1])
-        .context("pake2 pb tlv missing")?;
-    ctx.y = p256::EncodedPoint::from_bytes(pake2_pb)?;
-
-    let pake2_cb = pake2
-        .tlv
-        .get_octet_string(&[2])
-        .context("pake2 cb tlv missing")?;
 
-    // send pake3
-    let mut hash_seed = "CHIP PAKE V1 Commissioning".as_bytes().to_vec();
-    hash_seed.extend_from_slice(&pbkdf_req_protocol_message[6..]);
-    hash_seed.extend_from_slice(&pbkdf_response.payload);
-    engine.finish(&mut ctx, &hash_seed, pake2_cb)?;
-    let pake3_protocol_message = messages::pake3(
-        exchange,
-        &ctx.ca.context("ca value not present in context")?,
-        -1,
-    )?;
-    log::debug!("send pake3 request");
-    retrctx.send(&pake3_protocol_message).await?;
+    /// Arm the General Commissioning fail-safe for `expiry_secs` (ArmFailSafe on
+    /// endpoint 0). Zero disarms it, rolling back changes made under the fail-safe.
+    pub async fn arm_fail_safe(&self, expiry_secs: u16) -> Result<()> {
+        use crate::clusters::codec::general_commissioning_cluster as gc;
+        let fields = self
+            .invoke_request2(
+                0,
+                crate::clusters::defs::CLUSTER_ID_GENERAL_COMMISSIONING,
+                crate::clusters::defs::CLUSTER_GENERAL_COMMISSIONING_CMD_ID_ARMFAILSAFE,
+                &gc::encode_arm_fail_safe(expiry_secs, rand::random())?,
+            )
+            .await
+            .context("arm_fail_safe")?;
+        let resp = gc::decode_arm_fail_safe_response(&fields)?;
+        match resp.error_code {
+            Some(gc::CommissioningError::Ok) => Ok(()),
+            other => Err(anyhow::anyhow!(
+                "ArmFailSafe failed: {:?} {}",
+                other,
+                resp.debug_text.unwrap_or_default()
+            )),
+        }
+    }
 
-    let pake3_resp = retrctx.get_next_message().await?;
-    match &pake3_resp.status_report_info {
-        Some(s) => {
-            if !s.is_ok() {
-                return Err(anyhow::anyhow!("pake3 resp not ok), got {:?}", pake3_resp));
-            }
+    /// Commit changes made under the fail-safe (General Commissioning
+    /// CommissioningComplete on endpoint 0). Must run over a CASE session.
+    /// A failing ErrorCode is returned as [CommissioningCompleteFailed](commission::CommissioningCompleteFailed).
+    pub async fn commissioning_complete(&self) -> Result<()> {
+        let fields = self
+            .invoke_request2(
+                0,
+                crate::clusters::defs::CLUSTER_ID_GENERAL_COMMISSIONING,
+                crate::clusters::defs::CLUSTER_GENERAL_COMMISSIONING_CMD_ID_COMMISSIONINGCOMPLETE,
+                &[],
+            )
+            .await
+            .context("commissioning_complete")?;
+        // raw ErrorCode rather than the codec's decoded enum, so codes unknown to us are kept
+        let item = tlv::TlvItem { tag: 0, value: fields };
+        let code = item.get_int(&[0]).context("CommissioningCompleteResponse without ErrorCode")?;
+        if code != 0 {
+            return Err(commission::CommissioningCompleteFailed::new(code, item.get_string_owned(&[1])).into());
         }
-        None => {
+        Ok(())
+    }
+
+    /// Set label of the fabric this connection belongs to (Operational Credentials
+    /// UpdateFabricLabel on endpoint 0), so the device can show a friendly name.
+    /// Labels longer than 32 bytes are rejected before anything is sent.
+    pub async fn update_fabric_label(&self, label: &str) -> Result<()> {
+        if label.len() > MAX_FABRIC_LABEL_LEN {
             return Err(anyhow::anyhow!(
-                "expecting status report (pake3 resp), got {:?}",
-                pake3_resp
-            ))
+                "fabric label is {} bytes, max is {}",
+                label.len(),
+                MAX_FABRIC_LABEL_LEN
+            ));
+        }
+        use crate::clusters::codec::operational_credential_cluster as oc;
+        let fields = self
+            .invoke_request2(
+                0,
+                crate::clusters::defs::CLUSTER_ID_OPERATIONAL_CREDENTIALS,
+                crate::clusters::defs::CLUSTER_OPERATIONAL_CREDENTIALS_CMD_ID_UPDATEFABRICLABEL,
+                &oc::encode_update_fabric_label(label.to_owned())?,
+            )
+            .await
+            .context("update_fabric_label")?;
+        let resp = oc::decode_noc_response(&fields)?;
+        match resp.status_code {
+            Some(oc::NodeOperationalCertStatus::Ok) => Ok(()),
+            other => Err(anyhow::anyhow!(
+                "update_fabric_label failed: {:?} {}",
+                other,
+                resp.debug_text.unwrap_or_default()
+            )),
         }
     }
 
-    session.set_encrypt_key(&ctx.encrypt_key.context("encrypt key missing")?);
-    session.set_decrypt_key(&ctx.decrypt_key.context("decrypt key missing")?);
-    session.session_id = p_session as u16;
-    log::debug!("auth_spake ok; session: {}", session.session_id);
-    Ok(session)
-}
-
-pub(crate) async fn auth_sigma(
-    connection: &dyn ConnectionTrait,
-    fabric: &fabric::Fabric,
-    cm: &dyn certmanager::CertManager,
-    node_id: u64,
-    controller_id: u64,
-) -> Result<(session::Session, Option<sigma::ResumptionRecord>)> {
-    log::debug!("auth_sigma");
-    let exchange = rand::random();
-    let session = session::Session::new();
-    let mut retrctx = retransmit::RetrContext::new(connection, &session);
-    retrctx.subscribe_exchange(exchange);
-    let mut ctx = sigma::SigmaContext::new(node_id);
-    let ca_pubkey = cm.get_ca_key()?.public_key().to_sec1_bytes();
-    sigma::sigma1(fabric, &mut ctx, &ca_pubkey)?;
-    let s1 = messages::sigma1(exchange, &ctx.sigma1_payload)?;
+    /// Check whether `cluster` is a server cluster on `endpoint`, based on the
+    /// Descriptor ServerList. The list is read once per endpoint and cached
+    /// for the lifetime of the connection.
+    pub async fn has_cluster(&self, endpoint: u16, cluster: u32) -> Result<bool> {
+        Ok(self.server_list(endpoint).await?.contains(&cluster))
+    }
 
-    log::debug!("send sigma1 {}", exchange);
-    retrctx.send(&s1).await?;
+    /// Server clusters on `endpoint` with their names (None for clusters unknown to
+    /// [get_cluster_name](crate::clusters::names::get_cluster_name)).
+    pub async fn list_clusters(&self, endpoint: u16) -> Result<Vec<(u32, Option<&'static str>)>> {
+        Ok(self
+            .server_list(endpoint)
+            .await?
+            .into_iter()
+            .map(|id| (id, crate::clusters::names::get_cluster_name(id)))
+            .collect())
+    }
 
-    // receive sigma2
-    log::debug!("receive sigma2 {}", exchange);
-    let sigma2 = retrctx.get_next_message().await?;
-    log::debug!("sigma2 received {:?}", sigma2);
-    if sigma2.protocol_header.protocol_id == messages::ProtocolMessageHeader::PROTOCOL_ID_SECURE_CHANNEL
-        && sigma2.protocol_header.opcode == messages::ProtocolMessageHeader::OPCODE_STATUS
-    {
-        let sri = sigma2.status_report_info.context("status report info missing")?;
-        if sri.is_busy() {
-            return Err(anyhow::Error::new(SigmaBusy { wait_ms: sri.minimum_wait_time_ms() }));
+    /// Descriptor ServerList of `endpoint`, cached for the connection lifetime.
+    pub async fn server_list(&self, endpoint: u16) -> Result<Vec<u32>> {
+        if let Some(list) = self.server_lists.lock().unwrap().get(&endpoint) {
+            return Ok(list.clone());
         }
-        return Err(anyhow::anyhow!("sigma2 not received, status: {}", sri));
+        let list = crate::clusters::codec::descriptor_cluster::read_server_list(self, endpoint).await?;
+        self.server_lists.lock().unwrap().insert(endpoint, list.clone());
+        Ok(list)
     }
-    ctx.sigma2_payload = sigma2.payload;
-    ctx.responder_session = sigma2
-        .tlv
-        .get_int(&[2])
-        .context("responder session tlv missing in sigma2")? as u16;
-    ctx.responder_public = sigma2
-        .tlv
-        .get_octet_string(&[3])
-        .context("responder public tlv missing in sigma2")?
-        .to_vec();
 
-    log::debug!("verify sigma2 {}", exchange);
-    let resumption_id =
-        sigma::verify_sigma2(fabric, &ctx, &ca_pubkey).context("sigma2 verification failed")?;
+    /// Receive ReportData chunks on the exchange until the last chunk,
+    /// sending the IM StatusResponse between chunks as required, and return
+    /// the merged report. The final StatusResponse is only sent when the
+    /// device did not set SuppressResponse (e.g. subscribe priming reports).
+    async fn collect_reports(&self, exchange: &mut Exchange<'_>) -> Result<im::ReportData> {
+        let mut merged: Option<im::ReportData> = None;
+        loop {
+            let msg = exchange.recv().await?;
+            if let Some(status) = &msg.status_report_info {
+                return Err(anyhow::anyhow!(
+                    "status report while waiting for report data: {:?}",
+                    status
+                ));
+            }
+            if let Ok(status) = messages::parse_status_response(&msg) {
+                anyhow::bail!("status response while waiting for report data: {}", status);
+            }
+            if msg.protocol_header.protocol_id
+                != messages::ProtocolMessageHeader::PROTOCOL_ID_INTERACTION
+                || msg.protocol_header.opcode
+                    != messages::ProtocolMessageHeader::INTERACTION_OPCODE_REPORT_DATA
+            {
+                return Err(anyhow::anyhow!(
+                    "response is not expected report_data {:?}",
+                    msg.protocol_header
+                ));
+            }
+            let report = im::ReportData::parse(&msg.tlv)?;
+            let more = report.more_chunks;
+            let respond = more || !report.suppress_response;
+            match merged.as_mut() {
+                Some(m) => m.merge(report),
+                None => merged = Some(report),
+            }
+            if respond {
+                let flags = messages::im_status_flags_for(msg.protocol_header.exchange_flags);
+                let resp = messages::im_status_response(
+                    exchange.id,
+                    flags,
+                    msg.message_header.message_counter,
+                )?;
+                exchange.send(&resp).await?;
+            }
+            if !more {
+                return merged.context("no report data received");
+            }
+        }
+    }
 
-    let controller_private = cm.get_user_key(controller_id)?;
-    let controller_x509 = cm.get_user_cert(controller_id)?;
-    let controller_matter_cert =
-        cert_matter::convert_x509_bytes_to_matter(&controller_x509, &ca_pubkey)?;
+    /// Invoke command
+    pub async fn invoke_request(
+        &self,
+        endpoint: u16,
+        cluster: u32,
+        command: u32,
+        payload: &[u8],
+    ) -> Result<Message> {
+        let exchange: u16 = rand::random();
+        log::debug!(
+            "invoke_request exch:{} endpoint:{} cluster:{} command:{}",
+            exchange,
+            endpoint,
+            cluster,
+            command
+        );
+        let msg = messages::im_invoke_request(endpoint, cluster, command, exchange, payload, false)?;
+        self.active.request(exchange, &msg).await
+    }
 
-    // send sigma3
-    log::debug!("send sigma3 {} with piggyback ack for {}", exchange, sigma2.message_header.message_counter);
-    sigma::sigma3(
-        fabric,
-        &mut ctx,
-        &controller_private.to_sec1_der()?,
-        &controller_matter_cert,
-    )?;
-    let sigma3 = messages::sigma3(exchange, &ctx.sigma3_payload, sigma2.message_header.message_counter)?;
-    retrctx.send(&sigma3).await?;
+    /// Like [Connection::invoke_request], but fail when the InvokeResponse does not arrive
+    /// within `response_timeout`. MRP retransmission of the request runs independently of
+    /// this limit; use a long timeout for commands which take a while to complete
+    /// (e.g. MoveToLevel with long transition, OpenCommissioningWindow).
+    pub async fn invoke_request_with_timeout(
+        &self,
+        endpoint: u16,
+        cluster: u32,
+        command: u32,
+        payload: &[u8],
+        response_timeout: Duration,
+    ) -> Result<Message> {
+        tokio::time::timeout(
+            response_timeout,
+            self.invoke_request(endpoint, cluster, command, payload),
+        )
+        .await
+        .map_err(|_| {
+            anyhow::anyhow!(
+                "no response to command {:#x} of cluster {:#x} within {:?}",
+                command,
+                cluster,
+                response_timeout
+            )
+        })?
+    }
 
-    log::debug!("receive result {}", exchange);
-    let status = retrctx.get_next_message().await?;
-    if !status
-        .status_report_info
-        .as_ref()
-        .context("sigma3 status resp not received")?
-        .is_ok()
-    {
-        return Err(anyhow::anyhow!(format!(
-            "response to sigma3 does not contain status ok {:?}",
-            status
-        )));
+    /// Invoke the same command with the same `payload` on each of `endpoints`, e.g. switch
+    /// off all bulbs behind a bridge. Commands are batched into InvokeRequests of up to
+    /// the device's MaxPathsPerInvoke paths; devices without batching support (or not
+    /// reporting the limit) get one request per endpoint. Returns the outcome for each
+    /// endpoint in order of `endpoints`; a request which is not answered fails only the
+    /// endpoints it carried. The call itself fails only for invalid input (duplicate
+    /// endpoints, request which can not be encoded).
+    pub async fn invoke_broadcast_endpoints(
+        &self,
+        endpoints: &[u16],
+        cluster: u32,
+        command: u32,
+        payload: &[u8],
+    ) -> Result<Vec<(u16, Result<()>)>> {
+        let mut seen = std::collections::HashSet::new();
+        if let Some(dup) = endpoints.iter().find(|e| !seen.insert(**e)) {
+            anyhow::bail!("endpoint {} listed twice", dup);
+        }
+        let max_paths =
+            match crate::clusters::codec::basic_information_cluster::read_max_paths_per_invoke(self, 0).await {
+                Ok(n) => n.max(1) as usize,
+                Err(e) => {
+                    log::debug!("MaxPathsPerInvoke not available, invoking one by one: {:?}", e);
+                    1
+                }
+            };
+        let mut out = Vec::with_capacity(endpoints.len());
+        for chunk in endpoints.chunks(max_paths) {
+            let exchange: u16 = rand::random();
+            let msg = match chunk {
+                [endpoint] => messages::im_invoke_request(*endpoint, cluster, command, exchange, payload, false)?,
+                _ => {
+                    let paths: Vec<_> = chunk.iter().map(|e| (*e, cluster, command)).collect();
+                    messages::im_invoke_request_batch(&paths, exchange, payload)?
+                }
+            };
+            // a chunk which is not answered (or fails on the way) only fails its own
+            // endpoints; remaining chunks are still sent
+            let responses = match self.collect_invoke_responses(exchange, &msg).await {
+                Ok(responses) => responses,
+                Err(e) => {
+                    log::debug!("invoke of chunk {:?} failed: {:?}", chunk, e);
+                    let reason = format!("{:#}", e);
+                    for endpoint in chunk {
+                        out.push((*endpoint, Err(anyhow::anyhow!("no response for endpoint {}: {}", endpoint, reason))));
+                    }
+                    continue;
+                }
+            };
+            for endpoint in chunk {
+                let res = match responses.iter().find(|r| match r {
+                    im::InvokeResponse::Data { path, .. } | im::InvokeResponse::Status { path, .. } => {
+                        path.endpoint == Some(*endpoint)
+                    }
+                }) {
+                    Some(im::InvokeResponse::Status { status, cluster_status, .. }) if *status != 0 => {
+                        Err(anyhow::anyhow!(
+                            "command failed with status {} cluster status {:?}",
+                            messages::ImStatusCode(*status),
+                            cluster_status
+                        ))
+                    }
+                    Some(_) => Ok(()),
+                    None => Err(anyhow::anyhow!("no response for endpoint {}", endpoint)),
+                };
+                out.push((*endpoint, res));
+            }
+        }
+        Ok(out)
     }
 
-    //session keys
-    let mut th = ctx.sigma1_payload.clone();
-    th.extend_from_slice(&ctx.sigma2_payload);
+    /// Send InvokeRequest `msg` on `exchange` and collect the InvokeResponseIBs of all
+    /// InvokeResponse messages: while MoreChunkedMessages is set, the chunk is
+    /// acknowledged with a StatusResponse and the next one is awaited.
+    async fn collect_invoke_responses(&self, exchange: u16, msg: &[u8]) -> Result<Vec<im::InvokeResponse>> {
+        let mut exchange = self.active.open_exchange(exchange);
+        exchange.send(msg).await?;
+        let mut responses = Vec::new();
+        loop {
+            let msg = exchange.recv().await?;
+            if let Some(status) = &msg.status_report_info {
+                anyhow::bail!("status report while waiting for invoke response: {:?}", status);
+            }
+            if let Ok(status) = messages::parse_status_response(&msg) {
+                anyhow::bail!("status response while waiting for invoke response: {}", status);
+            }
+            responses.extend(im::parse_invoke_responses(&msg.tlv));
+            if msg.tlv.get_bool(&[2]) != Some(true) {
+                return Ok(responses);
+            }
+            let flags = messages::im_status_flags_for(msg.protocol_header.exchange_flags);
+            let resp = messages::im_status_response(exchange.id, flags, msg.message_header.message_counter)?;
+            exchange.send(&resp).await?;
+        }
+    }
 
-    let mut transcript = th;
-    transcript.extend_from_slice(&ctx.sigma3_payload);
-    let transcript_hash = cryptoutil::sha256(&transcript);
-    let mut salt = fabric.signed_ipk()?;
-    salt.extend_from_slice(&transcript_hash);
-    let shared = ctx.shared.context("shared secret not in context")?;
-    let shared_bytes: [u8; 32] = shared.raw_secret_bytes().as_slice()
-        .try_into()
-        .map_err(|_| anyhow::anyhow!("shared secret wrong length"))?;
-    let keypack = cryptoutil::hkdf_sha256(
-        &salt,
-        &shared_bytes,
-        "SessionKeys".as_bytes(),
-        16 * 3,
-    )?;
-    let mut ses = session::Session::new();
-    ses.session_id = ctx.responder_session;
-    ses.my_session_id = ctx.session_id;
-    ses.set_decrypt_key(&keypack[16..32]);
-    ses.set_encrypt_key(&keypack[..16]);
+    /// Invoke command with SuppressResponse flag set (fire-and-forget).
+    /// Returns once the request is acknowledged; device sends no InvokeResponse,
+    /// so command status is not known.
+    pub async fn invoke_no_response(
+        &self,
+        endpoint: u16,
+        cluster: u32,
+        command: u32,
+        payload: &[u8],
+    ) -> Result<()> {
+        let exchange: u16 = rand::random();
+        log::debug!(
+            "invoke_no_response exch:{} endpoint:{} cluster:{} command:{}",
+            exchange,
+            endpoint,
+            cluster,
+            command
+        );
+        let msg = messages::im_invoke_request_flags(
+            endpoint, cluster, command, exchange, payload, false, true,
+        )?;
+        self.active.send_wait_ack(exchange, &msg).await
+    }
 
-    let mut local_node = Vec::new();
-    local_node.write_u64::<LittleEndian>(controller_id)?;
-    ses.local_node = Some(local_node);
+    /// Make device on `endpoint` identify itself (blink, beep, ...) for `seconds`.
+    /// Useful to confirm which device is being paired. Zero stops identification.
+    pub async fn identify(&self, endpoint: u16, seconds: u16) -> Result<()> {
+        crate::clusters::codec::identify::identify(self, endpoint, seconds).await
+    }
 
-    let mut remote_node = Vec::new();
-    remote_node.write_u64::<LittleEndian>(node_id)?;
-    ses.remote_node = Some(remote_node);
+    /// Turn light on `endpoint` on for `on_time` (1/10 s), then off. After turning off
+    /// further OnWithTimedOff commands are ignored for `off_wait_time` (1/10 s), as used
+    /// for motion sensor lights. `on_off_control` is [onoffcontrol](crate::clusters::codec::on_off::onoffcontrol) bits.
+    pub async fn on_with_timed_off(
+        &self,
+        endpoint: u16,
+        on_off_control: crate::clusters::codec::on_off::OnOffControl,
+        on_time: u16,
+        off_wait_time: u16,
+    ) -> Result<()> {
+        crate::clusters::codec::on_off::on_with_timed_off(self, endpoint, on_off_control, on_time, off_wait_time).await
+    }
 
-    let resumption = resumption_id
-        .map(|id| sigma::ResumptionRecord { resumption_id: id, shared_secret: shared_bytes });
+    /// Turn `endpoint` off with a visual effect; `effect_variant` selects the variant
+    /// of `effect` (DelayedAllOffEffectVariant or DyingLightEffectVariant value).
+    pub async fn off_with_effect(
+        &self,
+        endpoint: u16,
+        effect: crate::clusters::codec::on_off::EffectIdentifier,
+        effect_variant: u8,
+    ) -> Result<()> {
+        crate::clusters::codec::on_off::off_with_effect(self, endpoint, effect, effect_variant).await
+    }
 
-    if resumption.is_none() {
-        log::debug!("auth_sigma: responder did not include a NewResumptionID - resumption unavailable for node {}", node_id);
+    /// Turn `endpoint` on, restoring the state saved by the last OffWithEffect.
+    pub async fn on_with_recall_global_scene(&self, endpoint: u16) -> Result<()> {
+        crate::clusters::codec::on_off::on_with_recall_global_scene(self, endpoint).await
     }
 
-    Ok((ses, resumption))
-}
+    /// Invoke command and return fields of the response command. When the device
+    /// answers with a failing status instead, the status is returned as error.
+    pub async fn invoke_request2(
+        &self,
+        endpoint: u16,
+        cluster: u32,
+        command: u32,
+        payload: &[u8],
+    ) -> Result<TlvItemValue> {
+        let res = self.invoke_request(endpoint, cluster, command, payload).await?;
+        let responses = im::parse_invoke_responses(&res.tlv);
+        for r in &responses {
+            if let im::InvokeResponse::Data { fields, .. } = r {
+                return Ok(fields.clone());
+            }
+        }
+        for r in &responses {
+            if let im::InvokeResponse::Status { status, cluster_status, .. } = r {
+                if *status != 0 {
+                    return Err(anyhow::anyhow!(
+                        "command failed with status {} cluster status {:?}",
+                        messages::ImStatusCode(*status),
+                        cluster_status
+                    ));
+                }
+            }
+        }
+        Err(anyhow::anyhow!("result not found"))
+    }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use crate::messages::ProtocolMessageHeader;
-    use crate::tlv;
-    use std::time::Duration;
+    /// Invoke command with parameters given as [TlvItemEnc](tlv::TlvItemEnc);
+    /// same as [Connection::invoke_request] with `params.encode()` as payload.
+    ///
+    /// ```no_run
+    /// # use matc::{clusters, controller, tlv};
+    /// # async fn example(c: &controller::Connection) -> anyhow::Result<()> {
+    /// let params = tlv::TlvItemEnc {
+    ///   tag: 0,
+    ///   value: tlv::TlvItemValueEnc::StructInvisible(vec![
+    ///     tlv::TlvItemEnc { tag: 0, value: tlv::TlvItemValueEnc::UInt8(50)   }, // level
+    ///     tlv::TlvItemEnc { tag: 1, value: tlv::TlvItemValueEnc::UInt16(1000)}, // transition time
+    ///     tlv::TlvItemEnc { tag: 2, value: tlv::TlvItemValueEnc::UInt8(0)    }, // options mask
+    ///     tlv::TlvItemEnc { tag: 3, value: tlv::TlvItemValueEnc::UInt8(0)    }, // options override
+    ///   ])
+    /// };
+    /// c.invoke_enc(1, // endpoint
+    ///              clusters::defs::CLUSTER_ID_LEVEL_CONTROL,
+    ///              clusters::defs::CLUSTER_LEVEL_CONTROL_CMD_ID_MOVETOLEVEL,
+    ///              &params).await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn invoke_enc(
+        &self,
+        endpoint: u16,
+        cluster: u32,
+        command: u32,
+        params: &tlv::TlvItemEnc,
+    ) -> Result<Message> {
+        let payload = params.encode().context("can't encode command parameters")?;
+        self.invoke_request(endpoint, cluster, command, &payload).await
+    }
 
-    // Loopback transport: the test acts as the device on the other end.
-    struct MockConn {
-        inbound: tokio::sync::Mutex<mpsc::Receiver<Vec<u8>>>,
-        outbound: mpsc::UnboundedSender<Vec<u8>>,
-        reliable: bool,
-        mrp: std::sync::Mutex<crate::mrp::MrpParameters>,
+    /// Send interaction model message with pre-encoded TLV `payload` and return the response.
+    /// Escape hatch for interactions without a high-level helper - the payload is sent as is
+    /// (protocol header is added) on a new exchange; response is not interpreted.
+    ///
+    /// ```no_run
+    /// # use matc::{clusters, controller, messages::ProtocolMessageHeader, tlv};
+    /// # async fn example(c: &controller::Connection) -> anyhow::Result<()> {
+    /// // InvokeRequest: OnOff Toggle on endpoint 1
+    /// let mut t = tlv::TlvBuffer::new();
+    /// t.write_anon_struct()?;
+    /// t.write_bool(0, false)?; // SuppressResponse
+    /// t.write_bool(1, false)?; // TimedRequest
+    /// t.write_array(2)?; // InvokeRequests
+    /// t.write_anon_struct()?;
+    /// t.write_list(0)?; // CommandPath
+    /// t.write_uint16(0, 1)?;
+    /// t.write_uint32(1, clusters::defs::CLUSTER_ID_ON_OFF)?;
+    /// t.write_uint32(2, clusters::defs::CLUSTER_ON_OFF_CMD_ID_TOGGLE)?;
+    /// t.write_struct_end()?;
+    /// t.write_struct(1)?; // CommandFields
+    /// t.write_struct_end()?;
+    /// t.write_struct_end()?;
+    /// t.write_struct_end()?;
+    /// t.write_uint8(0xff, 10)?; // InteractionModelRevision
+    /// t.write_struct_end()?;
+    /// let resp = c
+    ///     .send_raw_interaction(ProtocolMessageHeader::INTERACTION_OPCODE_INVOKE_REQ, &t.data)
+    ///     .await?;
+    /// resp.tlv.dump(1);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn send_raw_interaction(&self, opcode: u8, payload: &[u8]) -> Result<Message> {
+        let exchange: u16 = rand::random();
+        log::debug!("send_raw_interaction exch:{} opcode:{:#x}", exchange, opcode);
+        let msg = messages::im_raw_request(opcode, exchange, payload)?;
+        self.active.request(exchange, &msg).await
     }
 
-    #[async_trait::async_trait]
-    impl ConnectionTrait for MockConn {
-        async fn send(&self, data: &[u8]) -> Result<()> {
-            self.outbound
-                .send(data.to_vec())
-                .map_err(|_| anyhow::anyhow!("mock closed"))
-        }
-        async fn receive(&self, timeout: Duration) -> Result<Vec<u8>> {
-            let mut rx = self.inbound.lock().await;
-            match tokio::time::timeout(timeout, rx.recv()).await {
-                Ok(Some(d)) => Ok(d),
-                Ok(None) => Err(anyhow::anyhow!("mock closed")),
-                Err(_) => Err(anyhow::anyhow!("timeout")),
-            }
+    pub async fn write_request(
+        &self,
+        endpoint: u16,
+        cluster: u32,
+        attr: u32,
+        payload: &[u8],
+    ) -> Result<()> {
+        let exchange: u16 = rand::random();
+        log::debug!(
+            "write_request exch:{} endpoint:{} cluster:{} attr:{}",
+            exchange,
+            endpoint,
+            cluster,
+            attr,
+        );
+
+        let chunks = messages::im_write_request_chunked(
+            endpoint,
+            cluster,
+            attr,
+            exchange,
+            payload,
+            self.max_message_size(),
+        )?;
+        if chunks.len() > 1 {
+            log::debug!("write_request exch:{} chunked into {} messages", exchange, chunks.len());
         }
-        fn is_reliable(&self) -> bool {
-            self.reliable
+        for msg in &chunks {
+            let res = self.active.request(exchange, msg).await?;
+            check_write_response(&res)?;
         }
-        fn mrp_params(&self) -> crate::mrp::MrpParameters {
-            *self.mrp.lock().unwrap()
+        Ok(())
+    }
+
+    /// Limit size of messages sent on this connection, [messages::MAX_UDP_MESSAGE_SIZE]
+    /// by default. Writes of larger list attributes are chunked ([Connection::write_request]).
+    pub fn set_max_message_size(&self, size: usize) {
+        self.max_message_size.store(size, std::sync::atomic::Ordering::Relaxed);
+    }
+
+    pub fn max_message_size(&self) -> usize {
+        self.max_message_size.load(std::sync::atomic::Ordering::Relaxed)
+    }
+
+    /// Subscribe to attribute changes. `None` path fields act as wildcards.
+    /// Set `keep_subscriptions = true` when adding a second subscription on the same
+    /// connection so the device does not cancel the first one.
+    ///
+    /// Handles the full subscribe transaction (chunked priming report, IM
+    /// StatusResponse acks, SubscribeResponse) and returns a [Subscription]
+    /// delivering decoded updates; updates are acked automatically by the
+    /// background read loop.
+    pub async fn subscribe_attrs(
+        &self,
+        endpoint: Option<u16>,
+        cluster: Option<u32>,
+        attr: Option<u32>,
+        keep_subscriptions: bool,
+    ) -> Result<Subscription> {
+        let exchange: u16 = rand::random();
+        log::debug!(
+            "subscribe_attrs exch:{} endpoint:{:?} cluster:{:?} attr:{:?} keep:{}",
+            exchange, endpoint, cluster, attr, keep_subscriptions
+        );
+        let msg = messages::im_subscribe_request_attr(endpoint, cluster, attr, exchange, keep_subscriptions)?;
+        self.subscribe_internal(exchange, &msg).await
+    }
+
+    /// Like [Connection::subscribe_attrs] with explicit minimum and maximum reporting
+    /// interval in seconds (MinIntervalFloor / MaxIntervalCeiling). The device may grant
+    /// a longer maximum, see [Subscription::max_interval].
+    pub async fn subscribe_attrs_with_intervals(
+        &self,
+        endpoint: Option<u16>,
+        cluster: Option<u32>,
+        attr: Option<u32>,
+        keep_subscriptions: bool,
+        min_interval: u16,
+        max_interval: u16,
+    ) -> Result<Subscription> {
+        let exchange: u16 = rand::random();
+        let msg = messages::im_subscribe_request_attr_intervals(
+            endpoint,
+            cluster,
+            attr,
+            exchange,
+            keep_subscriptions,
+            min_interval,
+            max_interval,
+        )?;
+        self.subscribe_internal(exchange, &msg).await
+    }
+
+    /// Tell the device this session is no longer used (secure channel CloseSession),
+    /// so it can release the session and subscriptions bound to it right away instead
+    /// of waiting for them to time out. Connection must not be used afterwards.
+    pub async fn close_session(&self) -> Result<()> {
+        let msg = messages::close_session(rand::random())?;
+        self.active.send_unreliable(&msg).await
+    }
+
+    /// Subscribe to events. `None` path fields act as wildcards.
+    /// See [Connection::subscribe_attrs] for transaction details.
+    pub async fn subscribe_events(
+        &self,
+        endpoint: Option<u16>,
+        cluster: Option<u32>,
+        event: Option<u32>,
+        keep_subscriptions: bool,
+    ) -> Result<Subscription> {
+        let exchange: u16 = rand::random();
+        log::debug!(
+            "subscribe_events exch:{} endpoint:{:?} cluster:{:?} event:{:?} keep:{}",
+            exchange, endpoint, cluster, event, keep_subscriptions
+        );
+        let msg = messages::im_subscribe_request_event(endpoint, cluster, event, exchange, keep_subscriptions)?;
+        self.subscribe_internal(exchange, &msg).await
+    }
+
+    /// Subscribe to attribute `path` (endpoint, cluster, attribute) and wait until its value
+    /// matches `predicate`; the current value counts. Returns the matching value. The
+    /// temporary subscription keeps other subscriptions of the connection; to wait on an
+    /// existing one use [Subscription::wait_for_report].
+    ///
+    /// ```no_run
+    /// # use matc::{clusters::defs, controller, tlv::TlvItemValue};
+    /// # async fn example(c: &controller::Connection) -> anyhow::Result<()> {
+    /// let path = (1, defs::CLUSTER_ID_LEVEL_CONTROL, defs::CLUSTER_LEVEL_CONTROL_ATTR_ID_CURRENTLEVEL);
+    /// c.wait_for_report(path, |v| *v == TlvItemValue::Int(254), std::time::Duration::from_secs(30)).await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn wait_for_report(
+        &self,
+        path: (u16, u32, u32),
+        mut predicate: impl FnMut(&TlvItemValue) -> bool,
+        timeout: Duration,
+    ) -> Result<TlvItemValue> {
+        let (endpoint, cluster, attribute) = path;
+        let wait = async {
+            let mut sub = self
+                .subscribe_attrs_with_intervals(Some(endpoint), Some(cluster), Some(attribute), true, 0, 30)
+                .await?;
+            if let Some(value) = matching_report(&sub.priming_attribute_reports, path, &mut predicate) {
+                return Ok(value);
+            }
+            sub.wait_matching(path, &mut predicate).await
+        };
+        tokio::time::timeout(timeout, wait)
+            .await
+            .map_err(|_| anyhow::anyhow!("no matching report for {:?} within {:?}", path, timeout))?
+    }
+
+    async fn subscribe_internal(&self, exchange_id: u16, msg: &[u8]) -> Result<Subscription> {
+        let mut exchange = self.active.open_exchange(exchange_id);
+        exchange.send(msg).await?;
+        let priming = self.collect_reports(&mut exchange).await?;
+        let subscription_id = priming
+            .subscription_id
+            .context("priming report missing subscription id")?;
+
+        // Register before awaiting the SubscribeResponse so no update can be
+        // missed; the device cannot report before the transaction completes.
+        let rx = self.active.register_subscription(subscription_id);
+        let registry = self.active.subscriptions_handle();
+
+        let response = async {
+            let resp = exchange.recv().await?;
+            if let Ok(status) = messages::parse_status_response(&resp) {
+                anyhow::bail!("subscribe failed with status {}", status);
+            }
+            if resp.protocol_header.protocol_id
+                != messages::ProtocolMessageHeader::PROTOCOL_ID_INTERACTION
+                || resp.protocol_header.opcode
+                    != messages::ProtocolMessageHeader::INTERACTION_OPCODE_SUBSCRIBE_RESP
+            {
+                anyhow::bail!(
+                    "response is not expected subscribe_resp {:?}",
+                    resp.protocol_header
+                );
+            }
+            let sr = im::SubscribeResponse::parse(&resp.tlv)?;
+            if sr.subscription_id != subscription_id {
+                anyhow::bail!(
+                    "subscribe response id {} does not match priming report id {}",
+                    sr.subscription_id,
+                    subscription_id
+                );
+            }
+            Ok(sr)
         }
-        fn set_mrp_params(&self, params: crate::mrp::MrpParameters) {
-            *self.mrp.lock().unwrap() = params;
+        .await;
+
+        match response {
+            Ok(sr) => Ok(Subscription {
+                subscription_id,
+                max_interval: sr.max_interval,
+                priming_attribute_reports: priming.attribute_reports,
+                priming_event_reports: priming.event_reports,
+                rx,
+                registry,
+            }),
+            Err(e) => {
+                registry.lock().unwrap().remove(&subscription_id);
+                Err(e)
+            }
         }
     }
 
-    struct MockDevice {
-        rx: mpsc::UnboundedReceiver<Vec<u8>>,
-        tx: mpsc::Sender<Vec<u8>>,
-        session: session::Session,
+    /// Cancel all subscriptions on this session by sending a SubscribeRequest with
+    /// `KeepSubscriptions = false` and no paths. The device drops all prior subscriptions.
+    pub async fn im_unsubscribe_all(&self) -> Result<Message> {
+        let exchange: u16 = rand::random();
+        log::debug!("im_unsubscribe_all exch:{}", exchange);
+        let msg = messages::im_unsubscribe_all(exchange)?;
+        self.active.request(exchange, &msg).await
+    }
+
+    /// Enable or disable automatic IM StatusResponse replies to unsolicited
+    /// ReportData (enabled by default). Disable only when acking reports
+    /// manually via the raw message API.
+    pub fn set_auto_status_response(&self, enabled: bool) {
+        self.active.set_auto_status_response(enabled);
+    }
+
+    /// Invoke command with timed interaction
+    pub async fn invoke_request_timed(
+        &self,
+        endpoint: u16,
+        cluster: u32,
+        command: u32,
+        payload: &[u8],
+        timeout: u16,
+    ) -> Result<Message> {
+        let exchange: u16 = rand::random();
+
+        // Send timed request first
+        let tr = messages::im_timed_request(exchange, timeout)?;
+        let result = self.active.request(exchange, &tr).await?;
+
+        let status = messages::parse_status_response(&result)
+            .context("invoke_request_timed: unexpected response to timed request")?;
+        if !status.is_success() {
+            return Err(anyhow::anyhow!(
+                "invoke_request_timed: unexpected status {}",
+                status
+            ));
+        }
+
+        log::debug!(
+            "invoke_request exch:{} endpoint:{} cluster:{} command:{}",
+            exchange,
+            endpoint,
+            cluster,
+            command
+        );
+        let msg = messages::im_invoke_request(endpoint, cluster, command, exchange, payload, true)?;
+        self.active.request(exchange, &msg).await
+    }
+
+    /// Send a standalone ACK for message `counter` received on `exchange_id`.
+    /// Needed only for messages handled manually via the raw message API;
+    /// the connection acknowledges everything else itself.
+    pub async fn send_ack(&self, exchange_id: u16, counter: u32) -> Result<()> {
+        log::debug!("send_ack exch:{} counter:{}", exchange_id, counter);
+        let ack = messages::ack(exchange_id, counter as i64)?;
+        self.active.send_unreliable(&ack).await
+    }
+
+    /// Receive next unsolicited raw message not handled elsewhere (subscription
+    /// reports are delivered decoded via [Subscription]; only reports with an
+    /// unknown subscription id and other unsolicited messages end up here).
+    /// Returns None when connection is closed. Messages may be dropped when
+    /// nobody drains this channel.
+    pub async fn recv_event(&self) -> Option<Message> {
+        self.active.recv_event().await
+    }
+
+    /// Try receive event without blocking.
+    pub fn try_recv_event(&self) -> Option<Message> {
+        self.active.try_recv_event()
+    }
+
+    /// Re-run CASE over the existing transport channel without tearing it down.
+    /// Stops the active read loop, runs auth_sigma (with BUSY retry), swaps the session,
+    /// and restarts the read loop -- all on the same underlying UDP channel registration.
+    pub async fn reauth(
+        &self,
+        controller: &Controller,
+        node_id: u64,
+        controller_id: u64,
+    ) -> Result<()> {
+        self.active.pause_read_loop().await;
+        let new_session = controller
+            .auth_sigma_with_busy_retry(&self.active.transport_conn, node_id, controller_id)
+            .await?;
+        self.active.reauth_with_session(new_session).await
+    }
+}
+
+/// Active subscription created by [Connection::subscribe_attrs] or
+/// [Connection::subscribe_events]. Decoded updates are delivered via [Subscription::next];
+/// the background read loop acks them automatically. Dropping the handle stops
+/// delivery (the device-side subscription stays active until it expires or is
+/// cancelled via [Connection::im_unsubscribe_all]).
+pub struct Subscription {
+    pub subscription_id: u32,
+    /// Maximum reporting interval in seconds granted by the device.
+    pub max_interval: u16,
+    /// Attribute reports from the priming report (current values at subscribe time).
+    pub priming_attribute_reports: Vec<im::AttributeReport>,
+    /// Event reports from the priming report.
+    pub priming_event_reports: Vec<im::EventReport>,
+    rx: mpsc::Receiver<im::ReportUpdate>,
+    registry: Arc<std::sync::Mutex<HashMap<u32, mpsc::Sender<im::ReportUpdate>>>>,
+}
+
+impl Subscription {
+    /// Receive the next decoded update. Returns None when the connection is
+    /// closed or re-authenticated (the subscription is then gone; resubscribe).
+    pub async fn next(&mut self) -> Option<im::ReportUpdate> {
+        self.rx.recv().await
+    }
+
+    /// Wait for an update reporting attribute `path` (endpoint, cluster, attribute)
+    /// with a value matching `predicate`, e.g. CurrentLevel reaching 254. Reports for
+    /// other paths and non-matching values are skipped. Returns the matching value;
+    /// fails on `timeout` or when the subscription ends.
+    pub async fn wait_for_report(
+        &mut self,
+        path: (u16, u32, u32),
+        mut predicate: impl FnMut(&TlvItemValue) -> bool,
+        timeout: Duration,
+    ) -> Result<TlvItemValue> {
+        tokio::time::timeout(timeout, self.wait_matching(path, &mut predicate))
+            .await
+            .map_err(|_| anyhow::anyhow!("no matching report for {:?} within {:?}", path, timeout))?
+    }
+
+    async fn wait_matching(
+        &mut self,
+        path: (u16, u32, u32),
+        predicate: &mut impl FnMut(&TlvItemValue) -> bool,
+    ) -> Result<TlvItemValue> {
+        while let Some(update) = self.next().await {
+            if let Some(value) = matching_report(&update.attribute_reports, path, predicate) {
+                return Ok(value);
+            }
+        }
+        Err(anyhow::anyhow!(
+            "subscription {} ended while waiting for report for {:?}",
+            self.subscription_id,
+            path
+        ))
+    }
+}
+
+/// Value of the last report in `reports` for `path` when it matches `predicate`.
+fn matching_report(
+    reports: &[im::AttributeReport],
+    path: (u16, u32, u32),
+    predicate: &mut impl FnMut(&TlvItemValue) -> bool,
+) -> Option<TlvItemValue> {
+    let (endpoint, cluster, attribute) = path;
+    let value = reports
+        .iter()
+        .rev()
+        .filter(|r| {
+            r.path.endpoint == Some(endpoint)
+                && r.path.cluster == Some(cluster)
+                && r.path.attribute == Some(attribute)
+                && r.path.list_index.is_none()
+        })
+        .filter_map(|r| match &r.data {
+            im::AttributeData::Value(v) => Some(v),
+            im::AttributeData::Status { .. } => None,
+        })
+        .next()?;
+    predicate(value).then(|| value.clone())
+}
+
+impl Drop for Subscription {
+    fn drop(&mut self) {
+        self.registry.lock().unwrap().remove(&self.subscription_id);
+    }
+}
+
+pub fn pin_to_passcode(pin: u32) -> Result<Vec<u8>> {
+    let mut out = Vec::new();
+    out.write_u32::<LittleEndian>(pin)?;
+    Ok(out)
+}
+
+/// Switch retransmission timing of `connection` to MRP intervals the responder sent
+/// during session establishment; these take precedence over mDNS TXT values. Intervals
+/// the responder left out keep their current value.
+fn use_peer_mrp(
+    connection: &dyn ConnectionTrait,
+    session: Option<crate::mrp::SessionParams>,
+) -> Option<crate::mrp::MrpParameters> {
+    let params = connection.mrp_params().merge(&session?);
+    log::debug!("peer session parameters: {:?}", params);
+    connection.set_mrp_params(params);
+    Some(params)
+}
+
+/// Local session id reserved for a session being established. Released again when
+/// dropped before [SessionIdLease::keep], so failed handshakes do not leak ids.
+struct SessionIdLease<'a> {
+    connection: &'a dyn ConnectionTrait,
+    id: u16,
+    kept: bool,
+}
+
+impl<'a> SessionIdLease<'a> {
+    fn alloc(connection: &'a dyn ConnectionTrait) -> Result<Self> {
+        let id = connection.alloc_session_id()?;
+        Ok(Self { connection, id, kept: false })
+    }
+
+    /// Session is established; its owner releases the id from now on.
+    fn keep(mut self) -> u16 {
+        self.kept = true;
+        self.id
+    }
+}
+
+impl Drop for SessionIdLease<'_> {
+    fn drop(&mut self) {
+        if !self.kept {
+            self.connection.release_session_id(self.id);
+        }
+    }
+}
+
+/// PASE; with `known_params` the device is told we know its PBKDF parameters and
+/// they are used instead of ones from the PBKDF response.
+pub(crate) async fn auth_spake_with_params(
+    connection: &dyn ConnectionTrait,
+    pin: u32,
+    known_params: Option<&spake2p::PbkdfParams>,
+) -> Result<session::Session> {
+    let exchange = rand::random();
+    log::debug!("start auth_spake");
+    let mut session = session::Session::new();
+    let session_id = SessionIdLease::alloc(connection)?;
+    session.my_session_id = session_id.id;
+    let mut retrctx = retransmit::RetrContext::new(connection, &session);
+    // send pbkdf
+    log::debug!("send pbkdf request");
+    let pbkdf_req_protocol_message =
+        messages::pbkdf_req_params(exchange, session.my_session_id, known_params.is_some())?;
+    retrctx.send(&pbkdf_req_protocol_message).await?;
+
+    // get pbkdf response
+    let pbkdf_response = retrctx
+        .get_next_handshake_message(exchange, &[messages::ProtocolMessageHeader::OPCODE_PBKDF_RESP])
+        .await?;
+    if pbkdf_response.protocol_header.protocol_id
+        != messages::ProtocolMessageHeader::PROTOCOL_ID_SECURE_CHANNEL
+        || pbkdf_response.protocol_header.opcode
+            != messages::ProtocolMessageHeader::OPCODE_PBKDF_RESP
+    {
+        return Err(anyhow::anyhow!("pbkdf response not received"));
+    }
+
+    let (iterations, salt) = match known_params {
+        Some(p) => (p.iterations, p.salt.as_slice()),
+        None => (
+            pbkdf_response
+                .tlv
+                .get_int(&[4, 1])
+                .context("pbkdf_response - iterations missing")? as u32,
+            pbkdf_response
+                .tlv
+                .get_octet_string(&[4, 2])
+                .context("pbkdf_response - salt missing")?,
+        ),
+    };
+    spake2p::validate_pbkdf_params(iterations, salt).context("pbkdf_response")?;
+    let p_session = pbkdf_response
+        .tlv
+        .get_int(&[3])
+        .context("pbkdf_response - session missing")?;
+    let peer_mrp = use_peer_mrp(
+        connection,
+        crate::mrp::SessionParams::decode(&pbkdf_response.tlv, 5),
+    );
+
+    // send pake1
+    let engine = spake2p::Engine::new()?;
+    let mut ctx = engine.start(&pin_to_passcode(pin)?, salt, iterations)?;
+    log::debug!("send pake1 request");
+    let pake1_protocol_message = messages::pake1(exchange, ctx.x.as_bytes(), -1)?;
+    retrctx.send(&pake1_protocol_message).await?;
+
+    // receive pake2
+    let pake2 = retrctx
+        .get_next_handshake_message(exchange, &[messages::ProtocolMessageHeader::OPCODE_PASE_PAKE2])
+        .await?;
+    if pake2.protocol_header.protocol_id
+        != messages::ProtocolMessageHeader::PROTOCOL_ID_SECURE_CHANNEL
+        || pake2.protocol_header.opcode != messages::ProtocolMessageHeader::OPCODE_PASE_PAKE2
+    {
+        return Err(anyhow::anyhow!("pake2 not received"));
+    }
+    let pake2_pb = pake2
+        .tlv
+        .get_octet_string(&[1])
+        .context("pake2 pb tlv missing")?;
+    ctx.y = p256::EncodedPoint::from_bytes(pake2_pb)?;
+
+    let pake2_cb = pake2
+        .tlv
+        .get_octet_string(&[2])
+        .context("pake2 cb tlv missing")?;
+
+    // send pake3
+    let mut hash_seed = "CHIP PAKE V1 Commissioning".as_bytes().to_vec();
+    hash_seed.extend_from_slice(&pbkdf_req_protocol_message[6..]);
+    hash_seed.extend_from_slice(&pbkdf_response.payload);
+    engine.finish(&mut ctx, &hash_seed, pake2_cb)?;
+    let pake3_protocol_message = messages::pake3(
+        exchange,
+        &ctx.ca.context("ca value not present in context")?,
+        -1,
+    )?;
+    log::debug!("send pake3 request");
+    retrctx.send(&pake3_protocol_message).await?;
+
+    let pake3_resp = retrctx.get_next_handshake_message(exchange, &[]).await?;
+    match &pake3_resp.status_report_info {
+        Some(s) => {
+            if !s.is_ok() {
+                return Err(anyhow::anyhow!("pake3 resp not ok), got {:?}", pake3_resp));
+            }
+        }
+        None => {
+            return Err(anyhow::anyhow!(
+                "expecting status report (pake3 resp), got {:?}",
+                pake3_resp
+            ))
+        }
+    }
+
+    session.set_encrypt_key(&ctx.encrypt_key.context("encrypt key missing")?);
+    session.set_decrypt_key(&ctx.decrypt_key.context("decrypt key missing")?);
+    session.session_id = p_session as u16;
+    session.peer_mrp = peer_mrp;
+    session_id.keep();
+    log::debug!("auth_spake ok; session: {}", session.session_id);
+    Ok(session)
+}
+
+pub(crate) async fn auth_sigma(
+    connection: &dyn ConnectionTrait,
+    fabric: &fabric::Fabric,
+    cm: &dyn certmanager::CertManager,
+    node_id: u64,
+    controller_id: u64,
+) -> Result<(session::Session, Option<sigma::ResumptionRecord>)> {
+    log::debug!("auth_sigma");
+    let exchange = rand::random();
+    let session = session::Session::new();
+    let mut retrctx = retransmit::RetrContext::new(connection, &session);
+    retrctx.subscribe_exchange(exchange);
+    let mut ctx = sigma::SigmaContext::new(node_id);
+    let session_id = SessionIdLease::alloc(connection)?;
+    ctx.session_id = session_id.id;
+    let ca_pubkey = cm.get_ca_key()?.public_key().to_sec1_bytes();
+    sigma::sigma1(fabric, &mut ctx, &ca_pubkey)?;
+    let s1 = messages::sigma1(exchange, &ctx.sigma1_payload)?;
+
+    log::debug!("send sigma1 {}", exchange);
+    retrctx.send(&s1).await?;
+
+    // receive sigma2
+    log::debug!("receive sigma2 {}", exchange);
+    let sigma2 = retrctx
+        .get_next_handshake_message(exchange, &[messages::ProtocolMessageHeader::OPCODE_CASE_SIGMA2])
+        .await?;
+    log::debug!("sigma2 received {:?}", sigma2);
+    if sigma2.protocol_header.protocol_id == messages::ProtocolMessageHeader::PROTOCOL_ID_SECURE_CHANNEL
+        && sigma2.protocol_header.opcode == messages::ProtocolMessageHeader::OPCODE_STATUS
+    {
+        let sri = sigma2.status_report_info.context("status report info missing")?;
+        if sri.is_busy() {
+            return Err(anyhow::Error::new(SigmaBusy { wait_ms: sri.minimum_wait_time_ms() }));
+        }
+        return Err(anyhow::anyhow!("sigma2 not received, status: {}", sri));
+    }
+    ctx.sigma2_payload = sigma2.payload;
+    ctx.responder_session = sigma2
+        .tlv
+        .get_int(&[2])
+        .context("responder session tlv missing in sigma2")? as u16;
+    ctx.responder_public = sigma2
+        .tlv
+        .get_octet_string(&[3])
+        .context("responder public tlv missing in sigma2")?
+        .to_vec();
+
+    log::debug!("verify sigma2 {}", exchange);
+    let resumption_id =
+        sigma::verify_sigma2(fabric, &ctx, &ca_pubkey).context("sigma2 verification failed")?;
+    let peer_mrp = use_peer_mrp(
+        connection,
+        crate::mrp::SessionParams::decode(&sigma2.tlv, 5),
+    );
+
+    let controller_private = cm.get_user_key(controller_id)?;
+    let controller_x509 = cm.get_user_cert(controller_id)?;
+    let controller_matter_cert =
+        cert_matter::convert_x509_bytes_to_matter(&controller_x509, &ca_pubkey)?;
+
+    // send sigma3
+    log::debug!("send sigma3 {} with piggyback ack for {}", exchange, sigma2.message_header.message_counter);
+    sigma::sigma3(
+        fabric,
+        &mut ctx,
+        &controller_private.to_sec1_der()?,
+        &controller_matter_cert,
+    )?;
+    let sigma3 = messages::sigma3(exchange, &ctx.sigma3_payload, sigma2.message_header.message_counter)?;
+    retrctx.send(&sigma3).await?;
+
+    log::debug!("receive result {}", exchange);
+    let status = retrctx.get_next_handshake_message(exchange, &[]).await?;
+    if !status
+        .status_report_info
+        .as_ref()
+        .context("sigma3 status resp not received")?
+        .is_ok()
+    {
+        return Err(anyhow::anyhow!(format!(
+            "response to sigma3 does not contain status ok {:?}",
+            status
+        )));
+    }
+
+    //session keys
+    let mut th = ctx.sigma1_payload.clone();
+    th.extend_from_slice(&ctx.sigma2_payload);
+
+    let mut transcript = th;
+    transcript.extend_from_slice(&ctx.sigma3_payload);
+    let transcript_hash = cryptoutil::sha256(&transcript);
+    let mut salt = fabric.signed_ipk()?;
+    salt.extend_from_slice(&transcript_hash);
+    let shared = ctx.shared.context("shared secret not in context")?;
+    let shared_bytes: [u8; 32] = shared.raw_secret_bytes().as_slice()
+        .try_into()
+        .map_err(|_| anyhow::anyhow!("shared secret wrong length"))?;
+    let keypack = cryptoutil::hkdf_sha256(
+        &salt,
+        &shared_bytes,
+        "SessionKeys".as_bytes(),
+        16 * 3,
+    )?;
+    let mut ses = session::Session::new();
+    ses.session_id = ctx.responder_session;
+    ses.my_session_id = session_id.keep();
+    ses.peer_mrp = peer_mrp;
+    ses.set_decrypt_key(&keypack[16..32]);
+    ses.set_encrypt_key(&keypack[..16]);
+
+    let mut local_node = Vec::new();
+    local_node.write_u64::<LittleEndian>(controller_id)?;
+    ses.local_node = Some(local_node);
+
+    let mut remote_node = Vec::new();
+    remote_node.write_u64::<LittleEndian>(node_id)?;
+    ses.remote_node = Some(remote_node);
+
+    let resumption = resumption_id
+        .map(|id| sigma::ResumptionRecord { resumption_id: id, shared_secret: shared_bytes });
+
+    if resumption.is_none() {
+        log::debug!("auth_sigma: responder did not include a NewResumptionID - resumption unavailable for node {}", node_id);
+    }
+
+    Ok((ses, resumption))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::messages::ProtocolMessageHeader;
+    use crate::tlv;
+    use std::time::Duration;
+
+    // Loopback transport: the test acts as the device on the other end.
+    struct MockConn {
+        inbound: tokio::sync::Mutex<mpsc::Receiver<Vec<u8>>>,
+        outbound: mpsc::UnboundedSender<Vec<u8>>,
+        reliable: bool,
+        mrp: std::sync::Mutex<crate::mrp::MrpParameters>,
+    }
+
+    #[async_trait::async_trait]
+    impl ConnectionTrait for MockConn {
+        async fn send(&self, data: &[u8]) -> Result<()> {
+            self.outbound
+                .send(data.to_vec())
+                .map_err(|_| anyhow::anyhow!("mock closed"))
+        }
+        async fn receive(&self, timeout: Duration) -> Result<Vec<u8>> {
+            let mut rx = self.inbound.lock().await;
+            match tokio::time::timeout(timeout, rx.recv()).await {
+                Ok(Some(d)) => Ok(d),
+                Ok(None) => Err(anyhow::anyhow!("mock closed")),
+                Err(_) => Err(anyhow::anyhow!("timeout")),
+            }
+        }
+        fn is_reliable(&self) -> bool {
+            self.reliable
+        }
+        fn mrp_params(&self) -> crate::mrp::MrpParameters {
+            *self.mrp.lock().unwrap()
+        }
+        fn set_mrp_params(&self, params: crate::mrp::MrpParameters) {
+            *self.mrp.lock().unwrap() = params;
+        }
+    }
+
+    struct MockDevice {
+        rx: mpsc::UnboundedReceiver<Vec<u8>>,
+        tx: mpsc::Sender<Vec<u8>>,
+        session: session::Session,
+    }
+
+    impl MockDevice {
+        async fn recv(&mut self) -> Message {
+            let data = tokio::time::timeout(Duration::from_secs(2), self.rx.recv())
+                .await
+                .expect("timeout waiting for controller message")
+                .expect("mock closed");
+            Message::decode(&data).unwrap()
+        }
+
+        async fn expect_status_response(&mut self, want_flags: u8, want_ack: u32) {
+            let msg = self.recv().await;
+            assert_eq!(
+                msg.protocol_header.protocol_id,
+                ProtocolMessageHeader::PROTOCOL_ID_INTERACTION
+            );
+            assert_eq!(
+                msg.protocol_header.opcode,
+                ProtocolMessageHeader::INTERACTION_OPCODE_STATUS_RESP
+            );
+            assert_eq!(
+                msg.protocol_header.exchange_flags,
+                ProtocolMessageHeader::FLAG_RELIABILITY | want_flags
+            );
+            assert_eq!(msg.protocol_header.ack_counter, want_ack);
+            assert_eq!(msg.tlv.get_int(&[0]), Some(0));
+        }
+
+        async fn expect_silence(&mut self) {
+            assert!(
+                tokio::time::timeout(Duration::from_millis(200), self.rx.recv())
+                    .await
+                    .is_err(),
+                "unexpected message from controller"
+            );
+        }
+
+        async fn send(&self, payload: &[u8]) -> u32 {
+            let encoded = self.session.encode_message(payload).unwrap();
+            let (header, _) = messages::MessageHeader::decode(&encoded).unwrap();
+            self.tx.send(encoded).await.unwrap();
+            header.message_counter
+        }
+
+        async fn recv_within(&mut self, d: Duration) -> Option<Message> {
+            match tokio::time::timeout(d, self.rx.recv()).await {
+                Ok(Some(data)) => Some(Message::decode(&data).unwrap()),
+                _ => None,
+            }
+        }
+    }
+
+    fn mock_pair() -> (Connection, MockDevice) {
+        mock_pair_with(true, Default::default())
+    }
+
+    fn mock_pair_unreliable(mrp: crate::mrp::MrpParameters) -> (Connection, MockDevice) {
+        mock_pair_with(false, mrp)
+    }
+
+    fn mock_pair_with(reliable: bool, mrp: crate::mrp::MrpParameters) -> (Connection, MockDevice) {
+        let (to_ctrl_tx, to_ctrl_rx) = mpsc::channel(32);
+        let (to_dev_tx, to_dev_rx) = mpsc::unbounded_channel();
+        let mock = Arc::new(MockConn {
+            inbound: tokio::sync::Mutex::new(to_ctrl_rx),
+            outbound: to_dev_tx,
+            reliable,
+            mrp: std::sync::Mutex::new(mrp),
+        });
+        let conn = Connection::from_parts(mock, session::Session::new());
+        let device = MockDevice {
+            rx: to_dev_rx,
+            tx: to_ctrl_tx,
+            session: session::Session::new(),
+        };
+        (conn, device)
+    }
+
+    fn report_data(
+        exchange: u16,
+        flags: u8,
+        sub_id: Option<u32>,
+        values: &[(u16, bool)],
+        more: bool,
+        suppress: bool,
+    ) -> Vec<u8> {
+        let b = ProtocolMessageHeader {
+            exchange_flags: flags,
+            opcode: ProtocolMessageHeader::INTERACTION_OPCODE_REPORT_DATA,
+            exchange_id: exchange,
+            protocol_id: ProtocolMessageHeader::PROTOCOL_ID_INTERACTION,
+            ack_counter: 0,
+        }
+        .encode()
+        .unwrap();
+        let mut t = tlv::TlvBuffer::from_vec(b);
+        t.write_anon_struct().unwrap();
+        if let Some(id) = sub_id {
+            t.write_uint32(0, id).unwrap();
+        }
+        t.write_array(1).unwrap();
+        for (endpoint, value) in values {
+            t.write_anon_struct().unwrap();
+            t.write_struct(1).unwrap();
+            t.write_uint32(0, 0).unwrap();
+            t.write_list(1).unwrap();
+            t.write_uint16(2, *endpoint).unwrap();
+            t.write_uint32(3, 6).unwrap();
+            t.write_uint32(4, 0).unwrap();
+            t.write_struct_end().unwrap();
+            t.write_bool(2, *value).unwrap();
+            t.write_struct_end().unwrap();
+            t.write_struct_end().unwrap();
+        }
+        t.write_struct_end().unwrap();
+        if more {
+            t.write_bool(3, true).unwrap();
+        }
+        if suppress {
+            t.write_bool(4, true).unwrap();
+        }
+        t.write_struct_end().unwrap();
+        t.data
+    }
+
+    fn subscribe_response(exchange: u16, sub_id: u32, max_interval: u16) -> Vec<u8> {
+        let b = ProtocolMessageHeader {
+            exchange_flags: 0,
+            opcode: ProtocolMessageHeader::INTERACTION_OPCODE_SUBSCRIBE_RESP,
+            exchange_id: exchange,
+            protocol_id: ProtocolMessageHeader::PROTOCOL_ID_INTERACTION,
+            ack_counter: 0,
+        }
+        .encode()
+        .unwrap();
+        let mut t = tlv::TlvBuffer::from_vec(b);
+        t.write_anon_struct().unwrap();
+        t.write_uint32(0, sub_id).unwrap();
+        t.write_uint16(2, max_interval).unwrap();
+        t.write_struct_end().unwrap();
+        t.data
+    }
+
+    const FLAGS_RESPONDER: u8 = 0;
+    const FLAGS_DEVICE_INITIATED: u8 = ProtocolMessageHeader::FLAG_INITIATOR;
+    const ACK_AND_INITIATOR: u8 =
+        ProtocolMessageHeader::FLAG_INITIATOR | ProtocolMessageHeader::FLAG_ACK;
+
+    #[tokio::test]
+    async fn test_read_request2_single_chunk() {
+        let (conn, mut device) = mock_pair();
+        let task = tokio::spawn(async move {
+            let req = device.recv().await;
+            assert_eq!(
+                req.protocol_header.opcode,
+                ProtocolMessageHeader::INTERACTION_OPCODE_READ_REQ
+            );
+            let exchange = req.protocol_header.exchange_id;
+            device
+                .send(&report_data(exchange, FLAGS_RESPONDER, None, &[(1, true)], false, true))
+                .await;
+            device.expect_silence().await;
+        });
+        let val = conn.read_request2(1, 6, 0).await.unwrap();
+        assert_eq!(val, TlvItemValue::Bool(true));
+        task.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_read_request2_chunked() {
+        let (conn, mut device) = mock_pair();
+        let task = tokio::spawn(async move {
+            let req = device.recv().await;
+            let exchange = req.protocol_header.exchange_id;
+            let counter = device
+                .send(&report_data(exchange, FLAGS_RESPONDER, None, &[(1, true)], true, false))
+                .await;
+            device.expect_status_response(ACK_AND_INITIATOR, counter).await;
+            device
+                .send(&report_data(exchange, FLAGS_RESPONDER, None, &[(2, false)], false, true))
+                .await;
+            device.expect_silence().await;
+        });
+        let val = conn.read_request2(1, 6, 0).await.unwrap();
+        assert_eq!(val, TlvItemValue::Bool(true));
+        task.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_read_cluster_multiple_attributes() {
+        use crate::device_messages::{im_report_data, AttrReport};
+        let (conn, mut device) = mock_pair();
+        let task = tokio::spawn(async move {
+            let req = device.recv().await;
+            assert_eq!(req.tlv.get_int(&[0, 0, 3]), Some(0x300));
+            assert!(req.tlv.get(&[0, 0, 4]).is_none(), "attribute id must be wildcarded");
+            let exchange = req.protocol_header.exchange_id;
+            let value = |v: u16| {
+                let mut t = tlv::TlvBuffer::new();
+                t.write_uint16(2, v).unwrap();
+                t.data
+            };
+            let reports = [
+                AttrReport::Data { endpoint: 1, cluster: 0x300, attribute: 3, value_tlv: value(100) },
+                AttrReport::Data { endpoint: 1, cluster: 0x300, attribute: 4, value_tlv: value(200) },
+                AttrReport::Status { endpoint: 1, cluster: 0x300, attribute: 5, status: 0x86 },
+                AttrReport::Data { endpoint: 1, cluster: 0x300, attribute: 7, value_tlv: value(370) },
+            ];
+            device.send(&im_report_data(exchange, &reports, -1, None, false).unwrap()).await;
+            // report is reliable, so a standalone ack may precede the StatusResponse
+            loop {
+                let resp = device.recv().await;
+                if resp.protocol_header.protocol_id == ProtocolMessageHeader::PROTOCOL_ID_INTERACTION {
+                    assert_eq!(
+                        resp.protocol_header.opcode,
+                        ProtocolMessageHeader::INTERACTION_OPCODE_STATUS_RESP
+                    );
+                    break;
+                }
+            }
+        });
+        let attrs = conn.read_cluster(1, 0x300).await.unwrap();
+        assert_eq!(attrs.len(), 3);
+        assert_eq!(attrs.get(&3), Some(&TlvItemValue::Int(100)));
+        assert_eq!(attrs.get(&4), Some(&TlvItemValue::Int(200)));
+        assert_eq!(attrs.get(&7), Some(&TlvItemValue::Int(370)));
+        task.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_commission_result_fabric_index() {
+        use crate::device_messages::{im_report_data, AttrReport};
+        let (conn, mut device) = mock_pair();
+        let task = tokio::spawn(async move {
+            let req = device.recv().await;
+            assert_eq!(req.tlv.get_int(&[0, 0, 3]), Some(0x3e));
+            assert_eq!(req.tlv.get_int(&[0, 0, 4]), Some(5));
+            let exchange = req.protocol_header.exchange_id;
+            let mut t = tlv::TlvBuffer::new();
+            t.write_uint8(2, 3).unwrap();
+            let reports = [AttrReport::Data { endpoint: 0, cluster: 0x3e, attribute: 5, value_tlv: t.data }];
+            device.send(&im_report_data(exchange, &reports, -1, None, false).unwrap()).await;
+            while device.recv_within(Duration::from_millis(300)).await.is_some() {}
+        });
+        let res = CommissionResult::read(conn, 0x1234).await.unwrap();
+        assert_eq!(res.node_id, 0x1234);
+        assert_eq!(res.fabric_index, 3);
+        task.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_read_attributes_with_status() {
+        use crate::device_messages::{im_report_data, AttrReport};
+        let (conn, mut device) = mock_pair();
+        let task = tokio::spawn(async move {
+            let req = device.recv().await;
+            let Some(TlvItemValue::List(paths)) = req.tlv.get(&[0]) else {
+                panic!("read request without paths");
+            };
+            let paths: Vec<_> = paths
+                .iter()
+                .map(|p| (p.get_int(&[2]), p.get_int(&[3]), p.get_int(&[4])))
+                .collect();
+            assert_eq!(
+                paths,
+                [
+                    (Some(1), Some(6), Some(0)),
+                    (Some(1), Some(8), Some(0)),
+                    (Some(1), Some(6), Some(0x4000)),
+                    (Some(2), Some(6), Some(0)),
+                ]
+            );
+            let exchange = req.protocol_header.exchange_id;
+            let mut t = tlv::TlvBuffer::new();
+            t.write_bool(2, true).unwrap();
+            let reports = [
+                AttrReport::Data { endpoint: 1, cluster: 6, attribute: 0, value_tlv: t.data },
+                AttrReport::Status { endpoint: 1, cluster: 8, attribute: 0, status: 0x86 },
+                AttrReport::Status { endpoint: 1, cluster: 6, attribute: 0x4000, status: 0x86 },
+            ];
+            device.send(&im_report_data(exchange, &reports, -1, None, false).unwrap()).await;
+            while device.recv_within(Duration::from_millis(300)).await.is_some() {}
+        });
+        let attrs = conn
+            .read_attributes(&[(1, 6, 0), (1, 8, 0), (1, 6, 0x4000), (2, 6, 0)])
+            .await
+            .unwrap();
+        assert_eq!(attrs.len(), 3);
+        assert_eq!(attrs[&(1, 6, 0)], im::AttributeData::Value(TlvItemValue::Bool(true)));
+        assert_eq!(
+            attrs[&(1, 8, 0)],
+            im::AttributeData::Status { status: 0x86, cluster_status: None }
+        );
+        assert!(matches!(attrs[&(1, 6, 0x4000)], im::AttributeData::Status { status: 0x86, .. }));
+        task.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_has_cluster_caches_server_list() {
+        use crate::device_messages::{im_report_data, AttrReport};
+        let (conn, mut device) = mock_pair();
+        let task = tokio::spawn(async move {
+            let req = device.recv().await;
+            assert_eq!(req.tlv.get_int(&[0, 0, 2]), Some(1));
+            assert_eq!(req.tlv.get_int(&[0, 0, 3]), Some(0x1d));
+            assert_eq!(req.tlv.get_int(&[0, 0, 4]), Some(1));
+            let exchange = req.protocol_header.exchange_id;
+            let mut t = tlv::TlvBuffer::new();
+            t.write_array(2).unwrap();
+            for cluster in [0x03u32, 0x04, 0x06, 0x1d] {
+                t.write_uint32_notag(cluster).unwrap();
+            }
+            t.write_struct_end().unwrap();
+            let reports = [AttrReport::Data { endpoint: 1, cluster: 0x1d, attribute: 1, value_tlv: t.data }];
+            device.send(&im_report_data(exchange, &reports, -1, None, false).unwrap()).await;
+            // drain ack / StatusResponse; no further read may follow
+            while device.recv_within(Duration::from_millis(300)).await.is_some() {}
+            device
+        });
+        assert!(conn.has_cluster(1, 0x06).await.unwrap());
+        let mut device = task.await.unwrap();
+        assert!(!conn.has_cluster(1, 0x300).await.unwrap());
+        assert!(conn.has_cluster(1, 0x1d).await.unwrap());
+        device.expect_silence().await;
+    }
+
+    #[tokio::test]
+    async fn test_subscribe_and_updates() {
+        let (conn, mut device) = mock_pair();
+        let task = tokio::spawn(async move {
+            let req = device.recv().await;
+            assert_eq!(
+                req.protocol_header.opcode,
+                ProtocolMessageHeader::INTERACTION_OPCODE_SUBSCRIBE_REQ
+            );
+            let exchange = req.protocol_header.exchange_id;
+            let counter = device
+                .send(&report_data(exchange, FLAGS_RESPONDER, Some(7), &[(1, true)], true, false))
+                .await;
+            device.expect_status_response(ACK_AND_INITIATOR, counter).await;
+            let counter = device
+                .send(&report_data(exchange, FLAGS_RESPONDER, Some(7), &[(2, false)], false, false))
+                .await;
+            device.expect_status_response(ACK_AND_INITIATOR, counter).await;
+            device.send(&subscribe_response(exchange, 7, 60)).await;
+
+            // device-initiated update on a fresh exchange
+            let counter = device
+                .send(&report_data(0x4001, FLAGS_DEVICE_INITIATED, Some(7), &[(1, false)], false, false))
+                .await;
+            device
+                .expect_status_response(ProtocolMessageHeader::FLAG_ACK, counter)
+                .await;
+            device
+        });
+
+        let mut sub = conn.subscribe_attrs(Some(1), Some(6), Some(0), false).await.unwrap();
+        assert_eq!(sub.subscription_id, 7);
+        assert_eq!(sub.max_interval, 60);
+        assert_eq!(sub.priming_attribute_reports.len(), 2);
+        assert_eq!(sub.priming_attribute_reports[0].path.endpoint, Some(1));
+        assert_eq!(sub.priming_attribute_reports[1].path.endpoint, Some(2));
+
+        let update = sub.next().await.unwrap();
+        assert_eq!(update.subscription_id, 7);
+        assert_eq!(update.attribute_reports.len(), 1);
+        assert_eq!(
+            update.attribute_reports[0].data,
+            im::AttributeData::Value(TlvItemValue::Bool(false))
+        );
+        task.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_chunked_unsolicited_report() {
+        let (conn, mut device) = mock_pair();
+        let task = tokio::spawn(async move {
+            let req = device.recv().await;
+            let exchange = req.protocol_header.exchange_id;
+            let counter = device
+                .send(&report_data(exchange, FLAGS_RESPONDER, Some(9), &[(1, true)], false, false))
+                .await;
+            device.expect_status_response(ACK_AND_INITIATOR, counter).await;
+            device.send(&subscribe_response(exchange, 9, 60)).await;
+
+            // chunked device-initiated update
+            let counter = device
+                .send(&report_data(0x4002, FLAGS_DEVICE_INITIATED, Some(9), &[(1, false)], true, false))
+                .await;
+            device
+                .expect_status_response(ProtocolMessageHeader::FLAG_ACK, counter)
+                .await;
+            let counter = device
+                .send(&report_data(0x4002, FLAGS_DEVICE_INITIATED, Some(9), &[(2, true)], false, false))
+                .await;
+            device
+                .expect_status_response(ProtocolMessageHeader::FLAG_ACK, counter)
+                .await;
+        });
+
+        let mut sub = conn.subscribe_attrs(Some(1), Some(6), Some(0), false).await.unwrap();
+        let update = sub.next().await.unwrap();
+        assert_eq!(update.attribute_reports.len(), 2);
+        assert_eq!(update.attribute_reports[0].path.endpoint, Some(1));
+        assert_eq!(update.attribute_reports[1].path.endpoint, Some(2));
+        task.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_wait_for_report() {
+        let (conn, mut device) = mock_pair();
+        let task = tokio::spawn(async move {
+            for sub_id in [11, 12] {
+                let req = device.recv().await;
+                assert_eq!(
+                    req.protocol_header.opcode,
+                    ProtocolMessageHeader::INTERACTION_OPCODE_SUBSCRIBE_REQ
+                );
+                let exchange = req.protocol_header.exchange_id;
+                let counter = device
+                    .send(&report_data(exchange, FLAGS_RESPONDER, Some(sub_id), &[(1, false)], false, false))
+                    .await;
+                device.expect_status_response(ACK_AND_INITIATOR, counter).await;
+                device.send(&subscribe_response(exchange, sub_id, 60)).await;
+                if sub_id == 12 {
+                    break;
+                }
+                assert_eq!(req.tlv.get_bool(&[0]), Some(true), "other subscriptions are kept");
+                assert_eq!(req.tlv.get_int(&[1]), Some(0));
+
+                // other endpoint, then the watched attribute still off, then on
+                for (i, values) in [[(2, true)], [(1, false)], [(1, true)]].iter().enumerate() {
+                    let counter = device
+                        .send(&report_data(0x4010 + i as u16, FLAGS_DEVICE_INITIATED, Some(sub_id), values, false, false))
+                        .await;
+                    device
+                        .expect_status_response(ProtocolMessageHeader::FLAG_ACK, counter)
+                        .await;
+                }
+            }
+        });
+
+        let on = |v: &TlvItemValue| *v == TlvItemValue::Bool(true);
+        let value = conn
+            .wait_for_report((1, 6, 0), on, Duration::from_secs(2))
+            .await
+            .unwrap();
+        assert_eq!(value, TlvItemValue::Bool(true));
+
+        // existing subscription: no matching report arrives
+        let mut sub = conn.subscribe_attrs(Some(1), Some(6), Some(0), false).await.unwrap();
+        let err = sub
+            .wait_for_report((1, 6, 0), on, Duration::from_millis(100))
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("no matching report"), "got: {}", err);
+        task.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_unregistered_subscription_id() {
+        let (conn, mut device) = mock_pair();
+
+        let counter = device
+            .send(&report_data(0x4003, FLAGS_DEVICE_INITIATED, Some(99), &[(1, true)], false, false))
+            .await;
+        device
+            .expect_status_response(ProtocolMessageHeader::FLAG_ACK, counter)
+            .await;
+        let raw = conn.recv_event().await.unwrap();
+        assert_eq!(
+            raw.protocol_header.opcode,
+            ProtocolMessageHeader::INTERACTION_OPCODE_REPORT_DATA
+        );
+
+        conn.set_auto_status_response(false);
+        device
+            .send(&report_data(0x4004, FLAGS_DEVICE_INITIATED, Some(99), &[(1, true)], false, false))
+            .await;
+        device.expect_silence().await;
+        let raw = conn.recv_event().await.unwrap();
+        assert_eq!(raw.protocol_header.exchange_id, 0x4004);
+    }
+
+    #[tokio::test]
+    async fn test_send_ack() {
+        let (conn, mut device) = mock_pair();
+        conn.send_ack(0x4006, 0x01020304).await.unwrap();
+        let data = device.rx.recv().await.unwrap();
+        let (_, payload) = messages::MessageHeader::decode(&data).unwrap();
+        assert_eq!(
+            payload,
+            [
+                ProtocolMessageHeader::FLAG_INITIATOR | ProtocolMessageHeader::FLAG_ACK,
+                ProtocolMessageHeader::OPCODE_ACK,
+                0x06, 0x40, // exchange id
+                0x00, 0x00, // secure channel protocol
+                0x04, 0x03, 0x02, 0x01, // acknowledged counter
+            ]
+        );
+        device.expect_silence().await;
+    }
+
+    #[tokio::test]
+    async fn test_duplicate_message_dropped() {
+        let (conn, mut device) = mock_pair();
+
+        let payload =
+            report_data(0x4005, FLAGS_DEVICE_INITIATED, Some(99), &[(1, true)], false, false);
+        let encoded = device.session.encode_message(&payload).unwrap();
+        let (header, _) = messages::MessageHeader::decode(&encoded).unwrap();
+        device.tx.send(encoded.clone()).await.unwrap();
+        device
+            .expect_status_response(ProtocolMessageHeader::FLAG_ACK, header.message_counter)
+            .await;
+        let raw = conn.recv_event().await.unwrap();
+        assert_eq!(raw.protocol_header.exchange_id, 0x4005);
+
+        // replayed frame must be dropped: no status response, no event
+        device.tx.send(encoded).await.unwrap();
+        device.expect_silence().await;
+        assert!(conn.try_recv_event().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_initiator_flag_not_misrouted() {
+        let (conn, mut device) = mock_pair();
+        let task = tokio::spawn(async move {
+            let req = device.recv().await;
+            let exchange = req.protocol_header.exchange_id;
+            // device-initiated report colliding with the pending exchange id
+            // must not resolve the pending read request
+            let counter = device
+                .send(&report_data(exchange, FLAGS_DEVICE_INITIATED, None, &[(5, false)], false, false))
+                .await;
+            device
+                .expect_status_response(ProtocolMessageHeader::FLAG_ACK, counter)
+                .await;
+            device
+                .send(&report_data(exchange, FLAGS_RESPONDER, None, &[(1, true)], false, true))
+                .await;
+        });
+        let val = conn.read_request2(1, 6, 0).await.unwrap();
+        assert_eq!(val, TlvItemValue::Bool(true));
+        task.await.unwrap();
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_retransmit_schedule_and_give_up() {
+        let mrp = crate::mrp::MrpParameters::from_txt_ms(Some(5000), None, None);
+        let (conn, mut device) = mock_pair_unreliable(mrp);
+        let req = tokio::spawn(async move { conn.read_request2(1, 6, 0).await });
+
+        let mut times = Vec::new();
+        let mut counters = Vec::new();
+        for i in 0..crate::mrp::MRP_MAX_TRANSMISSIONS {
+            let msg = device
+                .recv_within(Duration::from_secs(30))
+                .await
+                .unwrap_or_else(|| panic!("missing transmission {}", i));
+            times.push(tokio::time::Instant::now());
+            counters.push(msg.message_header.message_counter);
+        }
+        assert!(counters.iter().all(|c| *c == counters[0]));
+
+        // gap n follows backoff: 5s * 1.1 * 1.6^max(0, n-1) plus up to 25% jitter
+        for (n, w) in times.windows(2).enumerate() {
+            let gap = (w[1] - w[0]).as_secs_f64();
+            let lower = 5.0 * 1.1 * 1.6f64.powi(n.saturating_sub(1) as i32);
+            let upper = lower * 1.25;
+            assert!(
+                gap >= lower - 0.01 && gap <= upper + 0.1,
+                "gap {} = {} not in [{}, {}]",
+                n, gap, lower, upper
+            );
+        }
+
+        // after the final backoff period the exchange is dropped and the request fails
+        let res = req.await.unwrap();
+        assert!(res.is_err(), "request should fail after give-up");
+        assert!(
+            device.recv_within(Duration::from_secs(120)).await.is_none(),
+            "no transmissions expected after give-up"
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_retransmit_stops_after_ack() {
+        let (conn, mut device) = mock_pair_unreliable(Default::default());
+        let _req = tokio::spawn(async move { conn.read_request2(1, 6, 0).await });
+
+        let msg = device.recv_within(Duration::from_secs(5)).await.expect("request");
+        let retr = device.recv_within(Duration::from_secs(5)).await.expect("retransmit");
+        assert_eq!(
+            msg.message_header.message_counter,
+            retr.message_header.message_counter
+        );
+
+        device
+            .send(&messages::ack(
+                msg.protocol_header.exchange_id,
+                msg.message_header.message_counter as i64,
+            ).unwrap())
+            .await;
+        assert!(
+            device.recv_within(Duration::from_secs(60)).await.is_none(),
+            "no retransmissions expected after ack"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_list_clusters() {
+        let (conn, mut device) = mock_pair();
+        let req = tokio::spawn(async move { conn.list_clusters(1).await });
+
+        let msg = device.recv().await;
+        assert_eq!(msg.tlv.get_int(&[0, 0, 3]), Some(0x1d));
+        assert_eq!(msg.tlv.get_int(&[0, 0, 4]), Some(1));
+        // ServerList of an extended color light, plus vendor specific cluster
+        let value_tlv = hex::decode("360204030404040504060408041d0500030604fcff1218").unwrap();
+        device
+            .send(&crate::device_messages::im_report_data(
+                msg.protocol_header.exchange_id,
+                &[crate::device_messages::AttrReport::Data {
+                    endpoint: 1,
+                    cluster: 0x1d,
+                    attribute: 1,
+                    value_tlv,
+                }],
+                msg.message_header.message_counter as i64,
+                None,
+                false,
+            ).unwrap())
+            .await;
+        let clusters = req.await.unwrap().unwrap();
+        assert_eq!(clusters.len(), 8);
+        assert_eq!(clusters[0], (0x3, Some("Identify")));
+        assert_eq!(clusters[3], (0x6, Some("On/Off")));
+        assert_eq!(clusters[6], (0x300, Some("Color Control")));
+        assert_eq!(clusters[7], (0x12fffc04, None));
     }
 
-    impl MockDevice {
-        async fn recv(&mut self) -> Message {
-            let data = tokio::time::timeout(Duration::from_secs(2), self.rx.recv())
+    #[tokio::test]
+    async fn test_send_raw_interaction() {
+        let (conn, mut device) = mock_pair();
+        let mut payload = tlv::TlvBuffer::new();
+        payload.write_anon_struct().unwrap();
+        payload.write_uint16(0, 0x1234).unwrap();
+        payload.write_struct_end().unwrap();
+        let sent = payload.data.clone();
+        let req = tokio::spawn(async move {
+            conn.send_raw_interaction(ProtocolMessageHeader::INTERACTION_OPCODE_TIMED_REQ, &sent)
                 .await
-                .expect("timeout waiting for controller message")
-                .expect("mock closed");
-            Message::decode(&data).unwrap()
-        }
+        });
 
-        async fn expect_status_response(&mut self, want_flags: u8, want_ack: u32) {
-            let msg = self.recv().await;
-            assert_eq!(
-                msg.protocol_header.protocol_id,
-                ProtocolMessageHeader::PROTOCOL_ID_INTERACTION
-            );
+        let msg = device.recv().await;
+        assert_eq!(
+            msg.protocol_header.protocol_id,
+            ProtocolMessageHeader::PROTOCOL_ID_INTERACTION
+        );
+        assert_eq!(
+            msg.protocol_header.opcode,
+            ProtocolMessageHeader::INTERACTION_OPCODE_TIMED_REQ
+        );
+        assert_eq!(msg.payload, payload.data);
+        assert_eq!(msg.tlv.get_int(&[0]), Some(0x1234));
+
+        device
+            .send(&messages::im_status_response(
+                msg.protocol_header.exchange_id,
+                0,
+                msg.message_header.message_counter,
+            ).unwrap())
+            .await;
+        let resp = req.await.unwrap().unwrap();
+        assert_eq!(
+            resp.protocol_header.opcode,
+            ProtocolMessageHeader::INTERACTION_OPCODE_STATUS_RESP
+        );
+    }
+
+    #[tokio::test]
+    async fn test_revoke_commissioning() {
+        use crate::clusters::defs::{
+            CLUSTER_ADMINISTRATOR_COMMISSIONING_CMD_ID_REVOKECOMMISSIONING as REVOKE,
+            CLUSTER_ID_ADMINISTRATOR_COMMISSIONING as ADMIN,
+        };
+        // (status, cluster status) -> expected result
+        for (status, cluster_status, expected) in [(0, None, Some(true)), (1, Some(4), Some(false)), (1, Some(2), None)] {
+            let (conn, mut device) = mock_pair();
+            let req = tokio::spawn(async move { conn.revoke_commissioning().await });
+
+            let msg = device.recv().await;
             assert_eq!(
                 msg.protocol_header.opcode,
-                ProtocolMessageHeader::INTERACTION_OPCODE_STATUS_RESP
+                ProtocolMessageHeader::INTERACTION_OPCODE_TIMED_REQ
             );
+            device
+                .send(&messages::im_status_response(
+                    msg.protocol_header.exchange_id,
+                    0,
+                    msg.message_header.message_counter,
+                ).unwrap())
+                .await;
+
+            // standalone ack of the status response precedes the invoke
+            let mut msg = device.recv().await;
+            if msg.protocol_header.opcode == ProtocolMessageHeader::OPCODE_ACK {
+                msg = device.recv().await;
+            }
             assert_eq!(
-                msg.protocol_header.exchange_flags,
-                ProtocolMessageHeader::FLAG_RELIABILITY | want_flags
+                msg.protocol_header.opcode,
+                ProtocolMessageHeader::INTERACTION_OPCODE_INVOKE_REQ
             );
-            assert_eq!(msg.protocol_header.ack_counter, want_ack);
-            assert_eq!(msg.tlv.get_int(&[0]), Some(0));
-        }
+            assert_eq!(msg.tlv.get_bool(&[1]), Some(true));
+            assert_eq!(msg.tlv.get_int(&[2, 0, 0, 0]), Some(0));
+            assert_eq!(msg.tlv.get_int(&[2, 0, 0, 1]), Some(ADMIN as u64));
+            assert_eq!(msg.tlv.get_int(&[2, 0, 0, 2]), Some(REVOKE as u64));
 
-        async fn expect_silence(&mut self) {
-            assert!(
-                tokio::time::timeout(Duration::from_millis(200), self.rx.recv())
-                    .await
-                    .is_err(),
-                "unexpected message from controller"
-            );
+            device
+                .send(&crate::device_messages::im_invoke_response_cluster_status(
+                    msg.protocol_header.exchange_id,
+                    0,
+                    ADMIN,
+                    REVOKE,
+                    status,
+                    cluster_status,
+                    msg.message_header.message_counter as i64,
+                ).unwrap())
+                .await;
+            let res = req.await.unwrap();
+            match expected {
+                Some(v) => assert_eq!(res.unwrap(), v),
+                None => assert!(res.is_err()),
+            }
         }
+    }
 
-        async fn send(&self, payload: &[u8]) -> u32 {
-            let encoded = self.session.encode_message(payload).unwrap();
-            let (header, _) = messages::MessageHeader::decode(&encoded).unwrap();
-            self.tx.send(encoded).await.unwrap();
-            header.message_counter
-        }
+    #[tokio::test]
+    async fn test_update_fabric_label() {
+        use crate::clusters::defs::{
+            CLUSTER_ID_OPERATIONAL_CREDENTIALS as OC,
+            CLUSTER_OPERATIONAL_CREDENTIALS_CMD_ID_UPDATEFABRICLABEL as UPDATE,
+        };
+        let (conn, mut device) = mock_pair();
+        // 33 bytes - rejected without contacting the device
+        assert!(conn.update_fabric_label(&"a".repeat(33)).await.is_err());
+        // 11 characters but 33 bytes in utf-8
+        assert!(conn.update_fabric_label(&"\u{20ac}".repeat(11)).await.is_err());
+        device.expect_silence().await;
 
-        async fn recv_within(&mut self, d: Duration) -> Option<Message> {
-            match tokio::time::timeout(d, self.rx.recv()).await {
-                Ok(Some(data)) => Some(Message::decode(&data).unwrap()),
-                _ => None,
+        let label = "b".repeat(32);
+        let req = tokio::spawn(async move { conn.update_fabric_label(&label).await });
+        let msg = device.recv().await;
+        assert_eq!(msg.tlv.get_int(&[2, 0, 0, 1]), Some(OC as u64));
+        assert_eq!(msg.tlv.get_int(&[2, 0, 0, 2]), Some(UPDATE as u64));
+        assert_eq!(msg.tlv.get_string_owned(&[2, 0, 1, 0]), Some("b".repeat(32)));
+
+        // NOCResponse: StatusCode OK, FabricIndex 1
+        device
+            .send(&crate::device_messages::im_invoke_response_data(
+                msg.protocol_header.exchange_id,
+                0,
+                OC,
+                crate::clusters::defs::CLUSTER_OPERATIONAL_CREDENTIALS_CMD_ID_NOCRESPONSE,
+                &[0x24, 0x00, 0x00, 0x24, 0x01, 0x01],
+                msg.message_header.message_counter as i64,
+            ).unwrap())
+            .await;
+        req.await.unwrap().unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_controller_operational_identity() {
+        let certs = certmanager::TestCertManager::new("controller_identity", &[0x1234]);
+        let cm: Arc<dyn certmanager::CertManager> = certs.cm.clone();
+        let transport = transport::Transport::new("127.0.0.1:0").await.unwrap();
+        let controller = Controller::new(&cm, &transport, 1000).unwrap();
+
+        let cert = controller.operational_cert(0x1234).unwrap();
+        assert_eq!(cert, cm.get_user_cert(0x1234).unwrap());
+        assert_eq!(controller.node_id(0x1234).unwrap(), 0x1234);
+        // no certificate issued for this id
+        assert!(controller.node_id(0x1235).is_err());
+    }
+
+    /// Next invoke request from controller, skipping standalone acks.
+    async fn recv_invoke(device: &mut MockDevice, cluster: u32, command: u32) -> Message {
+        loop {
+            let msg = device.recv().await;
+            if msg.protocol_header.opcode != ProtocolMessageHeader::INTERACTION_OPCODE_INVOKE_REQ {
+                continue;
             }
+            assert_eq!(msg.tlv.get_int(&[2, 0, 0, 1]), Some(cluster as u64));
+            assert_eq!(msg.tlv.get_int(&[2, 0, 0, 2]), Some(command as u64));
+            return msg;
         }
     }
 
-    fn mock_pair() -> (Connection, MockDevice) {
-        mock_pair_with(true, Default::default())
+    async fn respond_invoke(device: &MockDevice, req: &Message, cluster: u32, command: u32, fields: &[u8]) {
+        device
+            .send(&crate::device_messages::im_invoke_response_data(
+                req.protocol_header.exchange_id,
+                0,
+                cluster,
+                command,
+                fields,
+                req.message_header.message_counter as i64,
+            ).unwrap())
+            .await;
     }
 
-    fn mock_pair_unreliable(mrp: crate::mrp::MrpParameters) -> (Connection, MockDevice) {
-        mock_pair_with(false, mrp)
+    /// Drive [Controller::update_noc] against mock device up to UpdateNOC, which is answered
+    /// with NOCResponse `noc_status`. Returns the controller task, the device and the
+    /// certificate fixture, which has to outlive the task.
+    async fn update_noc_until_noc_response(
+        name: &str,
+        noc_status: u8,
+    ) -> (tokio::task::JoinHandle<Result<()>>, MockDevice, certmanager::TestCertManager) {
+        use crate::clusters::defs::*;
+        let certs = certmanager::TestCertManager::new(name, &[]);
+        let cm: Arc<dyn certmanager::CertManager> = certs.cm.clone();
+        let transport = transport::Transport::new("127.0.0.1:0").await.unwrap();
+        let controller = Controller::new(&cm, &transport, 1000).unwrap();
+        let (conn, mut device) = mock_pair();
+        let task = tokio::spawn(async move { controller.update_noc(&conn, 0x1234).await });
+
+        let msg = recv_invoke(&mut device, CLUSTER_ID_GENERAL_COMMISSIONING, CLUSTER_GENERAL_COMMISSIONING_CMD_ID_ARMFAILSAFE).await;
+        assert_eq!(msg.tlv.get_int(&[2, 0, 1, 0]), Some(UPDATE_NOC_FAILSAFE_SECS as u64));
+        // ArmFailSafeResponse: ErrorCode OK
+        respond_invoke(&device, &msg, CLUSTER_ID_GENERAL_COMMISSIONING, CLUSTER_GENERAL_COMMISSIONING_CMD_ID_ARMFAILSAFERESPONSE, &[0x24, 0x00, 0x00]).await;
+
+        let msg = recv_invoke(&mut device, CLUSTER_ID_OPERATIONAL_CREDENTIALS, CLUSTER_OPERATIONAL_CREDENTIALS_CMD_ID_CSRREQUEST).await;
+        assert_eq!(msg.tlv.get_bool(&[2, 0, 1, 1]), Some(true));
+        let key = p256::SecretKey::random(&mut rand::thread_rng());
+        let mut elements = tlv::TlvBuffer::new();
+        elements.write_anon_struct().unwrap();
+        elements.write_octetstring(1, &crate::device::commissioning::generate_csr(&key).unwrap()).unwrap();
+        elements.write_octetstring(2, &[0; 32]).unwrap();
+        elements.write_struct_end().unwrap();
+        let mut fields = tlv::TlvBuffer::new();
+        fields.write_octetstring(0, &elements.data).unwrap();
+        fields.write_octetstring(1, &[0; 64]).unwrap();
+        respond_invoke(&device, &msg, CLUSTER_ID_OPERATIONAL_CREDENTIALS, CLUSTER_OPERATIONAL_CREDENTIALS_CMD_ID_CSRRESPONSE, &fields.data).await;
+
+        let msg = recv_invoke(&mut device, CLUSTER_ID_OPERATIONAL_CREDENTIALS, CLUSTER_OPERATIONAL_CREDENTIALS_CMD_ID_UPDATENOC).await;
+        let noc = msg.tlv.get_octet_string(&[2, 0, 1, 0]).unwrap();
+        let noc = cert_matter::MatterCert::decode(noc).unwrap();
+        assert_eq!(noc.public_key, key.public_key().to_sec1_bytes().to_vec());
+        // NOCResponse: StatusCode, FabricIndex 1
+        respond_invoke(&device, &msg, CLUSTER_ID_OPERATIONAL_CREDENTIALS, CLUSTER_OPERATIONAL_CREDENTIALS_CMD_ID_NOCRESPONSE, &[0x24, 0x00, noc_status, 0x24, 0x01, 0x01]).await;
+        (task, device, certs)
     }
 
-    fn mock_pair_with(reliable: bool, mrp: crate::mrp::MrpParameters) -> (Connection, MockDevice) {
-        let (to_ctrl_tx, to_ctrl_rx) = mpsc::channel(32);
-        let (to_dev_tx, to_dev_rx) = mpsc::unbounded_channel();
-        let mock = Arc::new(MockConn {
-            inbound: tokio::sync::Mutex::new(to_ctrl_rx),
-            outbound: to_dev_tx,
-            reliable,
-            mrp: std::sync::Mutex::new(mrp),
-        });
-        let conn = Connection::from_parts(mock, session::Session::new());
-        let device = MockDevice {
-            rx: to_dev_rx,
-            tx: to_ctrl_tx,
-            session: session::Session::new(),
-        };
-        (conn, device)
+    #[tokio::test]
+    async fn test_update_noc() {
+        use crate::clusters::defs::*;
+        let (task, mut device, _certs) = update_noc_until_noc_response("update_noc", 0).await;
+        let msg = recv_invoke(&mut device, CLUSTER_ID_GENERAL_COMMISSIONING, CLUSTER_GENERAL_COMMISSIONING_CMD_ID_COMMISSIONINGCOMPLETE).await;
+        // CommissioningCompleteResponse: ErrorCode OK
+        respond_invoke(&device, &msg, CLUSTER_ID_GENERAL_COMMISSIONING, CLUSTER_GENERAL_COMMISSIONING_CMD_ID_COMMISSIONINGCOMPLETERESPONSE, &[0x24, 0x00, 0x00]).await;
+        task.await.unwrap().unwrap();
     }
 
-    fn report_data(
-        exchange: u16,
-        flags: u8,
-        sub_id: Option<u32>,
-        values: &[(u16, bool)],
-        more: bool,
-        suppress: bool,
-    ) -> Vec<u8> {
-        let b = ProtocolMessageHeader {
-            exchange_flags: flags,
-            opcode: ProtocolMessageHeader::INTERACTION_OPCODE_REPORT_DATA,
-            exchange_id: exchange,
-            protocol_id: ProtocolMessageHeader::PROTOCOL_ID_INTERACTION,
-            ack_counter: 0,
-        }
-        .encode()
-        .unwrap();
-        let mut t = tlv::TlvBuffer::from_vec(b);
-        t.write_anon_struct().unwrap();
-        if let Some(id) = sub_id {
-            t.write_uint32(0, id).unwrap();
-        }
-        t.write_array(1).unwrap();
-        for (endpoint, value) in values {
-            t.write_anon_struct().unwrap();
-            t.write_struct(1).unwrap();
-            t.write_uint32(0, 0).unwrap();
-            t.write_list(1).unwrap();
-            t.write_uint16(2, *endpoint).unwrap();
-            t.write_uint32(3, 6).unwrap();
-            t.write_uint32(4, 0).unwrap();
-            t.write_struct_end().unwrap();
-            t.write_bool(2, *value).unwrap();
-            t.write_struct_end().unwrap();
-            t.write_struct_end().unwrap();
-        }
-        t.write_struct_end().unwrap();
-        if more {
-            t.write_bool(3, true).unwrap();
+    #[tokio::test]
+    async fn test_update_noc_rejected_disarms_fail_safe() {
+        use crate::clusters::defs::*;
+        // NOCResponse StatusCode InvalidPublicKey
+        let (task, mut device, _certs) = update_noc_until_noc_response("update_noc_rejected", 1).await;
+        let msg = recv_invoke(&mut device, CLUSTER_ID_GENERAL_COMMISSIONING, CLUSTER_GENERAL_COMMISSIONING_CMD_ID_ARMFAILSAFE).await;
+        assert_eq!(msg.tlv.get_int(&[2, 0, 1, 0]), Some(0));
+        respond_invoke(&device, &msg, CLUSTER_ID_GENERAL_COMMISSIONING, CLUSTER_GENERAL_COMMISSIONING_CMD_ID_ARMFAILSAFERESPONSE, &[0x24, 0x00, 0x00]).await;
+        let err = task.await.unwrap().unwrap_err();
+        assert!(format!("{:?}", err).contains("UpdateNOC failed"), "got: {:?}", err);
+    }
+
+    #[tokio::test]
+    async fn test_close_session() {
+        let (conn, mut device) = mock_pair();
+        conn.close_session().await.unwrap();
+        let msg = device.recv().await;
+        assert_eq!(
+            msg.protocol_header.protocol_id,
+            ProtocolMessageHeader::PROTOCOL_ID_SECURE_CHANNEL
+        );
+        assert_eq!(msg.protocol_header.opcode, ProtocolMessageHeader::OPCODE_STATUS);
+        assert_eq!(
+            msg.protocol_header.exchange_flags & ProtocolMessageHeader::FLAG_RELIABILITY,
+            0
+        );
+        let sr = msg.status_report_info.unwrap();
+        assert_eq!(sr.to_string(), "StatusReportInfo: OK");
+        assert_eq!(
+            msg.payload,
+            [0, 0, 0, 0, 0, 0, messages::SecureChannelProtocolCode::CloseSession as u8, 0]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_locales() {
+        use crate::clusters::defs::{
+            CLUSTER_ID_LOCALIZATION_CONFIGURATION as LC,
+            CLUSTER_LOCALIZATION_CONFIGURATION_ATTR_ID_ACTIVELOCALE as ACTIVE,
+            CLUSTER_LOCALIZATION_CONFIGURATION_ATTR_ID_SUPPORTEDLOCALES as SUPPORTED,
+        };
+        let (conn, mut device) = mock_pair();
+        let conn = Arc::new(conn);
+        // 36 bytes - rejected without contacting the device
+        assert!(conn.set_active_locale(&"x".repeat(36)).await.is_err());
+        device.expect_silence().await;
+
+        let c = conn.clone();
+        let req = tokio::spawn(async move { c.supported_locales().await });
+        let msg = device.recv().await;
+        assert_eq!(msg.tlv.get_int(&[0, 0, 3]), Some(LC as u64));
+        assert_eq!(msg.tlv.get_int(&[0, 0, 4]), Some(SUPPORTED as u64));
+        let mut value = tlv::TlvBuffer::new();
+        value.write_array(2).unwrap();
+        for l in ["en-US", "de-DE", "cs-CZ"] {
+            // anonymous utf8 string, 1 byte length
+            value.data.extend([0x0c, l.len() as u8]);
+            value.data.extend(l.as_bytes());
         }
-        if suppress {
-            t.write_bool(4, true).unwrap();
+        value.write_struct_end().unwrap();
+        device
+            .send(&crate::device_messages::im_report_data(
+                msg.protocol_header.exchange_id,
+                &[crate::device_messages::AttrReport::Data {
+                    endpoint: 0,
+                    cluster: LC,
+                    attribute: SUPPORTED,
+                    value_tlv: value.data,
+                }],
+                msg.message_header.message_counter as i64,
+                None,
+                false,
+            ).unwrap())
+            .await;
+        assert_eq!(req.await.unwrap().unwrap(), ["en-US", "de-DE", "cs-CZ"]);
+
+        let c = conn.clone();
+        let req = tokio::spawn(async move { c.set_active_locale("de-DE").await });
+        // status response / ack of the report may precede the write
+        let mut msg = device.recv().await;
+        while msg.protocol_header.opcode != ProtocolMessageHeader::INTERACTION_OPCODE_WRITE_REQ {
+            msg = device.recv().await;
         }
-        t.write_struct_end().unwrap();
-        t.data
+        assert_eq!(msg.tlv.get_int(&[2, 0, 1, 4]), Some(ACTIVE as u64));
+        assert_eq!(msg.tlv.get_string_owned(&[2, 0, 2]), Some("de-DE".to_owned()));
+        device
+            .send(&crate::device_messages::im_write_response_success(
+                msg.protocol_header.exchange_id,
+                msg.message_header.message_counter as i64,
+                &[(0, LC, ACTIVE)],
+            ).unwrap())
+            .await;
+        req.await.unwrap().unwrap();
     }
 
-    fn subscribe_response(exchange: u16, sub_id: u32, max_interval: u16) -> Vec<u8> {
-        let b = ProtocolMessageHeader {
-            exchange_flags: 0,
-            opcode: ProtocolMessageHeader::INTERACTION_OPCODE_SUBSCRIBE_RESP,
-            exchange_id: exchange,
-            protocol_id: ProtocolMessageHeader::PROTOCOL_ID_INTERACTION,
-            ack_counter: 0,
+    #[tokio::test]
+    async fn test_node_label() {
+        use crate::clusters::defs::{
+            CLUSTER_BASIC_INFORMATION_ATTR_ID_NODELABEL as NODE_LABEL,
+            CLUSTER_ID_BASIC_INFORMATION as BI,
+        };
+        let (conn, mut device) = mock_pair();
+        let conn = Arc::new(conn);
+        // 33 bytes - rejected without contacting the device
+        assert!(conn.set_node_label(&"x".repeat(33)).await.is_err());
+        // multi-byte characters count in bytes: 11 * 3 = 33
+        assert!(conn.set_node_label(&"\u{20ac}".repeat(11)).await.is_err());
+        device.expect_silence().await;
+
+        let c = conn.clone();
+        let label = "k".repeat(32);
+        let written = label.clone();
+        let req = tokio::spawn(async move { c.set_node_label(&written).await });
+        let msg = device.recv().await;
+        assert_eq!(msg.protocol_header.opcode, ProtocolMessageHeader::INTERACTION_OPCODE_WRITE_REQ);
+        assert_eq!(msg.tlv.get_int(&[2, 0, 1, 3]), Some(BI as u64));
+        assert_eq!(msg.tlv.get_int(&[2, 0, 1, 4]), Some(NODE_LABEL as u64));
+        assert_eq!(msg.tlv.get_string_owned(&[2, 0, 2]), Some(label.clone()));
+        device
+            .send(&crate::device_messages::im_write_response_success(
+                msg.protocol_header.exchange_id,
+                msg.message_header.message_counter as i64,
+                &[(0, BI, NODE_LABEL)],
+            ).unwrap())
+            .await;
+        req.await.unwrap().unwrap();
+
+        let c = conn.clone();
+        let req = tokio::spawn(async move { c.node_label().await });
+        // ack of the write response may precede the read
+        let mut msg = device.recv().await;
+        while msg.protocol_header.opcode != ProtocolMessageHeader::INTERACTION_OPCODE_READ_REQ {
+            msg = device.recv().await;
         }
-        .encode()
-        .unwrap();
-        let mut t = tlv::TlvBuffer::from_vec(b);
-        t.write_anon_struct().unwrap();
-        t.write_uint32(0, sub_id).unwrap();
-        t.write_uint16(2, max_interval).unwrap();
-        t.write_struct_end().unwrap();
-        t.data
+        assert_eq!(msg.tlv.get_int(&[0, 0, 4]), Some(NODE_LABEL as u64));
+        let mut value = tlv::TlvBuffer::new();
+        value.write_string(2, "Kitchen lamp").unwrap();
+        device
+            .send(&crate::device_messages::im_report_data(
+                msg.protocol_header.exchange_id,
+                &[crate::device_messages::AttrReport::Data {
+                    endpoint: 0,
+                    cluster: BI,
+                    attribute: NODE_LABEL,
+                    value_tlv: value.data,
+                }],
+                msg.message_header.message_counter as i64,
+                None,
+                false,
+            ).unwrap())
+            .await;
+        assert_eq!(req.await.unwrap().unwrap(), "Kitchen lamp");
     }
 
-    const FLAGS_RESPONDER: u8 = 0;
-    const FLAGS_DEVICE_INITIATED: u8 = ProtocolMessageHeader::FLAG_INITIATOR;
-    const ACK_AND_INITIATOR: u8 =
-        ProtocolMessageHeader::FLAG_INITIATOR | ProtocolMessageHeader::FLAG_ACK;
-
     #[tokio::test]
-    async fn test_read_request2_single_chunk() {
+    async fn test_write_oversized_attribute() {
+        use crate::clusters::defs::{CLUSTER_ID_USER_LABEL, CLUSTER_USER_LABEL_ATTR_ID_LABELLIST as LABELS};
         let (conn, mut device) = mock_pair();
-        let task = tokio::spawn(async move {
-            let req = device.recv().await;
-            assert_eq!(
-                req.protocol_header.opcode,
-                ProtocolMessageHeader::INTERACTION_OPCODE_READ_REQ
-            );
-            let exchange = req.protocol_header.exchange_id;
+        let labels: Vec<(String, String)> = (0..80)
+            .map(|i| (format!("label-{:010}", i), format!("value-{:010}", i)))
+            .collect();
+        assert!(crate::clusters::typed::encode_labels(&labels).unwrap().len() > messages::MAX_UDP_MESSAGE_SIZE);
+        let written = labels.clone();
+        let req = tokio::spawn(async move { conn.write_user_labels(1, &written).await });
+
+        let mut received = Vec::new();
+        let mut chunks = 0;
+        loop {
+            let mut msg = device.recv().await;
+            while msg.protocol_header.opcode == ProtocolMessageHeader::OPCODE_ACK {
+                msg = device.recv().await;
+            }
+            assert_eq!(msg.protocol_header.opcode, ProtocolMessageHeader::INTERACTION_OPCODE_WRITE_REQ);
+            chunks += 1;
+            let Some(TlvItemValue::List(ibs)) = msg.tlv.get(&[2]) else { panic!("no AttributeDataIBs") };
+            for ib in ibs {
+                match ib.get(&[2]) {
+                    Some(TlvItemValue::List(items)) if items.is_empty() => received.clear(),
+                    Some(_) => received.push((
+                        ib.get_string_owned(&[2, 0]).unwrap(),
+                        ib.get_string_owned(&[2, 1]).unwrap(),
+                    )),
+                    None => panic!("AttributeDataIB without data"),
+                }
+            }
             device
-                .send(&report_data(exchange, FLAGS_RESPONDER, None, &[(1, true)], false, true))
-                .await;
-            device.expect_silence().await;
-        });
-        let val = conn.read_request2(1, 6, 0).await.unwrap();
-        assert_eq!(val, TlvItemValue::Bool(true));
-        task.await.unwrap();
+                .send(&crate::device_messages::im_write_response_success(
+                    msg.protocol_header.exchange_id,
+                    msg.message_header.message_counter as i64,
+                    &vec![(1, CLUSTER_ID_USER_LABEL, LABELS); ibs.len()],
+                ).unwrap())
+                .await;
+            if msg.tlv.get_bool(&[3]) != Some(true) {
+                break;
+            }
+        }
+        req.await.unwrap().unwrap();
+        assert!(chunks > 1);
+        assert_eq!(received, labels);
     }
 
     #[tokio::test]
-    async fn test_read_request2_chunked() {
+    async fn test_dump_device_json() {
+        use crate::device_messages::{im_report_data, status_report, AttrReport};
         let (conn, mut device) = mock_pair();
+        // data model: endpoint 0 with Basic Information, endpoint 1 with OnOff
+        // and manufacturer cluster 0xfc00 which fails to read
         let task = tokio::spawn(async move {
-            let req = device.recv().await;
-            let exchange = req.protocol_header.exchange_id;
-            let counter = device
-                .send(&report_data(exchange, FLAGS_RESPONDER, None, &[(1, true)], true, false))
-                .await;
-            device.expect_status_response(ACK_AND_INITIATOR, counter).await;
+            for _ in 0..6 {
+                let mut req = device.recv().await;
+                while req.protocol_header.opcode != ProtocolMessageHeader::INTERACTION_OPCODE_READ_REQ {
+                    req = device.recv().await;
+                }
+                let exchange = req.protocol_header.exchange_id;
+                let ack = req.message_header.message_counter as i64;
+                let endpoint = req.tlv.get_int(&[0, 0, 2]).unwrap() as u16;
+                let cluster = req.tlv.get_int(&[0, 0, 3]).unwrap() as u32;
+                let attr = req.tlv.get_int(&[0, 0, 4]).map(|a| a as u32);
+                let mut t = tlv::TlvBuffer::new();
+                let reports = match (endpoint, cluster, attr) {
+                    (0, 0x1d, Some(3)) => {
+                        t.write_array(2).unwrap();
+                        t.write_uint16_notag(1).unwrap();
+                        t.write_struct_end().unwrap();
+                        vec![AttrReport::Data { endpoint, cluster, attribute: 3, value_tlv: t.data }]
+                    }
+                    (_, 0x1d, Some(1)) => {
+                        let servers: &[u32] = if endpoint == 0 { &[0x28] } else { &[0x06, 0xfc00] };
+                        t.write_array(2).unwrap();
+                        for c in servers {
+                            t.write_uint32_notag(*c).unwrap();
+                        }
+                        t.write_struct_end().unwrap();
+                        vec![AttrReport::Data { endpoint, cluster, attribute: 1, value_tlv: t.data }]
+                    }
+                    (0, 0x28, None) => {
+                        t.write_string(2, "TEST_VENDOR").unwrap();
+                        vec![
+                            AttrReport::Data { endpoint, cluster, attribute: 1, value_tlv: t.data },
+                            AttrReport::Status { endpoint, cluster, attribute: 0x10, status: 0x86 },
+                        ]
+                    }
+                    (1, 0x06, None) => {
+                        let mut on = tlv::TlvBuffer::new();
+                        on.write_bool(2, true).unwrap();
+                        t.write_uint16(2, 6).unwrap();
+                        vec![
+                            AttrReport::Data { endpoint, cluster, attribute: 0, value_tlv: on.data },
+                            AttrReport::Data { endpoint, cluster, attribute: 0xfffd, value_tlv: t.data },
+                        ]
+                    }
+                    (1, 0xfc00, None) => {
+                        device.send(&status_report(exchange, 1, 0, 0, ack).unwrap()).await;
+                        continue;
+                    }
+                    other => panic!("unexpected read {:?}", other),
+                };
+                device.send(&im_report_data(exchange, &reports, ack, None, false).unwrap()).await;
+            }
+            // keep the mock alive until the last status report is acked
             device
-                .send(&report_data(exchange, FLAGS_RESPONDER, None, &[(2, false)], false, true))
-                .await;
-            device.expect_silence().await;
         });
-        let val = conn.read_request2(1, 6, 0).await.unwrap();
-        assert_eq!(val, TlvItemValue::Bool(true));
-        task.await.unwrap();
+        let json = conn.dump_device_json().await.unwrap();
+        let _device = task.await.unwrap();
+
+        let basic = &json["endpoints"]["0"]["clusters"]["0x0028"];
+        assert_eq!(basic["attributes"]["0x0001"]["name"], "VendorName");
+        assert_eq!(basic["attributes"]["0x0001"]["value"], "TEST_VENDOR");
+        assert_eq!(basic["attributes"]["0x0010"]["status"], 0x86);
+        assert!(basic["attributes"]["0x0010"].get("value").is_none());
+        let on_off = &json["endpoints"]["1"]["clusters"]["0x0006"];
+        assert_eq!(on_off["attributes"]["0x0000"]["name"], "OnOff");
+        assert_eq!(on_off["attributes"]["0x0000"]["value"], true);
+        assert_eq!(on_off["attributes"]["0xfffd"]["name"], "ClusterRevision");
+        assert_eq!(on_off["attributes"]["0xfffd"]["value"], 6);
+        let vendor = &json["endpoints"]["1"]["clusters"]["0xfc00"];
+        assert!(vendor["error"].is_string());
+        assert!(vendor.get("attributes").is_none());
     }
 
     #[tokio::test]
-    async fn test_subscribe_and_updates() {
+    async fn test_on_off_effect_commands() {
+        use crate::clusters::codec::on_off::{onoffcontrol, EffectIdentifier};
+        use crate::clusters::defs::*;
         let (conn, mut device) = mock_pair();
-        let task = tokio::spawn(async move {
-            let req = device.recv().await;
-            assert_eq!(
-                req.protocol_header.opcode,
-                ProtocolMessageHeader::INTERACTION_OPCODE_SUBSCRIBE_REQ
-            );
-            let exchange = req.protocol_header.exchange_id;
-            let counter = device
-                .send(&report_data(exchange, FLAGS_RESPONDER, Some(7), &[(1, true)], true, false))
-                .await;
-            device.expect_status_response(ACK_AND_INITIATOR, counter).await;
-            let counter = device
-                .send(&report_data(exchange, FLAGS_RESPONDER, Some(7), &[(2, false)], false, false))
-                .await;
-            device.expect_status_response(ACK_AND_INITIATOR, counter).await;
-            device.send(&subscribe_response(exchange, 7, 60)).await;
-
-            // device-initiated update on a fresh exchange
-            let counter = device
-                .send(&report_data(0x4001, FLAGS_DEVICE_INITIATED, Some(7), &[(1, false)], false, false))
-                .await;
+        let conn = Arc::new(conn);
+
+        // answer the next invoke and return its command id and fields
+        async fn answer(device: &mut MockDevice) -> (u32, TlvItemValue) {
+            // standalone ack of the previous response may precede the invoke
+            let mut msg = device.recv().await;
+            if msg.protocol_header.opcode == ProtocolMessageHeader::OPCODE_ACK {
+                msg = device.recv().await;
+            }
+            assert_eq!(msg.tlv.get_int(&[2, 0, 0, 0]), Some(1));
+            assert_eq!(msg.tlv.get_int(&[2, 0, 0, 1]), Some(CLUSTER_ID_ON_OFF as u64));
+            let command = msg.tlv.get_int(&[2, 0, 0, 2]).unwrap() as u32;
             device
-                .expect_status_response(ProtocolMessageHeader::FLAG_ACK, counter)
+                .send(&crate::device_messages::im_invoke_response_status(
+                    msg.protocol_header.exchange_id,
+                    1,
+                    CLUSTER_ID_ON_OFF,
+                    command,
+                    0,
+                    msg.message_header.message_counter as i64,
+                ).unwrap())
                 .await;
-            device
-        });
-
-        let mut sub = conn.subscribe_attrs(Some(1), Some(6), Some(0), false).await.unwrap();
-        assert_eq!(sub.subscription_id, 7);
-        assert_eq!(sub.max_interval, 60);
-        assert_eq!(sub.priming_attribute_reports.len(), 2);
-        assert_eq!(sub.priming_attribute_reports[0].path.endpoint, Some(1));
-        assert_eq!(sub.priming_attribute_reports[1].path.endpoint, Some(2));
+            let fields = msg.tlv.get(&[2, 0, 1]).cloned().unwrap_or(TlvItemValue::Invalid());
+            (command, fields)
+        }
 
-        let update = sub.next().await.unwrap();
-        assert_eq!(update.subscription_id, 7);
-        assert_eq!(update.attribute_reports.len(), 1);
+        let c = conn.clone();
+        let req = tokio::spawn(async move {
+            c.on_with_timed_off(1, onoffcontrol::ACCEPT_ONLY_WHEN_ON, 600, 50).await
+        });
+        let (command, fields) = answer(&mut device).await;
+        req.await.unwrap().unwrap();
+        assert_eq!(command, CLUSTER_ON_OFF_CMD_ID_ONWITHTIMEDOFF);
+        let TlvItemValue::List(items) = &fields else { panic!("fields {:?}", fields) };
+        let layout: Vec<_> = items.iter().map(|i| (i.tag, i.value.clone())).collect();
         assert_eq!(
-            update.attribute_reports[0].data,
-            im::AttributeData::Value(TlvItemValue::Bool(false))
+            layout,
+            [(0, TlvItemValue::Int(1)), (1, TlvItemValue::Int(600)), (2, TlvItemValue::Int(50))]
         );
-        task.await.unwrap();
-    }
-
-    #[tokio::test]
-    async fn test_chunked_unsolicited_report() {
-        let (conn, mut device) = mock_pair();
-        let task = tokio::spawn(async move {
-            let req = device.recv().await;
-            let exchange = req.protocol_header.exchange_id;
-            let counter = device
-                .send(&report_data(exchange, FLAGS_RESPONDER, Some(9), &[(1, true)], false, false))
-                .await;
-            device.expect_status_response(ACK_AND_INITIATOR, counter).await;
-            device.send(&subscribe_response(exchange, 9, 60)).await;
 
-            // chunked device-initiated update
-            let counter = device
-                .send(&report_data(0x4002, FLAGS_DEVICE_INITIATED, Some(9), &[(1, false)], true, false))
-                .await;
-            device
-                .expect_status_response(ProtocolMessageHeader::FLAG_ACK, counter)
-                .await;
-            let counter = device
-                .send(&report_data(0x4002, FLAGS_DEVICE_INITIATED, Some(9), &[(2, true)], false, false))
-                .await;
-            device
-                .expect_status_response(ProtocolMessageHeader::FLAG_ACK, counter)
-                .await;
+        let c = conn.clone();
+        let req = tokio::spawn(async move {
+            c.off_with_effect(1, EffectIdentifier::Dyinglight, 0).await
         });
-
-        let mut sub = conn.subscribe_attrs(Some(1), Some(6), Some(0), false).await.unwrap();
-        let update = sub.next().await.unwrap();
-        assert_eq!(update.attribute_reports.len(), 2);
-        assert_eq!(update.attribute_reports[0].path.endpoint, Some(1));
-        assert_eq!(update.attribute_reports[1].path.endpoint, Some(2));
-        task.await.unwrap();
+        let (command, fields) = answer(&mut device).await;
+        req.await.unwrap().unwrap();
+        assert_eq!(command, CLUSTER_ON_OFF_CMD_ID_OFFWITHEFFECT);
+        let TlvItemValue::List(items) = &fields else { panic!("fields {:?}", fields) };
+        let layout: Vec<_> = items.iter().map(|i| (i.tag, i.value.clone())).collect();
+        assert_eq!(layout, [(0, TlvItemValue::Int(1)), (1, TlvItemValue::Int(0))]);
+
+        let c = conn.clone();
+        let req = tokio::spawn(async move { c.on_with_recall_global_scene(1).await });
+        let (command, fields) = answer(&mut device).await;
+        req.await.unwrap().unwrap();
+        assert_eq!(command, CLUSTER_ON_OFF_CMD_ID_ONWITHRECALLGLOBALSCENE);
+        assert!(fields == TlvItemValue::List(Vec::new()), "command has no fields");
     }
 
     #[tokio::test]
-    async fn test_unregistered_subscription_id() {
+    async fn test_identify() {
         let (conn, mut device) = mock_pair();
+        let req = tokio::spawn(async move { conn.identify(1, 15).await });
 
-        let counter = device
-            .send(&report_data(0x4003, FLAGS_DEVICE_INITIATED, Some(99), &[(1, true)], false, false))
-            .await;
-        device
-            .expect_status_response(ProtocolMessageHeader::FLAG_ACK, counter)
-            .await;
-        let raw = conn.recv_event().await.unwrap();
+        let msg = device.recv().await;
         assert_eq!(
-            raw.protocol_header.opcode,
-            ProtocolMessageHeader::INTERACTION_OPCODE_REPORT_DATA
+            msg.protocol_header.opcode,
+            ProtocolMessageHeader::INTERACTION_OPCODE_INVOKE_REQ
+        );
+        assert_eq!(msg.tlv.get_int(&[2, 0, 0, 0]), Some(1));
+        assert_eq!(
+            msg.tlv.get_int(&[2, 0, 0, 1]),
+            Some(crate::clusters::defs::CLUSTER_ID_IDENTIFY as u64)
+        );
+        assert_eq!(
+            msg.tlv.get_int(&[2, 0, 0, 2]),
+            Some(crate::clusters::defs::CLUSTER_IDENTIFY_CMD_ID_IDENTIFY as u64)
         );
+        assert_eq!(msg.tlv.get_int(&[2, 0, 1, 0]), Some(15));
 
-        conn.set_auto_status_response(false);
         device
-            .send(&report_data(0x4004, FLAGS_DEVICE_INITIATED, Some(99), &[(1, true)], false, false))
+            .send(&crate::device_messages::im_invoke_response_status(
+                msg.protocol_header.exchange_id,
+                1,
+                crate::clusters::defs::CLUSTER_ID_IDENTIFY,
+                crate::clusters::defs::CLUSTER_IDENTIFY_CMD_ID_IDENTIFY,
+                0,
+                msg.message_header.message_counter as i64,
+            ).unwrap())
             .await;
-        device.expect_silence().await;
-        let raw = conn.recv_event().await.unwrap();
-        assert_eq!(raw.protocol_header.exchange_id, 0x4004);
+        req.await.unwrap().unwrap();
     }
 
     #[tokio::test]
-    async fn test_duplicate_message_dropped() {
+    async fn test_invoke_broadcast_endpoints() {
+        use crate::device_messages::{im_invoke_response_multi_status, im_report_data, AttrReport};
         let (conn, mut device) = mock_pair();
-
-        let payload =
-            report_data(0x4005, FLAGS_DEVICE_INITIATED, Some(99), &[(1, true)], false, false);
-        let encoded = device.session.encode_message(&payload).unwrap();
-        let (header, _) = messages::MessageHeader::decode(&encoded).unwrap();
-        device.tx.send(encoded.clone()).await.unwrap();
+        let req = tokio::spawn(async move { conn.invoke_broadcast_endpoints(&[1, 2, 3], 6, 0, &[]).await });
+
+        // device accepts two commands per InvokeRequest
+        let read = device.recv().await;
+        assert_eq!(read.tlv.get_int(&[0, 0, 3]), Some(0x28));
+        assert_eq!(read.tlv.get_int(&[0, 0, 4]), Some(0x16));
+        let mut t = tlv::TlvBuffer::new();
+        t.write_uint16(2, 2).unwrap();
+        let reports = [AttrReport::Data { endpoint: 0, cluster: 0x28, attribute: 0x16, value_tlv: t.data }];
         device
-            .expect_status_response(ProtocolMessageHeader::FLAG_ACK, header.message_counter)
+            .send(&im_report_data(read.protocol_header.exchange_id, &reports, -1, None, false).unwrap())
             .await;
-        let raw = conn.recv_event().await.unwrap();
-        assert_eq!(raw.protocol_header.exchange_id, 0x4005);
-
-        // replayed frame must be dropped: no status response, no event
-        device.tx.send(encoded).await.unwrap();
-        device.expect_silence().await;
-        assert!(conn.try_recv_event().is_none());
-    }
 
-    #[tokio::test]
-    async fn test_initiator_flag_not_misrouted() {
-        let (conn, mut device) = mock_pair();
-        let task = tokio::spawn(async move {
-            let req = device.recv().await;
-            let exchange = req.protocol_header.exchange_id;
-            // device-initiated report colliding with the pending exchange id
-            // must not resolve the pending read request
-            let counter = device
-                .send(&report_data(exchange, FLAGS_DEVICE_INITIATED, None, &[(5, false)], false, false))
-                .await;
-            device
-                .expect_status_response(ProtocolMessageHeader::FLAG_ACK, counter)
-                .await;
+        let mut invokes = Vec::new();
+        while invokes.len() < 2 {
+            let msg = device.recv().await;
+            if msg.protocol_header.opcode != ProtocolMessageHeader::INTERACTION_OPCODE_INVOKE_REQ
+                || msg.protocol_header.protocol_id != ProtocolMessageHeader::PROTOCOL_ID_INTERACTION
+            {
+                continue;
+            }
+            let Some(TlvItemValue::List(commands)) = msg.tlv.get(&[2]) else {
+                panic!("invoke without commands");
+            };
+            let paths: Vec<_> = commands
+                .iter()
+                .map(|c| (c.get_int(&[0, 0]).unwrap() as u16, c.get_int(&[2])))
+                .collect();
+            let statuses: Vec<_> = paths
+                .iter()
+                .map(|(e, _)| (*e, 6, 0, if *e == 2 { 0x81 } else { 0 }, None))
+                .collect();
             device
-                .send(&report_data(exchange, FLAGS_RESPONDER, None, &[(1, true)], false, true))
+                .send(&im_invoke_response_multi_status(
+                    msg.protocol_header.exchange_id,
+                    &statuses,
+                    msg.message_header.message_counter as i64,
+                ).unwrap())
                 .await;
-        });
-        let val = conn.read_request2(1, 6, 0).await.unwrap();
-        assert_eq!(val, TlvItemValue::Bool(true));
-        task.await.unwrap();
+            invokes.push(paths);
+        }
+        // batch carries CommandRefs, single command none
+        assert_eq!(invokes, [vec![(1, Some(0)), (2, Some(1))], vec![(3, None)]]);
+
+        let res = req.await.unwrap().unwrap();
+        let outcome: Vec<_> = res.iter().map(|(e, r)| (*e, r.is_ok())).collect();
+        assert_eq!(outcome, [(1, true), (2, false), (3, true)]);
     }
 
-    #[tokio::test(start_paused = true)]
-    async fn test_retransmit_schedule_and_give_up() {
-        let mrp = crate::mrp::MrpParameters::from_txt_ms(Some(5000), None, None);
-        let (conn, mut device) = mock_pair_unreliable(mrp);
-        let req = tokio::spawn(async move { conn.read_request2(1, 6, 0).await });
+    /// Answer the MaxPathsPerInvoke read of an invoke_broadcast_endpoints call.
+    async fn respond_max_paths(device: &mut MockDevice, max_paths: u16) {
+        use crate::device_messages::{im_report_data, AttrReport};
+        let read = device.recv().await;
+        assert_eq!(read.tlv.get_int(&[0, 0, 4]), Some(0x16));
+        let mut t = tlv::TlvBuffer::new();
+        t.write_uint16(2, max_paths).unwrap();
+        let reports = [AttrReport::Data { endpoint: 0, cluster: 0x28, attribute: 0x16, value_tlv: t.data }];
+        device
+            .send(&im_report_data(
+                read.protocol_header.exchange_id,
+                &reports,
+                read.message_header.message_counter as i64,
+                None,
+                false,
+            ).unwrap())
+            .await;
+    }
 
-        let mut times = Vec::new();
-        let mut counters = Vec::new();
-        for i in 0..crate::mrp::MRP_MAX_TRANSMISSIONS {
-            let msg = device
-                .recv_within(Duration::from_secs(30))
-                .await
-                .unwrap_or_else(|| panic!("missing transmission {}", i));
-            times.push(tokio::time::Instant::now());
-            counters.push(msg.message_header.message_counter);
+    fn invoke_endpoints(msg: &Message) -> Option<Vec<u16>> {
+        if msg.protocol_header.opcode != ProtocolMessageHeader::INTERACTION_OPCODE_INVOKE_REQ
+            || msg.protocol_header.protocol_id != ProtocolMessageHeader::PROTOCOL_ID_INTERACTION
+        {
+            return None;
         }
-        assert!(counters.iter().all(|c| *c == counters[0]));
+        let Some(TlvItemValue::List(commands)) = msg.tlv.get(&[2]) else {
+            panic!("invoke without commands");
+        };
+        Some(commands.iter().map(|c| c.get_int(&[0, 0]).unwrap() as u16).collect())
+    }
 
-        // gap n follows backoff: 5s * 1.1 * 1.6^max(0, n-1) plus up to 25% jitter
-        for (n, w) in times.windows(2).enumerate() {
-            let gap = (w[1] - w[0]).as_secs_f64();
-            let lower = 5.0 * 1.1 * 1.6f64.powi(n.saturating_sub(1) as i32);
-            let upper = lower * 1.25;
-            assert!(
-                gap >= lower - 0.01 && gap <= upper + 0.1,
-                "gap {} = {} not in [{}, {}]",
-                n, gap, lower, upper
-            );
+    #[tokio::test(start_paused = true)]
+    async fn test_invoke_broadcast_endpoints_unanswered_chunk() {
+        use crate::device_messages::im_invoke_response_multi_status;
+        let (conn, mut device) = mock_pair_unreliable(Default::default());
+        let req = tokio::spawn(async move { conn.invoke_broadcast_endpoints(&[1, 2, 3, 4, 5], 6, 0, &[]).await });
+        respond_max_paths(&mut device, 2).await;
+
+        // second InvokeRequest is never answered; controller gives up on it and still
+        // sends the third one
+        let mut answered = Vec::new();
+        let mut ignored = 0;
+        while answered.len() < 2 {
+            let msg = device.recv_within(Duration::from_secs(60)).await.expect("invoke request");
+            let Some(endpoints) = invoke_endpoints(&msg) else {
+                continue;
+            };
+            if endpoints == [3, 4] {
+                ignored += 1;
+                continue;
+            }
+            let statuses: Vec<_> = endpoints.iter().map(|e| (*e, 6, 0, 0, None)).collect();
+            device
+                .send(&im_invoke_response_multi_status(
+                    msg.protocol_header.exchange_id,
+                    &statuses,
+                    msg.message_header.message_counter as i64,
+                ).unwrap())
+                .await;
+            answered.push(endpoints);
         }
+        assert_eq!(answered, [vec![1, 2], vec![5]]);
+        assert!(ignored > 1, "unanswered request should be retransmitted");
+
+        let res = req.await.unwrap().unwrap();
+        let outcome: Vec<_> = res.iter().map(|(e, r)| (*e, r.is_ok())).collect();
+        assert_eq!(outcome, [(1, true), (2, true), (3, false), (4, false), (5, true)]);
+        let err = format!("{:#}", res[2].1.as_ref().unwrap_err());
+        assert!(err.contains("no response for endpoint 3"), "{}", err);
+    }
 
-        // after the final backoff period the exchange is dropped and the request fails
-        let res = req.await.unwrap();
-        assert!(res.is_err(), "request should fail after give-up");
-        assert!(
-            device.recv_within(Duration::from_secs(120)).await.is_none(),
-            "no transmissions expected after give-up"
-        );
+    #[tokio::test]
+    async fn test_invoke_broadcast_endpoints_chunked_response() {
+        use crate::device_messages::im_invoke_response_chunk;
+        let (conn, mut device) = mock_pair();
+        let req = tokio::spawn(async move { conn.invoke_broadcast_endpoints(&[1, 2, 3], 6, 0, &[]).await });
+        respond_max_paths(&mut device, 3).await;
+
+        let msg = loop {
+            let msg = device.recv().await;
+            if invoke_endpoints(&msg).is_some() {
+                break msg;
+            }
+        };
+        assert_eq!(invoke_endpoints(&msg).unwrap(), [1, 2, 3]);
+        let exchange = msg.protocol_header.exchange_id;
+        // responses split over two InvokeResponse messages
+        let counter = device
+            .send(&im_invoke_response_chunk(
+                exchange,
+                &[(1, 6, 0, 0, None), (2, 6, 0, 0x81, None)],
+                true,
+                msg.message_header.message_counter as i64,
+            ).unwrap())
+            .await;
+        // first chunk is acknowledged by a StatusResponse (after standalone acks)
+        let status = loop {
+            let msg = device.recv().await;
+            if msg.protocol_header.protocol_id == ProtocolMessageHeader::PROTOCOL_ID_INTERACTION {
+                break msg;
+            }
+        };
+        assert_eq!(status.protocol_header.opcode, ProtocolMessageHeader::INTERACTION_OPCODE_STATUS_RESP);
+        assert_eq!(status.protocol_header.ack_counter, counter);
+        device
+            .send(&im_invoke_response_chunk(exchange, &[(3, 6, 0, 0, None)], false, -1).unwrap())
+            .await;
+
+        let res = req.await.unwrap().unwrap();
+        let outcome: Vec<_> = res.iter().map(|(e, r)| (*e, r.is_ok())).collect();
+        assert_eq!(outcome, [(1, true), (2, false), (3, true)]);
     }
 
     #[tokio::test(start_paused = true)]
-    async fn test_retransmit_stops_after_ack() {
+    async fn test_invoke_no_response_returns_after_ack() {
         let (conn, mut device) = mock_pair_unreliable(Default::default());
-        let _req = tokio::spawn(async move { conn.read_request2(1, 6, 0).await });
+        let req = tokio::spawn(async move { conn.invoke_no_response(1, 6, 1, &[]).await });
 
-        let msg = device.recv_within(Duration::from_secs(5)).await.expect("request");
-        let retr = device.recv_within(Duration::from_secs(5)).await.expect("retransmit");
+        let msg = device.recv_within(Duration::from_secs(2)).await.expect("request");
         assert_eq!(
-            msg.message_header.message_counter,
-            retr.message_header.message_counter
+            msg.protocol_header.opcode,
+            ProtocolMessageHeader::INTERACTION_OPCODE_INVOKE_REQ
         );
+        assert_eq!(msg.tlv.get_bool(&[0]), Some(true), "SuppressResponse must be set");
+        assert_eq!(msg.tlv.get_bool(&[1]), Some(false));
 
         device
             .send(&messages::ack(
@@ -1460,10 +3936,69 @@ mod tests {
                 msg.message_header.message_counter as i64,
             ).unwrap())
             .await;
-        assert!(
-            device.recv_within(Duration::from_secs(60)).await.is_none(),
-            "no retransmissions expected after ack"
-        );
+        // completes without any InvokeResponse from device
+        tokio::time::timeout(Duration::from_secs(1), req)
+            .await
+            .expect("invoke_no_response should not wait for a response")
+            .unwrap()
+            .unwrap();
+        assert!(device.recv_within(Duration::from_secs(60)).await.is_none());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_invoke_request_with_timeout() {
+        use crate::device_messages::im_invoke_response_status;
+        let (conn, mut device) = mock_pair();
+        // device answers slow command only after 5s
+        let task = tokio::spawn(async move {
+            for _ in 0..2 {
+                let msg = device.recv().await;
+                assert_eq!(
+                    msg.protocol_header.opcode,
+                    ProtocolMessageHeader::INTERACTION_OPCODE_INVOKE_REQ
+                );
+                tokio::time::sleep(Duration::from_secs(5)).await;
+                let resp = im_invoke_response_status(
+                    msg.protocol_header.exchange_id,
+                    1,
+                    8,
+                    0,
+                    0,
+                    msg.message_header.message_counter as i64,
+                )
+                .unwrap();
+                device.send(&resp).await;
+            }
+            device
+        });
+
+        let started = tokio::time::Instant::now();
+        let err = conn
+            .invoke_request_with_timeout(1, 8, 0, &[], Duration::from_secs(2))
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("within 2s"), "got: {}", err);
+        assert_eq!(started.elapsed(), Duration::from_secs(2));
+
+        let res = conn
+            .invoke_request_with_timeout(1, 8, 0, &[], Duration::from_secs(30))
+            .await
+            .unwrap();
+        assert_eq!(messages::parse_im_invoke_resp(&res.tlv).unwrap(), (0, 0));
+        let _device = task.await.unwrap();
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_invoke_no_response_fails_without_ack() {
+        let (conn, mut device) = mock_pair_unreliable(Default::default());
+        let req = tokio::spawn(async move { conn.invoke_no_response(1, 6, 1, &[]).await });
+        for i in 0..crate::mrp::MRP_MAX_TRANSMISSIONS {
+            device
+                .recv_within(Duration::from_secs(30))
+                .await
+                .unwrap_or_else(|| panic!("missing transmission {}", i));
+        }
+        assert!(req.await.unwrap().is_err());
     }
 
     #[tokio::test(start_paused = true)]
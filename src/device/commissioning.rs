@@ -61,75 +61,7 @@ impl Device {
     }
 
     fn generate_csr(&self) -> Result<Vec<u8>> {
-        let public_key = self.operational_key.public_key();
-        let public_key_bytes = public_key.to_sec1_bytes();
-
-        let signing_key: ecdsa::SigningKey<p256::NistP256> =
-            ecdsa::SigningKey::from(&self.operational_key);
-
-        use crate::util::asn1::Encoder;
-        let mut enc = Encoder::new();
-
-        // CertificationRequestInfo SEQUENCE
-        enc.start_seq(0x30)?;
-
-        // version INTEGER 0
-        enc.write_int(0)?;
-
-        // subject:
-        enc.start_seq(0x30)?;
-        enc.start_seq(0x31)?; // SET
-        enc.start_seq(0x30)?; // SEQUENCE
-        enc.write_oid("2.5.4.10")?;
-        enc.write_string("CSR")?;
-        enc.end_seq();
-        enc.end_seq();
-        enc.end_seq();
-
-        // subjectPKInfo SEQUENCE
-        enc.start_seq(0x30)?;
-        // algorithm SEQUENCE (id-ecPublicKey + prime256v1)
-        enc.start_seq(0x30)?;
-        enc.write_oid("1.2.840.10045.2.1")?; // id-ecPublicKey
-        enc.write_oid("1.2.840.10045.3.1.7")?; // prime256v1
-        enc.end_seq();
-        // subjectPublicKey BIT STRING
-        let mut pk_bits: Vec<u8> = vec![0]; // 0 unused bits
-        pk_bits.extend_from_slice(&public_key_bytes);
-        enc.write_octet_string_with_tag(0x03, &pk_bits)?;
-        enc.end_seq();
-
-        // attributes [0]
-        enc.start_seq(0xa0)?;
-        enc.end_seq();
-
-        enc.end_seq(); // end CertificationRequestInfo
-
-        let cri_data = enc.encode();
-
-        // Now sign the CertificationRequestInfo
-        let (sig, _) = signing_key.sign_recoverable(&cri_data)?;
-        let sig_bytes = sig.to_der();
-
-        // Re-build with signature
-        let mut final_enc = Encoder::new();
-        final_enc.start_seq(0x30)?;
-
-        // Write raw CRI data
-        final_enc.write_raw(&cri_data);
-
-        // signatureAlgorithm SEQUENCE
-        final_enc.start_seq(0x30)?;
-        final_enc.write_oid("1.2.840.10045.4.3.2")?; // ecdsa-with-SHA256
-        final_enc.end_seq();
-
-        // signature BIT STRING
-        let mut sig_with_unused: Vec<u8> = vec![0]; // 0 unused bits
-        sig_with_unused.extend_from_slice(sig_bytes.as_bytes());
-        final_enc.write_octet_string_with_tag(0x03, &sig_with_unused)?;
-
-        final_enc.end_seq();
-        Ok(final_enc.encode())
+        generate_csr(&self.operational_key)
     }
 
     pub(crate) async fn handle_add_trusted_root(
@@ -462,7 +394,9 @@ impl Device {
             .get_octet_string(&[2, 0, 1, 0])
             .context("AttestationRequest: nonce (tag 0) missing")?;
 
-        let timestamp = std::time::SystemTime::now()
+        let timestamp = self
+            .clock
+            .now()
             .duration_since(std::time::UNIX_EPOCH)
             .unwrap_or_default()
             .as_secs() as u32;
@@ -539,3 +473,76 @@ impl Device {
         self.send_commissioning_reply(addr, msg_header.session_id, &resp).await
     }
 }
+
+/// PKCS#10 certificate signing request for `operational_key`, as sent in CSRResponse.
+pub(crate) fn generate_csr(operational_key: &p256::SecretKey) -> Result<Vec<u8>> {
+    let public_key = operational_key.public_key();
+    let public_key_bytes = public_key.to_sec1_bytes();
+
+    let signing_key: ecdsa::SigningKey<p256::NistP256> =
+        ecdsa::SigningKey::from(operational_key);
+
+    use crate::util::asn1::Encoder;
+    let mut enc = Encoder::new();
+
+    // CertificationRequestInfo SEQUENCE
+    enc.start_seq(0x30)?;
+
+    // version INTEGER 0
+    enc.write_int(0)?;
+
+    // subject:
+    enc.start_seq(0x30)?;
+    enc.start_seq(0x31)?; // SET
+    enc.start_seq(0x30)?; // SEQUENCE
+    enc.write_oid("2.5.4.10")?;
+    enc.write_string("CSR")?;
+    enc.end_seq();
+    enc.end_seq();
+    enc.end_seq();
+
+    // subjectPKInfo SEQUENCE
+    enc.start_seq(0x30)?;
+    // algorithm SEQUENCE (id-ecPublicKey + prime256v1)
+    enc.start_seq(0x30)?;
+    enc.write_oid("1.2.840.10045.2.1")?; // id-ecPublicKey
+    enc.write_oid("1.2.840.10045.3.1.7")?; // prime256v1
+    enc.end_seq();
+    // subjectPublicKey BIT STRING
+    let mut pk_bits: Vec<u8> = vec![0]; // 0 unused bits
+    pk_bits.extend_from_slice(&public_key_bytes);
+    enc.write_octet_string_with_tag(0x03, &pk_bits)?;
+    enc.end_seq();
+
+    // attributes [0]
+    enc.start_seq(0xa0)?;
+    enc.end_seq();
+
+    enc.end_seq(); // end CertificationRequestInfo
+
+    let cri_data = enc.encode();
+
+    // Now sign the CertificationRequestInfo
+    let (sig, _) = signing_key.sign_recoverable(&cri_data)?;
+    let sig_bytes = sig.to_der();
+
+    // Re-build with signature
+    let mut final_enc = Encoder::new();
+    final_enc.start_seq(0x30)?;
+
+    // Write raw CRI data
+    final_enc.write_raw(&cri_data);
+
+    // signatureAlgorithm SEQUENCE
+    final_enc.start_seq(0x30)?;
+    final_enc.write_oid("1.2.840.10045.4.3.2")?; // ecdsa-with-SHA256
+    final_enc.end_seq();
+
+    // signature BIT STRING
+    let mut sig_with_unused: Vec<u8> = vec![0]; // 0 unused bits
+    sig_with_unused.extend_from_slice(sig_bytes.as_bytes());
+    final_enc.write_octet_string_with_tag(0x03, &sig_with_unused)?;
+
+    final_enc.end_seq();
+    Ok(final_enc.encode())
+}
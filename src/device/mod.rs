@@ -2,7 +2,7 @@
 
 mod attributes;
 mod case_handler;
-mod commissioning;
+pub(crate) mod commissioning;
 mod crypto;
 mod interaction;
 mod pase;
@@ -78,6 +78,8 @@ pub struct Device {
     pub(crate) mdns: Arc<crate::mdns2::MdnsService>,
     /// Extra attributes to include in persistence (registered by user code).
     pub(crate) extra_persisted: Vec<(u16, u32, u32)>,
+    /// Time source for attestation timestamps.
+    pub(crate) clock: Arc<dyn crate::cert_x509::Clock>,
 }
 
 impl Device {
@@ -109,6 +111,7 @@ impl Device {
             dirty_attributes: HashSet::new(),
             mdns,
             extra_persisted: Vec::new(),
+            clock: Arc::new(crate::cert_x509::SystemClock),
         };
         device.setup_default_attributes()?;
         device.dirty_attributes.clear();
@@ -156,6 +159,11 @@ impl Device {
         self.message_counter.fetch_add(1, Ordering::Relaxed)
     }
 
+    /// Replace time source used for attestation timestamps (system time by default).
+    pub fn set_clock(&mut self, clock: Arc<dyn crate::cert_x509::Clock>) {
+        self.clock = clock;
+    }
+
     pub async fn run(&mut self, handler: &mut dyn AppHandler) -> Result<()> {
         let mut buf = [0u8; 4096];
         log::info!(
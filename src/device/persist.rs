@@ -87,11 +87,7 @@ impl Device {
         let ca_id = fi.ca_id()?;
         let fabric = fabric::Fabric::new(fabric_id, ca_id, &ca_public_key, &fi.ipk);
 
-        let iname = format!(
-            "{}-{:016X}",
-            hex::encode_upper(fabric.compressed()?),
-            nod_id
-        );
+        let iname = crate::discover::operational_instance_label(fabric.compressed_id()?, nod_id);
         let op_port: u16 = self
             .config
             .listen_address
@@ -183,6 +179,7 @@ impl Device {
             dirty_attributes: HashSet::new(),
             mdns,
             extra_persisted: Vec::new(),
+            clock: Arc::new(crate::cert_x509::SystemClock),
         };
 
         device.setup_default_attributes()?;
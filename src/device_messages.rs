@@ -338,6 +338,40 @@ pub fn im_invoke_response_status(
     command: u32,
     status: u16,
     ack: i64,
+) -> Result<Vec<u8>> {
+    im_invoke_response_cluster_status(exchange, endpoint, cluster, command, status, None, ack)
+}
+
+/// InvokeResponse with CommandStatusIB carrying optional cluster-specific status.
+pub fn im_invoke_response_cluster_status(
+    exchange: u16,
+    endpoint: u16,
+    cluster: u32,
+    command: u32,
+    status: u16,
+    cluster_status: Option<u8>,
+    ack: i64,
+) -> Result<Vec<u8>> {
+    im_invoke_response_multi_status(exchange, &[(endpoint, cluster, command, status, cluster_status)], ack)
+}
+
+/// InvokeResponse with one CommandStatusIB per `(endpoint, cluster, command, status,
+/// cluster_status)` entry, as sent for a batched InvokeRequest.
+pub fn im_invoke_response_multi_status(
+    exchange: u16,
+    statuses: &[(u16, u32, u32, u16, Option<u8>)],
+    ack: i64,
+) -> Result<Vec<u8>> {
+    im_invoke_response_chunk(exchange, statuses, false, ack)
+}
+
+/// Like [im_invoke_response_multi_status], with MoreChunkedMessages set when `more` is
+/// true (further InvokeResponses follow on the exchange).
+pub fn im_invoke_response_chunk(
+    exchange: u16,
+    statuses: &[(u16, u32, u32, u16, Option<u8>)],
+    more: bool,
+    ack: i64,
 ) -> Result<Vec<u8>> {
     let b = ProtocolMessageHeader {
         exchange_flags: device_flags(ack),
@@ -352,19 +386,27 @@ pub fn im_invoke_response_status(
     tlv.write_anon_struct()?;
     tlv.write_bool(0, false)?;
     tlv.write_array(1)?;
-    tlv.write_anon_struct()?;
-    tlv.write_struct(1)?; // CommandStatusIB (tag 1)
-    tlv.write_list(0)?; // CommandPathIB
-    tlv.write_uint16(0, endpoint)?;
-    tlv.write_uint32(1, cluster)?;
-    tlv.write_uint32(2, command)?;
-    tlv.write_struct_end()?; // end CommandPathIB
-    tlv.write_struct(1)?; // StatusIB
-    tlv.write_uint16(0, status)?;
-    tlv.write_struct_end()?; // end StatusIB
-    tlv.write_struct_end()?; // end CommandStatusIB
-    tlv.write_struct_end()?; // end InvokeResponseIB
+    for &(endpoint, cluster, command, status, cluster_status) in statuses {
+        tlv.write_anon_struct()?;
+        tlv.write_struct(1)?; // CommandStatusIB (tag 1)
+        tlv.write_list(0)?; // CommandPathIB
+        tlv.write_uint16(0, endpoint)?;
+        tlv.write_uint32(1, cluster)?;
+        tlv.write_uint32(2, command)?;
+        tlv.write_struct_end()?; // end CommandPathIB
+        tlv.write_struct(1)?; // StatusIB
+        tlv.write_uint16(0, status)?;
+        if let Some(cluster_status) = cluster_status {
+            tlv.write_uint8(1, cluster_status)?;
+        }
+        tlv.write_struct_end()?; // end StatusIB
+        tlv.write_struct_end()?; // end CommandStatusIB
+        tlv.write_struct_end()?; // end InvokeResponseIB
+    }
     tlv.write_struct_end()?; // end InvokeResponses array
+    if more {
+        tlv.write_bool(2, true)?; // MoreChunkedMessages
+    }
     tlv.write_struct_end()?; // end top-level struct
     Ok(tlv.data)
 }
@@ -1,11 +1,78 @@
 use anyhow::{Context, Result};
 use serde::{Deserialize, Serialize};
 
+/// Default overall limit for commissioning a device by address.
+pub const DEFAULT_COMMISSION_TIMEOUT_SECS: u64 = 60;
+
+/// Default number of addresses of a discovered device commissioned concurrently.
+pub const DEFAULT_COMMISSION_PARALLELISM: usize = 1;
+
+fn default_port() -> u16 {
+    crate::discover::DEFAULT_PORT
+}
+
+fn default_commission_timeout_secs() -> u64 {
+    DEFAULT_COMMISSION_TIMEOUT_SECS
+}
+
+fn default_commission_parallelism() -> usize {
+    DEFAULT_COMMISSION_PARALLELISM
+}
+
+/// Order in which the advertised addresses of a discovered device are tried.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AddressOrder {
+    /// Order of the address records in the mDNS response.
+    Advertised,
+    /// Address the mDNS response came from, then IPv4, non link-local IPv6
+    /// and link-local IPv6.
+    #[default]
+    SourceFirst,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ManagerConfig {
     pub fabric_id: u64,
     pub controller_id: u64,
     pub local_address: String,
+    /// Overall limit for commissioning a device by address, in seconds.
+    #[serde(default = "default_commission_timeout_secs")]
+    pub commission_timeout_secs: u64,
+    /// Order of commissioning attempts over the addresses of a discovered device.
+    #[serde(default)]
+    pub address_order: AddressOrder,
+    /// Number of addresses of a discovered device attempted concurrently.
+    /// The first successful attempt wins and the others are cancelled.
+    #[serde(default = "default_commission_parallelism")]
+    pub commission_parallelism: usize,
+    /// Port used for discovered devices whose mDNS response carries no port.
+    #[serde(default = "default_port")]
+    pub default_port: u16,
+    /// Network interface (name or address) used for discovery and device traffic;
+    /// all interfaces when unset. See [crate::transport::list_interfaces].
+    #[serde(default)]
+    pub interface: Option<String>,
+}
+
+impl ManagerConfig {
+    /// Config with default values for the optional settings.
+    pub fn new(fabric_id: u64, controller_id: u64, local_address: &str) -> Self {
+        Self {
+            fabric_id,
+            controller_id,
+            local_address: local_address.to_owned(),
+            commission_timeout_secs: DEFAULT_COMMISSION_TIMEOUT_SECS,
+            address_order: AddressOrder::default(),
+            commission_parallelism: DEFAULT_COMMISSION_PARALLELISM,
+            default_port: default_port(),
+            interface: None,
+        }
+    }
+
+    pub fn commission_timeout(&self) -> std::time::Duration {
+        std::time::Duration::from_secs(self.commission_timeout_secs)
+    }
 }
 
 pub(crate) fn config_path(base: &str) -> String {
@@ -32,3 +99,20 @@ pub(crate) fn save_config(base: &str, config: &ManagerConfig) -> Result<()> {
     std::fs::write(&path, data).context(format!("writing config to {}", path))
 }
 
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn config_without_timeout_uses_default() {
+        let cfg: ManagerConfig = serde_json::from_str(
+            r#"{"fabric_id": 1000, "controller_id": 100, "local_address": "0.0.0.0:5555"}"#,
+        )
+        .unwrap();
+        assert_eq!(cfg.commission_timeout_secs, DEFAULT_COMMISSION_TIMEOUT_SECS);
+        assert_eq!(cfg.address_order, AddressOrder::SourceFirst);
+        assert_eq!(cfg.commission_parallelism, DEFAULT_COMMISSION_PARALLELISM);
+        assert_eq!(cfg.default_port, 5540);
+    }
+}
@@ -1,3 +1,5 @@
+use std::collections::BTreeMap;
+
 use anyhow::{Context, Result};
 use serde::{Deserialize, Serialize};
 
@@ -15,6 +17,34 @@ pub struct Device {
     /// MRP active threshold advertised by the device (SAT, milliseconds)
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub sat_ms: Option<u32>,
+    /// Vendor ID read from Basic Information after commissioning
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub vendor_id: Option<u16>,
+    /// Product ID read from Basic Information after commissioning
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub product_id: Option<u16>,
+    /// Server clusters per endpoint, cached from the Descriptor cluster
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub endpoints: BTreeMap<u16, Vec<u32>>,
+    /// Last successful contact with the device (seconds since UNIX epoch)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_seen: Option<u64>,
+}
+
+/// Reachability summary returned by [`DeviceManager::status`](super::DeviceManager::status).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DeviceStatus {
+    /// Device advertises itself via operational mDNS, or was contacted recently
+    pub online: bool,
+    /// Last successful contact (seconds since UNIX epoch)
+    pub last_seen: Option<u64>,
+}
+
+pub(crate) fn now_secs() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
 }
 
 impl Device {
@@ -28,6 +58,8 @@ impl Device {
 pub(crate) struct DeviceRegistry {
     path: String,
     devices: Vec<Device>,
+    /// In-memory changes (last_seen) not yet written to `path`.
+    dirty: bool,
 }
 
 impl DeviceRegistry {
@@ -39,12 +71,15 @@ impl DeviceRegistry {
         Ok(Self {
             path: path.to_owned(),
             devices,
+            dirty: false,
         })
     }
 
-    fn save(&self) -> Result<()> {
+    fn save(&mut self) -> Result<()> {
         let data = serde_json::to_string_pretty(&self.devices)?;
-        std::fs::write(&self.path, data).context(format!("writing devices to {}", self.path))
+        std::fs::write(&self.path, data).context(format!("writing devices to {}", self.path))?;
+        self.dirty = false;
+        Ok(())
     }
 
     pub fn add(&mut self, device: Device) -> Result<()> {
@@ -102,6 +137,39 @@ impl DeviceRegistry {
         self.save()
     }
 
+    pub fn update_metadata(
+        &mut self,
+        node_id: u64,
+        vendor_id: Option<u16>,
+        product_id: Option<u16>,
+        endpoints: BTreeMap<u16, Vec<u32>>,
+    ) -> Result<()> {
+        let dev = self.devices.iter_mut().find(|d| d.node_id == node_id)
+            .context(format!("device {} not found", node_id))?;
+        dev.vendor_id = vendor_id;
+        dev.product_id = product_id;
+        dev.endpoints = endpoints;
+        self.save()
+    }
+
+    /// Record contact with a device at `now`. To avoid rewriting the file on every
+    /// connect, last_seen is written right away only when the device comes back online
+    /// (not seen within the online window); otherwise with the next registry change
+    /// or when the registry is dropped.
+    pub fn touch(&mut self, node_id: u64, now: u64) -> Result<()> {
+        let dev = self.devices.iter_mut().find(|d| d.node_id == node_id)
+            .context(format!("device {} not found", node_id))?;
+        let was_online = dev
+            .last_seen
+            .is_some_and(|t| now.saturating_sub(t) <= super::ONLINE_WINDOW_SECS);
+        dev.last_seen = Some(now);
+        if was_online {
+            self.dirty = true;
+            return Ok(());
+        }
+        self.save()
+    }
+
     pub fn rename(&mut self, node_id: u64, name: &str) -> Result<()> {
         // Check for duplicate name
         if let Some(existing) = self.devices.iter().find(|d| d.name == name) {
@@ -116,6 +184,16 @@ impl DeviceRegistry {
     }
 }
 
+impl Drop for DeviceRegistry {
+    fn drop(&mut self) {
+        if self.dirty {
+            if let Err(e) = self.save() {
+                log::debug!("failed to persist last_seen: {:?}", e);
+            }
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -189,4 +267,55 @@ mod tests {
         reg.remove(1).unwrap();
         assert!(reg.list().is_empty());
     }
+
+    #[test]
+    fn registry_loads_old_format() {
+        let path = test_path("reg_old_format");
+        std::fs::write(&path, r#"[{"node_id": 7, "address": "1.2.3.4:5540", "name": "old"}]"#).unwrap();
+
+        let reg = DeviceRegistry::load(&path).unwrap();
+        let dev = reg.get(7).unwrap();
+        assert_eq!(dev.name, "old");
+        assert_eq!(dev.vendor_id, None);
+        assert_eq!(dev.product_id, None);
+        assert!(dev.endpoints.is_empty());
+    }
+
+    #[test]
+    fn registry_metadata_round_trip() {
+        let path = test_path("reg_metadata");
+
+        let mut reg = DeviceRegistry::load(&path).unwrap();
+        reg.add(Device { node_id: 1, address: "1.2.3.4:5540".into(), name: "light".into(), ..Default::default() }).unwrap();
+        let endpoints = BTreeMap::from([(0, vec![0x1d, 0x28]), (1, vec![0x06, 0x08])]);
+        reg.update_metadata(1, Some(0xfff1), Some(0x8000), endpoints.clone()).unwrap();
+
+        let reg2 = DeviceRegistry::load(&path).unwrap();
+        let dev = reg2.get(1).unwrap();
+        assert_eq!(dev.vendor_id, Some(0xfff1));
+        assert_eq!(dev.product_id, Some(0x8000));
+        assert_eq!(dev.endpoints, endpoints);
+    }
+
+    #[test]
+    fn registry_last_seen_persisted() {
+        let path = test_path("reg_last_seen");
+
+        let mut reg = DeviceRegistry::load(&path).unwrap();
+        reg.add(Device { node_id: 1, address: "1.2.3.4:5540".into(), name: "light".into(), ..Default::default() }).unwrap();
+        assert_eq!(reg.get(1).unwrap().last_seen, None);
+        reg.touch(1, 1_700_000_000).unwrap();
+        assert!(reg.touch(2, 1_700_000_000).is_err());
+
+        // first contact is written at once
+        let reg2 = DeviceRegistry::load(&path).unwrap();
+        assert_eq!(reg2.get(1).unwrap().last_seen, Some(1_700_000_000));
+
+        // contact within the online window stays in memory until drop
+        reg.touch(1, 1_700_000_060).unwrap();
+        assert_eq!(reg.get(1).unwrap().last_seen, Some(1_700_000_060));
+        assert_eq!(DeviceRegistry::load(&path).unwrap().get(1).unwrap().last_seen, Some(1_700_000_000));
+        drop(reg);
+        assert_eq!(DeviceRegistry::load(&path).unwrap().get(1).unwrap().last_seen, Some(1_700_000_060));
+    }
 }
@@ -0,0 +1,158 @@
+//! Diagnostics for devices which do not answer at their address.
+
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, UdpSocket};
+
+/// Outcome of asking the OS for a route to a device address.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Route {
+    /// Packets leave from `local_ip`, on `interface` when it could be determined.
+    Via {
+        local_ip: IpAddr,
+        interface: Option<String>,
+    },
+    /// No route to the address; reason as reported by the OS.
+    None { reason: String },
+}
+
+/// Device at `address` did not answer. Carries what was found out about why,
+/// so the error message points at the likely cause (no route, stale address, ...).
+/// Detect with `err.downcast_ref::<devman::Unreachable>()`.
+#[derive(Debug, Clone)]
+pub struct Unreachable {
+    pub address: String,
+    /// Whether the address is present in the mDNS cache (A/AAAA records).
+    pub in_mdns_cache: bool,
+    /// None when `address` could not be parsed.
+    pub route: Option<Route>,
+}
+
+impl std::fmt::Display for Unreachable {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let ip = parse_address(&self.address).map(|a| a.ip());
+        let family = match ip {
+            Some(IpAddr::V4(_)) => "IPv4",
+            _ => "IPv6",
+        };
+        write!(f, "device at {} unreachable (", self.address)?;
+        match &self.route {
+            Some(Route::None { reason }) => {
+                write!(f, "no {} route", family)?;
+                if let Some(name) = scope_interface(&self.address) {
+                    write!(f, " on interface {}", name)?;
+                }
+                write!(f, ": {}", reason)?;
+            }
+            Some(Route::Via {
+                local_ip,
+                interface,
+            }) => {
+                write!(f, "no answer, routed via ")?;
+                match interface {
+                    Some(name) => write!(f, "{} ({})", name, local_ip)?,
+                    None => write!(f, "{}", local_ip)?,
+                }
+            }
+            None => write!(f, "no answer")?,
+        }
+        if self.in_mdns_cache {
+            write!(f, "; address is advertised via mDNS")
+        } else {
+            write!(
+                f,
+                "; address not in mDNS cache, device may have changed address"
+            )
+        }?;
+        write!(f, ")")
+    }
+}
+impl std::error::Error for Unreachable {}
+
+/// Parse address as stored in registry, e.g. `192.168.1.5:5540` or `[fe80::1%3]:5540`.
+pub(super) fn parse_address(address: &str) -> Option<SocketAddr> {
+    address.parse().ok()
+}
+
+/// Ask the OS which route packets to `addr` would take. Connecting a UDP socket
+/// does not send anything but fails when there is no route.
+pub fn check_route(addr: &SocketAddr) -> Route {
+    let bind: SocketAddr = match addr {
+        SocketAddr::V4(_) => (Ipv4Addr::UNSPECIFIED, 0).into(),
+        SocketAddr::V6(_) => (Ipv6Addr::UNSPECIFIED, 0).into(),
+    };
+    let local_ip = UdpSocket::bind(bind)
+        .and_then(|sock| {
+            sock.connect(addr)?;
+            sock.local_addr()
+        })
+        .map(|a| a.ip());
+    match local_ip {
+        Ok(local_ip) => Route::Via {
+            local_ip,
+            interface: interface_name(|iface| iface.ip() == local_ip),
+        },
+        Err(e) => Route::None {
+            reason: e.to_string(),
+        },
+    }
+}
+
+fn interface_name(pred: impl Fn(&if_addrs::Interface) -> bool) -> Option<String> {
+    if_addrs::get_if_addrs()
+        .ok()?
+        .into_iter()
+        .find(pred)
+        .map(|iface| iface.name)
+}
+
+/// Interface selected by the scope id of a link-local IPv6 address. Falls back to
+/// the index itself when no such interface exists.
+fn scope_interface(address: &str) -> Option<String> {
+    let SocketAddr::V6(v6) = parse_address(address)? else {
+        return None;
+    };
+    if v6.scope_id() == 0 {
+        return None;
+    }
+    Some(
+        interface_name(|iface| iface.index == Some(v6.scope_id()))
+            .unwrap_or_else(|| format!("#{}", v6.scope_id())),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unroutable_address() {
+        // link-local address on interface index which does not exist
+        let address = "[fe80::1%424242]:5540";
+        let addr = parse_address(address).expect("address with zone must parse");
+        let route = check_route(&addr);
+        assert!(matches!(route, Route::None { .. }), "got {:?}", route);
+
+        let err = Unreachable {
+            address: address.to_owned(),
+            in_mdns_cache: false,
+            route: Some(route),
+        };
+        let msg = err.to_string();
+        assert!(
+            msg.starts_with(
+                "device at [fe80::1%424242]:5540 unreachable (no IPv6 route on interface #424242: "
+            ),
+            "got: {}",
+            msg
+        );
+        assert!(msg.contains("not in mDNS cache"), "got: {}", msg);
+    }
+
+    #[test]
+    fn routable_address() {
+        let route = check_route(&"127.0.0.1:5540".parse().unwrap());
+        let Route::Via { local_ip, .. } = route else {
+            panic!("loopback must be routable, got {:?}", route);
+        };
+        assert_eq!(local_ip, IpAddr::V4(Ipv4Addr::LOCALHOST));
+    }
+}
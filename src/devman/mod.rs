@@ -21,8 +21,7 @@
 //! # use matc::devman::{DeviceManager, ManagerConfig};
 //! # #[tokio::main]
 //! # async fn main() -> anyhow::Result<()> {
-//! let config = ManagerConfig { fabric_id: 1000, controller_id: 100,
-//!                              local_address: "0.0.0.0:5555".into() };
+//! let config = ManagerConfig::new(1000, 100, "0.0.0.0:5555");
 //! let dm = DeviceManager::create("./matter-data", config).await?;
 //! let conn = dm.commission("192.168.1.100:5540", 123456, 300, "kitchen light").await?;
 //! # Ok(())
@@ -56,17 +55,40 @@
 
 mod config;
 mod device;
+mod diagnose;
 
-pub use config::ManagerConfig;
-pub use device::Device;
+pub use config::{AddressOrder, ManagerConfig, DEFAULT_COMMISSION_PARALLELISM, DEFAULT_COMMISSION_TIMEOUT_SECS};
+pub use device::{Device, DeviceStatus};
+pub use diagnose::{check_route, Route, Unreachable};
 
+use std::collections::BTreeMap;
+use std::future::Future;
+use std::net::{IpAddr, SocketAddr};
 use std::sync::Arc;
 
 use anyhow::{Context, Result};
 
 use std::time::Duration;
+use tokio_util::sync::CancellationToken;
 
-use crate::{certmanager, controller, discover::{self, MatterDeviceInfo}, fabric::Fabric, mdns2, onboarding, transport};
+use crate::{certmanager, controller, discover::{self, MatterDeviceInfo}, fabric::Fabric, mdns, mdns2, onboarding, transport};
+
+/// A device contacted within this many seconds is reported online even if
+/// its operational mDNS record is not (or no longer) cached.
+const ONLINE_WINDOW_SECS: u64 = 300;
+
+/// Commissioning did not complete within [ManagerConfig::commission_timeout_secs].
+/// Detect with `err.downcast_ref::<devman::Timeout>()`.
+#[derive(Debug, Clone, Copy)]
+pub struct Timeout {
+    pub after: Duration,
+}
+impl std::fmt::Display for Timeout {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "timed out after {:?}", self.after)
+    }
+}
+impl std::error::Error for Timeout {}
 
 pub struct DeviceManager {
     base_path: String,
@@ -92,10 +114,9 @@ impl DeviceManager {
         cm.create_user(config.controller_id)?;
 
         let cm: Arc<dyn certmanager::CertManager> = certmanager::FileCertManager::load(&pem)?;
-        let transport = transport::Transport::new(&config.local_address).await?;
+        let (transport, mdns) = Self::open_network(&config).await?;
         let controller = controller::Controller::new(&cm, &transport, config.fabric_id)?;
         let registry = device::DeviceRegistry::load(&config::devices_path(base_path))?;
-        let mdns = mdns2::MdnsService::new().await?;
 
         Ok(Self {
             base_path: base_path.to_owned(),
@@ -113,10 +134,9 @@ impl DeviceManager {
         let config = config::load_config(base_path)?;
         let pem = config::pem_path(base_path);
         let cm: Arc<dyn certmanager::CertManager> = certmanager::FileCertManager::load(&pem)?;
-        let transport = transport::Transport::new(&config.local_address).await?;
+        let (transport, mdns) = Self::open_network(&config).await?;
         let controller = controller::Controller::new(&cm, &transport, config.fabric_id)?;
         let registry = device::DeviceRegistry::load(&config::devices_path(base_path))?;
-        let mdns = mdns2::MdnsService::new().await?;
 
         Ok(Self {
             base_path: base_path.to_owned(),
@@ -129,6 +149,20 @@ impl DeviceManager {
         })
     }
 
+    /// Transport and mDNS service, restricted to [ManagerConfig::interface] when set.
+    async fn open_network(
+        config: &ManagerConfig,
+    ) -> Result<(Arc<transport::Transport>, Arc<mdns2::MdnsService>)> {
+        let options = transport::TransportOptions {
+            interface: config.interface.clone(),
+            ..Default::default()
+        };
+        let transport =
+            transport::Transport::new_with_options(&config.local_address, &options).await?;
+        let mdns = mdns2::MdnsService::new_on(config.interface.as_deref()).await?;
+        Ok((transport, mdns))
+    }
+
     /// Commission a device and save it to the registry.
     /// Returns an authenticated connection ready for commands.
     pub async fn commission(
@@ -138,45 +172,97 @@ impl DeviceManager {
         node_id: u64,
         name: &str,
     ) -> Result<controller::Connection> {
-        self.commission_at(address, pin, node_id, name, (None, None, None)).await
+        self.commission_cancellable(address, pin, node_id, name, &CancellationToken::new())
+            .await
     }
 
-    /// Commission at a known address with optional advertised MRP intervals
-    /// (SII/SAI/SAT milliseconds), which are applied to the connection and
-    /// persisted in the registry.
-    async fn commission_at(
+    /// Like [DeviceManager::commission], aborted with [Cancelled](crate::Cancelled) as soon
+    /// as `cancel` fires. A fail-safe armed on the device by then is disarmed.
+    pub async fn commission_cancellable(
         &self,
         address: &str,
         pin: u32,
         node_id: u64,
         name: &str,
+        cancel: &CancellationToken,
+    ) -> Result<controller::Connection> {
+        self.commission_any(vec![address.to_owned()], pin, node_id, name, (None, None, None), cancel)
+            .await
+    }
+
+    /// Commission at the first of `addresses` that succeeds, with at most
+    /// [ManagerConfig::commission_parallelism] attempts in flight. Optional advertised
+    /// MRP intervals (SII/SAI/SAT milliseconds) are applied to the connection and
+    /// persisted in the registry together with the winning address.
+    async fn commission_any(
+        &self,
+        addresses: Vec<String>,
+        pin: u32,
+        node_id: u64,
+        name: &str,
         mrp_ms: (Option<u32>, Option<u32>, Option<u32>),
+        cancel: &CancellationToken,
     ) -> Result<controller::Connection> {
-        let conn = self.transport.create_connection(address).await;
-        conn.set_mrp_params(crate::mrp::MrpParameters::from_txt_ms(mrp_ms.0, mrp_ms.1, mrp_ms.2));
-        let connection = self
-            .controller
-            .commission(&conn, pin, node_id, self.config.controller_id)
+        let mrp = crate::mrp::MrpParameters::from_txt_ms(mrp_ms.0, mrp_ms.1, mrp_ms.2);
+        let controller_id = self.config.controller_id;
+        let timeout = self.config.commission_timeout();
+        let (address, connection) =
+            race_addresses(addresses, self.config.commission_parallelism, |address| {
+                let transport = self.transport.clone();
+                let controller = self.controller.clone();
+                let cancel = cancel.clone();
+                async move {
+                    commission_with_timeout(
+                        &transport,
+                        &controller,
+                        &address,
+                        pin,
+                        node_id,
+                        controller_id,
+                        mrp,
+                        timeout,
+                        &cancel,
+                    )
+                    .await
+                }
+            })
             .await?;
 
         let device = Device {
             node_id,
-            address: address.to_owned(),
+            address,
             name: name.to_owned(),
             sii_ms: mrp_ms.0,
             sai_ms: mrp_ms.1,
             sat_ms: mrp_ms.2,
+            last_seen: Some(device::now_secs()),
+            ..Default::default()
         };
         self.registry
             .lock()
             .map_err(|e| anyhow::anyhow!("registry lock: {}", e))?
             .add(device)?;
 
+        // Metadata is informational only; a device that fails these reads is still commissioned.
+        match read_metadata(&connection).await {
+            Ok((vendor_id, product_id, endpoints)) => {
+                if let Err(e) = self.registry
+                    .lock()
+                    .map_err(|e| anyhow::anyhow!("registry lock: {}", e))?
+                    .update_metadata(node_id, Some(vendor_id), Some(product_id), endpoints)
+                {
+                    log::debug!("failed to persist metadata for node {}: {}", node_id, e);
+                }
+            }
+            Err(e) => log::debug!("failed to read metadata from node {}: {:?}", node_id, e),
+        }
+
         Ok(connection)
     }
 
     /// Connect to a previously commissioned device by node ID.
     /// If the stored address fails, automatically re-discovers the device via operational mDNS.
+    /// When the device does not answer at all, the error carries [Unreachable] diagnostics.
     pub async fn connect(&self, node_id: u64) -> Result<controller::Connection> {
         let address = {
             let reg = self.registry.lock().map_err(|e| anyhow::anyhow!("registry lock: {}", e))?;
@@ -188,6 +274,19 @@ impl DeviceManager {
         self.connect_with_rediscovery(node_id, &address).await
     }
 
+    /// Like [DeviceManager::connect], aborted with [Cancelled](crate::Cancelled) as soon
+    /// as `cancel` fires, also while waiting out BUSY or rediscovering the device.
+    pub async fn connect_cancellable(
+        &self,
+        node_id: u64,
+        cancel: &CancellationToken,
+    ) -> Result<controller::Connection> {
+        tokio::select! {
+            res = self.connect(node_id) => res,
+            _ = cancel.cancelled() => Err(crate::Cancelled.into()),
+        }
+    }
+
     /// Connect to a previously commissioned device by friendly name.
     /// If the stored address fails, automatically re-discovers the device via operational mDNS.
     pub async fn connect_by_name(&self, name: &str) -> Result<controller::Connection> {
@@ -215,35 +314,73 @@ impl DeviceManager {
         let mut current_address = address.to_string();
         // Create connection once and reuse across retries; only replace if address changes.
         let mut conn = self.transport.create_connection(&current_address).await;
-        conn.set_mrp_params(stored_mrp);
+        set_mrp_intervals(conn.as_ref(), stored_mrp);
 
         match self.controller.auth_sigma_with_busy_retry(&conn, node_id, self.config.controller_id).await {
-            Ok(ses) => Ok(controller::Connection::from_parts(conn, ses)),
+            Ok(ses) => {
+                self.touch(node_id);
+                Ok(controller::Connection::from_parts(conn, ses))
+            }
             Err(e) => {
                 // Try operational mDNS rediscovery once, then one more attempt.
                 log::info!(
                     "Connection to {} failed ({}), attempting operational rediscovery...",
                     current_address, e
                 );
-                let (new_address, matter_info) = self
+                let (new_address, matter_info) = match self
                     .discover_device_info(node_id, Duration::from_secs(10))
                     .await
-                    .context(format!("rediscovery for node {} after connect failure", node_id))?;
+                {
+                    Ok(v) => v,
+                    Err(rediscovery) => {
+                        let err = e.context(format!(
+                            "rediscovery for node {} after connect failure also failed: {:#}",
+                            node_id, rediscovery
+                        ));
+                        return Err(self.diagnose(err, &current_address).await);
+                    }
+                };
                 current_address = new_address;
                 conn = self.transport.create_connection(&current_address).await;
-                conn.set_mrp_params(matter_info.mrp_params());
-                let ses = self
+                set_mrp_intervals(conn.as_ref(), matter_info.mrp_params());
+                let ses = match self
                     .controller
                     .auth_sigma_with_busy_retry(&conn, node_id, self.config.controller_id)
                     .await
-                    .context(format!(
-                        "connection still failed after rediscovery at {}", current_address
-                    ))?;
+                {
+                    Ok(ses) => ses,
+                    Err(e) => {
+                        let err = e.context(format!(
+                            "connection still failed after rediscovery at {}",
+                            current_address
+                        ));
+                        return Err(self.diagnose(err, &current_address).await);
+                    }
+                };
+                self.touch(node_id);
                 Ok(controller::Connection::from_parts(conn, ses))
             }
         }
     }
 
+    /// When `err` is caused by the device not answering, add [Unreachable] with route
+    /// and mDNS cache diagnostics of `address`. Other errors are returned unchanged.
+    async fn diagnose(&self, err: anyhow::Error, address: &str) -> anyhow::Error {
+        if !err.chain().any(|c| c.is::<crate::NoResponse>()) {
+            return err;
+        }
+        let addr = diagnose::parse_address(address);
+        let in_mdns_cache = match addr {
+            Some(addr) => self.mdns.has_address(&addr.ip()).await,
+            None => false,
+        };
+        err.context(Unreachable {
+            address: address.to_owned(),
+            in_mdns_cache,
+            route: addr.map(|addr| check_route(&addr)),
+        })
+    }
+
     /// Re-run CASE on an existing controller::Connection without tearing down the
     /// transport channel. Delegates to Connection::reauth which pauses the read loop,
     /// calls auth_sigma_with_busy_retry, swaps the session, and restarts the loop.
@@ -254,6 +391,8 @@ impl DeviceManager {
     /// Commission a device using a manual pairing code.
     /// Decodes the pairing code to extract the discriminator, discovers the device via
     /// commissionable mDNS, then commissions it. Returns an authenticated connection.
+    /// Advertised addresses are tried in [ManagerConfig::address_order], up to
+    /// [ManagerConfig::commission_parallelism] of them at once.
     pub async fn commission_with_code(
         &self,
         pairing_code: &str,
@@ -289,26 +428,19 @@ impl DeviceManager {
             matter_info.session_active_interval_ms,
             matter_info.session_active_threshold_ms,
         );
-        let scope_id = matter_info.scope_id;
-        let ips = matter_info.ips;
-        let port = matter_info.port.unwrap_or(5540);
+        let port = matter_info.port_or(self.config.default_port);
 
-        if ips.is_empty() {
+        if matter_info.ips.is_empty() {
             anyhow::bail!("discovered device with discriminator {} but no IPs returned", discriminator);
         }
 
-        let mut last_err = anyhow::anyhow!("no IPs to try");
-        for ip in &ips {
-            let address = crate::discover::addr_string(ip, port, scope_id);
-            match self.commission_at(&address, passcode, node_id, name, mrp_ms).await {
-                Ok(conn) => return Ok(conn),
-                Err(e) => {
-                    log::debug!("Commission attempt at {} failed: {}", address, e);
-                    last_err = e;
-                }
-            }
-        }
-        Err(last_err).context(format!("commissioning failed on all IPs for discriminator {}", discriminator))
+        let addresses = order_ips(&matter_info.ips, &matter_info.source_ip, self.config.address_order)
+            .iter()
+            .map(|ip| matter_info.scoped_addr(*ip, port).to_string())
+            .collect();
+        self.commission_any(addresses, passcode, node_id, name, mrp_ms, &CancellationToken::new())
+            .await
+            .context(format!("commissioning failed on all IPs for discriminator {}", discriminator))
     }
 
     /// Commission a device that is currently advertising over BLE.
@@ -376,11 +508,8 @@ impl DeviceManager {
         node_id: u64,
         timeout: Duration,
     ) -> Result<(String, MatterDeviceInfo)> {
-        let ca_public_key = self.certmanager.get_ca_public_key()?;
-        let fabric = Fabric::new(self.config.fabric_id, 0, &ca_public_key, &self.certmanager.get_ipk_epoch_key());
-        let compressed = fabric.compressed().context("computing compressed fabric ID")?;
-        let instance_name = format!("{}-{:016X}", hex::encode_upper(&compressed), node_id);
-        let expected_target = format!("{}._matter._tcp.local.", instance_name);
+        let instance_name = self.instance_name(node_id)?;
+        let expected_target = format!("{}.", instance_name);
 
         log::info!("Operational discovery for instance {}...", instance_name);
 
@@ -394,8 +523,8 @@ impl DeviceManager {
 
         let ip = matter_info.ips.first()
             .context(format!("discovered {} but no IPs in response", instance_name))?;
-        let port = matter_info.port.unwrap_or(5540);
-        let address = crate::discover::addr_string(ip, port, matter_info.scope_id);
+        let port = matter_info.port_or(self.config.default_port);
+        let address = matter_info.scoped_addr(*ip, port).to_string();
 
         self.update_device_address(node_id, &address)?;
         if let Err(e) = self.registry
@@ -413,6 +542,49 @@ impl DeviceManager {
         Ok((address, matter_info))
     }
 
+    /// Operational mDNS instance name of a node, see [discover::operational_instance_name].
+    fn instance_name(&self, node_id: u64) -> Result<String> {
+        let ca_public_key = self.certmanager.get_ca_public_key()?;
+        let fabric = Fabric::new(self.config.fabric_id, 0, &ca_public_key, &self.certmanager.get_ipk_epoch_key());
+        let compressed = fabric.compressed_id().context("computing compressed fabric ID")?;
+        Ok(discover::operational_instance_name(compressed, node_id))
+    }
+
+    /// Record successful contact with a device. Called automatically on connect
+    /// and commission; applications may call it after successful commands.
+    /// The registry file is only rewritten when the device comes back online; later
+    /// contacts are saved with the next registry change or when the manager is dropped.
+    pub fn mark_seen(&self, node_id: u64) -> Result<()> {
+        self.registry
+            .lock()
+            .map_err(|e| anyhow::anyhow!("registry lock: {}", e))?
+            .touch(node_id, device::now_secs())
+    }
+
+    fn touch(&self, node_id: u64) {
+        if let Err(e) = self.mark_seen(node_id) {
+            log::debug!("failed to update last_seen for node {}: {}", node_id, e);
+        }
+    }
+
+    /// Reachability of a registered device. A device is considered online when
+    /// its operational mDNS SRV record is present in the cache of the shared
+    /// mDNS service, or when it was successfully contacted recently.
+    pub async fn status(&self, node_id: u64) -> Result<DeviceStatus> {
+        let last_seen = {
+            let reg = self.registry.lock().map_err(|e| anyhow::anyhow!("registry lock: {}", e))?;
+            reg.get(node_id)
+                .context(format!("device {} not found in registry", node_id))?
+                .last_seen
+        };
+        let target = format!("{}.", self.instance_name(node_id)?);
+        let advertised = !self.mdns.lookup(&target, mdns::TYPE_SRV).await.is_empty();
+        let recent = last_seen
+            .map(|t| device::now_secs().saturating_sub(t) <= ONLINE_WINDOW_SECS)
+            .unwrap_or(false);
+        Ok(DeviceStatus { online: advertised || recent, last_seen })
+    }
+
     pub async fn discover_commissionable_devices(&self, timeout: Duration) -> Result<Vec<(String, MatterDeviceInfo)>> {
         discover::discover_all(
             &self.mdns,
@@ -494,3 +666,227 @@ impl DeviceManager {
         &self.base_path
     }
 }
+
+/// Create the transport connection and commission over it, giving up with
+/// [Timeout] when the whole sequence takes longer than `timeout` and with
+/// [Cancelled](crate::Cancelled) when `cancel` fires.
+#[allow(clippy::too_many_arguments)]
+async fn commission_with_timeout(
+    transport: &Arc<transport::Transport>,
+    controller: &controller::Controller,
+    address: &str,
+    pin: u32,
+    node_id: u64,
+    controller_id: u64,
+    mrp: crate::mrp::MrpParameters,
+    timeout: Duration,
+    cancel: &CancellationToken,
+) -> Result<controller::Connection> {
+    let fut = async {
+        let conn = transport.create_connection(address).await;
+        set_mrp_intervals(conn.as_ref(), mrp);
+        controller
+            .commission_cancellable(&conn, pin, node_id, controller_id, cancel)
+            .await
+    };
+    match tokio::time::timeout(timeout, fut).await {
+        Ok(res) => res,
+        Err(_) => Err(anyhow::Error::new(Timeout { after: timeout }))
+            .context(format!("commissioning {}", address)),
+    }
+}
+
+/// Apply MRP intervals from mDNS TXT records (or the registry) to `conn`. Devices do
+/// not advertise backoff jitter, so the connection keeps its current one.
+fn set_mrp_intervals(conn: &dyn transport::ConnectionTrait, mrp: crate::mrp::MrpParameters) {
+    let backoff_jitter_percent = conn.mrp_params().backoff_jitter_percent;
+    conn.set_mrp_params(crate::mrp::MrpParameters { backoff_jitter_percent, ..mrp });
+}
+
+/// Order discovered `ips` by `order`. `source` is the address the mDNS response
+/// came from ([MatterDeviceInfo::source_ip]). Addresses a device can't be reached at
+/// are dropped, see [discover::address_rank].
+fn order_ips(ips: &[IpAddr], source: &str, order: AddressOrder) -> Vec<IpAddr> {
+    let mut out: Vec<IpAddr> = ips
+        .iter()
+        .filter(|ip| discover::address_rank(ip).is_some())
+        .copied()
+        .collect();
+    if order == AddressOrder::Advertised {
+        return out;
+    }
+    let source = source
+        .parse::<SocketAddr>()
+        .map(|s| s.ip())
+        .or_else(|_| source.parse::<IpAddr>())
+        .ok();
+    // stable sort keeps advertised order within a rank
+    out.sort_by_key(|ip| match discover::address_rank(ip) {
+        _ if Some(*ip) == source => 0,
+        Some(rank) => rank + 1,
+        None => u8::MAX,
+    });
+    out
+}
+
+/// Run `attempt` for `addresses` in order with at most `parallelism` attempts in flight.
+/// Returns the address and result of the first success, cancelling the attempts still
+/// running, or the error of the last failed attempt.
+async fn race_addresses<T, F, Fut>(
+    addresses: Vec<String>,
+    parallelism: usize,
+    attempt: F,
+) -> Result<(String, T)>
+where
+    F: Fn(String) -> Fut,
+    Fut: Future<Output = Result<T>> + Send + 'static,
+    T: Send + 'static,
+{
+    let mut pending = addresses.into_iter();
+    let mut running = tokio::task::JoinSet::new();
+    let mut last_err = anyhow::anyhow!("no addresses to try");
+    loop {
+        while running.len() < parallelism.max(1) {
+            let Some(address) = pending.next() else { break };
+            let fut = attempt(address.clone());
+            running.spawn(async move { (address, fut.await) });
+        }
+        match running.join_next().await {
+            None => return Err(last_err),
+            Some(Ok((address, Ok(v)))) => return Ok((address, v)),
+            Some(Ok((address, Err(e)))) => {
+                log::debug!("Commission attempt at {} failed: {}", address, e);
+                last_err = e;
+            }
+            Some(Err(e)) => last_err = e.into(),
+        }
+    }
+}
+
+/// Read vendor/product IDs and the endpoint/server-cluster map of a device.
+async fn read_metadata(conn: &controller::Connection) -> Result<(u16, u16, BTreeMap<u16, Vec<u32>>)> {
+    use crate::clusters::codec::{basic_information_cluster, descriptor_cluster};
+
+    let vendor_id = basic_information_cluster::read_vendor_id(conn, 0).await?;
+    let product_id = basic_information_cluster::read_product_id(conn, 0).await?;
+    let mut endpoints = BTreeMap::new();
+    endpoints.insert(0, descriptor_cluster::read_server_list(conn, 0).await?);
+    for ep in descriptor_cluster::read_parts_list(conn, 0).await? {
+        endpoints.insert(ep, descriptor_cluster::read_server_list(conn, ep).await?);
+    }
+    Ok((vendor_id, product_id, endpoints))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn mrp_intervals_keep_backoff_jitter() {
+        let transport = transport::Transport::new("127.0.0.1:0").await.unwrap();
+        let conn = transport.create_connection("127.0.0.1:5540").await;
+        let tuned = crate::mrp::MrpParameters { backoff_jitter_percent: 5, ..Default::default() };
+        conn.set_mrp_params(tuned);
+
+        set_mrp_intervals(conn.as_ref(), crate::mrp::MrpParameters::from_txt_ms(Some(2000), Some(400), None));
+        let params = conn.mrp_params();
+        assert_eq!(params.session_idle_interval, Duration::from_millis(2000));
+        assert_eq!(params.session_active_interval, Duration::from_millis(400));
+        assert_eq!(params.backoff_jitter_percent, 5);
+    }
+
+    #[tokio::test]
+    async fn commission_times_out_on_silent_address() {
+        let certs = certmanager::TestCertManager::new("devman_timeout", &[100]);
+        let cm: Arc<dyn certmanager::CertManager> = certs.cm.clone();
+        let transport = transport::Transport::new("127.0.0.1:0").await.unwrap();
+        let controller = controller::Controller::new(&cm, &transport, 1000).unwrap();
+
+        // bound but never answering - PASE would retransmit for much longer than the timeout
+        let silent = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+        let address = silent.local_addr().unwrap().to_string();
+
+        let timeout = Duration::from_millis(300);
+        let started = std::time::Instant::now();
+        let err = commission_with_timeout(
+            &transport,
+            &controller,
+            &address,
+            123456,
+            300,
+            100,
+            Default::default(),
+            timeout,
+            &CancellationToken::new(),
+        )
+        .await
+        .err()
+        .expect("commissioning a silent address must fail");
+        assert!(started.elapsed() < Duration::from_secs(5));
+        let t = err.downcast_ref::<Timeout>().expect("expected Timeout error");
+        assert_eq!(t.after, timeout);
+    }
+
+    #[test]
+    fn order_ips_prefers_mdns_source() {
+        let parse = |a: &[&str]| a.iter().map(|s| s.parse().unwrap()).collect::<Vec<IpAddr>>();
+        let ips = parse(&["fe80::1", "169.254.3.4", "fd00::1", "192.168.1.7", "::1", "2001:db8::1"]);
+        let ordered = order_ips(&ips, "[2001:db8::1]:5353", AddressOrder::SourceFirst);
+        // unreachable IPv4 link-local and loopback dropped
+        assert_eq!(ordered, parse(&["2001:db8::1", "192.168.1.7", "fd00::1", "fe80::1"]));
+        assert_eq!(
+            order_ips(&ips, "", AddressOrder::Advertised),
+            parse(&["fe80::1", "fd00::1", "192.168.1.7", "2001:db8::1"])
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn race_addresses_first_success_wins() {
+        // unreachable addresses fail only after a long timeout, reachable ones answer quickly
+        let attempt = |address: String| async move {
+            match address.as_str() {
+                "reachable-slow" => {
+                    tokio::time::sleep(Duration::from_secs(3)).await;
+                    Ok(address)
+                }
+                "reachable" => {
+                    tokio::time::sleep(Duration::from_secs(1)).await;
+                    Ok(address)
+                }
+                _ => {
+                    tokio::time::sleep(Duration::from_secs(10)).await;
+                    anyhow::bail!("{} timed out", address)
+                }
+            }
+        };
+        let addresses = |a: &[&str]| a.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+
+        let started = tokio::time::Instant::now();
+        let (address, _) = race_addresses(
+            addresses(&["unreachable-a", "reachable-slow", "reachable", "unreachable-b"]),
+            4,
+            attempt,
+        )
+        .await
+        .unwrap();
+        assert_eq!(address, "reachable");
+        assert_eq!(started.elapsed(), Duration::from_secs(1));
+
+        // serial attempts wait out each unreachable address in order
+        let started = tokio::time::Instant::now();
+        let (address, _) = race_addresses(
+            addresses(&["unreachable-a", "reachable-slow", "reachable"]),
+            1,
+            attempt,
+        )
+        .await
+        .unwrap();
+        assert_eq!(address, "reachable-slow");
+        assert_eq!(started.elapsed(), Duration::from_secs(13));
+
+        let err = race_addresses(addresses(&["unreachable-a", "unreachable-b"]), 2, attempt)
+            .await
+            .unwrap_err();
+        assert!(err.to_string().ends_with("timed out"));
+    }
+}
@@ -4,15 +4,147 @@
 //! If more control over discovery mechanism is required, it may be better to use some external mdns library.
 
 use crate::{mdns::{self, DnsMessage}, mdns2};
-use anyhow::{Context, Result};
-use byteorder::ReadBytesExt;
+use anyhow::Result;
 use std::{
     collections::{BTreeMap, HashMap},
-    io::{Cursor, Read},
-    net::{IpAddr, Ipv4Addr, Ipv6Addr},
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, SocketAddrV4, SocketAddrV6},
     time::Duration,
 };
-use tokio_util::bytes::Buf;
+
+/// Matter operational and commissioning UDP port, used when discovery omits the SRV port.
+pub const DEFAULT_PORT: u16 = 5540;
+
+/// True for IPv6 link-local (fe80::/10), which is only usable together with an interface.
+pub(crate) fn is_link_local_v6(v6: &Ipv6Addr) -> bool {
+    (v6.segments()[0] & 0xffc0) == 0xfe80
+}
+
+/// Preference of discovered `ip` for dialing a device, lower is better: IPv4, then
+/// routable IPv6, then link-local IPv6. None for addresses a device can't be reached at
+/// (loopback, unspecified, multicast, IPv4 link-local).
+pub(crate) fn address_rank(ip: &IpAddr) -> Option<u8> {
+    match ip {
+        IpAddr::V4(v4) if v4.is_loopback() || v4.is_link_local() || v4.is_unspecified() => None,
+        IpAddr::V4(_) => Some(0),
+        IpAddr::V6(v6) if v6.is_loopback() || v6.is_unspecified() || v6.is_multicast() => None,
+        IpAddr::V6(v6) if is_link_local_v6(v6) => Some(2),
+        IpAddr::V6(_) => Some(1),
+    }
+}
+
+/// Address to dial a device at. For link-local IPv6 it carries the interface index
+/// (zone) the address is valid on, as learned from the interface mDNS heard the device on;
+/// the scope is dropped for all other addresses.
+///
+/// Displays as `1.2.3.4:5540`, `[fd00::1]:5540` or `[fe80::1%3]:5540`, the form accepted by
+/// [crate::transport::Transport::create_connection] and stored in the device registry.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ScopedAddr {
+    pub ip: IpAddr,
+    pub port: u16,
+    pub scope_id: Option<u32>,
+}
+
+impl ScopedAddr {
+    pub fn new(ip: IpAddr, port: u16, scope_id: Option<u32>) -> Self {
+        let scope_id = match ip {
+            IpAddr::V6(v6) if is_link_local_v6(&v6) => scope_id.filter(|s| *s != 0),
+            _ => None,
+        };
+        Self { ip, port, scope_id }
+    }
+
+    pub fn is_link_local(&self) -> bool {
+        matches!(self.ip, IpAddr::V6(v6) if is_link_local_v6(&v6))
+    }
+
+    /// Link-local address without interface: the OS can't tell where to send it.
+    pub fn is_missing_scope(&self) -> bool {
+        self.is_link_local() && self.scope_id.is_none()
+    }
+
+    pub fn to_socket_addr(&self) -> SocketAddr {
+        match self.ip {
+            IpAddr::V4(v4) => SocketAddr::V4(SocketAddrV4::new(v4, self.port)),
+            IpAddr::V6(v6) => {
+                SocketAddr::V6(SocketAddrV6::new(v6, self.port, 0, self.scope_id.unwrap_or(0)))
+            }
+        }
+    }
+}
+
+impl From<SocketAddr> for ScopedAddr {
+    fn from(addr: SocketAddr) -> Self {
+        match addr {
+            SocketAddr::V4(v4) => Self::new(IpAddr::V4(*v4.ip()), v4.port(), None),
+            SocketAddr::V6(v6) => Self::new(IpAddr::V6(*v6.ip()), v6.port(), Some(v6.scope_id())),
+        }
+    }
+}
+
+impl From<ScopedAddr> for SocketAddr {
+    fn from(addr: ScopedAddr) -> Self {
+        addr.to_socket_addr()
+    }
+}
+
+impl std::str::FromStr for ScopedAddr {
+    type Err = std::net::AddrParseError;
+
+    /// Parse `ip:port`, with `%<interface index>` zone for link-local IPv6.
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        s.parse::<SocketAddr>().map(Self::from)
+    }
+}
+
+impl std::fmt::Display for ScopedAddr {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match (self.ip, self.scope_id) {
+            (IpAddr::V4(v4), _) => write!(f, "{}:{}", v4, self.port),
+            (IpAddr::V6(v6), Some(idx)) => write!(f, "[{}%{}]:{}", v6, idx, self.port),
+            (IpAddr::V6(v6), None) => write!(f, "[{}]:{}", v6, self.port),
+        }
+    }
+}
+
+/// Why an mDNS response was not turned into [MatterDeviceInfo].
+/// Detect with `err.downcast_ref::<discover::DiscoverError>()`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DiscoverError {
+    /// Response is not about the queried service, e.g. other mDNS traffic.
+    NotMatterService,
+    /// A/AAAA record whose rdata is not an address.
+    MalformedAddress { name: String, len: usize },
+    /// Matter service was found, but the response lacks a record needed to use it.
+    MissingRecord { instance: Option<String>, record: &'static str },
+}
+
+impl DiscoverError {
+    fn malformed_address(rr: &mdns::RR) -> Self {
+        Self::MalformedAddress {
+            name: rr.name.clone(),
+            len: rr.rdata.len(),
+        }
+    }
+}
+
+impl std::fmt::Display for DiscoverError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::NotMatterService => write!(f, "not matter service"),
+            Self::MalformedAddress { name, len } => {
+                write!(f, "malformed address record for {}: {} bytes", name, len)
+            }
+            Self::MissingRecord { instance: Some(instance), record } => {
+                write!(f, "matter service {} without {} record", instance, record)
+            }
+            Self::MissingRecord { instance: None, record } => {
+                write!(f, "matter service without {} record", record)
+            }
+        }
+    }
+}
+impl std::error::Error for DiscoverError {}
 
 #[derive(Debug, Clone)]
 pub enum CommissioningMode {
@@ -21,7 +153,7 @@ pub enum CommissioningMode {
     WithPasscode,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Default)]
 pub struct MatterDeviceInfo {
     pub instance: String,
     pub device: String,
@@ -45,6 +177,10 @@ pub struct MatterDeviceInfo {
     /// scope_id (interface index) for the device's link-local IPv6 addresses: the
     /// interface on which its mDNS reply arrived. Needed to send to `fe80::...`.
     pub scope_id: Option<u32>,
+    /// Compressed fabric id from operational instance name `<compressedfabric>-<nodeid>`.
+    pub compressed_fabric_id: Option<u64>,
+    /// Node id from operational instance name `<compressedfabric>-<nodeid>`.
+    pub node_id: Option<u64>,
 }
 
 impl MatterDeviceInfo {
@@ -58,6 +194,56 @@ impl MatterDeviceInfo {
         )
     }
 
+    /// Discovered port, or `default` when the response carried no SRV record.
+    pub fn port_or(&self, default: u16) -> u16 {
+        self.port.unwrap_or_else(|| {
+            log::info!("no port discovered for {}, using {}", self.instance, default);
+            default
+        })
+    }
+
+    /// Pick address to dial: routable IPv4 first, then non link-local IPv6
+    /// (global or ULA as used by Thread), then link-local IPv6 with its scope id.
+    /// Port defaults to [DEFAULT_PORT] when none was discovered.
+    /// Returns None when there is no usable address.
+    pub fn best_socket_addr(&self) -> Option<SocketAddr> {
+        self.best_socket_addr_or_port(DEFAULT_PORT)
+    }
+
+    /// Like [MatterDeviceInfo::best_socket_addr] with `default_port` used when
+    /// no port was discovered.
+    pub fn best_socket_addr_or_port(&self, default_port: u16) -> Option<SocketAddr> {
+        self.best_scoped_addr(default_port).map(|a| a.to_socket_addr())
+    }
+
+    /// Address [MatterDeviceInfo::best_socket_addr] picks, keeping the scope of a
+    /// link-local IPv6 address explicit.
+    pub fn best_scoped_addr(&self, default_port: u16) -> Option<ScopedAddr> {
+        let ip = self
+            .ips
+            .iter()
+            .filter_map(|ip| address_rank(ip).map(|r| (r, ip)))
+            .min_by_key(|(r, _)| *r)
+            .map(|(_, ip)| *ip)?;
+        Some(self.scoped_addr(ip, self.port_or(default_port)))
+    }
+
+    /// All discovered addresses in advertised order, link-local IPv6 ones with the
+    /// interface the device was discovered on.
+    pub fn scoped_addrs(&self, default_port: u16) -> Vec<ScopedAddr> {
+        let port = self.port_or(default_port);
+        self.ips.iter().map(|ip| self.scoped_addr(*ip, port)).collect()
+    }
+
+    /// `ip` (one of this device's addresses) with the device's scope applied.
+    pub fn scoped_addr(&self, ip: IpAddr, port: u16) -> ScopedAddr {
+        let addr = ScopedAddr::new(ip, port, self.scope_id);
+        if addr.is_missing_scope() {
+            log::debug!("no interface known for link-local {} of {}", ip, self.instance);
+        }
+        addr
+    }
+
     pub fn print_compact(&self) {
         let mut info = format!("{} ({})", self.instance, self.device);
         if let Some(name) = &self.name {
@@ -99,18 +285,43 @@ impl MatterDeviceInfo {
 }
 
 
+/// Operational instance name `<compressedfabric>-<nodeid>._matter._tcp.local`
+/// with both ids as 16 uppercase hex digits.
+pub fn operational_instance_name(compressed_fabric_id: u64, node_id: u64) -> String {
+    format!("{}._matter._tcp.local", operational_instance_label(compressed_fabric_id, node_id))
+}
+
+/// Instance label `<compressedfabric>-<nodeid>` of [operational_instance_name], as a
+/// device registers it for its `_matter._tcp.local` service.
+pub fn operational_instance_label(compressed_fabric_id: u64, node_id: u64) -> String {
+    format!("{:016X}-{:016X}", compressed_fabric_id, node_id)
+}
+
+/// Parse operational instance name `<compressedfabric>-<nodeid>` (16 + 16 hex digits)
+/// into (compressed_fabric_id, node_id). Service suffix like `._matter._tcp.local` is ignored.
+pub fn parse_operational_instance(instance: &str) -> Option<(u64, u64)> {
+    let label = instance.split('.').next()?;
+    let (fabric, node) = label.split_once('-')?;
+    if fabric.len() != 16 || node.len() != 16 {
+        return None;
+    }
+    let fabric = u64::from_str_radix(fabric, 16).ok()?;
+    let node = u64::from_str_radix(node, 16).ok()?;
+    Some((fabric, node))
+}
+
+/// Parse TXT rdata into key/value pairs following RFC 6763 6.3-6.4:
+/// the value is everything after the first `=` (and may contain `=`),
+/// a key without `=` is a boolean attribute stored with an empty value,
+/// empty strings are ignored and only the first occurrence of a key counts.
 pub fn parse_txt_records(data: &[u8]) -> Result<HashMap<String, String>> {
-    let mut cursor = Cursor::new(data);
     let mut out = HashMap::new();
-    while cursor.remaining() > 0 {
-        let len = cursor.read_u8()?;
-        let mut buf = vec![0; len as usize];
-        cursor.read_exact(buf.as_mut_slice())?;
-        let splitstr = std::str::from_utf8(&buf)?.splitn(2, "=");
-        let x: Vec<&str> = splitstr.collect();
-        if x.len() == 2 {
-            out.insert(x[0].to_owned(), x[1].to_owned());
+    for entry in mdns::parse_txt_strings(data) {
+        let (key, value) = entry.split_once('=').unwrap_or((entry.as_str(), ""));
+        if key.is_empty() {
+            continue;
         }
+        out.entry(key.to_owned()).or_insert_with(|| value.to_owned());
     }
     Ok(out)
 }
@@ -140,16 +351,17 @@ pub fn to_matter_info2(msg: &DnsMessage, svc: &str) -> Result<Vec<MatterDeviceIn
         }
     }
     if !matter_service {
-        return Err(anyhow::anyhow!("not matter service"));
+        return Err(DiscoverError::NotMatterService.into());
     }
     let mut services = HashMap::new();
     let mut targets = HashMap::new();
     for additional in &msg.additional {
         if additional.typ == mdns::TYPE_A {
-            let arr: [u8; 4] = match additional.rdata.clone().try_into() {
-                Ok(v) => v,
-                Err(_e) => return Err(anyhow::anyhow!("A record is not correct")),
-            };
+            let arr: [u8; 4] = additional
+                .rdata
+                .clone()
+                .try_into()
+                .map_err(|_| DiscoverError::malformed_address(additional))?;
             let val = IpAddr::V4(Ipv4Addr::from_bits(u32::from_be_bytes(arr)));
             if !targets.contains_key(&additional.name) {
                 targets.insert(additional.name.clone(), Vec::new());
@@ -157,10 +369,11 @@ pub fn to_matter_info2(msg: &DnsMessage, svc: &str) -> Result<Vec<MatterDeviceIn
             targets.get_mut(&additional.name).unwrap().push(val);
         }
         if additional.typ == mdns::TYPE_AAAA {
-            let arr: [u8; 16] = match additional.rdata.clone().try_into() {
-                Ok(v) => v,
-                Err(_e) => return Err(anyhow::anyhow!("AAAA record is not correct")),
-            };
+            let arr: [u8; 16] = additional
+                .rdata
+                .clone()
+                .try_into()
+                .map_err(|_| DiscoverError::malformed_address(additional))?;
             let val = IpAddr::V6(Ipv6Addr::from_bits(u128::from_be_bytes(arr)));
             if !targets.contains_key(&additional.name) {
                 targets.insert(additional.name.clone(), Vec::new());
@@ -185,6 +398,7 @@ pub fn to_matter_info2(msg: &DnsMessage, svc: &str) -> Result<Vec<MatterDeviceIn
                 }
             };
             let target_ip = targets.get(target_name).cloned().unwrap_or_default();
+            let operational = parse_operational_instance(&service_name);
             let mi = MatterDeviceInfo {
                 instance: service_name.clone(),
                 device: remove_string_suffix(target_name, ".local.").to_owned(),
@@ -201,7 +415,9 @@ pub fn to_matter_info2(msg: &DnsMessage, svc: &str) -> Result<Vec<MatterDeviceIn
                 session_active_interval_ms: None,
                 session_active_threshold_ms: None,
                 device_type: None,
-                scope_id: None,
+                scope_id: msg.source_scope(),
+                compressed_fabric_id: operational.map(|(f, _)| f),
+                node_id: operational.map(|(_, n)| n),
             };
             services.insert(service_name, mi);
         }
@@ -236,19 +452,21 @@ pub fn to_matter_info(msg: &DnsMessage, svc: &str) -> Result<MatterDeviceInfo> {
     }
     for additional in &msg.additional {
         if additional.typ == mdns::TYPE_A {
-            let arr: [u8; 4] = match additional.rdata.clone().try_into() {
-                Ok(v) => v,
-                Err(_e) => return Err(anyhow::anyhow!("A record is not correct")),
-            };
+            let arr: [u8; 4] = additional
+                .rdata
+                .clone()
+                .try_into()
+                .map_err(|_| DiscoverError::malformed_address(additional))?;
             let val = IpAddr::V4(Ipv4Addr::from_bits(u32::from_be_bytes(arr)));
             ips.insert(val, true);
             device = Some(remove_string_suffix(&additional.name, ".local."));
         }
         if additional.typ == mdns::TYPE_AAAA {
-            let arr: [u8; 16] = match additional.rdata.clone().try_into() {
-                Ok(v) => v,
-                Err(_e) => return Err(anyhow::anyhow!("AAAA record is not correct")),
-            };
+            let arr: [u8; 16] = additional
+                .rdata
+                .clone()
+                .try_into()
+                .map_err(|_| DiscoverError::malformed_address(additional))?;
             let val = IpAddr::V6(Ipv6Addr::from_bits(u128::from_be_bytes(arr)));
             ips.insert(val, true);
             device = Some(remove_string_suffix(&additional.name, ".local."));
@@ -284,12 +502,21 @@ pub fn to_matter_info(msg: &DnsMessage, svc: &str) -> Result<MatterDeviceInfo> {
     }
 
     if !matter_service {
-        return Err(anyhow::anyhow!("not matter service"));
+        return Err(DiscoverError::NotMatterService.into());
     }
 
+    let instance = service.ok_or(DiscoverError::MissingRecord {
+        instance: None,
+        record: "SRV",
+    })?;
+    let device = device.ok_or_else(|| DiscoverError::MissingRecord {
+        instance: Some(instance.clone()),
+        record: "A/AAAA",
+    })?;
+    let operational = parse_operational_instance(&instance);
     Ok(MatterDeviceInfo {
-        instance: service.context("service name not detected")?,
-        device: device.context("device name not detected")?,
+        instance,
+        device,
         ips: ips.into_keys().collect(),
         name,
         discriminator,
@@ -303,15 +530,28 @@ pub fn to_matter_info(msg: &DnsMessage, svc: &str) -> Result<MatterDeviceInfo> {
         session_active_interval_ms: mrp.1,
         session_active_threshold_ms: mrp.2,
         device_type,
-        scope_id: None,
+        scope_id: msg.source_scope(),
+        compressed_fabric_id: operational.map(|(f, _)| f),
+        node_id: operational.map(|(_, n)| n),
     })
 }
 
-async fn discover_common(timeout: Duration, svc_type: &str) -> Result<Vec<MatterDeviceInfo>> {
+/// Log response dropped by discovery. Unrelated mDNS traffic is expected; a matter
+/// service which can't be used hints at a device answering incompletely.
+fn log_skipped(dns: &DnsMessage, e: &anyhow::Error) {
+    match e.downcast_ref::<DiscoverError>() {
+        Some(DiscoverError::NotMatterService) => {
+            log::trace!("ignoring mdns message from {}: {}", dns.source, e)
+        }
+        _ => log::debug!("skipping matter service from {}: {}", dns.source, e),
+    }
+}
+
+async fn discover_common(timeout: Duration, svc_type: &str, interface: Option<&str>) -> Result<Vec<MatterDeviceInfo>> {
     let stop = tokio_util::sync::CancellationToken::new();
     let (sender, mut receiver) = tokio::sync::mpsc::unbounded_channel::<DnsMessage>();
 
-    mdns::discover(svc_type, mdns::QTYPE_ANY, sender, stop.child_token()).await?;
+    mdns::discover_on(svc_type, mdns::QTYPE_ANY, interface, sender, stop.child_token()).await?;
 
     tokio::spawn(async move {
         tokio::time::sleep(timeout).await;
@@ -325,7 +565,10 @@ async fn discover_common(timeout: Duration, svc_type: &str) -> Result<Vec<Matter
         }
         let info = match to_matter_info(&dns, svc_type) {
             Ok(info) => info,
-            Err(_) => continue,
+            Err(e) => {
+                log_skipped(&dns, &e);
+                continue;
+            }
         };
         out.push(info);
         cache.insert(dns, true);
@@ -335,12 +578,24 @@ async fn discover_common(timeout: Duration, svc_type: &str) -> Result<Vec<Matter
 
 /// Discover commissionable devices using mdns
 pub async fn discover_commissionable(timeout: Duration) -> Result<Vec<MatterDeviceInfo>> {
-    discover_common(timeout, "_matterc._udp.local").await
+    discover_commissionable_on(timeout, None).await
+}
+
+/// Discover commissionable devices using mdns, optionally only on one interface
+/// given by name or address.
+pub async fn discover_commissionable_on(timeout: Duration, interface: Option<&str>) -> Result<Vec<MatterDeviceInfo>> {
+    discover_common(timeout, "_matterc._udp.local", interface).await
 }
 
 /// Discover commissioned devices using mdns
 pub async fn discover_commissioned(timeout: Duration) -> Result<Vec<MatterDeviceInfo>> {
-    discover_common(timeout, "_matter._tcp.local").await
+    discover_commissioned_on(timeout, None).await
+}
+
+/// Discover commissioned devices using mdns, optionally only on one interface
+/// given by name or address.
+pub async fn discover_commissioned_on(timeout: Duration, interface: Option<&str>) -> Result<Vec<MatterDeviceInfo>> {
+    discover_common(timeout, "_matter._tcp.local", interface).await
 }
 
 
@@ -363,7 +618,7 @@ async fn discover_common2(timeout: Duration, svc_type: &str) -> Result<Vec<Matte
         let info = match to_matter_info2(&dns, svc_type) {
             Ok(info) => info,
             Err(e) => {
-                log::trace!("failed to parse mdns message from {}: {:?}", dns.source, e);
+                log_skipped(&dns, &e);
                 continue;
             },
         };
@@ -393,6 +648,10 @@ pub async fn discover_commissioned2(timeout: Duration, device: &Option<String>)
 
 
 
+/// How long discovery waits for SRV/TXT/address records that were not part
+/// of the response announcing a service instance.
+const MISSING_INFO_WAIT: Duration = Duration::from_secs(1);
+
 /// Discover the first device matching a predicate.
 ///
 /// Subscribes to the broadcast channel, sends `query` as an active mDNS lookup, then
@@ -431,6 +690,8 @@ where
                 if name != service_name {
                     continue;
                 }
+                let wait = MISSING_INFO_WAIT.min(deadline.saturating_duration_since(std::time::Instant::now()));
+                mdns.request_missing_info(&target, wait).await;
                 let info = match extract_matter_info(&target, mdns).await {
                     Ok(i) => i,
                     Err(e) => {
@@ -477,6 +738,8 @@ pub async fn discover_all(
                 if name != service_name {
                     continue;
                 }
+                let wait = MISSING_INFO_WAIT.min(deadline.saturating_duration_since(std::time::Instant::now()));
+                mdns.request_missing_info(&target, wait).await;
                 match extract_matter_info(&target, mdns).await {
                     Ok(info) => out.push((target, info)),
                     Err(e) => {
@@ -544,7 +807,7 @@ pub async fn extract_matter_info(target: &str, mdns: &mdns2::MdnsService) -> Res
     let mut scope_id = None;
     for ip in &ips {
         if let IpAddr::V6(v6) = ip {
-            if (v6.segments()[0] & 0xffc0) == 0xfe80 {
+            if is_link_local_v6(v6) {
                 scope_id = mdns.scope_for(v6).await;
                 if scope_id.is_some() {
                     break;
@@ -552,6 +815,7 @@ pub async fn extract_matter_info(target: &str, mdns: &mdns2::MdnsService) -> Res
             }
         }
     }
+    let operational = parse_operational_instance(target);
     Ok(MatterDeviceInfo {
         name,
         instance: target.trim_end_matches('.').to_owned(),
@@ -569,21 +833,56 @@ pub async fn extract_matter_info(target: &str, mdns: &mdns2::MdnsService) -> Res
         session_active_threshold_ms: sat,
         device_type,
         scope_id,
+        compressed_fabric_id: operational.map(|(f, _)| f),
+        node_id: operational.map(|(_, n)| n),
     })
 }
 
+/// Find the discovered commissionable device a scanned QR (or manual) code belongs to and
+/// return the address to dial.
+///
+/// Discriminator must match (only top 4 bits for short discriminators of manual codes).
+/// Vendor/product id must not contradict the code when both sides have them; devices confirming
+/// them, and devices in commissioning mode, are preferred. Devices without usable address are skipped.
+pub fn match_qr_to_discovered(
+    payload: &crate::onboarding::OnboardingInfo,
+    devices: &[MatterDeviceInfo],
+) -> Option<SocketAddr> {
+    let id_matches = |advertised: &Option<String>, expected: Option<u16>| -> Option<bool> {
+        let advertised = advertised.as_ref()?.parse::<u16>().ok()?;
+        Some(advertised == expected?)
+    };
+    devices
+        .iter()
+        .filter_map(|d| {
+            let mut disc = d.discriminator.as_ref()?.parse::<u16>().ok()?;
+            if payload.is_short_discriminator {
+                disc &= 0xf00;
+            }
+            if disc != payload.discriminator {
+                return None;
+            }
+            let vid = id_matches(&d.vendor_id, payload.vendor_id);
+            let pid = id_matches(&d.product_id, payload.product_id);
+            if vid == Some(false) || pid == Some(false) {
+                return None;
+            }
+            let commissioning = matches!(
+                d.commissioning_mode,
+                Some(CommissioningMode::Yes | CommissioningMode::WithPasscode)
+            );
+            let score = vid.is_some() as u8 + pid.is_some() as u8 + commissioning as u8;
+            Some((score, d.best_socket_addr()?))
+        })
+        .max_by_key(|(score, _)| *score)
+        .map(|(_, addr)| addr)
+}
+
 /// Build the address string for a UDP connection. For link-local IPv6 it appends
 /// the zone `%<scope_id>` (interface index); without it the OS cannot send to
-/// `fe80::...`.
+/// `fe80::...`. See [ScopedAddr].
 pub fn addr_string(ip: &IpAddr, port: u16, scope_id: Option<u32>) -> String {
-    match ip {
-        IpAddr::V6(v6) if (v6.segments()[0] & 0xffc0) == 0xfe80 => match scope_id {
-            Some(idx) => format!("[{}%{}]:{}", v6, idx, port),
-            None => format!("[{}]:{}", v6, port),
-        },
-        IpAddr::V6(v6) => format!("[{}]:{}", v6, port),
-        IpAddr::V4(v4) => format!("{}:{}", v4, port),
-    }
+    ScopedAddr::new(*ip, port, scope_id).to_string()
 }
 #[cfg(test)]
 mod tests {
@@ -610,4 +909,247 @@ mod tests {
         let rec = parse_txt_records(&txt_rdata(&["D=840"])).unwrap();
         assert_eq!(parse_mrp_txt(&rec), (None, None, None));
     }
+
+    #[test]
+    fn test_parse_txt_multi_string() {
+        let mut data = txt_rdata(&["D=840", "", "ICD", "T=1", "PI=a=b", "=orphan", "D=999"]);
+        data.extend_from_slice(&txt_rdata(&["VP=65521+32769"]));
+        let rec = parse_txt_records(&data).unwrap();
+        assert_eq!(rec.get("D").map(String::as_str), Some("840"));
+        assert_eq!(rec.get("ICD").map(String::as_str), Some(""));
+        assert_eq!(rec.get("T").map(String::as_str), Some("1"));
+        assert_eq!(rec.get("PI").map(String::as_str), Some("a=b"));
+        assert_eq!(rec.get("VP").map(String::as_str), Some("65521+32769"));
+        assert_eq!(rec.len(), 5);
+
+        // single zero-length string is an empty TXT record
+        assert!(parse_txt_records(&[0]).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_malformed_service() {
+        let rr = |name: &str, typ: u16, rdata: Vec<u8>| mdns::RR {
+            name: name.to_owned(),
+            typ,
+            class: 1,
+            ttl: 120,
+            rdata: rdata.clone(),
+            target: None,
+            data: mdns::RRData::Unknown(rdata),
+        };
+        let msg = |answers, additional| DnsMessage {
+            source: "192.168.1.5:5353".parse().unwrap(),
+            transaction: 0,
+            flags: 0x8400,
+            queries: Vec::new(),
+            answers,
+            authority: Vec::new(),
+            additional,
+        };
+        let svc = "_matterc._udp.local";
+        let ptr = || rr("_matterc._udp.local.", mdns::TYPE_PTR, Vec::new());
+        let srv = || {
+            let mut srv = rr("ABCD._matterc._udp.local.", mdns::TYPE_SRV, vec![0, 0, 0, 0, 0x15, 0xa4]);
+            srv.target = Some("host.local.".into());
+            srv
+        };
+        let err_of = |m: &DnsMessage| {
+            to_matter_info(m, svc)
+                .unwrap_err()
+                .downcast::<DiscoverError>()
+                .expect("DiscoverError")
+        };
+
+        let other = msg(vec![rr("_http._tcp.local.", mdns::TYPE_PTR, Vec::new())], Vec::new());
+        assert_eq!(err_of(&other), DiscoverError::NotMatterService);
+
+        let bad_a = msg(vec![ptr()], vec![srv(), rr("host.local.", mdns::TYPE_A, vec![192, 168, 1])]);
+        assert_eq!(
+            err_of(&bad_a),
+            DiscoverError::MalformedAddress { name: "host.local.".into(), len: 3 }
+        );
+        assert!(to_matter_info2(&bad_a, svc).is_err());
+
+        // service advertised without address records
+        let no_addr = msg(vec![ptr()], vec![srv()]);
+        let err = err_of(&no_addr);
+        assert_eq!(
+            err,
+            DiscoverError::MissingRecord { instance: Some("ABCD".into()), record: "A/AAAA" }
+        );
+        assert_eq!(err.to_string(), "matter service ABCD without A/AAAA record");
+
+        let mut ok = no_addr;
+        ok.additional.push(rr("host.local.", mdns::TYPE_A, vec![192, 168, 1, 5]));
+        let info = to_matter_info(&ok, svc).unwrap();
+        assert_eq!(info.instance, "ABCD");
+        assert_eq!(info.port, Some(5540));
+    }
+
+    #[test]
+    fn test_parse_operational_instance() {
+        assert_eq!(
+            parse_operational_instance("2906C908D115D362-8FC7772401CD0696"),
+            Some((0x2906C908D115D362, 0x8FC7772401CD0696))
+        );
+        assert_eq!(
+            parse_operational_instance("2906c908d115d362-0000000000000002._matter._tcp.local."),
+            Some((0x2906C908D115D362, 2))
+        );
+        assert_eq!(parse_operational_instance("2906C908D115D362-2"), None);
+        assert_eq!(parse_operational_instance("ABCDEF0123456789"), None);
+        assert_eq!(parse_operational_instance("XX06C908D115D362-8FC7772401CD0696"), None);
+    }
+
+    #[test]
+    fn test_operational_instance_name() {
+        assert_eq!(
+            operational_instance_name(0x2906C908D115D362, 2),
+            "2906C908D115D362-0000000000000002._matter._tcp.local"
+        );
+        assert_eq!(operational_instance_label(0x2906C908D115D362, 2), "2906C908D115D362-0000000000000002");
+        let name = operational_instance_name(0x0a0b0c0d0e0f1011, 0x8FC7772401CD0696);
+        assert_eq!(name, "0A0B0C0D0E0F1011-8FC7772401CD0696._matter._tcp.local");
+        assert_eq!(parse_operational_instance(&name), Some((0x0a0b0c0d0e0f1011, 0x8FC7772401CD0696)));
+    }
+
+    #[test]
+    fn test_best_socket_addr() {
+        let info = |ips: &[&str]| MatterDeviceInfo {
+            ips: ips.iter().map(|ip| ip.parse().unwrap()).collect(),
+            port: Some(5540),
+            scope_id: Some(3),
+            ..Default::default()
+        };
+        let mixed = info(&["fe80::1", "fd00::5", "127.0.0.1", "192.168.1.5"]);
+        assert_eq!(mixed.best_socket_addr(), Some("192.168.1.5:5540".parse().unwrap()));
+
+        let thread = info(&["fe80::1", "fd00::5", "2001:db8::5"]);
+        assert_eq!(thread.best_socket_addr(), Some("[fd00::5]:5540".parse().unwrap()));
+
+        let link_local = info(&["169.254.3.3", "fe80::1"]);
+        assert_eq!(
+            link_local.best_socket_addr(),
+            Some(SocketAddr::V6(SocketAddrV6::new("fe80::1".parse().unwrap(), 5540, 0, 3)))
+        );
+
+        assert_eq!(info(&["::1"]).best_socket_addr(), None);
+        let mut no_port = info(&["192.168.1.5"]);
+        no_port.port = None;
+        assert_eq!(no_port.best_socket_addr(), Some("192.168.1.5:5540".parse().unwrap()));
+        assert_eq!(
+            no_port.best_socket_addr_or_port(5541),
+            Some("192.168.1.5:5541".parse().unwrap())
+        );
+        assert_eq!(no_port.port_or(5541), 5541);
+        assert_eq!(info(&["192.168.1.5"]).port_or(5541), 5540);
+    }
+
+    #[test]
+    fn test_scoped_addr() {
+        let ll: IpAddr = "fe80::1".parse().unwrap();
+        let scoped = ScopedAddr::new(ll, 5540, Some(3));
+        assert_eq!(scoped.to_string(), "[fe80::1%3]:5540");
+        assert_eq!(
+            scoped.to_socket_addr(),
+            SocketAddr::V6(SocketAddrV6::new("fe80::1".parse().unwrap(), 5540, 0, 3))
+        );
+        assert_eq!("[fe80::1%3]:5540".parse::<ScopedAddr>().unwrap(), scoped);
+        assert_eq!(ScopedAddr::from(scoped.to_socket_addr()), scoped);
+
+        // zone only applies to link-local addresses
+        let global = ScopedAddr::new("fd00::5".parse().unwrap(), 5540, Some(3));
+        assert_eq!(global.scope_id, None);
+        assert_eq!(global.to_string(), "[fd00::5]:5540");
+        let v4 = ScopedAddr::new("192.168.1.5".parse().unwrap(), 5540, Some(3));
+        assert_eq!(v4.to_string(), "192.168.1.5:5540");
+
+        let unscoped = ScopedAddr::new(ll, 5540, Some(0));
+        assert!(unscoped.is_missing_scope());
+        assert_eq!(unscoped.to_string(), "[fe80::1]:5540");
+        assert_eq!(addr_string(&ll, 5540, Some(3)), "[fe80::1%3]:5540");
+
+        let info = MatterDeviceInfo {
+            ips: vec!["192.168.1.5".parse().unwrap(), ll],
+            scope_id: Some(3),
+            ..Default::default()
+        };
+        let addrs: Vec<String> = info.scoped_addrs(5541).iter().map(|a| a.to_string()).collect();
+        assert_eq!(addrs, ["192.168.1.5:5541", "[fe80::1%3]:5541"]);
+    }
+
+    #[test]
+    fn test_scope_from_mdns_source() {
+        let rr = |name: &str, typ: u16, rdata: Vec<u8>| mdns::RR {
+            name: name.to_owned(),
+            typ,
+            class: 1,
+            ttl: 120,
+            rdata: rdata.clone(),
+            target: None,
+            data: mdns::RRData::Unknown(rdata),
+        };
+        let ll: Ipv6Addr = "fe80::1".parse().unwrap();
+        let mut srv = rr("ABCD._matterc._udp.local.", mdns::TYPE_SRV, vec![0, 0, 0, 0, 0x15, 0xa4]);
+        srv.target = Some("host.local.".into());
+        let mut msg = DnsMessage {
+            source: "[fe80::1%7]:5353".parse().unwrap(),
+            transaction: 0,
+            flags: 0x8400,
+            queries: Vec::new(),
+            answers: vec![rr("_matterc._udp.local.", mdns::TYPE_PTR, Vec::new())],
+            authority: Vec::new(),
+            additional: vec![srv, rr("host.local.", mdns::TYPE_AAAA, ll.octets().to_vec())],
+        };
+        assert_eq!(msg.source_scope(), Some(7));
+        let info = to_matter_info(&msg, "_matterc._udp.local").unwrap();
+        assert_eq!(info.scope_id, Some(7));
+        assert_eq!(info.best_scoped_addr(DEFAULT_PORT).unwrap().to_string(), "[fe80::1%7]:5540");
+        let info2 = to_matter_info2(&msg, "_matterc._udp.local").unwrap();
+        assert_eq!(info2[0].scope_id, Some(7));
+
+        // reply over IPv4 says nothing about the IPv6 interface
+        msg.source = "192.168.1.5:5353".parse().unwrap();
+        assert_eq!(to_matter_info(&msg, "_matterc._udp.local").unwrap().scope_id, None);
+    }
+
+    #[test]
+    fn test_match_qr_to_discovered() {
+        let dev = |ip: &str, disc: &str, vid: Option<&str>, pid: Option<&str>| MatterDeviceInfo {
+            ips: vec![ip.parse().unwrap()],
+            port: Some(5540),
+            discriminator: Some(disc.to_owned()),
+            vendor_id: vid.map(str::to_owned),
+            product_id: pid.map(str::to_owned),
+            commissioning_mode: Some(CommissioningMode::Yes),
+            ..Default::default()
+        };
+        let devices = vec![
+            dev("192.168.1.10", "3840", Some("65521"), Some("32768")),
+            dev("192.168.1.11", "100", None, None),
+            dev("192.168.1.12", "100", Some("65521"), Some("32769")),
+            dev("192.168.1.13", "100", Some("4937"), Some("1")),
+        ];
+        // same default vendor/product/discriminator as onboarding::tests::test_qr_decode
+        let qr = crate::onboarding::decode_qr_payload("MT:-24J04QI14G6Q663000").unwrap();
+        assert_eq!(
+            match_qr_to_discovered(&qr, &devices),
+            Some("192.168.1.12:5540".parse().unwrap())
+        );
+        // device not advertising VP is accepted on discriminator alone
+        assert_eq!(
+            match_qr_to_discovered(&qr, &devices[1..2]),
+            Some("192.168.1.11:5540".parse().unwrap())
+        );
+        // same discriminator, other vendor
+        assert_eq!(match_qr_to_discovered(&qr, &devices[3..]), None);
+
+        let manual = crate::onboarding::decode_manual_pairing_code("34970112332").unwrap();
+        assert_eq!(
+            match_qr_to_discovered(&manual, &devices),
+            Some("192.168.1.10:5540".parse().unwrap())
+        );
+        let other = crate::onboarding::decode_manual_pairing_code("2585-103-3238").unwrap();
+        assert_eq!(match_qr_to_discovered(&other, &devices), None);
+    }
 }
@@ -37,6 +37,12 @@ impl Fabric {
         )
     }
 
+    /// Compressed fabric identifier as used in operational instance names
+    pub fn compressed_id(&self) -> Result<u64> {
+        let c = self.compressed()?;
+        Ok(u64::from_be_bytes(c.as_slice().try_into()?))
+    }
+
     /// Integrity Protection Key
     pub fn signed_ipk(&self) -> Result<Vec<u8>> {
         crate::util::cryptoutil::hkdf_sha256(
@@ -1,9 +1,12 @@
 //! Typed Interaction Model report layer.
 //!
-//! Parses ReportData and SubscribeResponse messages into typed structures,
+//! Parses ReportData, SubscribeResponse and InvokeResponse messages into typed structures,
 //! replacing manual TLV path navigation. Used by [crate::controller::Connection]
 //! for chunked report reassembly (reads and subscriptions) and by the
 //! subscription event stream ([crate::controller::Subscription]).
+//! [ReportAccumulator] folds successive reports into consolidated attribute state.
+
+use std::collections::BTreeMap;
 
 use anyhow::Result;
 
@@ -16,6 +19,25 @@ pub struct AttributePath {
     pub endpoint: Option<u16>,
     pub cluster: Option<u32>,
     pub attribute: Option<u32>,
+    /// ListIndex of the path; None when the report carries the whole attribute value.
+    pub list_index: Option<ListIndex>,
+}
+
+/// List operation encoded in AttributePathIB ListIndex.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ListIndex {
+    /// Null ListIndex - data is one item appended to the list (AppendItem).
+    Append,
+    /// Numeric ListIndex - data replaces the item at index, null data deletes it.
+    Item(u16),
+}
+
+fn parse_list_index(ib: &TlvItem, path: &[u8]) -> Option<ListIndex> {
+    match ib.get(path)? {
+        TlvItemValue::Nil() => Some(ListIndex::Append),
+        TlvItemValue::Int(i) => Some(ListIndex::Item(*i as u16)),
+        _ => None,
+    }
 }
 
 /// Payload of a single attribute report - either a value or a status code.
@@ -111,6 +133,7 @@ fn parse_attribute_report_ib(ib: &TlvItem) -> Option<AttributeReport> {
                 endpoint: ib.get_u16(&[1, 1, 2]),
                 cluster: ib.get_u32(&[1, 1, 3]),
                 attribute: ib.get_u32(&[1, 1, 4]),
+                list_index: parse_list_index(ib, &[1, 1, 5]),
             },
             data: AttributeData::Value(data.clone()),
             data_version: ib.get_u32(&[1, 0]),
@@ -121,6 +144,7 @@ fn parse_attribute_report_ib(ib: &TlvItem) -> Option<AttributeReport> {
                 endpoint: ib.get_u16(&[0, 0, 2]),
                 cluster: ib.get_u32(&[0, 0, 3]),
                 attribute: ib.get_u32(&[0, 0, 4]),
+                list_index: parse_list_index(ib, &[0, 0, 5]),
             },
             data: AttributeData::Status {
                 status,
@@ -131,6 +155,151 @@ fn parse_attribute_report_ib(ib: &TlvItem) -> Option<AttributeReport> {
     }
 }
 
+/// Command path from a CommandPathIB.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CommandPath {
+    pub endpoint: Option<u16>,
+    pub cluster: Option<u32>,
+    pub command: Option<u32>,
+}
+
+/// One decoded InvokeResponseIB - response command fields or a status.
+#[derive(Debug, Clone, PartialEq)]
+pub enum InvokeResponse {
+    Data { path: CommandPath, fields: TlvItemValue },
+    Status { path: CommandPath, status: u8, cluster_status: Option<u8> },
+}
+
+/// Parse all InvokeResponseIBs of an InvokeResponseMessage. Batched invokes and
+/// some commands produce more than one; entries of unknown shape are skipped.
+pub fn parse_invoke_responses(tlv: &TlvItem) -> Vec<InvokeResponse> {
+    let Some(TlvItemValue::List(list)) = tlv.get(&[1]) else {
+        return Vec::new();
+    };
+    let path = |ib: &TlvItem, at: u8| CommandPath {
+        endpoint: ib.get_u16(&[at, 0, 0]),
+        cluster: ib.get_u32(&[at, 0, 1]),
+        command: ib.get_u32(&[at, 0, 2]),
+    };
+    list.iter()
+        .filter_map(|ib| {
+            if let Some(fields) = ib.get(&[0, 1]) {
+                Some(InvokeResponse::Data { path: path(ib, 0), fields: fields.clone() })
+            } else {
+                ib.get_u8(&[1, 1, 0]).map(|status| InvokeResponse::Status {
+                    path: path(ib, 1),
+                    status,
+                    cluster_status: ib.get_u8(&[1, 1, 1]),
+                })
+            }
+        })
+        .collect()
+}
+
+/// Current value (or error status) of one attribute in [ReportAccumulator].
+#[derive(Debug, Clone, PartialEq)]
+pub struct AttributeState {
+    pub data: AttributeData,
+    pub data_version: Option<u32>,
+}
+
+/// Consolidated attribute state built from successive attribute reports -
+/// chunks of one read, or priming report and updates of a subscription.
+///
+/// Reports without ListIndex replace the attribute value. List item
+/// operations are applied to the stored list: AppendItem (null ListIndex)
+/// appends, numeric ListIndex replaces the item, or deletes it when data is null.
+/// Reports with incomplete path are ignored. Event reports are collected in order.
+#[derive(Debug, Clone, Default)]
+pub struct ReportAccumulator {
+    attributes: BTreeMap<(u16, u32, u32), AttributeState>,
+    events: Vec<EventReport>,
+}
+
+impl ReportAccumulator {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Apply all attribute and event reports of one ReportData (chunk).
+    pub fn ingest(&mut self, report: &ReportData) {
+        for a in &report.attribute_reports {
+            self.ingest_attribute(a);
+        }
+        self.events.extend(report.event_reports.iter().cloned());
+    }
+
+    /// Apply one attribute report.
+    pub fn ingest_attribute(&mut self, report: &AttributeReport) {
+        let (Some(endpoint), Some(cluster), Some(attribute)) =
+            (report.path.endpoint, report.path.cluster, report.path.attribute)
+        else {
+            log::debug!("ignoring attribute report with incomplete path {:?}", report.path);
+            return;
+        };
+        let key = (endpoint, cluster, attribute);
+        let value = match (&report.path.list_index, &report.data) {
+            (Some(index), AttributeData::Value(v)) => (index, v),
+            _ => {
+                self.attributes.insert(key, AttributeState {
+                    data: report.data.clone(),
+                    data_version: report.data_version,
+                });
+                return;
+            }
+        };
+        let state = self.attributes.entry(key).or_insert_with(|| AttributeState {
+            data: AttributeData::Value(TlvItemValue::List(Vec::new())),
+            data_version: None,
+        });
+        if !matches!(state.data, AttributeData::Value(TlvItemValue::List(_))) {
+            state.data = AttributeData::Value(TlvItemValue::List(Vec::new()));
+        }
+        if report.data_version.is_some() {
+            state.data_version = report.data_version;
+        }
+        let AttributeData::Value(TlvItemValue::List(items)) = &mut state.data else {
+            return;
+        };
+        match value {
+            (ListIndex::Append, v) => items.push(TlvItem { tag: 0, value: v.clone() }),
+            (ListIndex::Item(i), v) => {
+                let i = *i as usize;
+                if i >= items.len() {
+                    log::debug!("list index {} out of range for {:?}", i, key);
+                } else if matches!(v, TlvItemValue::Nil()) {
+                    items.remove(i);
+                } else {
+                    items[i].value = v.clone();
+                }
+            }
+        }
+    }
+
+    /// State of one attribute.
+    pub fn attribute(&self, endpoint: u16, cluster: u32, attribute: u32) -> Option<&AttributeState> {
+        self.attributes.get(&(endpoint, cluster, attribute))
+    }
+
+    /// Value of one attribute; None when unknown or reported with error status.
+    pub fn get(&self, endpoint: u16, cluster: u32, attribute: u32) -> Option<&TlvItemValue> {
+        match &self.attribute(endpoint, cluster, attribute)?.data {
+            AttributeData::Value(v) => Some(v),
+            AttributeData::Status { .. } => None,
+        }
+    }
+
+    /// All attributes ordered by (endpoint, cluster, attribute).
+    pub fn attributes(&self) -> impl Iterator<Item = (&(u16, u32, u32), &AttributeState)> {
+        self.attributes.iter()
+    }
+
+    /// Event reports received so far.
+    pub fn events(&self) -> &[EventReport] {
+        &self.events
+    }
+}
+
 /// Decoded SubscribeResponse message.
 #[derive(Debug, Clone)]
 pub struct SubscribeResponse {
@@ -201,6 +370,64 @@ mod tests {
         assert_eq!(rep.data_version, Some(0));
     }
 
+    #[test]
+    fn test_parse_captured_data_version() {
+        // ReportData for OnOff (endpoint 1) read captured from a device
+        let data = hex::decode(concat!(
+            "15",
+            "3601",
+            "1535012600b3a1c2d0",
+            "3701240201240306240400182902",
+            "1818",
+            "18",
+            "2904",
+            "24ff0b",
+            "18"
+        ))
+        .unwrap();
+        let tlv = tlv::decode_tlv(&data).unwrap();
+        let rd = ReportData::parse(&tlv).unwrap();
+        assert!(rd.suppress_response);
+        let rep = &rd.attribute_reports[0];
+        assert_eq!(rep.data_version, Some(0xd0c2a1b3));
+        assert_eq!(rep.path.cluster, Some(6));
+        assert_eq!(rep.data, AttributeData::Value(TlvItemValue::Bool(true)));
+    }
+
+    #[test]
+    fn test_parse_two_invoke_responses() {
+        // InvokeResponse with CommandDataIB followed by failing CommandStatusIB
+        let data = hex::decode(concat!(
+            "15",
+            "2800",
+            "3601",
+            "15", "3500", "3700240001240106240202", "18", "3501240005", "18", "18", "18",
+            "15", "3501", "3700240001240106240203", "18", "3501240001240104", "18", "18", "18",
+            "18",
+            "24ff0b",
+            "18"
+        ))
+        .unwrap();
+        let tlv = tlv::decode_tlv(&data).unwrap();
+        let responses = parse_invoke_responses(&tlv);
+        assert_eq!(responses.len(), 2);
+        let path = |command| CommandPath { endpoint: Some(1), cluster: Some(6), command: Some(command) };
+        match &responses[0] {
+            InvokeResponse::Data { path: p, fields } => {
+                assert_eq!(*p, path(2));
+                let fields = TlvItem { tag: 0, value: fields.clone() };
+                assert_eq!(fields.get_int(&[0]), Some(5));
+            }
+            other => panic!("expected data, got {:?}", other),
+        }
+        assert_eq!(
+            responses[1],
+            InvokeResponse::Status { path: path(3), status: 1, cluster_status: Some(4) }
+        );
+        // failing status of the second entry is not hidden by the first one
+        assert_eq!(crate::messages::parse_im_invoke_resp(&tlv).unwrap(), (1, 4));
+    }
+
     #[test]
     fn test_parse_status_report() {
         let msg = device_messages::im_report_data_status(7, 1, 6, 0, 0x86, -1).unwrap();
@@ -326,6 +553,120 @@ mod tests {
         assert_eq!(merged.attribute_reports[1].path.endpoint, Some(2));
     }
 
+    fn report(attribute: u32, list_index: Option<ListIndex>, value: TlvItemValue) -> AttributeReport {
+        AttributeReport {
+            path: AttributePath {
+                endpoint: Some(0),
+                cluster: Some(0x1f),
+                attribute: Some(attribute),
+                list_index,
+            },
+            data: AttributeData::Value(value),
+            data_version: Some(3),
+        }
+    }
+
+    fn list_ints(v: Option<&TlvItemValue>) -> Vec<u64> {
+        match v {
+            Some(TlvItemValue::List(items)) => items
+                .iter()
+                .map(|i| match i.value {
+                    TlvItemValue::Int(n) => n,
+                    _ => panic!("unexpected item {:?}", i.value),
+                })
+                .collect(),
+            other => panic!("not a list: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_parse_list_index() {
+        let mut buf = tlv::TlvBuffer::new();
+        buf.write_anon_struct().unwrap();
+        buf.write_array(1).unwrap();
+        buf.write_anon_struct().unwrap();
+        buf.write_struct(1).unwrap();
+        buf.write_uint32(0, 9).unwrap();
+        buf.write_list(1).unwrap();
+        buf.write_uint16(2, 0).unwrap();
+        buf.write_uint32(3, 0x1f).unwrap();
+        buf.write_uint32(4, 0).unwrap();
+        buf.write_raw(&[0x34, 0x05]).unwrap(); // ListIndex: null
+        buf.write_struct_end().unwrap();
+        buf.write_uint8(2, 7).unwrap();
+        buf.write_struct_end().unwrap();
+        buf.write_struct_end().unwrap();
+        buf.write_struct_end().unwrap();
+        buf.write_struct_end().unwrap();
+        let tlv = tlv::decode_tlv(&buf.data).unwrap();
+        let rd = ReportData::parse(&tlv).unwrap();
+        assert_eq!(rd.attribute_reports[0].path.list_index, Some(ListIndex::Append));
+        assert_eq!(rd.attribute_reports[0].data_version, Some(9));
+    }
+
+    #[test]
+    fn test_accumulator_list_operations() {
+        // ACL subscription: priming report replaces the list, later chunks
+        // and updates append, replace and delete single entries.
+        let mut acc = ReportAccumulator::new();
+        acc.ingest_attribute(&report(0, None, TlvItemValue::List(Vec::new())));
+        acc.ingest_attribute(&report(0, Some(ListIndex::Append), TlvItemValue::Int(1)));
+        acc.ingest_attribute(&report(0, Some(ListIndex::Append), TlvItemValue::Int(2)));
+        acc.ingest_attribute(&report(0, Some(ListIndex::Append), TlvItemValue::Int(3)));
+        assert_eq!(list_ints(acc.get(0, 0x1f, 0)), vec![1, 2, 3]);
+
+        acc.ingest_attribute(&report(0, Some(ListIndex::Item(1)), TlvItemValue::Int(20)));
+        assert_eq!(list_ints(acc.get(0, 0x1f, 0)), vec![1, 20, 3]);
+        acc.ingest_attribute(&report(0, Some(ListIndex::Item(0)), TlvItemValue::Nil()));
+        assert_eq!(list_ints(acc.get(0, 0x1f, 0)), vec![20, 3]);
+        acc.ingest_attribute(&report(0, Some(ListIndex::Item(9)), TlvItemValue::Int(5)));
+        assert_eq!(list_ints(acc.get(0, 0x1f, 0)), vec![20, 3]);
+
+        // full replace resets appended items; append to unknown attribute starts a list
+        acc.ingest_attribute(&report(0, None, TlvItemValue::List(Vec::new())));
+        assert_eq!(list_ints(acc.get(0, 0x1f, 0)), Vec::<u64>::new());
+        acc.ingest_attribute(&report(1, Some(ListIndex::Append), TlvItemValue::Int(7)));
+        assert_eq!(list_ints(acc.get(0, 0x1f, 1)), vec![7]);
+        assert_eq!(acc.attribute(0, 0x1f, 1).unwrap().data_version, Some(3));
+    }
+
+    #[test]
+    fn test_accumulator_ingest_reports() {
+        let chunk1 = parse_payload(
+            &device_messages::im_report_data(
+                7,
+                &[
+                    AttrReport::Data { endpoint: 1, cluster: 6, attribute: 0, value_tlv: bool_value_tlv(true) },
+                    AttrReport::Status { endpoint: 1, cluster: 6, attribute: 1, status: 0x86 },
+                ],
+                -1,
+                None,
+                true,
+            )
+            .unwrap(),
+        );
+        let chunk2 = parse_payload(
+            &device_messages::im_report_data(
+                7,
+                &[AttrReport::Data { endpoint: 1, cluster: 6, attribute: 0, value_tlv: bool_value_tlv(false) }],
+                -1,
+                None,
+                false,
+            )
+            .unwrap(),
+        );
+        let mut acc = ReportAccumulator::new();
+        acc.ingest(&chunk1);
+        acc.ingest(&chunk2);
+        assert_eq!(acc.get(1, 6, 0), Some(&TlvItemValue::Bool(false)));
+        assert_eq!(acc.get(1, 6, 1), None);
+        assert!(matches!(
+            acc.attribute(1, 6, 1).unwrap().data,
+            AttributeData::Status { status: 0x86, .. }
+        ));
+        assert_eq!(acc.attributes().count(), 2);
+    }
+
     #[test]
     fn test_parse_subscribe_response() {
         let msg = device_messages::im_subscribe_response(77, 9, -1, 60).unwrap();
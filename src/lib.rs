@@ -27,6 +27,7 @@
 //!                      Connections automatically re-discover devices via operational mDNS if the stored
 //!                      address is stale (e.g. device changed IP).
 //! - [clusters](clusters) - matter cluster definitions and encoders/decoders for cluster attributes and commands.
+//! - `blocking` - synchronous wrappers of controller and connection for non-async callers (requires `blocking` feature).
 //!
 //!
 //! Examples directory contains simple demo application and simple standalone examples on how to use APIs.
@@ -46,11 +47,7 @@
 //! const CONTROLLER_ID: u64 = 200;
 //! const LOCAL_ADDRESS: &str = "0.0.0.0:5555";
 //! const DATA_DIR: &str = "./matter-data";
-//! let config = ManagerConfig {
-//!             fabric_id: FABRIC_ID,
-//!             controller_id: CONTROLLER_ID,
-//!             local_address: LOCAL_ADDRESS.to_string(),
-//! };
+//! let config = ManagerConfig::new(FABRIC_ID, CONTROLLER_ID, LOCAL_ADDRESS);
 //! let devman = DeviceManager::create(DATA_DIR, config).await?;
 //! # Ok(())
 //! # }
@@ -274,6 +271,8 @@
 mod active_connection;
 #[cfg(feature = "ble")]
 pub mod ble;
+#[cfg(feature = "blocking")]
+pub mod blocking;
 #[cfg(feature = "ble")]
 pub mod btp;
 pub mod cert_matter;
@@ -281,7 +280,7 @@ pub mod cert_x509;
 pub mod certmanager;
 pub mod clusters;
 mod commission;
-pub use commission::NetworkCreds;
+pub use commission::{Cancelled, CommissioningCompleteFailed, CommissioningStep, NetworkCreds};
 pub mod controller;
 pub mod device;
 mod device_messages;
@@ -295,7 +294,9 @@ pub mod messages;
 pub mod mrp;
 pub mod onboarding;
 mod retransmit;
+pub use retransmit::{NoResponse, UnexpectedExchange};
 mod session;
+pub use session::{CounterExhausted, DecodeError, GroupMessageCounters};
 mod sigma;
 pub mod spake2p;
 pub mod tlv;
@@ -16,6 +16,9 @@ pub const TYPE_SRV: u16 = 33;
 pub const TYPE_NAPTR: u16 = 35;
 pub const QTYPE_ANY: u16 = 0xff;
 
+/// Truncation flag; responder continues with more records in following packets.
+pub const FLAG_TC: u16 = 0x0200;
+
 pub fn encode_label(label: &str, out: &mut Vec<u8>) -> Result<()> {
     for seg in label.split(".") {
         if seg.is_empty() {
@@ -81,8 +84,13 @@ pub(crate) fn create_query(label: &str, qtype: u16) -> Result<Vec<u8>> {
 }
 
 fn read_label(data: &[u8], cursor: &mut Cursor<&[u8]>) -> Result<String> {
+    read_label_depth(data, cursor, 0)
+}
+
+/// `depth` counts segments and pointers followed so far, so pointer loops
+/// are rejected instead of recursing forever.
+fn read_label_depth(data: &[u8], cursor: &mut Cursor<&[u8]>, mut depth: usize) -> Result<String> {
     let mut out = Vec::new();
-    let mut depth = 0;
     loop {
         depth += 1;
         if depth > 64 {
@@ -99,7 +107,7 @@ fn read_label(data: &[u8], cursor: &mut Cursor<&[u8]>) -> Result<String> {
             if off >= data.len() {
                 anyhow::bail!("invalid compression pointer offset");
             }
-            let frag = read_label(data, &mut Cursor::new(&data[off..]))?;
+            let frag = read_label_depth(data, &mut Cursor::new(&data[off..]), depth)?;
             out.extend_from_slice(frag.as_bytes());
             break;
         } else {
@@ -131,14 +139,18 @@ pub enum RRData {
     Unknown(Vec<u8>),
 }
 
+/// Resource record. The parser fills both raw `rdata` and its parsed form `data`.
 #[derive(Debug, Eq, PartialEq, Hash, Clone)]
 pub struct RR {
     pub name: String,
     pub typ: u16,
     pub class: u16,
     pub ttl: u32,
+    /// Raw rdata as received. Names inside may be compression pointers into the message.
     pub rdata: Vec<u8>,
+    /// Decompressed SRV target; same as target in [RRData::SRV].
     pub target: Option<String>,
+    /// Parsed rdata; [RRData::Unknown] for types not understood or malformed rdata.
     pub data: RRData,
 }
 
@@ -206,6 +218,24 @@ impl Query {
 }
 
 impl DnsMessage {
+    /// TC bit set: the sender's records continue in next packet(s).
+    pub fn is_truncated(&self) -> bool {
+        self.flags & FLAG_TC != 0
+    }
+
+    /// Interface index the message arrived on, when it came from a link-local
+    /// IPv6 source. Link-local addresses advertised in it are valid on that interface.
+    pub fn source_scope(&self) -> Option<u32> {
+        match self.source {
+            std::net::SocketAddr::V6(v6)
+                if crate::discover::is_link_local_v6(v6.ip()) && v6.scope_id() != 0 =>
+            {
+                Some(v6.scope_id())
+            }
+            _ => None,
+        }
+    }
+
     pub fn dump(&self) {
         println!("{:?} {} {:x}", self.source, self.transaction, self.flags);
         println!("  queries:");
@@ -235,15 +265,11 @@ fn parse_rr(data: &[u8], cursor: &mut Cursor<&[u8]>) -> Result<RR> {
     let dlen = cursor.read_u16::<BigEndian>()?;
     let mut rdata = vec![0; dlen as usize];
     cursor.read_exact(&mut rdata)?;
-    let mut target = None;
-    if typ == TYPE_SRV && rdata.len() >= 6 {
-        target = Some(read_label(data, &mut Cursor::new(&rdata[6..])).context("can't parse target from SRV")?);
-    }
     let rrdata = match typ {
         TYPE_A if rdata.len() == 4 => RRData::A(std::net::Ipv4Addr::from_octets(rdata[0..4].try_into().context("invalid A rdata length")?)),
         TYPE_AAAA if rdata.len() == 16 => RRData::AAAA(std::net::Ipv6Addr::from_octets(rdata[0..16].try_into().context("invalid AAAA rdata length")?)),
         TYPE_PTR => RRData::PTR(read_label(data, &mut Cursor::new(&rdata)).context("can't parse PTR rdata")?),
-        TYPE_TXT => RRData::TXT(rdata.split(|b| *b == 0).filter_map(|s| std::str::from_utf8(s).ok().map(|s| s.to_owned())).collect()),
+        TYPE_TXT => RRData::TXT(parse_txt_strings(&rdata)),
         TYPE_SRV if rdata.len() >= 6 => {
             let mut cursor = Cursor::new(rdata.as_slice());
             let priority = cursor.read_u16::<BigEndian>()?;
@@ -254,6 +280,10 @@ fn parse_rr(data: &[u8], cursor: &mut Cursor<&[u8]>) -> Result<RR> {
         }
         _ => RRData::Unknown(rdata.clone()),
     };
+    let target = match &rrdata {
+        RRData::SRV { target, .. } => Some(target.clone()),
+        _ => None,
+    };
 
     Ok(RR {
         name,
@@ -274,6 +304,31 @@ fn parse_q(data: &[u8], cursor: &mut Cursor<&[u8]>) -> Result<Query> {
     Ok(Query { name, typ, class })
 }
 
+/// Parse `count` records into `out`. In a truncated message (TC bit) the responder
+/// may cut the packet mid-record; then parsing stops, records parsed so far are kept
+/// and Ok(false) tells caller to skip remaining sections.
+fn parse_section(
+    data: &[u8],
+    cursor: &mut Cursor<&[u8]>,
+    count: u16,
+    truncated: bool,
+    out: &mut Vec<RR>,
+) -> Result<bool> {
+    for _ in 0..count {
+        match parse_rr(data, cursor) {
+            Ok(rr) => out.push(rr),
+            Err(e) if truncated => {
+                log::trace!("truncated mdns message ends mid-record: {}", e);
+                return Ok(false);
+            }
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(true)
+}
+
+/// Parse mDNS message. Messages with TC bit set may end mid-record; such
+/// record is dropped and [DnsMessage::is_truncated] tells that more records follow.
 pub fn parse_dns(data: &[u8], source: std::net::SocketAddr) -> Result<DnsMessage> {
     let mut cursor = Cursor::new(data);
     let transaction = cursor.read_u16::<BigEndian>()?;
@@ -291,14 +346,16 @@ pub fn parse_dns(data: &[u8], source: std::net::SocketAddr) -> Result<DnsMessage
     for _ in 0..nquestions {
         queries.push(parse_q(data, &mut cursor)?);
     }
-    for _ in 0..nanswers {
-        answers.push(parse_rr(data, &mut cursor)?);
-    }
-    for _ in 0..nauthority {
-        authority.push(parse_rr(data, &mut cursor)?);
-    }
-    for _ in 0..nadditional {
-        additional.push(parse_rr(data, &mut cursor)?);
+    let truncated = flags & FLAG_TC != 0;
+    let mut complete = true;
+    for (count, section) in [
+        (nanswers, &mut answers),
+        (nauthority, &mut authority),
+        (nadditional, &mut additional),
+    ] {
+        if complete {
+            complete = parse_section(data, &mut cursor, count, truncated, section)?;
+        }
     }
 
     Ok(DnsMessage {
@@ -315,6 +372,7 @@ pub fn parse_dns(data: &[u8], source: std::net::SocketAddr) -> Result<DnsMessage
 async fn discoverv4(
     label: &str,
     qtype: u16,
+    interface: std::net::Ipv4Addr,
     sender: tokio::sync::mpsc::UnboundedSender<DnsMessage>,
     cancel: tokio_util::sync::CancellationToken,
 ) -> Result<()> {
@@ -325,7 +383,10 @@ async fn discoverv4(
     let addr: std::net::SocketAddrV4 = "0.0.0.0:5353".parse()?;
     stdsocket.bind(&socket2::SockAddr::from(addr))?;
     let maddr: std::net::Ipv4Addr = "224.0.0.251".parse()?;
-    stdsocket.join_multicast_v4(&maddr, &std::net::Ipv4Addr::UNSPECIFIED)?;
+    stdsocket.join_multicast_v4(&maddr, &interface)?;
+    if !interface.is_unspecified() {
+        stdsocket.set_multicast_if_v4(&interface)?;
+    }
     stdsocket.set_nonblocking(true)?;
     let socket = tokio::net::UdpSocket::from_std(stdsocket.into())?;
     let query = create_query(label, qtype)?;
@@ -382,6 +443,16 @@ async fn discoverv6(
             _ = cancel.cancelled() => return Ok(())
         };
         buf.resize(n, 0);
+        // OS fills the zone of a link-local source; fall back to the joined interface.
+        let addr = match addr {
+            std::net::SocketAddr::V6(mut v6) if v6.scope_id() == 0 => {
+                if crate::discover::is_link_local_v6(v6.ip()) {
+                    v6.set_scope_id(interface);
+                }
+                std::net::SocketAddr::V6(v6)
+            }
+            _ => addr,
+        };
         let dns = parse_dns(&buf, addr);
         let dns = match dns {
             Ok(v) => v,
@@ -398,42 +469,116 @@ async fn discoverv6(
     }
 }
 
+/// Multicast socket to open for discovery.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum SocketTarget {
+    /// IPv4 socket joined on given interface address (unspecified = default interface).
+    V4(std::net::Ipv4Addr),
+    /// IPv6 socket joined on given interface index.
+    V6(u32),
+}
+
+/// Decide which sockets to open. Interfaces are given as (name, address, index).
+/// Without filter all IPv6 interfaces plus default IPv4 interface are used.
+/// Filter matches interface name or any of its addresses; all addresses of matching interface are used.
+fn plan_sockets(
+    ifaces: &[(String, std::net::IpAddr, Option<u32>)],
+    interface: Option<&str>,
+) -> Result<Vec<SocketTarget>> {
+    let mut out = Vec::new();
+    let names: Option<Vec<&str>> = interface.map(|filter| {
+        let addr = filter.parse::<std::net::IpAddr>().ok();
+        ifaces
+            .iter()
+            .filter(|(name, ip, _)| name == filter || Some(*ip) == addr)
+            .map(|(name, _, _)| name.as_str())
+            .collect()
+    });
+    if let Some(names) = &names {
+        if names.is_empty() {
+            anyhow::bail!("no interface matches {:?}", interface.unwrap_or_default());
+        }
+    }
+    for (name, ip, index) in ifaces {
+        if let Some(names) = &names {
+            if !names.contains(&name.as_str()) {
+                continue;
+            }
+        }
+        let target = match (ip, index) {
+            (std::net::IpAddr::V6(_), Some(index)) => SocketTarget::V6(*index),
+            (std::net::IpAddr::V4(ip), _) if names.is_some() => SocketTarget::V4(*ip),
+            _ => continue,
+        };
+        if !out.contains(&target) {
+            out.push(target);
+        }
+    }
+    if names.is_none() {
+        out.push(SocketTarget::V4(std::net::Ipv4Addr::UNSPECIFIED));
+    }
+    Ok(out)
+}
+
 pub async fn discover(
     label: &str,
     qtype: u16,
     sender: tokio::sync::mpsc::UnboundedSender<DnsMessage>,
     stop: tokio_util::sync::CancellationToken,
 ) -> Result<()> {
-    let ifaces = if_addrs::get_if_addrs();
-    if let Ok(ifaces) = ifaces {
-        for iface in ifaces {
-            let stop_child = stop.child_token();
-            if !iface.ip().is_ipv6() {
-                continue;
-            }
-            if let Some(index) = iface.index {
-                let sender2 = sender.clone();
-                let label = label.to_owned();
-                tokio::spawn(async move {
-                    let e = discoverv6(&label, qtype, index, sender2, stop_child).await;
-                    if let Err(e) = e {
-                        log::warn!("mdns discover error: {}", e);
-                    }
-                });
-            }
+    discover_on(label, qtype, None, sender, stop).await
+}
+
+/// Same as [discover], but when `interface` is set only sockets on that interface
+/// (given by name like "eth0" or by one of its addresses) are created.
+pub async fn discover_on(
+    label: &str,
+    qtype: u16,
+    interface: Option<&str>,
+    sender: tokio::sync::mpsc::UnboundedSender<DnsMessage>,
+    stop: tokio_util::sync::CancellationToken,
+) -> Result<()> {
+    let ifaces: Vec<_> = match if_addrs::get_if_addrs() {
+        Ok(ifaces) => ifaces.into_iter().map(|i| (i.name.clone(), i.ip(), i.index)).collect(),
+        Err(e) => {
+            log::warn!("can't list interfaces: {}", e);
+            Vec::new()
         }
     };
+    for target in plan_sockets(&ifaces, interface)? {
+        let stop_child = stop.child_token();
+        let sender2 = sender.clone();
+        let label = label.to_owned();
+        tokio::spawn(async move {
+            let e = match target {
+                SocketTarget::V4(ip) => discoverv4(&label, qtype, ip, sender2, stop_child).await,
+                SocketTarget::V6(index) => discoverv6(&label, qtype, index, sender2, stop_child).await,
+            };
+            if let Err(e) = e {
+                log::warn!("mdns discover error: {}", e);
+            }
+        });
+    }
+    Ok(())
+}
 
-    let stop_child = stop.child_token();
-    let label = label.to_owned();
-    tokio::spawn(async move {
-        let e = discoverv4(&label, qtype, sender, stop_child).await;
-        if let Err(e) = e {
-            log::warn!("mdns discover error: {}", e);
+/// Split TXT rdata into its length-prefixed character strings (RFC 6763 6.1).
+/// Empty strings are skipped, invalid UTF-8 is replaced and a string whose
+/// length runs past the end of rdata terminates parsing.
+pub fn parse_txt_strings(rdata: &[u8]) -> Vec<String> {
+    let mut out = Vec::new();
+    let mut rest = rdata;
+    while let Some((&len, tail)) = rest.split_first() {
+        let len = len as usize;
+        if len > tail.len() {
+            break;
         }
-    });
-
-    Ok(())
+        if len > 0 {
+            out.push(String::from_utf8_lossy(&tail[..len]).into_owned());
+        }
+        rest = &tail[len..];
+    }
+    out
 }
 
 #[cfg(test)]
@@ -496,4 +641,184 @@ mod tests {
         let label2 = read_label(&pkt, &mut Cursor::new(&pkt[second_start..])).unwrap();
         assert_eq!(label2, "bar._tcp.local.");
     }
+
+    #[test]
+    fn txt_strings_are_length_prefixed() {
+        // "SII=5000", "", "T", "D=8\x30" - zero bytes and '=' must not split strings
+        let rdata = b"\x08SII=5000\x00\x01T\x03D=0\x05a=b=c\x09trunc";
+        assert_eq!(parse_txt_strings(rdata), vec!["SII=5000", "T", "D=0", "a=b=c"]);
+        assert!(parse_txt_strings(&[0]).is_empty());
+        assert!(parse_txt_strings(&[]).is_empty());
+    }
+
+    fn test_ifaces() -> Vec<(String, std::net::IpAddr, Option<u32>)> {
+        vec![
+            ("lo".to_owned(), "127.0.0.1".parse().unwrap(), Some(1)),
+            ("eth0".to_owned(), "192.168.1.10".parse().unwrap(), Some(2)),
+            ("eth0".to_owned(), "fe80::1".parse().unwrap(), Some(2)),
+            ("docker0".to_owned(), "172.17.0.1".parse().unwrap(), Some(3)),
+            ("docker0".to_owned(), "fe80::42".parse().unwrap(), Some(3)),
+        ]
+    }
+
+    #[test]
+    fn plan_sockets_restricts_to_interface() {
+        let ifaces = test_ifaces();
+        let all = plan_sockets(&ifaces, None).unwrap();
+        assert_eq!(
+            all,
+            vec![SocketTarget::V6(2), SocketTarget::V6(3), SocketTarget::V4(std::net::Ipv4Addr::UNSPECIFIED)]
+        );
+
+        let by_name = plan_sockets(&ifaces, Some("eth0")).unwrap();
+        assert_eq!(by_name, vec![SocketTarget::V4("192.168.1.10".parse().unwrap()), SocketTarget::V6(2)]);
+
+        let by_addr = plan_sockets(&ifaces, Some("172.17.0.1")).unwrap();
+        assert_eq!(by_addr, vec![SocketTarget::V4("172.17.0.1".parse().unwrap()), SocketTarget::V6(3)]);
+
+        assert!(plan_sockets(&ifaces, Some("wlan0")).is_err());
+    }
+
+    /// Append resource record to packet; `rdata` is appended by the callback so
+    /// that names inside it can be compressed against the rest of the packet.
+    fn push_rr(
+        pkt: &mut Vec<u8>,
+        offsets: &mut HashMap<String, usize>,
+        name: &str,
+        typ: u16,
+        rdata: impl FnOnce(&mut Vec<u8>, &mut HashMap<String, usize>),
+    ) {
+        encode_label_compressed(name, pkt, offsets).unwrap();
+        pkt.write_u16::<BigEndian>(typ).unwrap();
+        pkt.write_u16::<BigEndian>(1).unwrap();
+        pkt.write_u32::<BigEndian>(120).unwrap();
+        let len_pos = pkt.len();
+        pkt.write_u16::<BigEndian>(0).unwrap();
+        rdata(pkt, offsets);
+        let len = (pkt.len() - len_pos - 2) as u16;
+        pkt[len_pos..len_pos + 2].copy_from_slice(&len.to_be_bytes());
+    }
+
+    fn response_header(answers: u16) -> Vec<u8> {
+        let mut pkt = Vec::new();
+        for v in [0, 0x8400, 0, answers, 0, 0] {
+            pkt.write_u16::<BigEndian>(v).unwrap();
+        }
+        pkt
+    }
+
+    #[test]
+    fn parse_fills_rdata_and_data() {
+        let mut pkt = response_header(5);
+        let mut offsets = HashMap::new();
+        let instance = "ABCD._matterc._udp.local";
+        push_rr(&mut pkt, &mut offsets, "_matterc._udp.local", TYPE_PTR, |p, _| {
+            encode_label(instance, p).unwrap()
+        });
+        push_rr(&mut pkt, &mut offsets, instance, TYPE_TXT, |p, _| p.extend_from_slice(b"\x05D=840"));
+        push_rr(&mut pkt, &mut offsets, instance, TYPE_SRV, |p, _| {
+            p.extend_from_slice(&[0, 1, 0, 2, 0x15, 0xa4]);
+            encode_label("host.local", p).unwrap();
+        });
+        push_rr(&mut pkt, &mut offsets, "host.local", TYPE_A, |p, _| p.extend_from_slice(&[10, 0, 0, 7]));
+        push_rr(&mut pkt, &mut offsets, "host.local", TYPE_AAAA, |p, _| {
+            p.extend_from_slice(&"fe80::7".parse::<std::net::Ipv6Addr>().unwrap().octets())
+        });
+
+        let msg = parse_dns(&pkt, "10.0.0.7:5353".parse().unwrap()).unwrap();
+        assert_eq!(msg.answers.len(), 5);
+        for rr in &msg.answers {
+            assert!(!rr.rdata.is_empty(), "{} has no rdata", rr);
+            assert!(!matches!(rr.data, RRData::Unknown(_)), "{} not parsed", rr);
+        }
+        assert_eq!(msg.answers[0].data, RRData::PTR("ABCD._matterc._udp.local.".to_owned()));
+        assert_eq!(msg.answers[1].data, RRData::TXT(vec!["D=840".to_owned()]));
+        assert_eq!(
+            msg.answers[2].data,
+            RRData::SRV { priority: 1, weight: 2, port: 5540, target: "host.local.".to_owned() }
+        );
+        assert_eq!(msg.answers[2].target.as_deref(), Some("host.local."));
+        assert_eq!(msg.answers[3].data, RRData::A(std::net::Ipv4Addr::new(10, 0, 0, 7)));
+        assert_eq!(msg.answers[3].rdata, vec![10, 0, 0, 7]);
+        assert_eq!(msg.answers[4].data, RRData::AAAA("fe80::7".parse().unwrap()));
+    }
+
+    #[test]
+    fn parse_compressed_srv_and_ptr_targets() {
+        let mut pkt = response_header(4);
+        let mut offsets = HashMap::new();
+        let instance = "2906C908D115D362-0000000000000002._matter._tcp.local";
+        let host = "B8AE1D2C.local";
+        // PTR, SRV target and owner names all compressed against earlier names
+        push_rr(&mut pkt, &mut offsets, "_matter._tcp.local", TYPE_PTR, |p, o| {
+            encode_label_compressed(instance, p, o).unwrap()
+        });
+        push_rr(&mut pkt, &mut offsets, instance, TYPE_SRV, |p, o| {
+            p.extend_from_slice(&[0, 0, 0, 0, 0x15, 0xa4]);
+            let before = p.len();
+            encode_label_compressed(host, p, o).unwrap();
+            // "B8AE1D2C" followed by pointer to "local"
+            assert_eq!(p.len() - before, 1 + 8 + 2);
+        });
+        push_rr(&mut pkt, &mut offsets, host, TYPE_A, |p, _| p.extend_from_slice(&[192, 168, 0, 3]));
+        push_rr(&mut pkt, &mut offsets, host, TYPE_AAAA, |p, _| {
+            p.extend_from_slice(&"fd00::3".parse::<std::net::Ipv6Addr>().unwrap().octets())
+        });
+
+        let msg = parse_dns(&pkt, "192.168.0.3:5353".parse().unwrap()).unwrap();
+        assert_eq!(msg.answers[0].data, RRData::PTR(format!("{}.", instance)));
+        let srv = &msg.answers[1];
+        assert_eq!(srv.name, format!("{}.", instance));
+        assert_eq!(srv.data, RRData::SRV { priority: 0, weight: 0, port: 5540, target: format!("{}.", host) });
+        assert_eq!(srv.target, Some(format!("{}.", host)));
+        // rdata keeps the pointer, only data is decompressed
+        assert_eq!(srv.rdata.len(), 6 + 1 + 8 + 2);
+        assert_eq!(msg.answers[2].name, format!("{}.", host));
+        assert_eq!(msg.answers[2].data, RRData::A(std::net::Ipv4Addr::new(192, 168, 0, 3)));
+        assert_eq!(msg.answers[3].data, RRData::AAAA("fd00::3".parse().unwrap()));
+    }
+
+    #[test]
+    fn parse_malformed_rdata() {
+        let mut pkt = response_header(2);
+        let mut offsets = HashMap::new();
+        push_rr(&mut pkt, &mut offsets, "short.local", TYPE_A, |p, _| p.extend_from_slice(&[1, 2, 3]));
+        // SRV with target pointing at itself must fail instead of recursing forever
+        push_rr(&mut pkt, &mut offsets, "loop.local", TYPE_SRV, |p, _| {
+            p.extend_from_slice(&[0, 0, 0, 0, 0, 1]);
+            let off = p.len();
+            p.extend_from_slice(&[0xc0 | (off >> 8) as u8, off as u8]);
+        });
+        assert!(parse_dns(&pkt, "10.0.0.1:5353".parse().unwrap()).is_err());
+
+        // A record with 3 bytes stays Unknown instead of failing the whole message
+        let mut pkt = response_header(1);
+        push_rr(&mut pkt, &mut HashMap::new(), "short.local", TYPE_A, |p, _| p.extend_from_slice(&[1, 2, 3]));
+        let msg = parse_dns(&pkt, "10.0.0.1:5353".parse().unwrap()).unwrap();
+        assert_eq!(msg.answers[0].data, RRData::Unknown(vec![1, 2, 3]));
+    }
+
+    #[test]
+    fn parse_truncated_message() {
+        let mut pkt = response_header(3);
+        pkt[2..4].copy_from_slice(&(0x8400 | FLAG_TC).to_be_bytes());
+        let mut offsets = HashMap::new();
+        push_rr(&mut pkt, &mut offsets, "_matter._tcp.local", TYPE_PTR, |p, _| {
+            encode_label("A-B._matter._tcp.local", p).unwrap()
+        });
+        push_rr(&mut pkt, &mut offsets, "host.local", TYPE_A, |p, _| p.extend_from_slice(&[10, 0, 0, 1]));
+        let full_len = pkt.len();
+        push_rr(&mut pkt, &mut offsets, "host.local", TYPE_A, |p, _| p.extend_from_slice(&[10, 0, 0, 2]));
+        // cut last record in the middle of its rdata
+        pkt.truncate(full_len + 5);
+
+        let msg = parse_dns(&pkt, "10.0.0.1:5353".parse().unwrap()).unwrap();
+        assert!(msg.is_truncated());
+        assert_eq!(msg.answers.len(), 2);
+        assert_eq!(msg.answers[1].data, RRData::A(std::net::Ipv4Addr::new(10, 0, 0, 1)));
+
+        // the same cut without TC bit is malformed
+        pkt[2..4].copy_from_slice(&0x8400u16.to_be_bytes());
+        assert!(parse_dns(&pkt, "10.0.0.1:5353".parse().unwrap()).is_err());
+    }
 }
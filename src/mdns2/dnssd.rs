@@ -39,13 +39,107 @@ pub enum MdnsEvent {
     },
 }
 
+/// How a query added by [`MdnsService::add_query`](super::MdnsService::add_query) is repeated.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum QuerySchedule {
+    /// Send once, never repeat.
+    OneShot,
+    /// Repeat with constant interval.
+    Fixed(Duration),
+    /// Start with `initial` interval and double it after every query up to `max` (RFC 6762 5.2).
+    Backoff { initial: Duration, max: Duration },
+}
+
+impl QuerySchedule {
+    /// Backoff recommended by RFC 6762: 1s, 2s, 4s, ... capped at one hour.
+    pub fn rfc6762() -> Self {
+        QuerySchedule::Backoff {
+            initial: Duration::from_secs(1),
+            max: Duration::from_secs(3600),
+        }
+    }
+}
+
+impl From<Duration> for QuerySchedule {
+    fn from(interval: Duration) -> Self {
+        QuerySchedule::Fixed(interval)
+    }
+}
+
 pub(super) struct PeriodicQuery {
     pub label: String,
     pub qtype: u16,
+    pub schedule: QuerySchedule,
+    /// Current interval; grows for backoff schedule.
     pub interval: Duration,
+    /// Maximum random extension of each interval, in percent of it.
+    pub jitter_percent: u16,
+    /// Wait until the next query: `interval` extended by random jitter.
+    wait: Duration,
     pub last_sent: Instant,
 }
 
+impl PeriodicQuery {
+    /// Query which was just sent at `sent_at`.
+    pub fn new(label: &str, qtype: u16, schedule: QuerySchedule, sent_at: Instant, jitter_percent: u16) -> Self {
+        let interval = match schedule {
+            QuerySchedule::OneShot => Duration::MAX,
+            QuerySchedule::Fixed(interval) => interval,
+            QuerySchedule::Backoff { initial, .. } => initial,
+        };
+        let mut q = Self {
+            label: label.to_owned(),
+            qtype,
+            schedule,
+            interval,
+            jitter_percent,
+            wait: interval,
+            last_sent: sent_at,
+        };
+        q.wait = q.jittered_interval();
+        q
+    }
+
+    /// Query was sent out of schedule at `sent_at`; start the schedule over.
+    pub fn restart(&mut self, sent_at: Instant) {
+        *self = Self::new(&self.label, self.qtype, self.schedule, sent_at, self.jitter_percent);
+    }
+
+    fn jittered_interval(&self) -> Duration {
+        if self.schedule == QuerySchedule::OneShot || self.jitter_percent == 0 {
+            return self.interval;
+        }
+        let jitter = self.jitter_percent as f64 / 100.0;
+        self.interval
+            .saturating_add(self.interval.mul_f64(rand::random::<f64>() * jitter))
+    }
+
+    /// Returns true when query shall be sent at `now` and advances the schedule.
+    pub fn poll(&mut self, now: Instant) -> bool {
+        if self.schedule == QuerySchedule::OneShot
+            || now.duration_since(self.last_sent) < self.wait
+        {
+            return false;
+        }
+        self.last_sent = now;
+        if let QuerySchedule::Backoff { max, .. } = self.schedule {
+            self.interval = self.interval.saturating_mul(2).min(max);
+        }
+        self.wait = self.jittered_interval();
+        true
+    }
+}
+
+/// True when PTR target `instance` names one of our own registered services,
+/// i.e. we are hearing our own announcement looped back by multicast.
+pub(super) fn is_own_instance(services: &[ServiceRegistration], instance: &str) -> bool {
+    let instance = instance.trim_end_matches('.');
+    services.iter().any(|reg| {
+        let own = format!("{}.{}", reg.instance_name, reg.service_type);
+        own.trim_end_matches('.').eq_ignore_ascii_case(instance)
+    })
+}
+
 /// Build the set of DNS records for a service registration.
 pub(super) fn build_service_records(
     reg: &ServiceRegistration,
@@ -111,10 +205,16 @@ pub(super) fn build_service_records(
 
     // TXT
     let mut txt_rdata = Vec::new();
+    let mut txt_strings = Vec::new();
     for (k, v) in &reg.txt_records {
         let entry = format!("{}={}", k, v);
+        if entry.len() > 255 {
+            log::warn!("mdns2: TXT entry for key {} exceeds 255 bytes, skipped", k);
+            continue;
+        }
         let _ = txt_rdata.write_u8(entry.len() as u8);
         txt_rdata.extend_from_slice(entry.as_bytes());
+        txt_strings.push(entry);
     }
     if txt_rdata.is_empty() {
         txt_rdata.push(0); // RFC 6763: empty TXT record has single zero-length byte
@@ -126,12 +226,7 @@ pub(super) fn build_service_records(
         ttl: reg.ttl,
         rdata: txt_rdata,
         target: None,
-        data: mdns::RRData::TXT(
-            reg.txt_records
-                .iter()
-                .map(|(k, v)| format!("{}={}", k, v))
-                .collect(),
-        ),
+        data: mdns::RRData::TXT(txt_strings),
     });
 
 
@@ -231,3 +326,45 @@ pub(super) fn find_matching_services(
     (answers, additional)
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn send_times(schedule: QuerySchedule, secs: u64) -> Vec<u64> {
+        let start = Instant::now();
+        let mut q = PeriodicQuery::new("_matter._tcp.local", mdns::QTYPE_ANY, schedule, start, 0);
+        (1..=secs)
+            .filter(|t| q.poll(start + Duration::from_secs(*t)))
+            .collect()
+    }
+
+    #[test]
+    fn test_query_schedule_send_times() {
+        let backoff = QuerySchedule::Backoff {
+            initial: Duration::from_secs(1),
+            max: Duration::from_secs(8),
+        };
+        assert_eq!(send_times(backoff, 40), vec![1, 3, 7, 15, 23, 31, 39]);
+        assert_eq!(send_times(Duration::from_secs(10).into(), 40), vec![10, 20, 30, 40]);
+        assert!(send_times(QuerySchedule::OneShot, 40).is_empty());
+    }
+
+    #[test]
+    fn test_query_jitter() {
+        let start = Instant::now();
+        let schedule = QuerySchedule::Fixed(Duration::from_secs(10));
+        let mut q = PeriodicQuery::new("_matter._tcp.local", mdns::QTYPE_ANY, schedule, start, 20);
+        let mut sent = vec![0u64];
+        for ms in (10..=200_000).step_by(10) {
+            if q.poll(start + Duration::from_millis(ms)) {
+                sent.push(ms);
+            }
+        }
+        let gaps: Vec<u64> = sent.windows(2).map(|w| w[1] - w[0]).collect();
+        assert!(gaps.len() >= 15);
+        for gap in &gaps {
+            assert!((10_000..=12_010).contains(gap), "gap {} ms outside jitter bound", gap);
+        }
+        assert!(gaps.windows(2).any(|w| w[0] != w[1]), "successive intervals must differ");
+    }
+}
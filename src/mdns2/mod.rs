@@ -10,11 +10,11 @@
 mod dnssd;
 mod protocol;
 
-pub use dnssd::{MdnsEvent, ServiceRegistration};
+pub use dnssd::{MdnsEvent, QuerySchedule, ServiceRegistration};
 pub use protocol::{CachedRecord, RecordCache};
 
 use std::collections::HashSet;
-use std::net::{Ipv4Addr, Ipv6Addr};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
 use std::sync::Arc;
 use std::time::{Duration, Instant};
 
@@ -26,9 +26,9 @@ use tokio::sync::mpsc::{self, UnboundedSender};
 use tokio_util::sync::CancellationToken;
 
 use crate::mdns;
-use dnssd::{PeriodicQuery, build_service_records, find_matching_services};
+use dnssd::{PeriodicQuery, build_service_records, find_matching_services, is_own_instance};
 use protocol::{
-    MDNS_ADDR_V4, MDNS_ADDR_V6, McastSocket, SendCommand, build_response,
+    MDNS_ADDR_V4, MDNS_ADDR_V6, McastSocket, SendCommand, build_response, fqdn,
     create_multicast_socket_v4, create_multicast_socket_v6, get_local_ips, send_loop,
 };
 
@@ -46,10 +46,38 @@ struct MdnsServiceInner {
     /// Device link-local IPv6 -> index of the interface its mDNS reply arrived
     /// on. That index is the correct scope_id for sending to `fe80::...`.
     link_local_scopes: std::collections::HashMap<Ipv6Addr, u32>,
+    /// Truncated responses by sender, waiting for continuation.
+    truncated: std::collections::HashMap<std::net::SocketAddr, PendingTruncated>,
+    /// Jitter of periodic query intervals, see [`MdnsService::set_query_jitter_percent`].
+    query_jitter_percent: u16,
 }
 
 const EVENT_CHANNEL_CAPACITY: usize = 256;
 
+/// Default maximum random extension of periodic query intervals, in percent of the interval.
+pub const DEFAULT_QUERY_JITTER_PERCENT: u16 = 10;
+
+/// How long to wait for continuation of a truncated response (RFC 6762 7.2 suggests 400-500ms).
+const TRUNCATED_WAIT: Duration = Duration::from_millis(500);
+
+/// Discoveries from truncated response(s) waiting for the rest of the records.
+struct PendingTruncated {
+    received: Instant,
+    ptrs: Vec<(String, String)>,
+    records: Vec<mdns::RR>,
+}
+
+/// Report discovered (service type, instance) pairs along with all records of the response.
+fn emit_discovered(event_tx: &broadcast::Sender<MdnsEvent>, ptrs: Vec<(String, String)>, records: &[mdns::RR]) {
+    for (name, target) in ptrs {
+        let _ = event_tx.send(MdnsEvent::ServiceDiscovered {
+            name,
+            target,
+            records: records.to_vec(),
+        });
+    }
+}
+
 /// Long-running mDNS service with discovery, caching, and service registration.
 pub struct MdnsService {
     inner: Arc<Mutex<MdnsServiceInner>>,
@@ -107,23 +135,38 @@ async fn recv_loop(
                 state.cache.ingest(rr);
                 // Remember the receive interface for link-local AAAA records
                 if let (Some(idx), mdns::RRData::AAAA(ip)) = (interface, &rr.data) {
-                    if (ip.segments()[0] & 0xffc0) == 0xfe80 {
+                    if crate::discover::is_link_local_v6(ip) {
                         state.link_local_scopes.insert(*ip, idx);
                     }
                 }
                 if rr.typ == mdns::TYPE_PTR {
                     if let mdns::RRData::PTR(ref target) = rr.data {
-                        new_ptr_records.push((rr.name.clone(), target.clone()));
+                        // multicast loopback delivers our own announcements too
+                        if !is_own_instance(&state.services, target) {
+                            new_ptr_records.push((rr.name.clone(), target.clone()));
+                        }
                     }
                 }
             }
-            for (name, target) in new_ptr_records {
-                let _ = event_tx.send(MdnsEvent::ServiceDiscovered {
-                    name,
-                    target,
-                    records: all_records.clone(),
+            if msg.is_truncated() {
+                // more records follow in next packet; report once the continuation arrives
+                let pending = state.truncated.entry(addr).or_insert_with(|| PendingTruncated {
+                    received: Instant::now(),
+                    ptrs: Vec::new(),
+                    records: Vec::new(),
                 });
+                pending.ptrs.extend(new_ptr_records);
+                pending.records.extend(all_records);
+                continue;
             }
+            let (mut ptrs, mut records) = match state.truncated.remove(&addr) {
+                Some(pending) => (pending.ptrs, pending.records),
+                None => (Vec::new(), Vec::new()),
+            };
+            drop(state);
+            ptrs.extend(new_ptr_records);
+            records.extend(all_records);
+            emit_discovered(&event_tx, ptrs, &records);
         } else {
             let state = inner.lock().await;
             if state.services.is_empty() {
@@ -180,15 +223,27 @@ async fn periodic_loop(
             let _ = event_tx.send(MdnsEvent::ServiceExpired { name, rtype });
         }
 
+        // Continuation of truncated response did not come; report what we have
+        let stale: Vec<_> = state
+            .truncated
+            .iter()
+            .filter(|(_, p)| p.received.elapsed() >= TRUNCATED_WAIT)
+            .map(|(addr, _)| *addr)
+            .collect();
+        for addr in stale {
+            if let Some(pending) = state.truncated.remove(&addr) {
+                emit_discovered(&event_tx, pending.ptrs, &pending.records);
+            }
+        }
+
         // Send due queries
         let now = Instant::now();
         let mut packets = Vec::new();
         for q in &mut state.queries {
-            if now.duration_since(q.last_sent) >= q.interval {
+            if q.poll(now) {
                 if let Ok(pkt) = mdns::create_query(&q.label, q.qtype) {
                     packets.push(pkt);
                 }
-                q.last_sent = now;
             }
         }
         drop(state);
@@ -211,6 +266,13 @@ impl MdnsService {
     /// Call [`subscribe`](Self::subscribe) on the returned handle to receive discovery events.
     /// Multiple independent subscribers may receive events concurrently.
     pub async fn new() -> Result<Arc<Self>> {
+        Self::new_on(None).await
+    }
+
+    /// Like [`new`](Self::new), but when `interface` is set (name or address, see
+    /// [`crate::transport::list_interfaces`]) multicast is sent and received only on it.
+    pub async fn new_on(interface: Option<&str>) -> Result<Arc<Self>> {
+        let selected = interface.map(crate::transport::find_interface).transpose()?;
         let (event_tx, _) = broadcast::channel(EVENT_CHANNEL_CAPACITY);
         let (send_tx, send_rx) = mpsc::unbounded_channel();
         let cancel = CancellationToken::new();
@@ -223,14 +285,21 @@ impl MdnsService {
             local_ips_v4: v4,
             local_ips_v6: v6,
             link_local_scopes: std::collections::HashMap::new(),
+            truncated: std::collections::HashMap::new(),
+            query_jitter_percent: DEFAULT_QUERY_JITTER_PERCENT,
         }));
 
         // Create sockets
         let mut mcast_sockets: Vec<McastSocket> = Vec::new();
 
-        // IPv4
-        match create_multicast_socket_v4() {
-            Ok(std_sock) => match UdpSocket::from_std(std_sock) {
+        // IPv4: default interface, or the first address of the selected one
+        let v4_interface = match &selected {
+            Some(iface) => iface.ipv4.first().copied(),
+            None => Some(std::net::Ipv4Addr::UNSPECIFIED),
+        };
+        match v4_interface.map(create_multicast_socket_v4).transpose() {
+            Ok(None) => {}
+            Ok(Some(std_sock)) => match UdpSocket::from_std(std_sock) {
                 Ok(s) => mcast_sockets.push(McastSocket {
                     sock: Arc::new(s),
                     multicast_addr: MDNS_ADDR_V4,
@@ -248,6 +317,9 @@ impl MdnsService {
                     continue;
                 }
                 if let Some(idx) = iface.index {
+                    if selected.as_ref().is_some_and(|s| s.index != Some(idx)) {
+                        continue;
+                    }
                     if !seen_indices.insert(idx) {
                         continue;
                     }
@@ -331,20 +403,33 @@ impl MdnsService {
         self.event_tx.subscribe()
     }
 
-    /// Add a periodic query. The query will be sent immediately, then every interval.
-    pub async fn add_query(&self, label: &str, qtype: u16, interval: Duration) {
+    /// Add a query. The query is sent immediately, then repeated according to `schedule`:
+    /// a plain [`Duration`] repeats with constant interval, [`QuerySchedule::Backoff`]
+    /// doubles the interval after each query and [`QuerySchedule::OneShot`] never repeats.
+    pub async fn add_query(&self, label: &str, qtype: u16, schedule: impl Into<QuerySchedule>) {
+        let schedule = schedule.into();
         let mut state = self.inner.lock().await;
         // Send immediately
         let sent_at = Instant::now();
         if let Ok(pkt) = mdns::create_query(label, qtype) {
             let _ = self.send_tx.send(SendCommand::Multicast(pkt));
         }
-        state.queries.push(PeriodicQuery {
-            label: label.to_owned(),
-            qtype,
-            interval,
-            last_sent: sent_at,
-        });
+        if schedule != QuerySchedule::OneShot {
+            let jitter_percent = state.query_jitter_percent;
+            state.queries.push(PeriodicQuery::new(label, qtype, schedule, sent_at, jitter_percent));
+        }
+    }
+
+    /// Set the maximum random extension of periodic query intervals in percent of
+    /// the interval (default [`DEFAULT_QUERY_JITTER_PERCENT`], 0 disables), same unit as
+    /// [`crate::mrp::MrpParameters::backoff_jitter_percent`]. Keeps several controllers
+    /// started together from querying in lockstep.
+    pub async fn set_query_jitter_percent(&self, jitter_percent: u16) {
+        let mut state = self.inner.lock().await;
+        state.query_jitter_percent = jitter_percent;
+        for q in &mut state.queries {
+            q.jitter_percent = jitter_percent;
+        }
     }
 
     /// Remove a periodic query by label.
@@ -419,12 +504,138 @@ impl MdnsService {
         }
     }
 
+    /// Whether `ip` is a cached address (A/AAAA record) of any host.
+    pub async fn has_address(&self, ip: &IpAddr) -> bool {
+        self.inner.lock().await.cache.has_address(ip)
+    }
+
     pub async fn active_lookup(&self, name: &str, qtype: u16) {
         if let Ok(pkt) = mdns::create_query(name, qtype) {
             let _ = self.send_tx.send(SendCommand::Multicast(pkt));
         }
     }
 
+    /// Resolve a host name (e.g. `ABCD1234.local`) to its addresses.
+    ///
+    /// Returns cached A/AAAA results immediately when present; otherwise sends
+    /// A and AAAA queries and waits until answers arrive in the cache or
+    /// `timeout` elapses. An empty result means the host did not answer.
+    pub async fn resolve_host(&self, hostname: &str, timeout: Duration) -> Vec<IpAddr> {
+        let name = fqdn(hostname);
+        let cached = self.inner.lock().await.cache.lookup_host(&name);
+        if !cached.is_empty() {
+            return cached;
+        }
+        self.active_lookup(&name, mdns::TYPE_A).await;
+        self.active_lookup(&name, mdns::TYPE_AAAA).await;
+
+        let deadline = tokio::time::Instant::now() + timeout;
+        let mut poll = tokio::time::interval(Duration::from_millis(50));
+        while tokio::time::Instant::now() < deadline {
+            tokio::select! {
+                _ = poll.tick() => {}
+                _ = tokio::time::sleep_until(deadline) => break,
+            }
+            let found = self.inner.lock().await.cache.lookup_host(&name);
+            if !found.is_empty() {
+                // give the other address family a moment to arrive as well
+                tokio::time::sleep(Duration::from_millis(100).min(deadline.saturating_duration_since(tokio::time::Instant::now()))).await;
+                return self.inner.lock().await.cache.lookup_host(&name);
+            }
+        }
+        Vec::new()
+    }
+
+    /// Records still needed to describe service instance `target`:
+    /// its SRV and TXT and, once SRV is known, the A/AAAA of the SRV target.
+    async fn missing_info(&self, target: &str) -> Vec<(String, u16)> {
+        let state = self.inner.lock().await;
+        let mut missing = Vec::new();
+        if state.cache.lookup(target, mdns::TYPE_TXT).is_empty() {
+            missing.push((target.to_owned(), mdns::TYPE_TXT));
+        }
+        let srv = state.cache.lookup(target, mdns::TYPE_SRV);
+        if srv.is_empty() {
+            missing.push((target.to_owned(), mdns::TYPE_SRV));
+        }
+        for rr in srv {
+            if let mdns::RRData::SRV { target: host, .. } = rr.data {
+                if state.cache.lookup_host(&host).is_empty() {
+                    missing.push((host.clone(), mdns::TYPE_A));
+                    missing.push((host, mdns::TYPE_AAAA));
+                }
+            }
+        }
+        missing
+    }
+
+    /// Make sure SRV, TXT and address records of service instance `target` are cached.
+    ///
+    /// Devices do not always put every record into one response, so a PTR may arrive
+    /// without the rest. Missing records are queried actively and the cache is polled
+    /// until they arrive or `timeout` elapses. Returns true when nothing is missing.
+    pub async fn request_missing_info(&self, target: &str, timeout: Duration) -> bool {
+        let mut queried = HashSet::new();
+        let deadline = tokio::time::Instant::now() + timeout;
+        let mut poll = tokio::time::interval(Duration::from_millis(50));
+        loop {
+            let missing = self.missing_info(target).await;
+            if missing.is_empty() {
+                return true;
+            }
+            for (name, qtype) in missing {
+                if queried.insert((name.clone(), qtype)) {
+                    log::debug!("mdns2: no {} record for {}, sending active query", qtype, name);
+                    self.active_lookup(&name, qtype).await;
+                }
+            }
+            tokio::select! {
+                _ = poll.tick() => {}
+                _ = tokio::time::sleep_until(deadline) => return false,
+            }
+        }
+    }
+
+    /// Drop all cached records and learned link-local scopes, e.g. after the host
+    /// changed networks and addresses of the old one would otherwise linger until
+    /// their TTL expires. Emits [`MdnsEvent::ServiceExpired`] for every removed record set.
+    pub async fn clear_cache(&self) {
+        let mut state = self.inner.lock().await;
+        let removed = state.cache.clear();
+        state.link_local_scopes.clear();
+        state.truncated.clear();
+        drop(state);
+        for (name, rtype) in removed {
+            let _ = self.event_tx.send(MdnsEvent::ServiceExpired { name, rtype });
+        }
+    }
+
+    /// Evict expired records and send all periodic queries immediately, restarting
+    /// their schedules. Together with [`clear_cache`](Self::clear_cache) this rebuilds
+    /// the cache from current answers.
+    pub async fn refresh(&self) {
+        let mut state = self.inner.lock().await;
+        let expired = state.cache.evict_expired();
+        let (v4, v6) = get_local_ips();
+        state.local_ips_v4 = v4;
+        state.local_ips_v6 = v6;
+        let now = Instant::now();
+        let mut packets = Vec::new();
+        for q in &mut state.queries {
+            q.restart(now);
+            if let Ok(pkt) = mdns::create_query(&q.label, q.qtype) {
+                packets.push(pkt);
+            }
+        }
+        drop(state);
+        for (name, rtype) in expired {
+            let _ = self.event_tx.send(MdnsEvent::ServiceExpired { name, rtype });
+        }
+        for pkt in packets {
+            let _ = self.send_tx.send(SendCommand::Multicast(pkt));
+        }
+    }
+
     /// Shut down all background tasks.
     pub fn shutdown(&self) {
         self.cancel.cancel();
@@ -436,3 +647,199 @@ impl Drop for MdnsService {
         self.cancel.cancel();
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Service without sockets; returns receiver of packets it wants to send.
+    fn detached() -> (MdnsService, mpsc::UnboundedReceiver<SendCommand>) {
+        let (event_tx, _) = broadcast::channel(EVENT_CHANNEL_CAPACITY);
+        let (send_tx, send_rx) = mpsc::unbounded_channel();
+        let inner = Arc::new(Mutex::new(MdnsServiceInner {
+            cache: RecordCache::new(),
+            queries: Vec::new(),
+            services: Vec::new(),
+            local_ips_v4: Vec::new(),
+            local_ips_v6: Vec::new(),
+            link_local_scopes: std::collections::HashMap::new(),
+            truncated: std::collections::HashMap::new(),
+            query_jitter_percent: DEFAULT_QUERY_JITTER_PERCENT,
+        }));
+        let service = MdnsService { inner, send_tx, event_tx, cancel: CancellationToken::new() };
+        (service, send_rx)
+    }
+
+    fn rr(name: &str, typ: u16, data: mdns::RRData) -> mdns::RR {
+        mdns::RR { name: name.to_owned(), typ, class: 1, ttl: 120, rdata: Vec::new(), target: None, data }
+    }
+
+    #[tokio::test]
+    async fn test_request_missing_info_queries_actively() {
+        let (service, mut sent) = detached();
+        let instance = "ABCD._matterc._udp.local.";
+        let host = "host1.local.";
+        let inner = service.inner.clone();
+        // mock responder: answers only what was actively queried
+        let responder = tokio::spawn(async move {
+            let mut asked = Vec::new();
+            while let Some(SendCommand::Multicast(pkt)) = sent.recv().await {
+                let msg = mdns::parse_dns(&pkt, "127.0.0.1:5353".parse().unwrap()).unwrap();
+                let mut state = inner.lock().await;
+                for q in msg.queries {
+                    asked.push(q.typ);
+                    let answer = match q.typ {
+                        mdns::TYPE_TXT => rr(instance, q.typ, mdns::RRData::TXT(vec!["D=840".to_owned()])),
+                        mdns::TYPE_SRV => rr(
+                            instance,
+                            q.typ,
+                            mdns::RRData::SRV { priority: 0, weight: 0, port: 5540, target: host.to_owned() },
+                        ),
+                        mdns::TYPE_A => rr(host, q.typ, mdns::RRData::A(Ipv4Addr::new(192, 168, 1, 9))),
+                        _ => continue,
+                    };
+                    state.cache.ingest(&answer);
+                }
+                if asked.contains(&mdns::TYPE_A) {
+                    return asked;
+                }
+            }
+            asked
+        });
+
+        assert!(service.lookup(instance, mdns::TYPE_SRV).await.is_empty());
+        assert!(service.request_missing_info(instance, Duration::from_secs(2)).await);
+        assert_eq!(service.lookup(instance, mdns::TYPE_SRV).await.len(), 1);
+        let asked = responder.await.unwrap();
+        assert!(asked.contains(&mdns::TYPE_TXT));
+        assert!(asked.contains(&mdns::TYPE_SRV));
+        assert!(asked.contains(&mdns::TYPE_A));
+    }
+
+    #[tokio::test]
+    async fn test_request_missing_info_gives_up() {
+        let (service, _sent) = detached();
+        let start = tokio::time::Instant::now();
+        assert!(!service.request_missing_info("none._matterc._udp.local.", Duration::from_millis(200)).await);
+        assert!(start.elapsed() >= Duration::from_millis(200));
+    }
+
+    fn registration(instance: &str) -> ServiceRegistration {
+        ServiceRegistration {
+            service_type: "_matter._tcp.local".to_owned(),
+            instance_name: instance.to_owned(),
+            port: 5540,
+            hostname: "host1.local".to_owned(),
+            txt_records: Vec::new(),
+            ttl: 120,
+            subtypes: Vec::new(),
+            ips_v4: Some(vec![Ipv4Addr::LOCALHOST]),
+            ips_v6: Some(Vec::new()),
+        }
+    }
+
+    /// Run receive loop of `service` on a loopback socket; returns its address.
+    async fn spawn_recv_loop(service: &MdnsService) -> std::net::SocketAddr {
+        let sock = Arc::new(UdpSocket::bind("127.0.0.1:0").await.unwrap());
+        let addr = sock.local_addr().unwrap();
+        tokio::spawn(recv_loop(
+            sock,
+            None,
+            service.inner.clone(),
+            service.send_tx.clone(),
+            service.event_tx.clone(),
+            service.cancel.child_token(),
+        ));
+        addr
+    }
+
+    #[tokio::test]
+    async fn test_own_announcement_not_discovered() {
+        let (service, _sent) = detached();
+        service.register_service(registration("OWN")).await;
+        let mut events = service.subscribe();
+
+        let addr = spawn_recv_loop(&service).await;
+        let peer = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        for instance in ["OWN", "OTHER"] {
+            let records = build_service_records(&registration(instance), &[Ipv4Addr::LOCALHOST], &[]);
+            let pkt = build_response(&records, &[]).unwrap();
+            peer.send_to(&pkt, addr).await.unwrap();
+        }
+
+        let event = tokio::time::timeout(Duration::from_secs(2), events.recv()).await.unwrap().unwrap();
+        match event {
+            MdnsEvent::ServiceDiscovered { target, .. } => {
+                assert_eq!(target, "OTHER._matter._tcp.local.")
+            }
+            other => panic!("unexpected event {:?}", other),
+        }
+        assert!(events.try_recv().is_err());
+        service.shutdown();
+    }
+
+    #[tokio::test]
+    async fn test_truncated_response_waits_for_continuation() {
+        let (service, _sent) = detached();
+        let mut events = service.subscribe();
+        let addr = spawn_recv_loop(&service).await;
+        let peer = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+
+        let records = build_service_records(&registration("DEV"), &[Ipv4Addr::LOCALHOST], &[]);
+        let (ptr, rest): (Vec<_>, Vec<_>) = records.into_iter().partition(|r| r.typ == mdns::TYPE_PTR);
+        let mut first = build_response(&ptr, &[]).unwrap();
+        first[2..4].copy_from_slice(&(0x8400 | mdns::FLAG_TC).to_be_bytes());
+        peer.send_to(&first, addr).await.unwrap();
+        assert!(tokio::time::timeout(Duration::from_millis(200), events.recv()).await.is_err());
+
+        peer.send_to(&build_response(&rest, &[]).unwrap(), addr).await.unwrap();
+        let event = tokio::time::timeout(Duration::from_secs(2), events.recv()).await.unwrap().unwrap();
+        match event {
+            MdnsEvent::ServiceDiscovered { target, records, .. } => {
+                assert_eq!(target, "DEV._matter._tcp.local.");
+                assert!(records.iter().any(|r| r.typ == mdns::TYPE_PTR));
+                assert!(records.iter().any(|r| r.typ == mdns::TYPE_SRV));
+            }
+            other => panic!("unexpected event {:?}", other),
+        }
+        assert!(service.inner.lock().await.truncated.is_empty());
+        service.shutdown();
+    }
+
+    #[tokio::test]
+    async fn test_clear_cache_and_refresh() {
+        let (service, mut sent) = detached();
+        let host = "host1.local.";
+        {
+            let mut state = service.inner.lock().await;
+            state.cache.ingest(&rr(host, mdns::TYPE_A, mdns::RRData::A(Ipv4Addr::new(192, 168, 1, 9))));
+            state.cache.ingest(&rr(host, mdns::TYPE_AAAA, mdns::RRData::AAAA("fe80::1".parse().unwrap())));
+            state.link_local_scopes.insert("fe80::1".parse().unwrap(), 3);
+        }
+        assert_eq!(service.lookup(host, mdns::QTYPE_ANY).await.len(), 2);
+        let mut events = service.subscribe();
+
+        service.clear_cache().await;
+        assert!(service.lookup(host, mdns::TYPE_A).await.is_empty());
+        assert!(service.lookup(host, mdns::QTYPE_ANY).await.is_empty());
+        assert!(service.scope_for(&"fe80::1".parse().unwrap()).await.is_none());
+        let mut expired = Vec::new();
+        while let Ok(MdnsEvent::ServiceExpired { rtype, .. }) = events.try_recv() {
+            expired.push(rtype);
+        }
+        expired.sort();
+        assert_eq!(expired, vec![mdns::TYPE_A, mdns::TYPE_AAAA]);
+
+        service.add_query("_matter._tcp.local", mdns::QTYPE_ANY, QuerySchedule::rfc6762()).await;
+        service.add_query("_matterc._udp.local", mdns::QTYPE_ANY, Duration::from_secs(60)).await;
+        while sent.try_recv().is_ok() {}
+        service.refresh().await;
+        let mut queried = Vec::new();
+        while let Ok(SendCommand::Multicast(pkt)) = sent.try_recv() {
+            let msg = mdns::parse_dns(&pkt, "127.0.0.1:5353".parse().unwrap()).unwrap();
+            queried.extend(msg.queries.into_iter().map(|q| q.name));
+        }
+        queried.sort();
+        assert_eq!(queried, vec!["_matter._tcp.local.", "_matterc._udp.local."]);
+    }
+}
@@ -1,7 +1,7 @@
 //! basic mDNS protocol: record caching, wire-format encoding, multicast sockets, send loop.
 
 use std::collections::HashMap;
-use std::net::{Ipv4Addr, Ipv6Addr, SocketAddrV4, SocketAddrV6};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddrV4, SocketAddrV6};
 use std::sync::Arc;
 use std::time::{Duration, Instant};
 
@@ -86,6 +86,11 @@ impl RecordCache {
         expired_keys
     }
 
+    /// Remove all entries. Returns list of (name, type) keys that were removed.
+    pub fn clear(&mut self) -> Vec<(String, u16)> {
+        self.entries.drain().map(|(key, _)| key).collect()
+    }
+
     /// Lookup non-expired records by exact (lowercase name, type).
     pub fn lookup(&self, name: &str, qtype: u16) -> Vec<mdns::RR> {
         let key = (name.to_lowercase(), qtype);
@@ -109,6 +114,43 @@ impl RecordCache {
             .flat_map(|(_, v)| v.iter().filter(|c| !c.is_expired()).map(|c| c.rr.clone()))
             .collect()
     }
+
+    /// Addresses of a host from cached A and AAAA records (IPv4 first).
+    /// The trailing dot of `hostname` is optional.
+    pub fn lookup_host(&self, hostname: &str) -> Vec<IpAddr> {
+        let name = fqdn(hostname);
+        let v4 = self.lookup(&name, mdns::TYPE_A).into_iter().filter_map(|rr| match rr.data {
+            mdns::RRData::A(ip) => Some(IpAddr::V4(ip)),
+            _ => None,
+        });
+        let v6 = self.lookup(&name, mdns::TYPE_AAAA).into_iter().filter_map(|rr| match rr.data {
+            mdns::RRData::AAAA(ip) => Some(IpAddr::V6(ip)),
+            _ => None,
+        });
+        v4.chain(v6).collect()
+    }
+
+    /// Whether any non-expired A or AAAA record points to `ip`.
+    pub fn has_address(&self, ip: &IpAddr) -> bool {
+        self.entries
+            .values()
+            .flatten()
+            .filter(|c| !c.is_expired())
+            .any(|c| match (&c.rr.data, ip) {
+                (mdns::RRData::A(a), IpAddr::V4(ip)) => a == ip,
+                (mdns::RRData::AAAA(a), IpAddr::V6(ip)) => a == ip,
+                _ => false,
+            })
+    }
+}
+
+/// Name with trailing dot, as stored in the cache.
+pub(super) fn fqdn(name: &str) -> String {
+    if name.ends_with('.') {
+        name.to_owned()
+    } else {
+        format!("{}.", name)
+    }
 }
 
 impl Default for RecordCache {
@@ -181,7 +223,8 @@ pub(super) fn build_response(answers: &[mdns::RR], additional: &[mdns::RR]) -> R
     Ok(out)
 }
 
-pub(super) fn create_multicast_socket_v4() -> Result<std::net::UdpSocket> {
+/// IPv4 mDNS socket joined on interface with address `interface` (unspecified = default interface).
+pub(super) fn create_multicast_socket_v4(interface: Ipv4Addr) -> Result<std::net::UdpSocket> {
     let sock = socket2::Socket::new(Domain::IPV4, Type::DGRAM, Some(Protocol::UDP))?;
     sock.set_reuse_address(true)?;
     #[cfg(not(target_os = "windows"))]
@@ -189,7 +232,10 @@ pub(super) fn create_multicast_socket_v4() -> Result<std::net::UdpSocket> {
     let addr: SocketAddrV4 = "0.0.0.0:5353".parse()?;
     sock.bind(&socket2::SockAddr::from(addr))?;
     let maddr: Ipv4Addr = "224.0.0.251".parse()?;
-    sock.join_multicast_v4(&maddr, &Ipv4Addr::UNSPECIFIED)?;
+    sock.join_multicast_v4(&maddr, &interface)?;
+    if !interface.is_unspecified() {
+        sock.set_multicast_if_v4(&interface)?;
+    }
     sock.set_nonblocking(true)?;
     Ok(sock.into())
 }
@@ -266,3 +312,35 @@ pub(super) async fn send_loop(
     }
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rr(name: &str, typ: u16, ttl: u32, data: mdns::RRData) -> mdns::RR {
+        let rdata = match &data {
+            mdns::RRData::A(ip) => ip.octets().to_vec(),
+            mdns::RRData::AAAA(ip) => ip.octets().to_vec(),
+            _ => Vec::new(),
+        };
+        mdns::RR { name: name.to_owned(), typ, class: 1, ttl, rdata, target: None, data }
+    }
+
+    #[test]
+    fn test_lookup_host() {
+        let mut cache = RecordCache::new();
+        let v4 = Ipv4Addr::new(192, 168, 1, 50);
+        let v6: Ipv6Addr = "fe80::1234".parse().unwrap();
+        cache.ingest(&rr("ABCD1234.local.", mdns::TYPE_AAAA, 120, mdns::RRData::AAAA(v6)));
+        cache.ingest(&rr("ABCD1234.local.", mdns::TYPE_A, 120, mdns::RRData::A(v4)));
+        cache.ingest(&rr("other.local.", mdns::TYPE_A, 120, mdns::RRData::A(Ipv4Addr::new(10, 0, 0, 1))));
+
+        let want = vec![IpAddr::V4(v4), IpAddr::V6(v6)];
+        assert_eq!(cache.lookup_host("ABCD1234.local"), want);
+        assert_eq!(cache.lookup_host("abcd1234.local."), want);
+        assert!(cache.lookup_host("missing.local").is_empty());
+
+        // goodbye record removes the address
+        cache.ingest(&rr("ABCD1234.local.", mdns::TYPE_A, 0, mdns::RRData::A(v4)));
+        assert_eq!(cache.lookup_host("ABCD1234.local"), vec![IpAddr::V6(v6)]);
+    }
+}
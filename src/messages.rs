@@ -130,6 +130,7 @@ impl ProtocolMessageHeader {
     pub const OPCODE_CASE_SIGMA1: u8 = 0x30;
     pub const OPCODE_CASE_SIGMA2: u8 = 0x31;
     pub const OPCODE_CASE_SIGMA3: u8 = 0x32;
+    pub const OPCODE_CASE_SIGMA2_RESUME: u8 = 0x33;
     pub const OPCODE_STATUS: u8 = 0x40;
 
     pub const INTERACTION_OPCODE_STATUS_RESP:    u8 = 0x1;
@@ -433,6 +434,13 @@ pub fn ack(exchange: u16, ack: i64) -> Result<Vec<u8>> {
 }
 
 pub fn pbkdf_req(exchange: u16) -> Result<Vec<u8>> {
+    pbkdf_req_params(exchange, 1, false)
+}
+
+/// PBKDFParamRequest proposing `session_id` as initiator session id; with
+/// `has_pbkdf_parameters` the initiator tells the device it already knows
+/// iterations and salt, so the response will not carry them.
+pub fn pbkdf_req_params(exchange: u16, session_id: u16, has_pbkdf_parameters: bool) -> Result<Vec<u8>> {
     let mut b = ProtocolMessageHeader {
         exchange_flags: ProtocolMessageHeader::FLAG_INITIATOR
             | ProtocolMessageHeader::FLAG_RELIABILITY,
@@ -447,14 +455,31 @@ pub fn pbkdf_req(exchange: u16) -> Result<Vec<u8>> {
     let mut initiator_random = [0u8; 32];
     rand::thread_rng().fill_bytes(&mut initiator_random);
     tlv.write_octetstring(0x1, &initiator_random)?;
-    tlv.write_uint16(2, 1)?;
+    tlv.write_uint16(2, session_id)?;
     tlv.write_uint8(3, 0)?;
-    tlv.write_bool(4, false)?;
+    tlv.write_bool(4, has_pbkdf_parameters)?;
     tlv.write_struct_end()?;
     b.write_all(&tlv.data)?;
     Ok(b)
 }
 
+/// Secure channel CloseSession status report. Sent without reliability flag - peer
+/// tears the session down and would not acknowledge it.
+pub fn close_session(exchange: u16) -> Result<Vec<u8>> {
+    let mut b = ProtocolMessageHeader {
+        exchange_flags: ProtocolMessageHeader::FLAG_INITIATOR,
+        opcode: ProtocolMessageHeader::OPCODE_STATUS,
+        exchange_id: exchange,
+        protocol_id: ProtocolMessageHeader::PROTOCOL_ID_SECURE_CHANNEL,
+        ack_counter: 0,
+    }
+    .encode()?;
+    b.write_u16::<LittleEndian>(0)?; // general code SUCCESS
+    b.write_u32::<LittleEndian>(ProtocolMessageHeader::PROTOCOL_ID_SECURE_CHANNEL as u32)?;
+    b.write_u16::<LittleEndian>(SecureChannelProtocolCode::CloseSession as u16)?;
+    Ok(b)
+}
+
 pub fn pake1(exchange: u16, key: &[u8], ack: i64) -> Result<Vec<u8>> {
     let mut flags = ProtocolMessageHeader::FLAG_INITIATOR | ProtocolMessageHeader::FLAG_RELIABILITY;
     if ack >= 0 {
@@ -552,6 +577,21 @@ pub fn im_invoke_request(
     exchange_id: u16,
     payload: &[u8],
     timed: bool,
+) -> Result<Vec<u8>> {
+    im_invoke_request_flags(endpoint, cluster, command, exchange_id, payload, timed, false)
+}
+
+/// Same as [im_invoke_request] with explicit SuppressResponse flag.
+/// When set, device executes the command without sending an InvokeResponse.
+#[allow(clippy::too_many_arguments)]
+pub fn im_invoke_request_flags(
+    endpoint: u16,
+    cluster: u32,
+    command: u32,
+    exchange_id: u16,
+    payload: &[u8],
+    timed: bool,
+    suppress_response: bool,
 ) -> Result<Vec<u8>> {
     let b = ProtocolMessageHeader {
         exchange_flags: 5,
@@ -564,7 +604,7 @@ pub fn im_invoke_request(
 
     let mut tlv = tlv::TlvBuffer::from_vec(b);
     tlv.write_anon_struct()?;
-    tlv.write_bool(0x0, false)?;
+    tlv.write_bool(0x0, suppress_response)?;
     tlv.write_bool(0x1, timed)?; // timed
     tlv.write_array(2)?;
     tlv.write_anon_struct()?;
@@ -583,6 +623,61 @@ pub fn im_invoke_request(
     Ok(tlv.data)
 }
 
+/// Batched InvokeRequest: one CommandDataIB per `(endpoint, cluster, command)` path,
+/// all with the same `payload`. CommandRef is the index into `paths`. Devices accept
+/// up to MaxPathsPerInvoke (Basic Information) paths in one request.
+pub fn im_invoke_request_batch(
+    paths: &[(u16, u32, u32)],
+    exchange_id: u16,
+    payload: &[u8],
+) -> Result<Vec<u8>> {
+    let b = ProtocolMessageHeader {
+        exchange_flags: 5,
+        opcode: ProtocolMessageHeader::INTERACTION_OPCODE_INVOKE_REQ,
+        exchange_id,
+        protocol_id: ProtocolMessageHeader::PROTOCOL_ID_INTERACTION,
+        ack_counter: 0,
+    }
+    .encode()?;
+
+    let mut tlv = tlv::TlvBuffer::from_vec(b);
+    tlv.write_anon_struct()?;
+    tlv.write_bool(0x0, false)?;
+    tlv.write_bool(0x1, false)?; // timed
+    tlv.write_array(2)?;
+    for (i, (endpoint, cluster, command)) in paths.iter().enumerate() {
+        tlv.write_anon_struct()?;
+        tlv.write_list(0)?;
+        tlv.write_uint16(0, *endpoint)?;
+        tlv.write_uint32(1, *cluster)?;
+        tlv.write_uint32(2, *command)?;
+        tlv.write_struct_end()?;
+        tlv.write_struct(1)?;
+        tlv.write_raw(payload)?;
+        tlv.write_struct_end()?;
+        tlv.write_uint16(2, i as u16)?; // CommandRef
+        tlv.write_struct_end()?;
+    }
+    tlv.write_struct_end()?;
+    tlv.write_uint8(0xff, 10)?;
+    tlv.write_struct_end()?;
+    Ok(tlv.data)
+}
+
+/// Interaction model message with caller supplied opcode and pre-encoded TLV payload.
+pub fn im_raw_request(opcode: u8, exchange_id: u16, payload: &[u8]) -> Result<Vec<u8>> {
+    let mut b = ProtocolMessageHeader {
+        exchange_flags: 5,
+        opcode,
+        exchange_id,
+        protocol_id: ProtocolMessageHeader::PROTOCOL_ID_INTERACTION,
+        ack_counter: 0,
+    }
+    .encode()?;
+    b.extend_from_slice(payload);
+    Ok(b)
+}
+
 pub fn im_timed_request(exchange_id: u16, timeout: u16) -> Result<Vec<u8>> {
     let b = ProtocolMessageHeader {
         exchange_flags: 5,
@@ -602,6 +697,30 @@ pub fn im_timed_request(exchange_id: u16, timeout: u16) -> Result<Vec<u8>> {
 }
 
 pub fn im_read_request(endpoint: u16, cluster: u32, attr: u32, exchange: u16) -> Result<Vec<u8>> {
+    im_read_request_path(endpoint, cluster, Some(attr), true, exchange)
+}
+
+/// ReadRequest for one attribute path; `attr: None` wildcards the attribute id
+/// so every attribute of the cluster on the endpoint is reported.
+/// With `fabric_filtered` false, fabric-scoped lists (ACL, Fabrics, Bindings)
+/// include entries of other fabrics as far as the device exposes them.
+pub fn im_read_request_path(
+    endpoint: u16,
+    cluster: u32,
+    attr: Option<u32>,
+    fabric_filtered: bool,
+    exchange: u16,
+) -> Result<Vec<u8>> {
+    im_read_request_paths(&[(endpoint, cluster, attr)], fabric_filtered, exchange)
+}
+
+/// ReadRequest for several `(endpoint, cluster, attribute)` paths in one interaction.
+/// Attribute `None` wildcards the attribute id as in [im_read_request_path].
+pub fn im_read_request_paths(
+    paths: &[(u16, u32, Option<u32>)],
+    fabric_filtered: bool,
+    exchange: u16,
+) -> Result<Vec<u8>> {
     let b = ProtocolMessageHeader {
         exchange_flags: 5,
         opcode: ProtocolMessageHeader::INTERACTION_OPCODE_READ_REQ,
@@ -614,19 +733,74 @@ pub fn im_read_request(endpoint: u16, cluster: u32, attr: u32, exchange: u16) ->
     let mut tlv = tlv::TlvBuffer::from_vec(b);
     tlv.write_anon_struct()?;
     tlv.write_array(0)?;
-    tlv.write_anon_list()?;
+    for (endpoint, cluster, attr) in paths {
+        tlv.write_anon_list()?;
+        tlv.write_uint16(2, *endpoint)?;
+        tlv.write_uint32(3, *cluster)?;
+        if let Some(attr) = attr {
+            tlv.write_uint32(4, *attr)?;
+        }
+        tlv.write_struct_end()?;
+    }
+    tlv.write_struct_end()?;
+    tlv.write_bool(3, fabric_filtered)?;
+    tlv.write_uint8(0xff, 10)?;
+    tlv.write_struct_end()?;
+    Ok(tlv.data)
+}
+
+/// Largest Matter message carried over UDP: IPv6 minimum MTU (1280) minus IPv6 and UDP headers.
+pub const MAX_UDP_MESSAGE_SIZE: usize = 1232;
+
+/// Bytes a secure session adds to an application payload: message header with
+/// source and destination node ids, plus MIC.
+pub const SECURE_MESSAGE_OVERHEAD: usize = 40;
+
+/// Attribute value does not fit into one message and can't be chunked: it is not a
+/// list, or a single list item is too large. Would need a transport without the
+/// UDP size limit (TCP), which is not supported.
+/// Detect with `err.downcast_ref::<messages::WriteTooLarge>()`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WriteTooLarge {
+    /// Encoded size of the value (or list item) being written.
+    pub size: usize,
+    /// Maximum message size the write had to fit in.
+    pub max_message_size: usize,
+}
+
+impl std::fmt::Display for WriteTooLarge {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "attribute data of {} bytes does not fit into message of {} bytes (TCP transport required)",
+            self.size, self.max_message_size
+        )
+    }
+}
+
+impl std::error::Error for WriteTooLarge {}
+
+/// Encode AttributeDataIB writing `data` (element with context tag 2) to attribute.
+/// `append` adds ListIndex null to the path: `data` is appended to the list.
+fn attribute_data_ib(endpoint: u16, cluster: u32, attr: u32, append: bool, data: &[u8]) -> Result<Vec<u8>> {
+    let mut tlv = tlv::TlvBuffer::new();
+    tlv.write_anon_struct()?;
+    //tlv.write_uint32(0, 0)?; // dataversion
+    tlv.write_list(1)?;
     tlv.write_uint16(2, endpoint)?;
     tlv.write_uint32(3, cluster)?;
     tlv.write_uint32(4, attr)?;
+    if append {
+        tlv.write_null(5)?; // ListIndex
+    }
     tlv.write_struct_end()?;
-    tlv.write_struct_end()?;
-    tlv.write_bool(3, true)?;
-    tlv.write_uint8(0xff, 10)?;
+    tlv.write_raw(data)?;
     tlv.write_struct_end()?;
     Ok(tlv.data)
 }
 
-pub fn im_write_request(endpoint: u16, cluster: u32, attr: u32, exchange: u16, data: &[u8]) -> Result<Vec<u8>> {
+/// WriteRequest with encoded AttributeDataIBs `ibs`.
+fn write_request_message(exchange: u16, ibs: &[Vec<u8>], more_chunks: bool) -> Result<Vec<u8>> {
     let b = ProtocolMessageHeader {
         exchange_flags: 5,
         opcode: ProtocolMessageHeader::INTERACTION_OPCODE_WRITE_REQ,
@@ -641,26 +815,88 @@ pub fn im_write_request(endpoint: u16, cluster: u32, attr: u32, exchange: u16, d
     //tlv.write_bool(0, false)?;
     tlv.write_bool(1, false)?; // timed
     tlv.write_array(2)?;
-    tlv.write_anon_struct()?;
-    //tlv.write_uint32(0, 0)?; // dataversion
-    tlv.write_list(1)?;
-    tlv.write_uint16(2, endpoint)?;
-    tlv.write_uint32(3, cluster)?;
-    tlv.write_uint32(4, attr)?;
-    tlv.write_struct_end()?;
-    tlv.write_raw(data)?;
-    tlv.write_struct_end()?;
+    for ib in ibs {
+        tlv.write_raw(ib)?;
+    }
     tlv.write_struct_end()?;
-    tlv.write_bool(3, false)?;
+    tlv.write_bool(3, more_chunks)?; // MoreChunkedMessages
     tlv.write_uint8(0xff, 10)?; // InteractionModelRevision
     tlv.write_struct_end()?;
     Ok(tlv.data)
 }
 
+pub fn im_write_request(endpoint: u16, cluster: u32, attr: u32, exchange: u16, data: &[u8]) -> Result<Vec<u8>> {
+    let ib = attribute_data_ib(endpoint, cluster, attr, false, data)?;
+    write_request_message(exchange, &[ib], false)
+}
+
+/// WriteRequests writing `data` to attribute such that no message exceeds
+/// `max_message_size` bytes (including [SECURE_MESSAGE_OVERHEAD]); all are sent on one exchange.
+///
+/// A value which fits is a single [im_write_request]. A larger list value is chunked:
+/// the first AttributeDataIB replaces the list with an empty one, the following ones
+/// append one item each (ListIndex null). All messages but the last carry
+/// MoreChunkedMessages. Other values fail with [WriteTooLarge].
+pub fn im_write_request_chunked(
+    endpoint: u16,
+    cluster: u32,
+    attr: u32,
+    exchange: u16,
+    data: &[u8],
+    max_message_size: usize,
+) -> Result<Vec<Vec<u8>>> {
+    let single = im_write_request(endpoint, cluster, attr, exchange, data)?;
+    if single.len() + SECURE_MESSAGE_OVERHEAD <= max_message_size {
+        return Ok(vec![single]);
+    }
+    let too_large = |size| WriteTooLarge { size, max_message_size };
+    let items = tlv::split_array(data)?.ok_or_else(|| too_large(data.len()))?;
+
+    let budget = max_message_size.saturating_sub(SECURE_MESSAGE_OVERHEAD);
+    let base = write_request_message(exchange, &[], true)?.len();
+    let mut empty = tlv::TlvBuffer::new();
+    empty.write_array(2)?;
+    empty.write_struct_end()?;
+    let replace = attribute_data_ib(endpoint, cluster, attr, false, &empty.data)?;
+    let mut size = base + replace.len();
+    let mut chunks = vec![vec![replace]];
+    for item in items {
+        let ib = attribute_data_ib(endpoint, cluster, attr, true, &tlv::with_context_tag(item, 2)?)?;
+        if base + ib.len() > budget {
+            return Err(too_large(item.len()).into());
+        }
+        if size + ib.len() > budget {
+            chunks.push(Vec::new());
+            size = base;
+        }
+        size += ib.len();
+        chunks.last_mut().unwrap().push(ib);
+    }
+    let count = chunks.len();
+    chunks
+        .iter()
+        .enumerate()
+        .map(|(i, ibs)| write_request_message(exchange, ibs, i + 1 < count))
+        .collect()
+}
+
 /// Build a SubscribeRequest for an attribute path (AttributeRequests, tag 3).
 /// `keep_subscriptions`: if true the device keeps existing subscriptions alive;
 /// if false the device cancels all prior subscriptions before creating this one.
 pub fn im_subscribe_request_attr(endpoint: Option<u16>, cluster: Option<u32>, attr: Option<u32>, exchange: u16, keep_subscriptions: bool) -> Result<Vec<u8>> {
+    im_subscribe_request_attr_intervals(endpoint, cluster, attr, exchange, keep_subscriptions, 10, 30)
+}
+
+/// Like [im_subscribe_request_attr] with explicit MinIntervalFloor / MaxIntervalCeiling (seconds).
+pub fn im_subscribe_request_attr_intervals(
+    endpoint: Option<u16>,
+    cluster: Option<u32>,
+    attr: Option<u32>,
+    exchange: u16,
+    keep_subscriptions: bool,
+    min_interval: u16,
+    max_interval: u16,
+) -> Result<Vec<u8>> {
     let b = ProtocolMessageHeader {
         exchange_flags: 5,
         opcode: ProtocolMessageHeader::INTERACTION_OPCODE_SUBSCRIBE_REQ,
@@ -673,8 +909,8 @@ pub fn im_subscribe_request_attr(endpoint: Option<u16>, cluster: Option<u32>, at
     let mut tlv = tlv::TlvBuffer::from_vec(b);
     tlv.write_anon_struct()?;
     tlv.write_bool(0, keep_subscriptions)?; // KeepSubscriptions
-    tlv.write_uint16(1, 10)?;       // MinIntervalFloor
-    tlv.write_uint16(2, 30)?;       // MaxIntervalCeiling
+    tlv.write_uint16(1, min_interval)?; // MinIntervalFloor
+    tlv.write_uint16(2, max_interval)?; // MaxIntervalCeiling
     tlv.write_array(3)?;            // AttributeRequests
 
     tlv.write_anon_list()?;
@@ -792,22 +1028,107 @@ pub fn im_status_response(exchange: u16, flags: u8, ack: u32) -> Result<Vec<u8>>
     Ok(tlv.data)
 }
 
+/// Interaction model status code (StatusIB Status, spec 8.10).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ImStatusCode(pub u8);
+
+impl ImStatusCode {
+    pub const SUCCESS: ImStatusCode = ImStatusCode(0x00);
+    pub const FAILURE: ImStatusCode = ImStatusCode(0x01);
+    pub const UNSUPPORTED_ACCESS: ImStatusCode = ImStatusCode(0x7e);
+    pub const INVALID_ACTION: ImStatusCode = ImStatusCode(0x80);
+    pub const UNSUPPORTED_COMMAND: ImStatusCode = ImStatusCode(0x81);
+    pub const UNSUPPORTED_ATTRIBUTE: ImStatusCode = ImStatusCode(0x86);
+    pub const CONSTRAINT_ERROR: ImStatusCode = ImStatusCode(0x87);
+    pub const BUSY: ImStatusCode = ImStatusCode(0x9c);
+    pub const UNSUPPORTED_CLUSTER: ImStatusCode = ImStatusCode(0xc3);
+    pub const NEEDS_TIMED_INTERACTION: ImStatusCode = ImStatusCode(0xc6);
+    pub const TIMED_REQUEST_MISMATCH: ImStatusCode = ImStatusCode(0xc9);
+    pub const FAILSAFE_REQUIRED: ImStatusCode = ImStatusCode(0xca);
+
+    pub fn is_success(&self) -> bool {
+        self.0 == 0
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self.0 {
+            0x00 => "SUCCESS",
+            0x01 => "FAILURE",
+            0x7d => "INVALID_SUBSCRIPTION",
+            0x7e => "UNSUPPORTED_ACCESS",
+            0x7f => "UNSUPPORTED_ENDPOINT",
+            0x80 => "INVALID_ACTION",
+            0x81 => "UNSUPPORTED_COMMAND",
+            0x85 => "INVALID_COMMAND",
+            0x86 => "UNSUPPORTED_ATTRIBUTE",
+            0x87 => "CONSTRAINT_ERROR",
+            0x88 => "UNSUPPORTED_WRITE",
+            0x89 => "RESOURCE_EXHAUSTED",
+            0x8b => "NOT_FOUND",
+            0x8c => "UNREPORTABLE_ATTRIBUTE",
+            0x8d => "INVALID_DATA_TYPE",
+            0x8f => "UNSUPPORTED_READ",
+            0x92 => "DATA_VERSION_MISMATCH",
+            0x94 => "TIMEOUT",
+            0x9c => "BUSY",
+            0x9d => "ACCESS_RESTRICTED",
+            0xc3 => "UNSUPPORTED_CLUSTER",
+            0xc5 => "NO_UPSTREAM_SUBSCRIPTION",
+            0xc6 => "NEEDS_TIMED_INTERACTION",
+            0xc7 => "UNSUPPORTED_EVENT",
+            0xc8 => "PATHS_EXHAUSTED",
+            0xc9 => "TIMED_REQUEST_MISMATCH",
+            0xca => "FAILSAFE_REQUIRED",
+            0xcb => "INVALID_IN_STATE",
+            0xcc => "NO_COMMAND_RESPONSE",
+            _ => "UNKNOWN",
+        }
+    }
+}
+
+impl fmt::Display for ImStatusCode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} (0x{:02x})", self.name(), self.0)
+    }
+}
+
+/// Parse status of an IM StatusResponse message.
+/// Fails when `msg` is not a StatusResponse or status is missing.
+pub fn parse_status_response(msg: &Message) -> Result<ImStatusCode> {
+    if msg.protocol_header.protocol_id != ProtocolMessageHeader::PROTOCOL_ID_INTERACTION
+        || msg.protocol_header.opcode != ProtocolMessageHeader::INTERACTION_OPCODE_STATUS_RESP
+    {
+        anyhow::bail!("not a status response: {:?}", msg.protocol_header);
+    }
+    let status = msg
+        .tlv
+        .get_int(&[0])
+        .context("status not found in status response")?;
+    Ok(ImStatusCode(status as u8))
+}
+
+/// Overall (status, cluster status) of an InvokeResponseMessage. All InvokeResponseIBs
+/// are checked: the first failing status is returned, (0, 0) when every entry is a
+/// success status or response data.
 pub fn parse_im_invoke_resp(resp: &TlvItem) -> Result<(u32, u32)> {
-    let common_status = resp
-        .get_int(&[1, 0, 1, 1, 0])
-        .context("parse_im_invoke_resp: status not found")?;
-    if common_status == 0 {
-        return Ok((0, 0));
+    let responses = crate::im::parse_invoke_responses(resp);
+    if responses.is_empty() {
+        return Err(anyhow::anyhow!("parse_im_invoke_resp: status not found"));
+    }
+    for r in responses {
+        if let crate::im::InvokeResponse::Status { status, cluster_status, .. } = r {
+            if status != 0 {
+                let stat = cluster_status.context("parse_im_invoke_resp: unexpected response")?;
+                return Ok((status as u32, stat as u32));
+            }
+        }
     }
-    let stat = resp
-        .get_int(&[1, 0, 1, 1, 1])
-        .context("parse_im_invoke_resp: unexpected response")?;
-    Ok((common_status as u32, stat as u32))
+    Ok((0, 0))
 }
 
 #[cfg(test)]
 mod tests {
-    use super::Message;
+    use super::{Message, ProtocolMessageHeader};
 
     #[test]
     pub fn test_1() {
@@ -821,4 +1142,132 @@ mod tests {
         let m = Message::decode(&msg).unwrap();
         println!("{:?}", m);
     }
+
+    #[test]
+    fn decode_rejects_pathological_tlv() {
+        // unsecured PBKDFParamRequest header followed by array of many one byte elements
+        let mut msg =
+            hex::decode("04000000000000000000000000000000012001000000").unwrap();
+        msg.push(0x16);
+        msg.extend(vec![0x08; crate::tlv::MAX_DECODE_ELEMENTS * 4]);
+        msg.push(0x18);
+        assert!(Message::decode(&msg).is_err());
+
+        // nested empty arrays
+        let mut msg =
+            hex::decode("04000000000000000000000000000000012001000000").unwrap();
+        msg.extend(vec![0x16; 600]);
+        msg.extend(vec![0x18; 600]);
+        assert!(Message::decode(&msg).is_err());
+    }
+
+    fn decode_protocol_message(data: &[u8]) -> Message {
+        let encoded = crate::session::Session::new().encode_message(data).unwrap();
+        Message::decode(&encoded).unwrap()
+    }
+
+    fn status_response_with(status: u8) -> Message {
+        let mut t = crate::tlv::TlvBuffer::from_vec(
+            ProtocolMessageHeader {
+                exchange_flags: 0,
+                opcode: ProtocolMessageHeader::INTERACTION_OPCODE_STATUS_RESP,
+                exchange_id: 7,
+                protocol_id: ProtocolMessageHeader::PROTOCOL_ID_INTERACTION,
+                ack_counter: 0,
+            }
+            .encode()
+            .unwrap(),
+        );
+        t.write_anon_struct().unwrap();
+        t.write_uint8(0, status).unwrap();
+        t.write_uint8(0xff, 10).unwrap();
+        t.write_struct_end().unwrap();
+        decode_protocol_message(&t.data)
+    }
+
+    #[test]
+    fn test_parse_status_response() {
+        let ok = status_response_with(0);
+        let st = super::parse_status_response(&ok).unwrap();
+        assert!(st.is_success());
+        assert_eq!(st, super::ImStatusCode::SUCCESS);
+
+        let failed = status_response_with(0xc6);
+        let st = super::parse_status_response(&failed).unwrap();
+        assert!(!st.is_success());
+        assert_eq!(st, super::ImStatusCode::NEEDS_TIMED_INTERACTION);
+        assert_eq!(st.to_string(), "NEEDS_TIMED_INTERACTION (0xc6)");
+        assert_eq!(super::ImStatusCode(0x42).name(), "UNKNOWN");
+
+        let other = decode_protocol_message(&super::im_timed_request(7, 100).unwrap());
+        assert!(super::parse_status_response(&other).is_err());
+    }
+
+    #[test]
+    fn test_read_request_fabric_filtered_flag() {
+        let filtered = super::im_read_request_path(1, 0x1f, Some(0), true, 7).unwrap();
+        let unfiltered = super::im_read_request_path(1, 0x1f, Some(0), false, 7).unwrap();
+        assert_eq!(filtered.len(), unfiltered.len());
+        let diff: Vec<usize> = (0..filtered.len()).filter(|&i| filtered[i] != unfiltered[i]).collect();
+        assert_eq!(diff.len(), 1, "only the fabricFiltered control byte should differ");
+
+        for (data, want) in [(&filtered, true), (&unfiltered, false)] {
+            let (_, rest) = ProtocolMessageHeader::decode(data).unwrap();
+            let tlv = crate::tlv::decode_tlv(&rest).unwrap();
+            assert_eq!(tlv.get_bool(&[3]), Some(want));
+        }
+    }
+
+    #[test]
+    fn test_write_request_chunked() {
+        let item = [0xab; 40];
+        let mut t = crate::tlv::TlvBuffer::new();
+        t.write_array(2).unwrap();
+        for i in 0..100u8 {
+            let mut item = item;
+            item[0] = i;
+            t.write_octetstring_notag(&item).unwrap();
+        }
+        t.write_struct_end().unwrap();
+
+        let max = super::MAX_UDP_MESSAGE_SIZE;
+        let chunks = super::im_write_request_chunked(1, 0x1f, 0, 9, &t.data, max).unwrap();
+        assert!(chunks.len() >= 4, "got {} chunks", chunks.len());
+        let mut appended = Vec::new();
+        for (i, chunk) in chunks.iter().enumerate() {
+            assert!(chunk.len() + super::SECURE_MESSAGE_OVERHEAD <= max);
+            let (header, rest) = ProtocolMessageHeader::decode(chunk).unwrap();
+            assert_eq!(header.exchange_id, 9);
+            let tlv = crate::tlv::decode_tlv(&rest).unwrap();
+            assert_eq!(tlv.get_bool(&[3]), Some(i + 1 < chunks.len()));
+            let Some(crate::tlv::TlvItemValue::List(ibs)) = tlv.get(&[2]) else {
+                panic!("no AttributeDataIBs")
+            };
+            for (j, ib) in ibs.iter().enumerate() {
+                assert_eq!(ib.get_int(&[1, 4]), Some(0));
+                if i == 0 && j == 0 {
+                    // replace with empty list, no ListIndex
+                    assert!(ib.get(&[1, 5]).is_none());
+                    assert!(ib.get(&[2]) == Some(&crate::tlv::TlvItemValue::List(Vec::new())));
+                } else {
+                    assert!(ib.get(&[1, 5]) == Some(&crate::tlv::TlvItemValue::Nil()));
+                    appended.push(ib.get_octet_string(&[2]).unwrap()[0]);
+                }
+            }
+        }
+        assert_eq!(appended, (0..100).collect::<Vec<u8>>());
+
+        // fits: unchanged single request
+        let small = super::im_write_request_chunked(1, 0x1f, 0, 9, &t.data, 8192).unwrap();
+        assert_eq!(small, [super::im_write_request(1, 0x1f, 0, 9, &t.data).unwrap()]);
+
+        // a value which is not a list can't be chunked
+        let mut big = crate::tlv::TlvBuffer::new();
+        big.write_octetstring(2, &[0; 2000]).unwrap();
+        let err = super::im_write_request_chunked(0, 0x35, 0, 9, &big.data, max).unwrap_err();
+        assert_eq!(
+            err.downcast_ref::<super::WriteTooLarge>(),
+            Some(&super::WriteTooLarge { size: big.data.len(), max_message_size: max })
+        );
+    }
 }
@@ -23,7 +23,9 @@ pub const MRP_MAX_TRANSMISSIONS: u32 = 5;
 pub const MRP_BACKOFF_MARGIN: f64 = 1.1;
 pub const MRP_BACKOFF_BASE: f64 = 1.6;
 pub const MRP_BACKOFF_THRESHOLD: u32 = 1;
-pub const MRP_BACKOFF_JITTER: f64 = 0.25;
+pub const MRP_BACKOFF_JITTER: f64 = MRP_BACKOFF_JITTER_PERCENT as f64 / 100.0;
+/// [`MRP_BACKOFF_JITTER`] in percent, default of [`MrpParameters::backoff_jitter_percent`].
+pub const MRP_BACKOFF_JITTER_PERCENT: u16 = 25;
 /// Spec cap for advertised SII/SAI values (milliseconds).
 pub const MRP_MAX_INTERVAL_MS: u32 = 3_600_000;
 
@@ -40,6 +42,9 @@ pub struct MrpParameters {
     pub session_active_interval: Duration,
     /// SAT - how long after the last received message the peer counts as active.
     pub session_active_threshold: Duration,
+    /// Maximum random extension of each retransmission interval in percent of it
+    /// (JITTER in the backoff formula, spec value [`MRP_BACKOFF_JITTER_PERCENT`]).
+    pub backoff_jitter_percent: u16,
 }
 
 impl Default for MrpParameters {
@@ -48,6 +53,7 @@ impl Default for MrpParameters {
             session_idle_interval: Duration::from_millis(DEFAULT_IDLE_INTERVAL_MS as u64),
             session_active_interval: Duration::from_millis(DEFAULT_ACTIVE_INTERVAL_MS as u64),
             session_active_threshold: Duration::from_millis(DEFAULT_ACTIVE_THRESHOLD_MS as u64),
+            backoff_jitter_percent: MRP_BACKOFF_JITTER_PERCENT,
         }
     }
 }
@@ -68,10 +74,56 @@ impl MrpParameters {
             session_active_threshold: Duration::from_millis(
                 sat.unwrap_or(DEFAULT_ACTIVE_THRESHOLD_MS) as u64,
             ),
+            backoff_jitter_percent: MRP_BACKOFF_JITTER_PERCENT,
+        }
+    }
+
+    /// These parameters with intervals the peer included in `session` replacing
+    /// the current ones; SII/SAI are clamped to [`MRP_MAX_INTERVAL_MS`].
+    pub fn merge(self, session: &SessionParams) -> Self {
+        let clamp = |v: u32| Duration::from_millis(v.min(MRP_MAX_INTERVAL_MS) as u64);
+        Self {
+            session_idle_interval: session.idle_interval_ms.map_or(self.session_idle_interval, clamp),
+            session_active_interval: session.active_interval_ms.map_or(self.session_active_interval, clamp),
+            session_active_threshold: session
+                .active_threshold_ms
+                .map_or(self.session_active_threshold, |v| Duration::from_millis(v as u64)),
+            ..self
         }
     }
 }
 
+/// Session parameters a peer sent during session establishment. Each interval is
+/// optional in the handshake; absent ones leave the current value in place
+/// ([`MrpParameters::merge`]).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SessionParams {
+    /// SII in milliseconds.
+    pub idle_interval_ms: Option<u32>,
+    /// SAI in milliseconds.
+    pub active_interval_ms: Option<u32>,
+    /// SAT in milliseconds.
+    pub active_threshold_ms: Option<u16>,
+}
+
+impl SessionParams {
+    /// Parse session-parameter-struct at `tag` of a handshake message
+    /// (PBKDFParamResponse / Sigma2 tag 5, Sigma2Resume tag 4).
+    /// Inside the struct SII is tag 1, SAI tag 2, SAT tag 3 (milliseconds).
+    /// Returns None when the responder did not include the struct.
+    pub fn decode(tlv: &crate::tlv::TlvItem, tag: u8) -> Option<Self> {
+        let params = tlv.get_item(&[tag])?;
+        if !matches!(params.value, crate::tlv::TlvItemValue::List(_)) {
+            return None;
+        }
+        Some(Self {
+            idle_interval_ms: params.get_u32(&[1]),
+            active_interval_ms: params.get_u32(&[2]),
+            active_threshold_ms: params.get_u16(&[3]),
+        })
+    }
+}
+
 /// Base retransmission interval: the peer's active interval if it was heard
 /// from within the active threshold, otherwise its idle interval.
 pub fn base_interval(params: &MrpParameters, last_rx_elapsed: Option<Duration>) -> Duration {
@@ -84,13 +136,14 @@ pub fn base_interval(params: &MrpParameters, last_rx_elapsed: Option<Duration>)
 }
 
 /// Wait time before the next retransmission per spec 4.12 backoff formula.
-/// `retransmission_index` is 0 for the wait after the initial transmission.
-pub fn backoff_interval(base: Duration, retransmission_index: u32) -> Duration {
+/// `retransmission_index` is 0 for the wait after the initial transmission,
+/// `jitter_percent` is [`MrpParameters::backoff_jitter_percent`].
+pub fn backoff_interval(base: Duration, retransmission_index: u32, jitter_percent: u16) -> Duration {
     let exponent = retransmission_index.saturating_sub(MRP_BACKOFF_THRESHOLD);
     let t = base.as_secs_f64()
         * MRP_BACKOFF_MARGIN
         * MRP_BACKOFF_BASE.powi(exponent as i32)
-        * (1.0 + rand::random::<f64>() * MRP_BACKOFF_JITTER);
+        * (1.0 + rand::random::<f64>() * jitter_percent as f64 / 100.0);
     Duration::from_secs_f64(t)
 }
 
@@ -142,7 +195,7 @@ mod tests {
             let lower = 0.5 * MRP_BACKOFF_MARGIN * MRP_BACKOFF_BASE.powi(exponent as i32);
             let upper = lower * (1.0 + MRP_BACKOFF_JITTER);
             for _ in 0..50 {
-                let t = backoff_interval(base, n).as_secs_f64();
+                let t = backoff_interval(base, n, MRP_BACKOFF_JITTER_PERCENT).as_secs_f64();
                 assert!(t >= lower - 1e-9, "n={} t={} lower={}", n, t, lower);
                 assert!(t <= upper + 1e-9, "n={} t={} upper={}", n, t, upper);
             }
@@ -150,4 +203,67 @@ mod tests {
             prev_lower = lower;
         }
     }
+
+    #[test]
+    fn test_backoff_jitter_configurable() {
+        let base = Duration::from_millis(500);
+        let exact = backoff_interval(base, 0, 0);
+        assert_eq!(exact, backoff_interval(base, 0, 0));
+        assert!((exact.as_secs_f64() - 0.5 * MRP_BACKOFF_MARGIN).abs() < 1e-9);
+
+        let samples: Vec<f64> = (0..20).map(|_| backoff_interval(base, 0, 50).as_secs_f64()).collect();
+        for t in &samples {
+            assert!(*t >= exact.as_secs_f64() - 1e-9 && *t <= exact.as_secs_f64() * 1.5 + 1e-9, "t={}", t);
+        }
+        assert!(samples.windows(2).any(|w| w[0] != w[1]), "successive intervals must differ");
+    }
+
+    /// Sigma2 with session-parameter-struct holding `session_params` (tag, value) at tag 5.
+    fn sigma2_tlv(session_params: Option<&[(u8, u32)]>) -> crate::tlv::TlvItem {
+        let mut b = crate::tlv::TlvBuffer::new();
+        b.write_anon_struct().unwrap();
+        b.write_octetstring(1, &[1u8; 32]).unwrap();
+        b.write_uint16(2, 0x1234).unwrap();
+        b.write_octetstring(3, &[4u8; 65]).unwrap();
+        b.write_octetstring(4, &[5u8; 16]).unwrap();
+        if let Some(fields) = session_params {
+            b.write_struct(5).unwrap();
+            for (tag, value) in fields {
+                b.write_uint32(*tag, *value).unwrap();
+            }
+            b.write_struct_end().unwrap();
+        }
+        b.write_struct_end().unwrap();
+        crate::tlv::decode_tlv(&b.data).unwrap()
+    }
+
+    #[test]
+    fn test_session_params() {
+        let s = SessionParams::decode(&sigma2_tlv(Some(&[(1, 5000), (2, 800), (3, 2000)])), 5)
+            .expect("session params present");
+        let p = MrpParameters::default().merge(&s);
+        assert_eq!(p.session_idle_interval, Duration::from_millis(5000));
+        assert_eq!(p.session_active_interval, Duration::from_millis(800));
+        assert_eq!(p.session_active_threshold, Duration::from_millis(2000));
+
+        assert_eq!(SessionParams::decode(&sigma2_tlv(None), 5), None);
+        // tag 4 of Sigma2 is the encrypted blob, not a struct
+        assert_eq!(SessionParams::decode(&sigma2_tlv(None), 4), None);
+    }
+
+    #[test]
+    fn test_merge_keeps_missing_intervals() {
+        // only SAI sent; SII/SAT from mDNS and tuned jitter stay
+        let s = SessionParams::decode(&sigma2_tlv(Some(&[(2, 800)])), 5).unwrap();
+        let current = MrpParameters {
+            backoff_jitter_percent: 0,
+            ..MrpParameters::from_txt_ms(Some(5000), None, Some(1000))
+        };
+        let p = current.merge(&s);
+        assert_eq!(p.session_idle_interval, Duration::from_millis(5000));
+        assert_eq!(p.session_active_interval, Duration::from_millis(800));
+        assert_eq!(p.session_active_threshold, Duration::from_millis(1000));
+        assert_eq!(p.backoff_jitter_percent, 0);
+        assert_eq!(current.merge(&SessionParams::default()), current);
+    }
 }
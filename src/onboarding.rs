@@ -16,9 +16,9 @@ pub struct OnboardingInfo {
     pub passcode: u32,
     /// True when decoded from a manual pairing code (only top 4 bits of discriminator are valid).
     pub is_short_discriminator: bool,
-    /// Present only when decoded from a QR code payload.
+    /// Present when decoded from a QR code payload or a 21 digit manual pairing code.
     pub vendor_id: Option<u16>,
-    /// Present only when decoded from a QR code payload.
+    /// Present when decoded from a QR code payload or a 21 digit manual pairing code.
     pub product_id: Option<u16>,
     /// Present only when decoded from a QR code payload.
     pub discovery_capabilities: Option<DiscoveryCapabilities>,
@@ -130,26 +130,106 @@ pub fn decode_qr_payload(qr: &str) -> Result<OnboardingInfo> {
     })
 }
 
+/// Last digit of a manual pairing code does not match the check digit computed from
+/// the others, typically a typo. Detect with `err.downcast_ref::<onboarding::InvalidCheckDigit>()`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct InvalidCheckDigit {
+    pub expected: u8,
+    pub found: u8,
+}
+
+impl std::fmt::Display for InvalidCheckDigit {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "manual pairing code check digit is {}, expected {}; code is probably mistyped",
+            self.found, self.expected
+        )
+    }
+}
+impl std::error::Error for InvalidCheckDigit {}
+
+/// Bit of the first digit telling the code carries vendor and product id (21 digit form).
+const MANUAL_CODE_VID_PID_PRESENT: u32 = 0x4;
+
+/// `input` typed by user with digit group separators (`-`, `.`, spaces, tabs) removed.
+fn strip_separators(input: &str) -> String {
+    input.chars().filter(|c| !matches!(c, '-' | '.' | ' ' | '\t')).collect()
+}
+
+/// Decode a manual pairing code: 11 digits (`2585-103-3238`), or 21 digits when it also
+/// carries vendor and product id. Separators (`-`, `.`, spaces, tabs) are ignored. The trailing
+/// Verhoeff check digit is verified; a mismatch fails with [InvalidCheckDigit].
 pub fn decode_manual_pairing_code(code: &str) -> Result<OnboardingInfo> {
-    let norm = code.replace("-", "");
-    let first_grp = &norm[0..1];
-    let second_grp = &norm[1..6];
-    let third_grp = &norm[6..10];
-    let first = first_grp.parse::<u32>()?;
-    let second = second_grp.parse::<u32>()?;
-    let third = third_grp.parse::<u32>()?;
+    let norm = strip_separators(code);
+    if !norm.chars().all(|c| c.is_ascii_digit()) || !matches!(norm.len(), 11 | 21) {
+        bail!("invalid manual pairing code '{}': expected 11 or 21 digits", code);
+    }
+    let (digits, check) = norm.split_at(norm.len() - 1);
+    let expected = verhoeff_checksum(digits);
+    let found = check.parse::<u8>()?;
+    if found != expected {
+        return Err(InvalidCheckDigit { expected, found }.into());
+    }
+    let first = digits[0..1].parse::<u32>()?;
+    let second = digits[1..6].parse::<u32>()?;
+    let third = digits[6..10].parse::<u32>()?;
+    if first > 7 {
+        bail!("invalid manual pairing code '{}': first digit must be 0-7", code);
+    }
+    let long = first & MANUAL_CODE_VID_PID_PRESENT != 0;
+    if long != (norm.len() == 21) {
+        bail!(
+            "invalid manual pairing code '{}': {} digits but vendor/product flag {}",
+            code,
+            norm.len(),
+            if long { "set" } else { "clear" }
+        );
+    }
+    let (vendor_id, product_id) = if long {
+        (
+            Some(digits[10..15].parse::<u16>().context("vendor id out of range")?),
+            Some(digits[15..20].parse::<u16>().context("product id out of range")?),
+        )
+    } else {
+        (None, None)
+    };
     let passcode = second & 0x3fff | (third << 14);
     let discriminator = (((first & 3) << 10) | (second >> 6) & 0x300) as u16;
     Ok(OnboardingInfo {
         discriminator,
         passcode,
         is_short_discriminator: true,
-        vendor_id: None,
-        product_id: None,
+        vendor_id,
+        product_id,
         discovery_capabilities: None,
     })
 }
 
+/// Passcodes the Matter specification forbids (5.1.7.1).
+const INVALID_PASSCODES: [u32; 12] = [
+    0, 11111111, 22222222, 33333333, 44444444, 55555555, 66666666, 77777777, 88888888,
+    99999999, 12345678, 87654321,
+];
+
+/// Largest valid passcode.
+const MAX_PASSCODE: u32 = 99999998;
+
+/// Parse setup passcode (PIN) entered by user. Separators (`-`, `.`, spaces, tabs) are ignored.
+/// Fails for non-numeric input, values out of range and passcodes forbidden by the specification,
+/// which devices never accept.
+pub fn parse_passcode(pin: &str) -> Result<u32> {
+    let digits = strip_separators(pin);
+    if digits.is_empty() || digits.len() > 8 || !digits.chars().all(|c| c.is_ascii_digit()) {
+        bail!("invalid passcode '{}': expected up to 8 digits", pin);
+    }
+    let passcode: u32 = digits.parse()?;
+    if passcode > MAX_PASSCODE || INVALID_PASSCODES.contains(&passcode) {
+        bail!("passcode {:08} is not allowed by the Matter specification", passcode);
+    }
+    Ok(passcode)
+}
+
 static D: [[u8; 10]; 10] = [
     [0, 1, 2, 3, 4, 5, 6, 7, 8, 9],
     [1, 2, 3, 4, 0, 6, 7, 8, 9, 5],
@@ -229,6 +309,34 @@ mod tests {
         let encoded = encode_manual_pairing_code(&res);
         assert_eq!(encoded.replace("-", ""), "34970112332");
     }
+    #[test]
+    pub fn test_manual_code_check_digit() {
+        let valid = decode_manual_pairing_code("2585 103 3238").unwrap();
+        assert_eq!(valid.passcode, 54453390);
+        assert_eq!(decode_manual_pairing_code("2585.103\t3238").unwrap().passcode, 54453390);
+
+        let err = decode_manual_pairing_code("2585-103-3239").unwrap_err();
+        let bad = err.downcast_ref::<super::InvalidCheckDigit>().expect("InvalidCheckDigit");
+        assert_eq!((bad.expected, bad.found), (8, 9));
+        // flipped digit in payload
+        let err = decode_manual_pairing_code("2585-113-3238").unwrap_err();
+        assert!(err.downcast_ref::<super::InvalidCheckDigit>().is_some(), "got: {}", err);
+
+        assert!(decode_manual_pairing_code("2585-103-323").is_err());
+        assert!(decode_manual_pairing_code("2585-1a3-3238").is_err());
+    }
+
+    #[test]
+    pub fn test_manual_code_long_form() {
+        let res = decode_manual_pairing_code("749701123365521327694").unwrap();
+        assert_eq!(res.discriminator, 3840);
+        assert_eq!(res.passcode, 20202021);
+        assert_eq!(res.vendor_id, Some(0xFFF1));
+        assert_eq!(res.product_id, Some(0x8001));
+        // long form flag with short length
+        assert!(decode_manual_pairing_code("74970112334").is_err());
+    }
+
     #[test]
     pub fn test_3() {
         let oi = OnboardingInfo {
@@ -268,4 +376,20 @@ mod tests {
         let dc = info.discovery_capabilities.unwrap();
         assert!(dc.has_on_network());
     }
+
+    #[test]
+    pub fn test_parse_passcode() {
+        assert_eq!(super::parse_passcode("20202021").unwrap(), 20202021);
+        assert_eq!(super::parse_passcode("2020-2021").unwrap(), 20202021);
+        assert_eq!(super::parse_passcode(" 123 456 ").unwrap(), 123456);
+        for forbidden in [
+            "00000000", "11111111", "22222222", "33333333", "44444444", "55555555", "66666666",
+            "77777777", "88888888", "99999999", "12345678", "87654321", "0000-0000", "1234.5678",
+        ] {
+            assert!(super::parse_passcode(forbidden).is_err(), "{} must be rejected", forbidden);
+        }
+        assert!(super::parse_passcode("").is_err());
+        assert!(super::parse_passcode("12a45").is_err());
+        assert!(super::parse_passcode("123456789").is_err());
+    }
 }
@@ -5,16 +5,62 @@
 //! the peer's advertised intervals ([`ConnectionTrait::mrp_params`], SAI when
 //! the peer is active within SAT, SII otherwise). A message is given up on
 //! after [`mrp::MRP_MAX_TRANSMISSIONS`] transmissions; waiting for a response
-//! is additionally bounded by an overall deadline.
+//! is additionally bounded by an overall deadline. Once exchanges are subscribed,
+//! messages of other exchanges are not returned to the caller.
 
 use anyhow::Result;
-use std::{collections::HashMap, time::Duration};
+use std::{
+    collections::{HashMap, HashSet},
+    time::Duration,
+};
 use tokio::time::Instant;
 
 use crate::{messages, mrp, session, transport::ConnectionTrait};
 
 const MAX_RESPONSE_WAIT: Duration = Duration::from_secs(60);
 
+/// Peer did not answer a handshake message.
+/// Detect with `err.downcast_ref::<NoResponse>()`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NoResponse {
+    /// Message was sent [mrp::MRP_MAX_TRANSMISSIONS] times without being acknowledged.
+    RetransmitLimit { counter: u32, transmissions: u32 },
+    /// No response arrived within the overall deadline.
+    Deadline,
+}
+impl std::fmt::Display for NoResponse {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            NoResponse::RetransmitLimit { counter, transmissions } => write!(
+                f,
+                "MRP retransmit limit reached for counter {} after {} transmissions",
+                counter, transmissions
+            ),
+            NoResponse::Deadline => write!(f, "response timeout"),
+        }
+    }
+}
+impl std::error::Error for NoResponse {}
+
+/// Peer sent a message on an exchange this context neither subscribed to nor used.
+/// Detect with `err.downcast_ref::<UnexpectedExchange>()`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UnexpectedExchange {
+    pub exchange_id: u16,
+    pub protocol_id: u16,
+    pub opcode: u8,
+}
+impl std::fmt::Display for UnexpectedExchange {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "message on unexpected exchange {} (protocol:{} opcode:{:#x})",
+            self.exchange_id, self.protocol_id, self.opcode
+        )
+    }
+}
+impl std::error::Error for UnexpectedExchange {}
+
 struct SentEntry {
     exchange_id: u16,
     data: Vec<u8>,
@@ -27,6 +73,8 @@ pub struct RetrContext<'a> {
     sent: HashMap<u32, SentEntry>,
     /// exchange-ids use is interested in. empty for all
     subscribed_exchanges: HashMap<u16, bool>,
+    /// exchange-ids of messages sent through this context
+    used_exchanges: HashSet<u16>,
     connection: &'a dyn ConnectionTrait,
     session: &'a session::Session,
 }
@@ -39,16 +87,15 @@ impl<'b> RetrContext<'b> {
         Self {
             sent: HashMap::new(),
             subscribed_exchanges: HashMap::new(),
+            used_exchanges: HashSet::new(),
             connection,
             session,
         }
     }
     fn backoff_from_now(&self, retransmission_index: u32) -> Instant {
-        let base = mrp::base_interval(
-            &self.connection.mrp_params(),
-            self.connection.last_received_elapsed(),
-        );
-        Instant::now() + mrp::backoff_interval(base, retransmission_index)
+        let params = self.connection.mrp_params();
+        let base = mrp::base_interval(&params, self.connection.last_received_elapsed());
+        Instant::now() + mrp::backoff_interval(base, retransmission_index, params.backoff_jitter_percent)
     }
     fn send_internal(&mut self, d: &[u8], exchange_id: u16) {
         let reliable = self.connection.is_reliable();
@@ -66,9 +113,38 @@ impl<'b> RetrContext<'b> {
             );
         }
     }
-    fn received_ack(&mut self, c: u32) {
-        log::trace!("received ack counter:{}", c);
-        self.sent.remove(&c);
+    /// Ack of counter `c` counts only when it arrives on the exchange the message was sent on.
+    fn received_ack(&mut self, c: u32, exchange_id: u16) {
+        log::trace!("received ack counter:{} exchange:{}", c, exchange_id);
+        if self.sent.get(&c).is_some_and(|e| e.exchange_id == exchange_id) {
+            self.sent.remove(&c);
+        }
+    }
+
+    /// Decide whether message is for the caller: Ok(true) deliver, Ok(false) drop it
+    /// (late message of other exchange we used), error for exchange unknown to this context.
+    /// Without subscriptions everything is delivered.
+    fn check_exchange(&self, msg: &messages::Message) -> Result<bool> {
+        let ph = &msg.protocol_header;
+        if self.subscribed_exchanges.is_empty()
+            || self.subscribed_exchanges.contains_key(&ph.exchange_id)
+        {
+            return Ok(true);
+        }
+        if self.used_exchanges.contains(&ph.exchange_id) {
+            log::debug!(
+                "dropping message of unsubscribed exchange:{} opcode:{:#x}",
+                ph.exchange_id,
+                ph.opcode
+            );
+            return Ok(false);
+        }
+        Err(UnexpectedExchange {
+            exchange_id: ph.exchange_id,
+            protocol_id: ph.protocol_id,
+            opcode: ph.opcode,
+        }
+        .into())
     }
 
     fn implicit_ack_exchange(&mut self, exchange_id: u16) {
@@ -86,11 +162,11 @@ impl<'b> RetrContext<'b> {
         for counter in due {
             let entry = self.sent.get(&counter).unwrap();
             if entry.transmissions >= mrp::MRP_MAX_TRANSMISSIONS {
-                anyhow::bail!(
-                    "MRP retransmit limit reached for counter {} after {} transmissions",
+                return Err(NoResponse::RetransmitLimit {
                     counter,
-                    entry.transmissions
-                );
+                    transmissions: entry.transmissions,
+                }
+                .into());
             }
             let data = entry.data.clone();
             log::trace!(
@@ -130,6 +206,9 @@ impl<'b> RetrContext<'b> {
                     log::trace!("reliable transport: skipping standalone ack");
                     continue;
                 }
+                if !self.check_exchange(&decoded)? {
+                    continue;
+                }
                 return Ok(decoded);
             }
         }
@@ -139,7 +218,7 @@ impl<'b> RetrContext<'b> {
         loop {
             let now = Instant::now();
             if now >= deadline {
-                anyhow::bail!("response timeout");
+                return Err(NoResponse::Deadline.into());
             }
             let timeout = self
                 .sent
@@ -171,7 +250,10 @@ impl<'b> RetrContext<'b> {
             log::trace!("received message {:?}", decoded);
 
             // apply ack - remove from retransmit buffer
-            self.received_ack(decoded.protocol_header.ack_counter);
+            self.received_ack(
+                decoded.protocol_header.ack_counter,
+                decoded.protocol_header.exchange_id,
+            );
 
             self.implicit_ack_exchange(decoded.protocol_header.exchange_id);
 
@@ -229,21 +311,58 @@ impl<'b> RetrContext<'b> {
                 );
             }
 
-            if !self.subscribed_exchanges.is_empty()
-                && !self
-                    .subscribed_exchanges
-                    .contains_key(&decoded.protocol_header.exchange_id)
-            {
+            if !self.check_exchange(&decoded)? {
                 continue;
             }
             return Ok(decoded);
         }
     }
+    /// Wait for next handshake (secure channel) message on `exchange_id` with one of `opcodes`.
+    /// StatusReports on the exchange are always returned so that the caller can report failure.
+    /// Anything else - messages of stale exchanges, leftovers of earlier handshake steps -
+    /// is skipped instead of being mistaken for the expected message.
+    pub async fn get_next_handshake_message(
+        &mut self,
+        exchange_id: u16,
+        opcodes: &[u8],
+    ) -> Result<messages::Message> {
+        loop {
+            let msg = match self.get_next_message().await {
+                Ok(msg) => msg,
+                Err(e) => match e.downcast_ref::<UnexpectedExchange>() {
+                    // stale exchange of an earlier handshake attempt
+                    Some(u) => {
+                        log::debug!("skipping {} (waiting for exchange {})", u, exchange_id);
+                        continue;
+                    }
+                    None => return Err(e),
+                },
+            };
+            let ph = &msg.protocol_header;
+            if ph.exchange_id == exchange_id
+                && ph.protocol_id == messages::ProtocolMessageHeader::PROTOCOL_ID_SECURE_CHANNEL
+                && (ph.opcode == messages::ProtocolMessageHeader::OPCODE_STATUS
+                    || opcodes.contains(&ph.opcode))
+            {
+                return Ok(msg);
+            }
+            log::debug!(
+                "skipping unexpected message exchange:{} protocol:{} opcode:{:#x} (waiting for {:x?} on exchange {})",
+                ph.exchange_id,
+                ph.protocol_id,
+                ph.opcode,
+                opcodes,
+                exchange_id
+            );
+        }
+    }
+
     pub async fn send(&mut self, data: &[u8]) -> Result<()> {
         // `data` is the protocol-layer message (protocol header + payload);
         // session.encode_message prepends the MessageHeader.
         let (ph, _) = messages::ProtocolMessageHeader::decode(data)?;
         let out = self.session.encode_message(data)?;
+        self.used_exchanges.insert(ph.exchange_id);
         self.send_internal(&out, ph.exchange_id);
         self.connection.send(&out).await?;
         Ok(())
@@ -256,6 +375,17 @@ mod tests {
 
     struct TestConn {
         sent: std::sync::Mutex<Vec<(Instant, Vec<u8>)>>,
+        /// messages delivered by receive, in order
+        inbound: std::sync::Mutex<std::collections::VecDeque<Vec<u8>>>,
+    }
+
+    impl TestConn {
+        fn new(inbound: Vec<Vec<u8>>) -> Self {
+            Self {
+                sent: std::sync::Mutex::new(Vec::new()),
+                inbound: std::sync::Mutex::new(inbound.into()),
+            }
+        }
     }
 
     #[async_trait::async_trait]
@@ -265,14 +395,31 @@ mod tests {
             Ok(())
         }
         async fn receive(&self, timeout: Duration) -> Result<Vec<u8>> {
+            if let Some(m) = self.inbound.lock().unwrap().pop_front() {
+                return Ok(m);
+            }
             tokio::time::sleep(timeout).await;
             anyhow::bail!("timeout")
         }
     }
 
+    fn peer_message(peer: &session::Session, exchange_id: u16, protocol_id: u16, opcode: u8) -> Vec<u8> {
+        let mut m = messages::ProtocolMessageHeader {
+            exchange_flags: 0,
+            opcode,
+            exchange_id,
+            protocol_id,
+            ack_counter: 0,
+        }
+        .encode()
+        .unwrap();
+        m.extend_from_slice(&[0x15, 0x18]);
+        peer.encode_message(&m).unwrap()
+    }
+
     #[tokio::test(start_paused = true)]
     async fn test_retransmit_give_up() {
-        let conn = TestConn { sent: std::sync::Mutex::new(Vec::new()) };
+        let conn = TestConn::new(Vec::new());
         let session = session::Session::new();
         let mut ctx = RetrContext::new(&conn, &session);
         ctx.send(&messages::ack(1, 2).unwrap()).await.unwrap();
@@ -298,14 +445,90 @@ mod tests {
 
     #[tokio::test(start_paused = true)]
     async fn test_response_wait_timeout() {
-        let conn = TestConn { sent: std::sync::Mutex::new(Vec::new()) };
+        let conn = TestConn::new(Vec::new());
         let session = session::Session::new();
         let mut ctx = RetrContext::new(&conn, &session);
 
         let start = Instant::now();
         let err = ctx.get_next_message().await.unwrap_err();
         assert!(err.to_string().contains("response timeout"), "got: {}", err);
+        assert_eq!(err.downcast_ref::<NoResponse>(), Some(&NoResponse::Deadline));
         assert!(start.elapsed() >= MAX_RESPONSE_WAIT);
         assert!(conn.sent.lock().unwrap().is_empty());
     }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_handshake_message_skips_unexpected() {
+        use messages::ProtocolMessageHeader as P;
+        let peer = session::Session::new();
+        let sc = P::PROTOCOL_ID_SECURE_CHANNEL;
+        let conn = TestConn::new(vec![
+            peer_message(&peer, 9, sc, P::OPCODE_ACK),
+            // stale handshake on another exchange
+            peer_message(&peer, 8, sc, P::OPCODE_PASE_PAKE2),
+            // leftover of the previous step
+            peer_message(&peer, 9, sc, P::OPCODE_PBKDF_RESP),
+            peer_message(&peer, 9, P::PROTOCOL_ID_INTERACTION, P::INTERACTION_OPCODE_REPORT_DATA),
+            peer_message(&peer, 9, sc, P::OPCODE_PASE_PAKE2),
+            peer_message(&peer, 9, sc, P::OPCODE_PASE_PAKE2),
+            peer.encode_message(&messages::status_report_success(9).unwrap()).unwrap(),
+        ]);
+        let session = session::Session::new();
+        let mut ctx = RetrContext::new(&conn, &session);
+
+        let m = ctx.get_next_handshake_message(9, &[P::OPCODE_PASE_PAKE2]).await.unwrap();
+        assert_eq!((m.protocol_header.exchange_id, m.protocol_header.opcode), (9, P::OPCODE_PASE_PAKE2));
+        // status report is returned even when waiting for something else
+        let m = ctx.get_next_handshake_message(9, &[P::OPCODE_CASE_SIGMA2]).await.unwrap();
+        assert_eq!(m.protocol_header.opcode, P::OPCODE_STATUS);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_interleaved_exchanges() {
+        use messages::ProtocolMessageHeader as P;
+        let im = P::PROTOCOL_ID_INTERACTION;
+        let session = session::Session::new();
+        let conn = TestConn::new(Vec::new());
+        let mut ctx = RetrContext::new(&conn, &session);
+        ctx.subscribe_exchange(5);
+        ctx.send(&messages::im_read_request(0, 6, 0, 5).unwrap()).await.unwrap();
+        ctx.send(&messages::im_read_request(0, 6, 0, 6).unwrap()).await.unwrap();
+        let counter = messages::MessageHeader::decode(&conn.sent.lock().unwrap()[0].1)
+            .unwrap()
+            .0
+            .message_counter;
+
+        let peer = session::Session::new();
+        // exchange 7 was never used; its ack must not release our message on exchange 5
+        let mut foreign = messages::ProtocolMessageHeader {
+            exchange_flags: P::FLAG_ACK,
+            opcode: P::INTERACTION_OPCODE_REPORT_DATA,
+            exchange_id: 7,
+            protocol_id: im,
+            ack_counter: counter,
+        }
+        .encode()
+        .unwrap();
+        foreign.extend_from_slice(&[0x15, 0x18]);
+        conn.inbound.lock().unwrap().extend([
+            peer.encode_message(&foreign).unwrap(),
+            // late response on exchange we used but do not wait for
+            peer_message(&peer, 6, im, P::INTERACTION_OPCODE_REPORT_DATA),
+            peer_message(&peer, 5, im, P::INTERACTION_OPCODE_REPORT_DATA),
+        ]);
+
+        let err = ctx.get_next_message().await.unwrap_err();
+        assert_eq!(
+            err.downcast_ref::<UnexpectedExchange>(),
+            Some(&UnexpectedExchange {
+                exchange_id: 7,
+                protocol_id: im,
+                opcode: P::INTERACTION_OPCODE_REPORT_DATA
+            })
+        );
+        assert!(ctx.sent.contains_key(&counter));
+
+        let m = ctx.get_next_message().await.unwrap();
+        assert_eq!(m.protocol_header.exchange_id, 5);
+    }
 }
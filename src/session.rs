@@ -3,23 +3,36 @@ use byteorder::{LittleEndian, WriteBytesExt};
 
 use crate::{messages, util::cryptoutil};
 use anyhow::Result;
+use std::collections::HashMap;
 use std::io::Write;
 use std::sync::atomic::{AtomicU32, Ordering};
 
 /// Message Reception State per Matter spec 4.6.5: sliding-window duplicate
 /// and replay detection. Tracks the largest received counter plus a bitmap
 /// of the MSG_COUNTER_WINDOW_SIZE counters below it. Counters older than the
-/// window are rejected. No-rollover semantics (secure unicast counters never
-/// wrap; initial counters are random in [1, 2^28]).
+/// window are rejected. By default no-rollover semantics (secure unicast counters
+/// never wrap; initial counters are random in [1, 2^28]); group counters use
+/// rollover semantics, see [MessageReceptionState::with_rollover].
 #[derive(Default)]
 pub(crate) struct MessageReceptionState {
     max_counter: u32,
     /// Bit i set means counter (max_counter - 1 - i) was received.
     window: u32,
     initialized: bool,
+    /// Counters wrap around; counter is "ahead" when within 2^31 after max_counter.
+    rollover: bool,
 }
 
 impl MessageReceptionState {
+    /// Reception state with rollover semantics (spec 4.6.5.2), used for group messages.
+    /// Group senders share one global counter across all groups, which does wrap.
+    pub(crate) fn with_rollover() -> Self {
+        Self {
+            rollover: true,
+            ..Default::default()
+        }
+    }
+
     /// Returns true if the counter is new (accepted) and records it;
     /// false if it is a duplicate or older than the window.
     pub(crate) fn counter_is_new(&mut self, counter: u32) -> bool {
@@ -32,8 +45,13 @@ impl MessageReceptionState {
         if counter == self.max_counter {
             return false;
         }
-        if counter > self.max_counter {
-            let shift = counter - self.max_counter;
+        let ahead = if self.rollover {
+            counter.wrapping_sub(self.max_counter) < 1 << 31
+        } else {
+            counter > self.max_counter
+        };
+        if ahead {
+            let shift = counter.wrapping_sub(self.max_counter);
             self.window = if shift > 32 {
                 0
             } else {
@@ -42,7 +60,7 @@ impl MessageReceptionState {
             self.max_counter = counter;
             return true;
         }
-        let diff = self.max_counter - counter;
+        let diff = self.max_counter.wrapping_sub(counter);
         if diff > 32 {
             return false;
         }
@@ -55,6 +73,86 @@ impl MessageReceptionState {
     }
 }
 
+/// Reception state of group messages. Group messages are not bound to a session; their
+/// counters are tracked per sender (fabric index, source node id) in a counter space
+/// separate from unicast sessions, so a group message never advances or consumes a unicast
+/// session's window and vice versa.
+#[derive(Default)]
+pub struct GroupMessageCounters {
+    peers: std::sync::Mutex<HashMap<(u8, u64), MessageReceptionState>>,
+}
+
+impl GroupMessageCounters {
+    /// Check an incoming group message counter from `source_node_id` on fabric `fabric_index`.
+    /// As with sessions, call only after the message was authenticated.
+    pub fn counter_is_new(&self, fabric_index: u8, source_node_id: u64, counter: u32) -> bool {
+        self.peers
+            .lock()
+            .unwrap()
+            .entry((fabric_index, source_node_id))
+            .or_insert_with(MessageReceptionState::with_rollover)
+            .counter_is_new(counter)
+    }
+
+    /// Forget reception state of all senders of fabric (e.g. fabric removed or group keys rotated).
+    pub fn remove_fabric(&self, fabric_index: u8) {
+        self.peers
+            .lock()
+            .unwrap()
+            .retain(|(f, _), _| *f != fabric_index);
+    }
+}
+
+/// Sending stops this many messages before the counter would wrap. Secure session
+/// counters must not roll over (nonce reuse); the session has to be re-established.
+const COUNTER_EXHAUSTION_MARGIN: u32 = 1 << 16;
+
+/// Error returned when sending on a session whose outgoing message counter is
+/// close to wrapping. Connection must be re-established with new CASE session.
+#[derive(Debug, Clone, Copy)]
+pub struct CounterExhausted;
+impl std::fmt::Display for CounterExhausted {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "session message counter exhausted, session must be re-established")
+    }
+}
+impl std::error::Error for CounterExhausted {}
+
+/// Session id of messages of the unsecured session (handshake messages).
+const UNSECURED_SESSION_ID: u16 = 0;
+
+/// Length of AES-CCM nonce of secured unicast messages.
+const NONCE_LENGTH: usize = 13;
+
+/// Length of message integrity check appended to encrypted payload.
+const MIC_LENGTH: usize = 16;
+
+/// Reason why received secured message could not be decoded.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DecodeError {
+    /// Message header could not be parsed.
+    Header(String),
+    /// Message is shorter than its header plus integrity check.
+    TooShort { len: usize },
+    /// Message belongs to another session.
+    SessionMismatch { expected: u16, got: u16 },
+    /// Integrity check failed - wrong key or tampered message.
+    AuthFailed,
+}
+impl std::fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DecodeError::Header(e) => write!(f, "can't parse message header: {}", e),
+            DecodeError::TooShort { len } => write!(f, "message too short ({} bytes)", len),
+            DecodeError::SessionMismatch { expected, got } => {
+                write!(f, "session id mismatch. expected:{} got:{}", expected, got)
+            }
+            DecodeError::AuthFailed => write!(f, "message authentication failed (wrong key or tampered message)"),
+        }
+    }
+}
+impl std::error::Error for DecodeError {}
+
 pub struct Session {
     pub session_id: u16,
     pub my_session_id: u16,
@@ -66,6 +164,8 @@ pub struct Session {
     pub decrypt_key: Option<crypto_common::Key<Aes128Ccm>>,
     /// Fabric index for this session (0 = PASE / unassigned).
     pub fabric_index: u8,
+    /// MRP parameters the responder sent in PBKDFParamResponse / Sigma2 / Sigma2Resume.
+    pub peer_mrp: Option<crate::mrp::MrpParameters>,
 }
 type Aes128Ccm = ccm::Ccm<aes::Aes128, ccm::consts::U16, ccm::consts::U13>;
 impl Session {
@@ -80,8 +180,14 @@ impl Session {
             encrypt_key: None,
             decrypt_key: None,
             fabric_index: 0,
+            peer_mrp: None,
         }
     }
+    /// True for CASE session - secured and bound to peer's operational node id.
+    /// PASE session is secured too, but has no peer node id.
+    pub fn is_case(&self) -> bool {
+        self.encrypt_key.is_some() && self.remote_node.is_some()
+    }
     pub fn set_encrypt_key(&mut self, k: &[u8]) {
         self.encrypt_key = Some(*crypto_common::Key::<Aes128Ccm>::from_slice(k))
     }
@@ -90,10 +196,16 @@ impl Session {
     }
 
     pub fn encode_message(&self, data: &[u8]) -> Result<Vec<u8>> {
-        let counter = self.counter.fetch_add(1, Ordering::Relaxed);
+        let counter = self
+            .counter
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |c| {
+                (c < u32::MAX - COUNTER_EXHAUSTION_MARGIN).then_some(c + 1)
+            })
+            .map_err(|_| CounterExhausted)?;
+        let security_flags = 0;
         let mg = messages::MessageHeader {
             flags: 0,
-            security_flags: 0,
+            security_flags,
             session_id: self.session_id,
             message_counter: counter,
             source_node_id: self.local_node.clone(),
@@ -102,7 +214,7 @@ impl Session {
         let mut b = mg.encode()?;
         match self.encrypt_key {
             Some(key) => {
-                let nonce = self.make_nonce3(counter)?;
+                let nonce = make_nonce(security_flags, counter, self.local_node.as_deref())?;
                 let enc = cryptoutil::aes128_ccm_encrypt(&key, &nonce, &b, data)?;
                 b.extend_from_slice(&enc);
             }
@@ -112,27 +224,49 @@ impl Session {
         Ok(b)
     }
 
+    /// Decrypt incoming message and return it with plain payload.
+    ///
+    /// Session without decrypt key is the unsecured session (session id 0) used during
+    /// PASE/CASE handshakes; messages on it are passed through unchanged. Secured
+    /// messages arriving there (nonzero session id) are rejected with
+    /// [DecodeError::SessionMismatch].
     pub fn decode_message(&self, data: &[u8]) -> Result<Vec<u8>> {
-        if self.decrypt_key.is_none() {
+        let (header, rest) = messages::MessageHeader::decode(data)
+            .map_err(|e| DecodeError::Header(e.to_string()))?;
+        let Some(decrypt_key) = self.decrypt_key else {
+            if header.session_id != UNSECURED_SESSION_ID {
+                return Err(DecodeError::SessionMismatch {
+                    expected: UNSECURED_SESSION_ID,
+                    got: header.session_id,
+                }
+                .into());
+            }
             return Ok(data.to_vec());
-        }
-        let (header, rest) = messages::MessageHeader::decode(data)?;
+        };
         if header.session_id != self.my_session_id {
-            anyhow::bail!(
-                "session id mismatch. expected:{} got:{}",
-                self.my_session_id,
-                header.session_id
-            );
+            return Err(DecodeError::SessionMismatch {
+                expected: self.my_session_id,
+                got: header.session_id,
+            }
+            .into());
+        }
+        if rest.len() < MIC_LENGTH {
+            return Err(DecodeError::TooShort { len: data.len() }.into());
         }
         log::trace!("decode msg header:{:?} session:{}", header, self.session_id);
-        let nonce = Self::make_nonce3_extern(header.message_counter, self.remote_node.as_deref())?;
+        let nonce = make_nonce(
+            header.security_flags,
+            header.message_counter,
+            self.remote_node.as_deref(),
+        )?;
         let add = &data[..data.len() - rest.len()];
         let decoded = cryptoutil::aes128_ccm_decrypt(
-            &self.decrypt_key.unwrap_or_default(),
+            &decrypt_key,
             &nonce,
             add,
             &rest,
-        )?;
+        )
+        .map_err(|_| DecodeError::AuthFailed)?;
         let mut out = Vec::new();
         out.extend_from_slice(add);
         out.extend_from_slice(&decoded);
@@ -146,21 +280,29 @@ impl Session {
         self.reception_state.lock().unwrap().counter_is_new(counter)
     }
 
-    fn make_nonce3(&self, counter: u32) -> Result<Vec<u8>> {
-        Self::make_nonce3_extern(counter, self.local_node.as_deref())
-    }
-
-    fn make_nonce3_extern(counter: u32, node: Option<&[u8]>) -> Result<Vec<u8>> {
-        let mut out = Vec::with_capacity(128);
-        out.write_u8(0)?;
-        out.write_u32::<LittleEndian>(counter)?;
-        match node {
-            Some(s) => out.write_all(s)?,
-            None => out.write_all(&[0, 0, 0, 0, 0, 0, 0, 0])?,
-        };
+}
 
-        Ok(out)
+/// AES-CCM nonce of secured unicast message (spec 4.8.1.1):
+///
+/// ```text
+/// security flags (1) | message counter (4, LE) | source node id (8, LE)
+/// ```
+///
+/// Source node id is the node id of the sender - our own when encrypting, peer's
+/// when decrypting - and 0 (unspecified) for PASE sessions.
+fn make_nonce(security_flags: u8, counter: u32, source_node: Option<&[u8]>) -> Result<Vec<u8>> {
+    let source_node = source_node.unwrap_or(&[0; 8]);
+    if source_node.len() != 8 {
+        return Err(anyhow::anyhow!(
+            "nonce: source node id must be 8 bytes, got {}",
+            source_node.len()
+        ));
     }
+    let mut out = Vec::with_capacity(NONCE_LENGTH);
+    out.write_u8(security_flags)?;
+    out.write_u32::<LittleEndian>(counter)?;
+    out.write_all(source_node)?;
+    Ok(out)
 }
 
 impl Default for Session {
@@ -171,7 +313,139 @@ impl Default for Session {
 
 #[cfg(test)]
 mod tests {
-    use super::MessageReceptionState;
+    use std::sync::atomic::Ordering;
+
+    use super::{
+        make_nonce, CounterExhausted, DecodeError, GroupMessageCounters, MessageReceptionState,
+        Session,
+        COUNTER_EXHAUSTION_MARGIN, NONCE_LENGTH,
+    };
+
+    /// Sender and receiver sides of one secured session.
+    fn session_pair() -> (Session, Session) {
+        let mut tx = Session::new();
+        tx.session_id = 7;
+        tx.local_node = Some(1u64.to_le_bytes().to_vec());
+        tx.set_encrypt_key(&[3; 16]);
+        let mut rx = Session::new();
+        rx.my_session_id = 7;
+        rx.remote_node = tx.local_node.clone();
+        rx.set_decrypt_key(&[3; 16]);
+        (tx, rx)
+    }
+
+    fn decode_error(rx: &Session, data: &[u8]) -> DecodeError {
+        rx.decode_message(data)
+            .unwrap_err()
+            .downcast_ref::<DecodeError>()
+            .cloned()
+            .expect("typed decode error")
+    }
+
+    #[test]
+    fn decode_errors_are_distinct() {
+        let (tx, rx) = session_pair();
+        let msg = tx.encode_message(b"payload").unwrap();
+        assert!(rx.decode_message(&msg).unwrap().ends_with(b"payload"));
+
+        let mut corrupted = msg.clone();
+        *corrupted.last_mut().unwrap() ^= 1;
+        assert_eq!(decode_error(&rx, &corrupted), DecodeError::AuthFailed);
+
+        // header covered by additional data - changing it breaks authentication too
+        let mut corrupted = msg.clone();
+        corrupted[4] ^= 1;
+        assert_eq!(decode_error(&rx, &corrupted), DecodeError::AuthFailed);
+
+        let short = &msg[..msg.len() - 10];
+        assert!(matches!(decode_error(&rx, short), DecodeError::TooShort { .. }));
+        assert!(matches!(decode_error(&rx, &msg[..5]), DecodeError::Header(_)));
+
+        let mut other = Session::new();
+        other.my_session_id = 8;
+        other.set_decrypt_key(&[3; 16]);
+        assert_eq!(decode_error(&other, &msg), DecodeError::SessionMismatch { expected: 8, got: 7 });
+    }
+
+    #[test]
+    fn nonce_layout() {
+        let node = 0x0102030405060708u64.to_le_bytes();
+        let nonce = make_nonce(0, 0x12345678, Some(&node)).unwrap();
+        assert_eq!(nonce.len(), NONCE_LENGTH);
+        assert_eq!(hex::encode(&nonce), "00785634120807060504030201");
+
+        // security flags are copied, unspecified node id is zero
+        let nonce = make_nonce(0x80, 1, None).unwrap();
+        assert_eq!(hex::encode(&nonce), "80010000000000000000000000");
+
+        assert!(make_nonce(0, 1, Some(&[1, 2, 3, 4])).is_err());
+    }
+
+    #[test]
+    fn nonce_uses_sender_node_id() {
+        let (tx, rx) = session_pair();
+        let msg = tx.encode_message(b"payload").unwrap();
+        assert!(rx.decode_message(&msg).is_ok());
+
+        // receiver expecting different sender can't authenticate the message
+        let mut wrong = Session::new();
+        wrong.my_session_id = 7;
+        wrong.remote_node = Some(2u64.to_le_bytes().to_vec());
+        wrong.set_decrypt_key(&[3; 16]);
+        assert_eq!(decode_error(&wrong, &msg), DecodeError::AuthFailed);
+    }
+
+    #[test]
+    fn unsecured_status_report_passes_through() {
+        // device answers PBKDFParamRequest with unsecured Busy status report
+        let sr = crate::device_messages::status_report(
+            5,
+            crate::messages::SecureChannelGeneralCode::Busy as u16,
+            crate::messages::ProtocolMessageHeader::PROTOCOL_ID_SECURE_CHANNEL as u32,
+            crate::messages::SecureChannelProtocolCode::Busy as u16,
+            -1,
+        )
+        .unwrap();
+        let data = Session::new().encode_message(&sr).unwrap();
+
+        // PASE initiator: own session id already chosen, no keys yet
+        let mut pase = Session::new();
+        pase.my_session_id = 1;
+        let decoded = pase.decode_message(&data).unwrap();
+        assert_eq!(decoded, data);
+        let msg = crate::messages::Message::decode(&decoded).unwrap();
+        assert!(msg.status_report_info.unwrap().is_busy());
+
+        // secured message can't be passed through unsecured session
+        let (tx, _) = session_pair();
+        let secured = tx.encode_message(b"payload").unwrap();
+        assert_eq!(
+            decode_error(&pase, &secured),
+            DecodeError::SessionMismatch { expected: 0, got: 7 }
+        );
+    }
+
+    #[test]
+    fn fresh_sessions_start_with_random_counter() {
+        let a = Session::new().counter.load(Ordering::Relaxed);
+        let b = Session::new().counter.load(Ordering::Relaxed);
+        assert_ne!(a, b);
+        for c in [a, b] {
+            assert!((1..=1 << 28).contains(&c), "initial counter {} out of range", c);
+        }
+    }
+
+    #[test]
+    fn counter_exhaustion_is_error() {
+        let s = Session::new();
+        let last = u32::MAX - COUNTER_EXHAUSTION_MARGIN - 1;
+        s.counter.store(last, Ordering::Relaxed);
+        s.encode_message(&[1, 2, 3]).unwrap();
+        let err = s.encode_message(&[1, 2, 3]).unwrap_err();
+        assert!(err.downcast_ref::<CounterExhausted>().is_some());
+        // counter does not move once exhausted
+        assert_eq!(s.counter.load(Ordering::Relaxed), last + 1);
+    }
 
     #[test]
     fn in_order_and_duplicates() {
@@ -246,4 +520,45 @@ mod tests {
         assert!(s.counter_is_new(13));
         assert!(!s.counter_is_new(12));
     }
+
+    #[test]
+    fn group_counters_roll_over() {
+        let mut s = MessageReceptionState::with_rollover();
+        assert!(s.counter_is_new(u32::MAX - 1));
+        assert!(s.counter_is_new(1));
+        assert!(s.counter_is_new(u32::MAX));
+        assert!(s.counter_is_new(0));
+        assert!(!s.counter_is_new(u32::MAX));
+        assert!(!s.counter_is_new(u32::MAX - 1));
+        // more than 2^31 ahead counts as behind the window
+        assert!(!s.counter_is_new(1 + (1 << 31) + 1));
+
+        let mut s = MessageReceptionState::default();
+        assert!(s.counter_is_new(u32::MAX - 1));
+        assert!(!s.counter_is_new(1), "unicast counters never wrap");
+    }
+
+    #[test]
+    fn group_and_unicast_counters_are_separate() {
+        let session = Session::new();
+        let groups = GroupMessageCounters::default();
+        for c in 100..110 {
+            assert!(session.counter_is_new(c));
+            assert!(groups.counter_is_new(1, 0x10, c), "group counter {} must be new", c);
+            assert!(groups.counter_is_new(1, 0x20, c), "other sender has own window");
+            assert!(groups.counter_is_new(2, 0x10, c), "other fabric has own window");
+        }
+        for c in 100..110 {
+            assert!(!session.counter_is_new(c));
+            assert!(!groups.counter_is_new(1, 0x10, c));
+        }
+        // group traffic far ahead does not move the unicast window
+        assert!(groups.counter_is_new(1, 0x10, 5000));
+        assert!(session.counter_is_new(110));
+        assert!(!groups.counter_is_new(1, 0x10, 110));
+
+        groups.remove_fabric(1);
+        assert!(groups.counter_is_new(1, 0x10, 100));
+        assert!(!groups.counter_is_new(2, 0x10, 100));
+    }
 }
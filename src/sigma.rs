@@ -143,6 +143,8 @@ pub struct Sigma2ResumeParsed {
     pub new_resumption_id: [u8; 16],
     pub sigma2_resume_mic: [u8; 16],
     pub responder_session_id: u16,
+    /// responderSessionParams (tag 4), if present.
+    pub responder_mrp: Option<crate::mrp::SessionParams>,
 }
 
 pub fn parse_sigma2_resume(payload: &[u8]) -> Result<Sigma2ResumeParsed> {
@@ -161,6 +163,7 @@ pub fn parse_sigma2_resume(payload: &[u8]) -> Result<Sigma2ResumeParsed> {
         new_resumption_id: id.try_into().map_err(|_| anyhow::anyhow!("Sigma2Resume: resumptionID wrong length"))?,
         sigma2_resume_mic: mic.try_into().map_err(|_| anyhow::anyhow!("Sigma2Resume: MIC wrong length"))?,
         responder_session_id: session_id,
+        responder_mrp: crate::mrp::SessionParams::decode(&tlv, 4),
     })
 }
 
@@ -9,6 +9,42 @@ use std::ops::Mul;
 
 use crate::util::cryptoutil;
 
+/// PBKDF iteration count range accepted during PASE (spec 3.9, PBKDF parameters).
+pub const PBKDF_MIN_ITERATIONS: u32 = 1000;
+pub const PBKDF_MAX_ITERATIONS: u32 = 100000;
+/// PBKDF salt length range in bytes.
+pub const PBKDF_MIN_SALT_LEN: usize = 16;
+pub const PBKDF_MAX_SALT_LEN: usize = 32;
+
+/// PBKDF parameters of a device. When known in advance (e.g. from previous
+/// pairing) they can be given to PASE so the device does not have to send them.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PbkdfParams {
+    pub iterations: u32,
+    pub salt: Vec<u8>,
+}
+
+/// Check that PBKDF parameters are within the ranges allowed by the spec.
+pub fn validate_pbkdf_params(iterations: u32, salt: &[u8]) -> Result<()> {
+    if !(PBKDF_MIN_ITERATIONS..=PBKDF_MAX_ITERATIONS).contains(&iterations) {
+        return Err(anyhow::anyhow!(
+            "PBKDF iterations {} out of range {}..={}",
+            iterations,
+            PBKDF_MIN_ITERATIONS,
+            PBKDF_MAX_ITERATIONS
+        ));
+    }
+    if !(PBKDF_MIN_SALT_LEN..=PBKDF_MAX_SALT_LEN).contains(&salt.len()) {
+        return Err(anyhow::anyhow!(
+            "PBKDF salt length {} out of range {}..={}",
+            salt.len(),
+            PBKDF_MIN_SALT_LEN,
+            PBKDF_MAX_SALT_LEN
+        ));
+    }
+    Ok(())
+}
+
 pub struct Context {
     w0: p256::Scalar,
     w1: p256::Scalar,
@@ -283,6 +319,17 @@ impl Verifier {
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_validate_pbkdf_params() {
+        validate_pbkdf_params(1000, &[0; 16]).unwrap();
+        validate_pbkdf_params(100000, &[0; 32]).unwrap();
+        assert!(validate_pbkdf_params(1000, &[0; 15]).is_err());
+        assert!(validate_pbkdf_params(1000, &[0; 33]).is_err());
+        assert!(validate_pbkdf_params(1000, &[]).is_err());
+        assert!(validate_pbkdf_params(999, &[0; 16]).is_err());
+        assert!(validate_pbkdf_params(100001, &[0; 16]).is_err());
+    }
+
     #[test]
     fn test_spake2p_prover_verifier_keys_match() -> Result<()> {
         let engine = Engine::new()?;
@@ -36,10 +36,14 @@ const TYPE_BOOL_TRUE: u8 = 9;
 const TYPE_FLOAT_4: u8 = 0xA;
 const TYPE_FLOAT_8: u8 = 0xB;
 const TYPE_UTF8_L1: u8 = 0xC;
+const TYPE_UTF8_L2: u8 = 0xD;
+const TYPE_UTF8_L4: u8 = 0xE;
+const TYPE_UTF8_L8: u8 = 0xF;
 const TYPE_OCTET_STRING_L1: u8 = 0x10;
 const TYPE_OCTET_STRING_L2: u8 = 0x11;
 const TYPE_OCTET_STRING_L4: u8 = 0x12;
 const TYPE_OCTET_STRING_L8: u8 = 0x13;
+const TYPE_NULL: u8 = 0x14;
 
 const TYPE_STRUCT: u8 = 0x15;
 const TYPE_ARRAY: u8 = 0x16;
@@ -88,16 +92,29 @@ impl TlvBuffer {
         Ok(())
     }
     pub fn write_string(&mut self, tag: u8, data: &str) -> Result<()> {
-        let ctrl = CTRL_CTX_L1 | TYPE_UTF8_L1;
         let bytes = data.as_bytes();
-        self.data.write_u8(ctrl)?;
-        self.data.write_u8(tag)?;
-        self.data.write_u8(bytes.len() as u8)?;
+        if bytes.len() > 0xffff {
+            self.data.write_u8(CTRL_CTX_L1 | TYPE_UTF8_L4)?;
+            self.data.write_u8(tag)?;
+            self.data.write_u32::<LittleEndian>(bytes.len() as u32)?;
+        } else if bytes.len() > 0xff {
+            self.data.write_u8(CTRL_CTX_L1 | TYPE_UTF8_L2)?;
+            self.data.write_u8(tag)?;
+            self.data.write_u16::<LittleEndian>(bytes.len() as u16)?;
+        } else {
+            self.data.write_u8(CTRL_CTX_L1 | TYPE_UTF8_L1)?;
+            self.data.write_u8(tag)?;
+            self.data.write_u8(bytes.len() as u8)?;
+        }
         self.data.write_all(bytes)?;
         Ok(())
     }
     pub fn write_octetstring(&mut self, tag: u8, data: &[u8]) -> Result<()> {
-        if data.len() > 0xff {
+        if data.len() > 0xffff {
+            self.data.write_u8(CTRL_CTX_L1 | TYPE_OCTET_STRING_L4)?;
+            self.data.write_u8(tag)?;
+            self.data.write_u32::<LittleEndian>(data.len() as u32)?;
+        } else if data.len() > 0xff {
             self.data.write_u8(CTRL_CTX_L1 | TYPE_OCTET_STRING_L2)?;
             self.data.write_u8(tag)?;
             self.data.write_u16::<LittleEndian>(data.len() as u16)?;
@@ -135,7 +152,10 @@ impl TlvBuffer {
         self.data.write_u8(value)
     }
     pub fn write_octetstring_notag(&mut self, data: &[u8]) -> Result<()> {
-        if data.len() > 0xff {
+        if data.len() > 0xffff {
+            self.data.write_u8(TYPE_OCTET_STRING_L4)?;
+            self.data.write_u32::<LittleEndian>(data.len() as u32)?;
+        } else if data.len() > 0xff {
             self.data.write_u8(TYPE_OCTET_STRING_L2)?;
             self.data.write_u16::<LittleEndian>(data.len() as u16)?;
         } else {
@@ -179,6 +199,10 @@ impl TlvBuffer {
         }
         self.data.write_u8(tag)
     }
+    pub fn write_null(&mut self, tag: u8) -> Result<()> {
+        self.data.write_u8(CTRL_CTX_L1 | TYPE_NULL)?;
+        self.data.write_u8(tag)
+    }
 }
 
 impl Default for TlvBuffer {
@@ -200,6 +224,34 @@ pub enum TlvItemValue {
     Invalid(),
 }
 
+impl TlvItemValue {
+    /// Generic JSON form of the value, for values without a cluster specific decoder.
+    /// Octet strings become hex strings. Containers whose elements are all anonymous
+    /// (or tagged 0) become arrays, other containers objects keyed by context tag.
+    pub fn to_json(&self) -> serde_json::Value {
+        match self {
+            TlvItemValue::Int(v) => serde_json::Value::from(*v),
+            TlvItemValue::Bool(v) => serde_json::Value::from(*v),
+            TlvItemValue::String(v) => serde_json::Value::from(v.as_str()),
+            TlvItemValue::OctetString(v) => serde_json::Value::from(hex::encode(v)),
+            TlvItemValue::Float(v) => serde_json::Value::from(*v),
+            TlvItemValue::List(items) => {
+                if items.iter().all(|i| i.tag == 0) {
+                    serde_json::Value::Array(items.iter().map(|i| i.value.to_json()).collect())
+                } else {
+                    serde_json::Value::Object(
+                        items
+                            .iter()
+                            .map(|i| (i.tag.to_string(), i.value.to_json()))
+                            .collect(),
+                    )
+                }
+            }
+            TlvItemValue::Nil() | TlvItemValue::Invalid() => serde_json::Value::Null,
+        }
+    }
+}
+
 impl From<TlvItemValue> for bool {
     fn from(value: TlvItemValue) -> Self {
         match value {
@@ -406,23 +458,120 @@ impl TlvItem {
     }
 }
 
-fn read_tag(tagctrl: u8, cursor: &mut Cursor<&[u8]>) -> Result<u8> {
-    if tagctrl == 1 {
-        cursor.read_u8()
-    } else {
-        Ok(0)
+/// Element tag as encoded by the tag control bits of the control byte.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Tag {
+    Anonymous,
+    Context(u8),
+    CommonProfile(u32),
+    Implicit(u32),
+    FullyQualified { vendor: u16, profile: u16, tag: u32 },
+}
+
+impl Tag {
+    /// Tag number stored in [TlvItem::tag]. Only context tags are used by the
+    /// interaction model; other forms are stored as 0 like anonymous elements.
+    pub fn context(&self) -> u8 {
+        match self {
+            Tag::Context(t) => *t,
+            _ => 0,
+        }
     }
 }
 
-fn decode(cursor: &mut Cursor<&[u8]>, container: &mut Vec<TlvItem>) -> Result<()> {
+fn read_tag(tagctrl: u8, cursor: &mut Cursor<&[u8]>) -> Result<Tag> {
+    Ok(match tagctrl {
+        0 => Tag::Anonymous,
+        1 => Tag::Context(cursor.read_u8()?),
+        2 => Tag::CommonProfile(cursor.read_u16::<LittleEndian>()? as u32),
+        3 => Tag::CommonProfile(cursor.read_u32::<LittleEndian>()?),
+        4 => Tag::Implicit(cursor.read_u16::<LittleEndian>()? as u32),
+        5 => Tag::Implicit(cursor.read_u32::<LittleEndian>()?),
+        6 => Tag::FullyQualified {
+            vendor: cursor.read_u16::<LittleEndian>()?,
+            profile: cursor.read_u16::<LittleEndian>()?,
+            tag: cursor.read_u16::<LittleEndian>()? as u32,
+        },
+        _ => Tag::FullyQualified {
+            vendor: cursor.read_u16::<LittleEndian>()?,
+            profile: cursor.read_u16::<LittleEndian>()?,
+            tag: cursor.read_u32::<LittleEndian>()?,
+        },
+    })
+}
+
+/// Maximum number of elements (including containers) accepted by decoder.
+/// Decoded input typically comes from network, so this bounds work and memory
+/// spent on single message regardless of how it is crafted.
+pub const MAX_DECODE_ELEMENTS: usize = 4096;
+/// Maximum container nesting accepted by decoder.
+pub const MAX_DECODE_DEPTH: usize = 32;
+
+/// Limits tracked while decoding one buffer.
+struct DecodeBudget {
+    elements: usize,
+    depth: usize,
+}
+
+impl DecodeBudget {
+    fn new() -> Self {
+        Self {
+            elements: 0,
+            depth: 0,
+        }
+    }
+    fn add_element(&mut self) -> Result<()> {
+        self.elements += 1;
+        if self.elements > MAX_DECODE_ELEMENTS {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                format!("tlv contains more than {} elements", MAX_DECODE_ELEMENTS),
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// read `size` bytes, failing before allocation when the buffer is shorter
+fn read_bytes(cursor: &mut Cursor<&[u8]>, size: u64) -> Result<Vec<u8>> {
+    let remaining = cursor.get_ref().len() as u64 - cursor.position();
+    if size > remaining {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::UnexpectedEof,
+            format!("tlv element length {} exceeds remaining {} bytes", size, remaining),
+        ));
+    }
+    let mut value = vec![0; size as usize];
+    cursor.read_exact(&mut value)?;
+    Ok(value)
+}
+
+fn decode_container(cursor: &mut Cursor<&[u8]>, budget: &mut DecodeBudget) -> Result<Vec<TlvItem>> {
+    if budget.depth >= MAX_DECODE_DEPTH {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            format!("tlv nesting deeper than {}", MAX_DECODE_DEPTH),
+        ));
+    }
+    budget.depth += 1;
+    let mut c2 = Vec::new();
+    decode(cursor, &mut c2, budget)?;
+    budget.depth -= 1;
+    Ok(c2)
+}
+
+fn decode(cursor: &mut Cursor<&[u8]>, container: &mut Vec<TlvItem>, budget: &mut DecodeBudget) -> Result<()> {
     while cursor.position() < cursor.get_ref().len() as u64 {
         let fb = cursor.read_u8()?;
         let tp = fb & 0x1f;
         let tagctrl = fb >> 5;
-        let tag = read_tag(tagctrl, cursor)?;
+        let tag = read_tag(tagctrl, cursor)?.context();
+        if tp != TYPE_END_CONTAINER {
+            budget.add_element()?;
+        }
         match tp {
             TYPE_INT_1 => {
-                let value = cursor.read_u8()?;
+                let value = cursor.read_i8()?;
                 let item = TlvItem {
                     tag,
                     value: TlvItemValue::Int(value as u64),
@@ -515,11 +664,15 @@ fn decode(cursor: &mut Cursor<&[u8]>, container: &mut Vec<TlvItem>) -> Result<()
                 };
                 container.push(item);
             }
-            TYPE_UTF8_L1 => {
+            TYPE_UTF8_L1 | TYPE_UTF8_L2 | TYPE_UTF8_L4 | TYPE_UTF8_L8 => {
                 // utf8 string
-                let size = cursor.read_u8()?;
-                let mut value = vec![0; size as usize];
-                cursor.read_exact(&mut value)?;
+                let size = match tp {
+                    TYPE_UTF8_L1 => cursor.read_u8()? as u64,
+                    TYPE_UTF8_L2 => cursor.read_u16::<LittleEndian>()? as u64,
+                    TYPE_UTF8_L4 => cursor.read_u32::<LittleEndian>()? as u64,
+                    _ => cursor.read_u64::<LittleEndian>()?,
+                };
+                let value = read_bytes(cursor, size)?;
                 let str = String::from_utf8(value);
                 let typ = match str {
                     Ok(s) => TlvItemValue::String(s),
@@ -531,8 +684,7 @@ fn decode(cursor: &mut Cursor<&[u8]>, container: &mut Vec<TlvItem>) -> Result<()
             TYPE_OCTET_STRING_L1 => {
                 // octet string
                 let size = cursor.read_u8()?;
-                let mut value = vec![0; size as usize];
-                cursor.read_exact(&mut value)?;
+                let value = read_bytes(cursor, size as u64)?;
                 let item = TlvItem {
                     tag,
                     value: TlvItemValue::OctetString(value),
@@ -542,8 +694,7 @@ fn decode(cursor: &mut Cursor<&[u8]>, container: &mut Vec<TlvItem>) -> Result<()
             TYPE_OCTET_STRING_L2 => {
                 // octet string large
                 let size = cursor.read_u16::<LittleEndian>()?;
-                let mut value = vec![0; size as usize];
-                cursor.read_exact(&mut value)?;
+                let value = read_bytes(cursor, size as u64)?;
                 let item = TlvItem {
                     tag,
                     value: TlvItemValue::OctetString(value),
@@ -553,8 +704,7 @@ fn decode(cursor: &mut Cursor<&[u8]>, container: &mut Vec<TlvItem>) -> Result<()
             TYPE_OCTET_STRING_L4 => {
                 // octet string very large
                 let size = cursor.read_u32::<LittleEndian>()?;
-                let mut value = vec![0; size as usize];
-                cursor.read_exact(&mut value)?;
+                let value = read_bytes(cursor, size as u64)?;
                 let item = TlvItem {
                     tag,
                     value: TlvItemValue::OctetString(value),
@@ -564,8 +714,7 @@ fn decode(cursor: &mut Cursor<&[u8]>, container: &mut Vec<TlvItem>) -> Result<()
             TYPE_OCTET_STRING_L8 => {
                 // octet string very very xtra large
                 let size = cursor.read_u64::<LittleEndian>()?;
-                let mut value = vec![0; size as usize];
-                cursor.read_exact(&mut value)?;
+                let value = read_bytes(cursor, size)?;
                 let item = TlvItem {
                     tag,
                     value: TlvItemValue::OctetString(value),
@@ -574,8 +723,7 @@ fn decode(cursor: &mut Cursor<&[u8]>, container: &mut Vec<TlvItem>) -> Result<()
             }
             TYPE_STRUCT => {
                 //list
-                let mut c2 = Vec::new();
-                decode(cursor, &mut c2)?;
+                let c2 = decode_container(cursor, budget)?;
                 let item = TlvItem {
                     tag,
                     value: TlvItemValue::List(c2),
@@ -584,8 +732,7 @@ fn decode(cursor: &mut Cursor<&[u8]>, container: &mut Vec<TlvItem>) -> Result<()
             }
             TYPE_ARRAY => {
                 //list
-                let mut c2 = Vec::new();
-                decode(cursor, &mut c2)?;
+                let c2 = decode_container(cursor, budget)?;
                 let item = TlvItem {
                     tag,
                     value: TlvItemValue::List(c2),
@@ -594,8 +741,7 @@ fn decode(cursor: &mut Cursor<&[u8]>, container: &mut Vec<TlvItem>) -> Result<()
             }
             TYPE_LIST => {
                 //list
-                let mut c2 = Vec::new();
-                decode(cursor, &mut c2)?;
+                let c2 = decode_container(cursor, budget)?;
                 let item = TlvItem {
                     tag,
                     value: TlvItemValue::List(c2),
@@ -603,7 +749,7 @@ fn decode(cursor: &mut Cursor<&[u8]>, container: &mut Vec<TlvItem>) -> Result<()
                 container.push(item);
             }
             TYPE_END_CONTAINER => return Ok(()),
-            0x14 => {
+            TYPE_NULL => {
                 let item = TlvItem {
                     tag,
                     value: TlvItemValue::Nil(),
@@ -621,11 +767,13 @@ fn decode(cursor: &mut Cursor<&[u8]>, container: &mut Vec<TlvItem>) -> Result<()
     Ok(())
 }
 
-/// decode raw buffer with tlv data
+/// decode raw buffer with tlv data.
+/// Input is bounded by [MAX_DECODE_ELEMENTS] and [MAX_DECODE_DEPTH]; element lengths
+/// are checked against remaining input before anything is allocated.
 pub fn decode_tlv(data: &[u8]) -> Result<TlvItem> {
     let mut container = Vec::new();
     let mut cursor = std::io::Cursor::new(data);
-    decode(&mut cursor, &mut container)?;
+    decode(&mut cursor, &mut container, &mut DecodeBudget::new())?;
     if container.len() == 1 {
         if let Some(i) = container.pop() {
             Ok(i)
@@ -643,6 +791,122 @@ pub fn decode_tlv(data: &[u8]) -> Result<TlvItem> {
     }
 }
 
+/// decode raw buffer which must contain exactly one complete tlv element.
+/// Unlike [decode_tlv], leftover bytes after the element (including a stray
+/// end-of-container) are reported as error instead of being ignored.
+pub fn decode_tlv_strict(data: &[u8]) -> Result<TlvItem> {
+    let mut container = Vec::new();
+    let mut cursor = std::io::Cursor::new(data);
+    decode(&mut cursor, &mut container, &mut DecodeBudget::new())?;
+    if cursor.position() != data.len() as u64 {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            format!("{} trailing bytes after tlv element", data.len() as u64 - cursor.position()),
+        ));
+    }
+    if container.len() != 1 {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            format!("expected single tlv element, found {}", container.len()),
+        ));
+    }
+    container.pop().ok_or_else(|| {
+        std::io::Error::new(std::io::ErrorKind::InvalidData, "no data found")
+    })
+}
+
+/// Advance `cursor` past one complete element, including nested containers.
+fn skip_element(cursor: &mut Cursor<&[u8]>, depth: usize) -> Result<()> {
+    if depth >= MAX_DECODE_DEPTH {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            format!("tlv nesting deeper than {}", MAX_DECODE_DEPTH),
+        ));
+    }
+    let fb = cursor.read_u8()?;
+    let tp = fb & 0x1f;
+    read_tag(fb >> 5, cursor)?;
+    let size = match tp {
+        TYPE_INT_1 | TYPE_UINT_1 => 1,
+        TYPE_INT_2 | TYPE_UINT_2 => 2,
+        TYPE_INT_4 | TYPE_UINT_4 | TYPE_FLOAT_4 => 4,
+        TYPE_INT_8 | TYPE_UINT_8 | TYPE_FLOAT_8 => 8,
+        TYPE_BOOL_FALSE | TYPE_BOOL_TRUE | TYPE_NULL => 0,
+        TYPE_UTF8_L1 | TYPE_OCTET_STRING_L1 => cursor.read_u8()? as u64,
+        TYPE_UTF8_L2 | TYPE_OCTET_STRING_L2 => cursor.read_u16::<LittleEndian>()? as u64,
+        TYPE_UTF8_L4 | TYPE_OCTET_STRING_L4 => cursor.read_u32::<LittleEndian>()? as u64,
+        TYPE_UTF8_L8 | TYPE_OCTET_STRING_L8 => cursor.read_u64::<LittleEndian>()?,
+        TYPE_STRUCT | TYPE_ARRAY | TYPE_LIST => {
+            while peek_type(cursor)? != TYPE_END_CONTAINER {
+                skip_element(cursor, depth + 1)?;
+            }
+            cursor.read_u8()?;
+            0
+        }
+        _ => {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                format!("unknown tlv type 0x{:x}", tp),
+            ))
+        }
+    };
+    let remaining = cursor.get_ref().len() as u64 - cursor.position();
+    if size > remaining {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::UnexpectedEof,
+            format!("tlv element length {} exceeds remaining {} bytes", size, remaining),
+        ));
+    }
+    cursor.set_position(cursor.position() + size);
+    Ok(())
+}
+
+/// Element type of the next element without consuming it.
+fn peek_type(cursor: &Cursor<&[u8]>) -> Result<u8> {
+    cursor
+        .get_ref()
+        .get(cursor.position() as usize)
+        .map(|fb| fb & 0x1f)
+        .ok_or_else(|| std::io::Error::new(std::io::ErrorKind::UnexpectedEof, "unterminated tlv container"))
+}
+
+/// Split encoded array or list element `data` into the encoded bytes of its members,
+/// preserving their exact encoding (integer widths are lost by [decode_tlv]).
+/// Returns None when `data` is not an array or list.
+pub fn split_array(data: &[u8]) -> Result<Option<Vec<&[u8]>>> {
+    let mut cursor = Cursor::new(data);
+    let fb = cursor.read_u8()?;
+    if !matches!(fb & 0x1f, TYPE_ARRAY | TYPE_LIST) {
+        return Ok(None);
+    }
+    read_tag(fb >> 5, &mut cursor)?;
+    let mut out = Vec::new();
+    while peek_type(&cursor)? != TYPE_END_CONTAINER {
+        let start = cursor.position() as usize;
+        skip_element(&mut cursor, 1)?;
+        out.push(&data[start..cursor.position() as usize]);
+    }
+    Ok(Some(out))
+}
+
+/// Re-encode anonymous element `element` (e.g. an array member from [split_array])
+/// with context tag `tag`.
+pub fn with_context_tag(element: &[u8], tag: u8) -> Result<Vec<u8>> {
+    match element.first() {
+        Some(fb) if fb >> 5 == 0 => {
+            let mut out = Vec::with_capacity(element.len() + 1);
+            out.push(fb | CTRL_CTX_L1);
+            out.push(tag);
+            out.extend_from_slice(&element[1..]);
+            Ok(out)
+        }
+        _ => Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            "element is not anonymous",
+        )),
+    }
+}
+
 #[derive(Debug)]
 pub enum TlvItemValueEnc {
     Int8(i8),
@@ -777,9 +1041,91 @@ impl TlvItemEnc {
     }
 }
 
+/// Builder for command parameters - fields of the command struct with context tags.
+///
+/// ```
+/// # use matc::tlv;
+/// // MoveToLevel: level, transition time, options mask, options override
+/// let params = tlv::CommandParams::new().u8(0, 50).u16(1, 1000).u8(2, 0).u8(3, 0).build().unwrap();
+/// ```
+#[derive(Debug, Default)]
+pub struct CommandParams {
+    items: Vec<TlvItemEnc>,
+}
+
+impl CommandParams {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn field(mut self, tag: u8, value: TlvItemValueEnc) -> Self {
+        self.items.push(TlvItemEnc { tag, value });
+        self
+    }
+
+    pub fn u8(self, tag: u8, v: u8) -> Self {
+        self.field(tag, TlvItemValueEnc::UInt8(v))
+    }
+
+    pub fn u16(self, tag: u8, v: u16) -> Self {
+        self.field(tag, TlvItemValueEnc::UInt16(v))
+    }
+
+    pub fn u32(self, tag: u8, v: u32) -> Self {
+        self.field(tag, TlvItemValueEnc::UInt32(v))
+    }
+
+    pub fn u64(self, tag: u8, v: u64) -> Self {
+        self.field(tag, TlvItemValueEnc::UInt64(v))
+    }
+
+    pub fn i8(self, tag: u8, v: i8) -> Self {
+        self.field(tag, TlvItemValueEnc::Int8(v))
+    }
+
+    pub fn i16(self, tag: u8, v: i16) -> Self {
+        self.field(tag, TlvItemValueEnc::Int16(v))
+    }
+
+    pub fn i32(self, tag: u8, v: i32) -> Self {
+        self.field(tag, TlvItemValueEnc::Int32(v))
+    }
+
+    pub fn i64(self, tag: u8, v: i64) -> Self {
+        self.field(tag, TlvItemValueEnc::Int64(v))
+    }
+
+    pub fn bool(self, tag: u8, v: bool) -> Self {
+        self.field(tag, TlvItemValueEnc::Bool(v))
+    }
+
+    pub fn string(self, tag: u8, v: &str) -> Self {
+        self.field(tag, TlvItemValueEnc::String(v.to_owned()))
+    }
+
+    pub fn octets(self, tag: u8, v: &[u8]) -> Self {
+        self.field(tag, TlvItemValueEnc::OctetString(v.to_vec()))
+    }
+
+    /// Add arbitrary element (struct, list, ...) built with [TlvItemEnc].
+    pub fn item(mut self, item: TlvItemEnc) -> Self {
+        self.items.push(item);
+        self
+    }
+
+    /// Encode parameters as payload for [invoke_request](crate::controller::Connection::invoke_request).
+    pub fn build(self) -> Result<Vec<u8>> {
+        TlvItemEnc {
+            tag: 0,
+            value: TlvItemValueEnc::StructInvisible(self.items),
+        }
+        .encode()
+    }
+}
+
 #[cfg(test)]
 mod tests {
-    use super::{decode_tlv, TlvBuffer, TlvItemEnc, TlvItemValue, TlvItemValueEnc};
+    use super::{decode_tlv, decode_tlv_strict, read_tag, split_array, with_context_tag, MAX_DECODE_DEPTH, MAX_DECODE_ELEMENTS, CommandParams, Tag, TlvBuffer, TlvItemEnc, TlvItemValue, TlvItemValueEnc};
 
     #[test]
     fn test_1() {
@@ -844,6 +1190,59 @@ mod tests {
         assert_eq!(decoded.get_u64(&[]), Some(1000000000000));
     }
 
+    #[test]
+    fn test_signed_integers_round_trip() {
+        for v in [0i8, 1, -1, i8::MIN, i8::MAX] {
+            let mut tlv = TlvBuffer::new();
+            tlv.write_int8(1, v).unwrap();
+            assert_eq!(tlv.data.len(), 3);
+            let decoded = decode_tlv(&tlv.data).unwrap();
+            assert_eq!(decoded.get_int(&[]).map(|x| x as i64), Some(v as i64));
+        }
+        for v in [0i16, -2, i16::MIN, i16::MAX] {
+            let mut tlv = TlvBuffer::new();
+            tlv.write_int16(1, v).unwrap();
+            assert_eq!(tlv.data.len(), 4);
+            let decoded = decode_tlv(&tlv.data).unwrap();
+            assert_eq!(decoded.get_int(&[]).map(|x| x as i64), Some(v as i64));
+        }
+        for v in [0i32, -100000, i32::MIN, i32::MAX] {
+            let mut tlv = TlvBuffer::new();
+            tlv.write_int32(1, v).unwrap();
+            assert_eq!(tlv.data.len(), 6);
+            let decoded = decode_tlv(&tlv.data).unwrap();
+            assert_eq!(decoded.get_int(&[]).map(|x| x as i64), Some(v as i64));
+        }
+        for v in [0i64, -1_000_000_000_000, i64::MIN, i64::MAX] {
+            let mut tlv = TlvBuffer::new();
+            tlv.write_int64(1, v).unwrap();
+            assert_eq!(tlv.data.len(), 10);
+            let decoded = decode_tlv(&tlv.data).unwrap();
+            assert_eq!(decoded.get_int(&[]).map(|x| x as i64), Some(v));
+        }
+    }
+
+    #[test]
+    fn test_null_round_trip() {
+        let mut tlv = TlvBuffer::new();
+        tlv.write_anon_struct().unwrap();
+        tlv.write_null(2).unwrap();
+        tlv.write_struct_end().unwrap();
+        assert_eq!(tlv.data, [0x15, 0x34, 0x02, 0x18]);
+        let decoded = decode_tlv(&tlv.data).unwrap();
+        assert!(matches!(decoded.get(&[2]), Some(TlvItemValue::Nil())));
+    }
+
+    #[test]
+    fn test_long_string_round_trip() {
+        let long = "x".repeat(300);
+        let mut tlv = TlvBuffer::new();
+        tlv.write_string(1, &long).unwrap();
+        assert_eq!(tlv.data[0] & 0x1f, 0xD);
+        let decoded = decode_tlv(&tlv.data).unwrap();
+        assert_eq!(decoded.get_string_owned(&[]), Some(long));
+    }
+
     #[test]
     fn test_decode_booleans() {
         // Test true
@@ -1109,4 +1508,192 @@ mod tests {
         let item = decoded.get_item(&[99]);
         assert!(item.is_none());
     }
+
+    #[test]
+    fn test_decode_strict_trailing_bytes() {
+        let mut tlv = TlvBuffer::new();
+        tlv.write_anon_struct().unwrap();
+        tlv.write_uint8(1, 7).unwrap();
+        tlv.write_struct_end().unwrap();
+        let decoded = decode_tlv_strict(&tlv.data).unwrap();
+        assert_eq!(decoded.get_u8(&[1]), Some(7));
+
+        // another complete element after the first one
+        let mut junk = tlv.data.clone();
+        junk.extend_from_slice(&[0x24, 0x02, 0x05]);
+        assert!(decode_tlv(&junk).is_ok());
+        assert!(decode_tlv_strict(&junk).is_err());
+
+        // stray end-of-container followed by garbage
+        let mut junk = tlv.data.clone();
+        junk.extend_from_slice(&[0x18, 0xaa, 0xbb]);
+        assert!(decode_tlv_strict(&junk).is_err());
+    }
+
+    #[test]
+    fn test_command_params_builder() {
+        let built = CommandParams::new().u8(0, 50).u16(1, 1000).u8(2, 0).u8(3, 0).build().unwrap();
+        let manual = TlvItemEnc {
+            tag: 0,
+            value: TlvItemValueEnc::StructInvisible(vec![
+                (0, TlvItemValueEnc::UInt8(50)).into(),
+                (1, TlvItemValueEnc::UInt16(1000)).into(),
+                (2, TlvItemValueEnc::UInt8(0)).into(),
+                (3, TlvItemValueEnc::UInt8(0)).into(),
+            ]),
+        }
+        .encode()
+        .unwrap();
+        assert_eq!(built, manual);
+        assert_eq!(hex::encode(&built), "2400322501e803240200240300");
+
+        let built = CommandParams::new()
+            .octets(0, &[1, 2, 3])
+            .string(1, "ab")
+            .bool(2, true)
+            .i8(3, -1)
+            .item((4, TlvItemValueEnc::Struct(vec![(0, TlvItemValueEnc::UInt32(7)).into()])).into())
+            .build()
+            .unwrap();
+        assert_eq!(hex::encode(built), "3000030102032c0102616229022003ff350426000700000018");
+    }
+
+    #[test]
+    fn test_tag_forms() {
+        let forms: [(&str, Tag); 8] = [
+            ("04", Tag::Anonymous),
+            ("24", Tag::Context(5)),
+            ("44", Tag::CommonProfile(0x1234)),
+            ("64", Tag::CommonProfile(0x12345678)),
+            ("84", Tag::Implicit(0x1234)),
+            ("a4", Tag::Implicit(0x12345678)),
+            ("c4", Tag::FullyQualified { vendor: 0xfff1, profile: 0xdead, tag: 0x1234 }),
+            ("e4", Tag::FullyQualified { vendor: 0xfff1, profile: 0xdead, tag: 0x12345678 }),
+        ];
+        let tag_bytes = ["", "05", "3412", "78563412", "3412", "78563412", "f1ffadde3412", "f1ffadde78563412"];
+        for ((ctrl, want), tag) in forms.iter().zip(tag_bytes) {
+            // uint8 element with given tag form followed by context tagged uint8 element
+            let data = hex::decode(format!("15{}{}2a24010718", ctrl, tag)).unwrap();
+            let fb = data[1];
+            let mut cursor = std::io::Cursor::new(&data[2..]);
+            assert_eq!(read_tag(fb >> 5, &mut cursor).unwrap(), *want);
+            assert_eq!(cursor.position() as usize, tag.len() / 2);
+
+            let decoded = decode_tlv(&data).unwrap();
+            let TlvItemValue::List(items) = &decoded.value else {
+                panic!("not a struct: {:?}", decoded);
+            };
+            assert_eq!(items.len(), 2, "{:?}", want);
+            assert_eq!(items[0].tag, want.context());
+            assert_eq!(items[0].value, TlvItemValue::Int(42));
+            assert_eq!(decoded.get_u8(&[1]), Some(7));
+        }
+    }
+
+    #[test]
+    fn test_large_octet_string_l4() {
+        let data: Vec<u8> = (0..70_000u32).map(|i| i as u8).collect();
+        let mut tlv = TlvBuffer::new();
+        tlv.write_anon_struct().unwrap();
+        tlv.write_octetstring(1, &data).unwrap();
+        tlv.write_uint8(2, 9).unwrap();
+        tlv.write_struct_end().unwrap();
+        // control byte: context tag, octet string with 4 byte length
+        assert_eq!(tlv.data[1], 0x32);
+        assert_eq!(&tlv.data[3..7], &70_000u32.to_le_bytes());
+        let decoded = decode_tlv(&tlv.data).unwrap();
+        assert_eq!(decoded.get_octet_string(&[1]), Some(data.as_slice()));
+        assert_eq!(decoded.get_u8(&[2]), Some(9));
+
+        let mut tlv = TlvBuffer::new();
+        tlv.write_octetstring_notag(&data).unwrap();
+        assert_eq!(tlv.data[0], 0x12);
+        assert_eq!(tlv.data.len(), 1 + 4 + data.len());
+    }
+
+    #[test]
+    fn test_decode_element_limit() {
+        // anonymous array of MAX_DECODE_ELEMENTS bools - one byte each
+        let mut data = vec![0x16];
+        data.extend(vec![0x08; MAX_DECODE_ELEMENTS - 1]);
+        data.push(0x18);
+        let decoded = decode_tlv(&data).unwrap();
+        match decoded.value {
+            TlvItemValue::List(l) => assert_eq!(l.len(), MAX_DECODE_ELEMENTS - 1),
+            _ => panic!("expected list"),
+        }
+        let mut data = vec![0x16];
+        data.extend(vec![0x08; MAX_DECODE_ELEMENTS]);
+        data.push(0x18);
+        assert!(decode_tlv(&data).is_err());
+    }
+
+    #[test]
+    fn test_decode_depth_limit() {
+        let nested = |depth: usize| {
+            let mut data = vec![0x16; depth];
+            data.extend(vec![0x18; depth]);
+            data
+        };
+        assert!(decode_tlv(&nested(MAX_DECODE_DEPTH)).is_ok());
+        assert!(decode_tlv(&nested(MAX_DECODE_DEPTH + 1)).is_err());
+    }
+
+    #[test]
+    fn test_decode_length_beyond_input() {
+        // octet string claiming u64::MAX bytes must fail without allocating
+        let mut data = vec![0x13];
+        data.extend(u64::MAX.to_le_bytes());
+        assert!(decode_tlv(&data).is_err());
+        let mut data = vec![0x0f];
+        data.extend(u64::MAX.to_le_bytes());
+        assert!(decode_tlv(&data).is_err());
+    }
+
+    #[test]
+    fn test_split_array() {
+        let mut t = TlvBuffer::new();
+        t.write_array(2).unwrap();
+        t.write_uint8_notag(7).unwrap();
+        t.write_uint32_notag(0x10000).unwrap();
+        t.write_anon_struct().unwrap();
+        t.write_string(0, "x").unwrap();
+        t.write_anon_list().unwrap();
+        t.write_struct_end().unwrap();
+        t.write_struct_end().unwrap();
+        t.write_struct_end().unwrap();
+        let items = split_array(&t.data).unwrap().unwrap();
+        assert_eq!(items.len(), 3);
+        // members keep their encoding, e.g. integer width
+        assert_eq!(items[0], [0x04, 7]);
+        assert_eq!(items[1], [0x06, 0, 0, 1, 0]);
+        assert_eq!(items[2], [0x15, 0x2c, 0, 1, b'x', 0x17, 0x18, 0x18]);
+        assert_eq!(items.concat().len() + 3, t.data.len());
+
+        let tagged = with_context_tag(items[1], 2).unwrap();
+        assert_eq!(decode_tlv(&tagged).unwrap().tag, 2);
+        assert_eq!(decode_tlv(&tagged).unwrap().value, TlvItemValue::Int(0x10000));
+        assert!(with_context_tag(&tagged, 3).is_err());
+
+        let mut s = TlvBuffer::new();
+        s.write_uint8(2, 1).unwrap();
+        assert!(split_array(&s.data).unwrap().is_none());
+        // unterminated array
+        assert!(split_array(&t.data[..t.data.len() - 1]).is_err());
+    }
+
+    #[test]
+    fn test_to_json() {
+        let mut t = TlvBuffer::new();
+        t.write_anon_struct().unwrap();
+        t.write_uint8(1, 5).unwrap();
+        t.write_octetstring(2, &[0xde, 0xad]).unwrap();
+        t.write_array(3).unwrap();
+        t.write_uint8_notag(7).unwrap();
+        t.write_uint8_notag(8).unwrap();
+        t.write_struct_end().unwrap();
+        t.write_struct_end().unwrap();
+        let v = decode_tlv(&t.data).unwrap().value.to_json();
+        assert_eq!(v, serde_json::json!({"1": 5, "2": "dead", "3": [7, 8]}));
+    }
 }
@@ -1,10 +1,12 @@
 // Simple UDP transport abstraction that multiplexes datagrams by remote address
 // into per-connection mpsc channels. Each Connection is a logical association
-// identified solely by the peer's socket address string.
+// identified by the peer's socket address string and, once a secure session is
+// set up over it, by the local session ids it allocated from the Transport.
+// Several Connections (e.g. of different Controllers) may share one remote address.
 
 /// Returned by [`Connection::receive`] when the underlying mpsc channel has been
 /// closed (e.g. because the same remote address was re-registered via
-/// [`Transport::create_connection`]). Callers can detect this via
+/// [`Transport::create_connection`] before this connection allocated a session id). Callers can detect this via
 /// `anyhow::Error::downcast_ref::<ConnectionClosed>()` and bail immediately
 /// instead of spinning on retransmit.
 #[derive(Debug)]
@@ -21,7 +23,7 @@ impl std::error::Error for ConnectionClosed {}
 use anyhow::{Context, Result};
 use std::{
     collections::HashMap,
-    net::{IpAddr, SocketAddr},
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
     sync::{
         atomic::{AtomicU64, Ordering},
         Arc,
@@ -82,22 +84,169 @@ pub trait ConnectionTrait: Send + Sync {
     /// Time since the last message was received from the peer, if any.
     /// Used to select the active vs idle retransmission interval.
     fn last_received_elapsed(&self) -> Option<Duration> { None }
+    /// Pick a local session id for a new PASE/CASE session over this connection.
+    /// Shared transports hand out ids unique among their connections, so secured
+    /// messages addressed to the id are routed to this connection.
+    fn alloc_session_id(&self) -> Result<u16> {
+        Ok(random_session_id())
+    }
+    /// Return session id from [ConnectionTrait::alloc_session_id] once its session is
+    /// replaced or closed, so the connection does not keep reserving it.
+    fn release_session_id(&self, _id: u16) {}
 }
 
+/// Random non-zero session id (0 is the unsecured session).
+fn random_session_id() -> u16 {
+    loop {
+        let id = rand::random();
+        if id != UNSECURED_SESSION_ID {
+            return id;
+        }
+    }
+}
+
+const UNSECURED_SESSION_ID: u16 = 0;
+
+/// Random picks tried by [Transport] before giving up on finding a free session id.
+const MAX_SESSION_ID_ATTEMPTS: usize = 1000;
+
 #[derive(Debug, Clone)]
 struct ConnectionInfo {
     sender: tokio::sync::mpsc::Sender<Vec<u8>>,
     generation: u64,
 }
 
+/// Socket options for [Transport::new_with_options].
+#[derive(Debug, Clone, Default)]
+pub struct TransportOptions {
+    /// Set SO_REUSEADDR (and SO_REUSEPORT where available) before binding, so a fixed
+    /// source port can be bound again while a previous socket still holds it (e.g. on
+    /// quick restart). It does not let running controllers share the port: the kernel
+    /// delivers each inbound unicast datagram to just one of the bound sockets (Linux
+    /// picks it by hash of the address 4-tuple), so replies may reach the wrong
+    /// controller. Separate controllers should bind port 0 or share one [Transport].
+    pub reuse_port: bool,
+    /// Send and receive only via this network interface, given by name or by one of
+    /// its addresses (see [list_interfaces]). Supported on Linux and Apple platforms.
+    pub interface: Option<String>,
+}
+
+/// Network interface with its addresses, as returned by [list_interfaces].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InterfaceInfo {
+    pub name: String,
+    /// OS interface index; scope id of link-local IPv6 addresses on this interface.
+    pub index: Option<u32>,
+    pub ipv4: Vec<Ipv4Addr>,
+    pub ipv6: Vec<Ipv6Addr>,
+}
+
+impl InterfaceInfo {
+    /// Whether `filter` names this interface or is one of its addresses.
+    pub fn matches(&self, filter: &str) -> bool {
+        if self.name == filter {
+            return true;
+        }
+        match filter.parse::<IpAddr>() {
+            Ok(IpAddr::V4(ip)) => self.ipv4.contains(&ip),
+            Ok(IpAddr::V6(ip)) => self.ipv6.contains(&ip),
+            Err(_) => false,
+        }
+    }
+}
+
+/// Network interfaces usable for discovery and transport, in the order the OS reports them.
+pub fn list_interfaces() -> Result<Vec<InterfaceInfo>> {
+    let ifaces = if_addrs::get_if_addrs().context("listing network interfaces")?;
+    Ok(group_interfaces(ifaces))
+}
+
+/// Interface selected by name or address, see [InterfaceInfo::matches].
+pub fn find_interface(filter: &str) -> Result<InterfaceInfo> {
+    list_interfaces()?
+        .into_iter()
+        .find(|iface| iface.matches(filter))
+        .context(format!("no interface matches {:?}", filter))
+}
+
+/// Merge the per-address entries of `if_addrs` into one entry per interface.
+fn group_interfaces(ifaces: Vec<if_addrs::Interface>) -> Vec<InterfaceInfo> {
+    let mut out: Vec<InterfaceInfo> = Vec::new();
+    for iface in ifaces {
+        let pos = match out.iter().position(|i| i.name == iface.name) {
+            Some(pos) => pos,
+            None => {
+                out.push(InterfaceInfo {
+                    name: iface.name.clone(),
+                    index: iface.index,
+                    ipv4: Vec::new(),
+                    ipv6: Vec::new(),
+                });
+                out.len() - 1
+            }
+        };
+        match iface.ip() {
+            IpAddr::V4(ip) => out[pos].ipv4.push(ip),
+            IpAddr::V6(ip) => out[pos].ipv6.push(ip),
+        }
+    }
+    out
+}
+
+/// Restrict socket to the interface selected in [TransportOptions::interface].
+fn bind_interface(sock: &socket2::Socket, addr: &SocketAddr, filter: &str) -> Result<()> {
+    let iface = find_interface(filter)?;
+    #[cfg(any(target_os = "android", target_os = "fuchsia", target_os = "linux"))]
+    {
+        _ = addr;
+        sock.bind_device(Some(iface.name.as_bytes()))
+            .context(format!("can't bind to interface {}", iface.name))?;
+        Ok(())
+    }
+    #[cfg(any(target_os = "ios", target_os = "macos", target_os = "tvos", target_os = "visionos", target_os = "watchos"))]
+    {
+        let index = iface
+            .index
+            .and_then(std::num::NonZeroU32::new)
+            .context(format!("no index for interface {}", iface.name))?;
+        match addr {
+            SocketAddr::V4(_) => sock.bind_device_by_index_v4(Some(index)),
+            SocketAddr::V6(_) => sock.bind_device_by_index_v6(Some(index)),
+        }
+        .context(format!("can't bind to interface {}", iface.name))?;
+        Ok(())
+    }
+    #[cfg(not(any(
+        target_os = "android",
+        target_os = "fuchsia",
+        target_os = "linux",
+        target_os = "ios",
+        target_os = "macos",
+        target_os = "tvos",
+        target_os = "visionos",
+        target_os = "watchos"
+    )))]
+    {
+        _ = (sock, addr);
+        anyhow::bail!("binding to interface {} not supported on this platform", iface.name)
+    }
+}
+
 /// Shared transport holding:
 /// * a single UDP socket
-/// * a map of remote_addr -> channel sender
+/// * a map of remote_addr -> channel senders of connections to that address
+/// * a map of allocated local session id -> generation of owning connection
 /// * a task to read incoming datagrams and dispatch them
 /// * a task to remove connection entries when Connections drop
+///
+/// Secured datagrams go to the connection which allocated their destination
+/// session id, falling back to the newest connection to the sender's address.
+/// Unsecured datagrams (PASE/CASE handshakes) go to every connection to the
+/// sender's address; handshakes pick theirs by exchange id.
 pub struct Transport {
     socket: Arc<UdpSocket>,
-    connections: Mutex<HashMap<String, ConnectionInfo>>,
+    connections: Mutex<HashMap<String, Vec<ConnectionInfo>>>,
+    sessions: std::sync::Mutex<HashMap<u16, u64>>,
     remove_channel_sender: tokio::sync::mpsc::UnboundedSender<(String, u64)>,
     next_generation: AtomicU64,
     stop_receive_token: tokio_util::sync::CancellationToken,
@@ -143,14 +292,35 @@ impl Transport {
             let self_strong = self_weak
                 .upgrade()
                 .context("weakpointer to self is gone - just stop")?;
-            let cons = self_strong.connections.lock().await;
-            if let Some(c) = cons.get(&scopeless_key(addr)) {
-                _ = c.sender.send(buf).await;
+            let targets = self_strong.route(&scopeless_key(addr), &buf).await;
+            for sender in targets {
+                _ = sender.send(buf.clone()).await;
             }
         }
         Ok(())
     }
 
+    /// Channels which should receive datagram `data` from `remote`.
+    async fn route(&self, remote: &str, data: &[u8]) -> Vec<tokio::sync::mpsc::Sender<Vec<u8>>> {
+        let cons = self.connections.lock().await;
+        let Some(infos) = cons.get(remote) else {
+            return Vec::new();
+        };
+        let session_id = crate::messages::MessageHeader::decode(data)
+            .map(|(header, _)| header.session_id)
+            .ok();
+        if session_id == Some(UNSECURED_SESSION_ID) {
+            return infos.iter().map(|c| c.sender.clone()).collect();
+        }
+        let owner = session_id.and_then(|id| self.sessions.lock().unwrap().get(&id).copied());
+        infos
+            .iter()
+            .find(|c| Some(c.generation) == owner)
+            .or_else(|| infos.last())
+            .map(|c| vec![c.sender.clone()])
+            .unwrap_or_default()
+    }
+
     async fn read_from_delete_queue_loop(
         mut remove_channel_receiver: tokio::sync::mpsc::UnboundedReceiver<(String, u64)>,
         self_weak: std::sync::Weak<Transport>,
@@ -167,12 +337,15 @@ impl Transport {
                         .upgrade()
                         .context("weak to self is gone - just stop")?;
                     let mut cons = self_strong.connections.lock().await;
-                    // Only remove if the entry still belongs to this Connection.
-                    // A concurrent create_connection for the same address inserts a
-                    // newer generation, so the stale remove becomes a no-op.
-                    if cons.get(&addr).map(|c| c.generation) == Some(gen) {
-                        cons.remove(&addr);
+                    // Only remove the entry of this Connection; the entry may already
+                    // be gone when a newer connection to the address replaced it.
+                    if let Some(infos) = cons.get_mut(&addr) {
+                        infos.retain(|c| c.generation != gen);
+                        if infos.is_empty() {
+                            cons.remove(&addr);
+                        }
                     }
+                    self_strong.sessions.lock().unwrap().retain(|_, g| *g != gen);
                 }
                 None => break, // Sender dropped => shutdown
             }
@@ -181,8 +354,15 @@ impl Transport {
     }
 
     /// Bind a UDP socket and spawn background tasks.
+    /// Use port 0 (e.g. `0.0.0.0:0`) to let the OS pick an ephemeral port;
+    /// [Transport::local_addr] returns the port actually bound.
     pub async fn new(local: &str) -> Result<Arc<Self>> {
-        let socket = UdpSocket::bind(local).await?;
+        Self::new_with_options(local, &TransportOptions::default()).await
+    }
+
+    /// Like [Transport::new] with additional socket options.
+    pub async fn new_with_options(local: &str, options: &TransportOptions) -> Result<Arc<Self>> {
+        let socket = Self::bind(local, options).await?;
         let (remove_channel_sender, remove_channel_receiver) =
             tokio::sync::mpsc::unbounded_channel();
         let stop_receive_token = tokio_util::sync::CancellationToken::new();
@@ -190,6 +370,7 @@ impl Transport {
         let o = Arc::new(Self {
             socket: Arc::new(socket),
             connections: Mutex::new(HashMap::new()),
+            sessions: std::sync::Mutex::new(HashMap::new()),
             remove_channel_sender,
             next_generation: AtomicU64::new(1),
             stop_receive_token,
@@ -206,14 +387,92 @@ impl Transport {
         Ok(o)
     }
 
-    /// Create (or replace) a logical connection entry for the given remote address.
+    async fn bind(local: &str, options: &TransportOptions) -> Result<UdpSocket> {
+        let addr = tokio::net::lookup_host(local)
+            .await?
+            .next()
+            .context(format!("no address for {}", local))?;
+        let sock = socket2::Socket::new(
+            socket2::Domain::for_address(addr),
+            socket2::Type::DGRAM,
+            Some(socket2::Protocol::UDP),
+        )?;
+        if options.reuse_port {
+            sock.set_reuse_address(true)?;
+            #[cfg(not(target_os = "windows"))]
+            sock.set_reuse_port(true)?;
+        }
+        if let Some(interface) = &options.interface {
+            bind_interface(&sock, &addr, interface)?;
+        }
+        sock.bind(&addr.into())
+            .context(format!("can't bind {}", addr))?;
+        sock.set_nonblocking(true)?;
+        Ok(UdpSocket::from_std(sock.into())?)
+    }
+
+    /// Local address of the socket, including the port chosen by the OS when bound to port 0.
+    pub fn local_addr(&self) -> Result<SocketAddr> {
+        Ok(self.socket.local_addr()?)
+    }
+
+    /// Allocate local session id unused by other connections of this transport.
+    /// Fails when no free id is hit within [MAX_SESSION_ID_ATTEMPTS] random picks.
+    fn alloc_session_id(&self, generation: u64) -> Result<u16> {
+        let mut sessions = self.sessions.lock().unwrap();
+        for _ in 0..MAX_SESSION_ID_ATTEMPTS {
+            let id = random_session_id();
+            if let std::collections::hash_map::Entry::Vacant(e) = sessions.entry(id) {
+                e.insert(generation);
+                return Ok(id);
+            }
+        }
+        anyhow::bail!("no free session id ({} in use)", sessions.len())
+    }
+
+    /// Release session id allocated by connection `generation`.
+    fn release_session_id(&self, generation: u64, id: u16) {
+        let mut sessions = self.sessions.lock().unwrap();
+        if sessions.get(&id) == Some(&generation) {
+            sessions.remove(&id);
+        }
+    }
+
+    /// Create a logical connection entry for the given remote address. Earlier
+    /// connections to the same address are closed unless they allocated a session id
+    /// (see [ConnectionTrait::alloc_session_id]), so connections of several
+    /// controllers to one device coexist while stale handshakes are abandoned.
+    ///
+    /// Connections which stay alive keep receiving: secured datagrams go to the owner
+    /// of the destination session id, while every connection to the address receives
+    /// unsecured datagrams (handshakes), each picking its own by exchange id.
     pub async fn create_connection(self: &Arc<Self>, remote: &str) -> Arc<dyn ConnectionTrait> {
         let (remote, scope_id) = split_scope(remote);
-        let remote = normalize_remote_for_socket(&self.socket, &remote);
+        self.register_connection(&remote, scope_id).await
+    }
+
+    /// Like [Transport::create_connection] for an already resolved address. The scope id
+    /// of an IPv6 address (interface to reach a link-local peer) is kept.
+    pub async fn create_connection_addr(self: &Arc<Self>, remote: SocketAddr) -> Arc<dyn ConnectionTrait> {
+        let (remote, scope_id) = split_socket_addr(remote);
+        self.register_connection(&remote, scope_id).await
+    }
+
+    async fn register_connection(
+        self: &Arc<Self>,
+        remote: &str,
+        scope_id: Option<u32>,
+    ) -> Arc<dyn ConnectionTrait> {
+        let remote = normalize_remote_for_socket(&self.socket, remote);
         let mut clock = self.connections.lock().await;
         let generation = self.next_generation.fetch_add(1, Ordering::Relaxed);
         let (sender, receiver) = tokio::sync::mpsc::channel(32);
-        clock.insert(remote.to_owned(), ConnectionInfo { sender, generation });
+        let infos = clock.entry(remote.to_owned()).or_default();
+        {
+            let sessions = self.sessions.lock().unwrap();
+            infos.retain(|c| sessions.values().any(|g| *g == c.generation));
+        }
+        infos.push(ConnectionInfo { sender, generation });
         Arc::new(Connection {
             transport: self.clone(),
             remote_address: remote,
@@ -227,11 +486,6 @@ impl Transport {
     }
 }
 
-/// True for IPv6 link-local (fe80::/10).
-fn is_link_local_v6(ip: &std::net::Ipv6Addr) -> bool {
-    (ip.segments()[0] & 0xffc0) == 0xfe80
-}
-
 /// Split the zone out of `[fe80::...%<idx>]:port`: returns the zone-less address and the scope_id.
 fn split_scope(remote: &str) -> (String, Option<u32>) {
     if let Some(pct) = remote.find('%') {
@@ -246,6 +500,15 @@ fn split_scope(remote: &str) -> (String, Option<u32>) {
     (remote.to_owned(), None)
 }
 
+/// Zone-less address and scope_id of a resolved address.
+fn split_socket_addr(addr: SocketAddr) -> (String, Option<u32>) {
+    let scope_id = match addr {
+        SocketAddr::V6(v6) if v6.scope_id() != 0 => Some(v6.scope_id()),
+        _ => None,
+    };
+    (scopeless_key(addr), scope_id)
+}
+
 /// Connection key without the zone.
 fn scopeless_key(addr: SocketAddr) -> String {
     match addr {
@@ -267,7 +530,7 @@ impl Connection {
         if let (Some(scope), Ok(SocketAddr::V6(v6))) =
             (self.scope_id, self.remote_address.parse::<SocketAddr>())
         {
-            if is_link_local_v6(v6.ip()) {
+            if crate::discover::is_link_local_v6(v6.ip()) {
                 let target = std::net::SocketAddrV6::new(*v6.ip(), v6.port(), v6.flowinfo(), scope);
                 socket.send_to(data, SocketAddr::V6(target)).await?;
                 return Ok(());
@@ -325,6 +588,12 @@ impl ConnectionTrait for Connection {
         }
         Some(self.created.elapsed().saturating_sub(Duration::from_millis(ms)))
     }
+    fn alloc_session_id(&self) -> Result<u16> {
+        self.transport.alloc_session_id(self.generation)
+    }
+    fn release_session_id(&self, id: u16) {
+        self.transport.release_session_id(self.generation, id)
+    }
 }
 
 impl Drop for Connection {
@@ -336,3 +605,253 @@ impl Drop for Connection {
     }
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::{Ipv6Addr, SocketAddrV6};
+
+    #[test]
+    fn test_split_scoped_addr() {
+        let addr = SocketAddr::V6(SocketAddrV6::new("fe80::1".parse().unwrap(), 5540, 0, 3));
+        assert_eq!(split_socket_addr(addr), ("[fe80::1]:5540".to_owned(), Some(3)));
+        // same result as the string form with zone
+        assert_eq!(split_socket_addr(addr), split_scope("[fe80::1%3]:5540"));
+
+        let addr = SocketAddr::V6(SocketAddrV6::new(Ipv6Addr::LOCALHOST, 5540, 0, 0));
+        assert_eq!(split_socket_addr(addr), ("[::1]:5540".to_owned(), None));
+        let addr: SocketAddr = "192.168.1.2:5540".parse().unwrap();
+        assert_eq!(split_socket_addr(addr), ("192.168.1.2:5540".to_owned(), None));
+    }
+
+    #[test]
+    fn test_list_interfaces() {
+        let ifaces = list_interfaces().unwrap();
+        let raw = if_addrs::get_if_addrs().unwrap();
+        // every address reported by if_addrs appears exactly once, under its interface
+        assert_eq!(ifaces.iter().map(|i| i.ipv4.len() + i.ipv6.len()).sum::<usize>(), raw.len());
+        for r in &raw {
+            let iface = ifaces.iter().find(|i| i.name == r.name).expect("interface listed");
+            assert_eq!(iface.index, r.index);
+            assert!(iface.matches(&r.ip().to_string()));
+        }
+        let mut names: Vec<_> = ifaces.iter().map(|i| i.name.as_str()).collect();
+        names.sort_unstable();
+        names.dedup();
+        assert_eq!(names.len(), ifaces.len());
+
+        assert!(find_interface("no-such-interface0").is_err());
+        if let Some(first) = ifaces.first() {
+            assert_eq!(find_interface(&first.name).unwrap(), *first);
+        }
+    }
+
+    #[tokio::test]
+    #[ignore = "needs a loopback interface and SO_BINDTODEVICE (CAP_NET_RAW on older Linux)"]
+    async fn test_bind_interface() {
+        let lo = list_interfaces()
+            .unwrap()
+            .into_iter()
+            .find(|i| i.ipv4.contains(&Ipv4Addr::LOCALHOST))
+            .expect("no interface with 127.0.0.1");
+        let options = TransportOptions { interface: Some(lo.name.clone()), ..Default::default() };
+        let transport = Transport::new_with_options("0.0.0.0:0", &options).await.unwrap();
+        let peer = tokio::net::UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let conn = transport.create_connection(&peer.local_addr().unwrap().to_string()).await;
+        conn.send(b"hello").await.unwrap();
+        let mut buf = [0u8; 16];
+        let (n, _) = tokio::time::timeout(Duration::from_secs(2), peer.recv_from(&mut buf))
+            .await
+            .expect("datagram via loopback interface")
+            .unwrap();
+        assert_eq!(&buf[..n], b"hello");
+    }
+
+    #[tokio::test]
+    async fn test_bind_unknown_interface() {
+        let options = TransportOptions { interface: Some("no-such-interface0".into()), ..Default::default() };
+        assert!(Transport::new_with_options("0.0.0.0:0", &options).await.is_err());
+    }
+
+    #[tokio::test]
+    #[ignore = "needs a network interface with an IPv6 link-local address"]
+    async fn test_dial_scoped_link_local() {
+        let (ip, index) = if_addrs::get_if_addrs()
+            .unwrap_or_default()
+            .into_iter()
+            .find_map(|iface| match (iface.ip(), iface.index) {
+                (std::net::IpAddr::V6(v6), Some(index)) if crate::discover::is_link_local_v6(&v6) => {
+                    Some((v6, index))
+                }
+                _ => None,
+            })
+            .expect("no interface with IPv6 link-local address");
+        let peer = tokio::net::UdpSocket::bind(SocketAddrV6::new(ip, 0, 0, index)).await.unwrap();
+        let addr = crate::discover::ScopedAddr::new(
+            std::net::IpAddr::V6(ip),
+            peer.local_addr().unwrap().port(),
+            Some(index),
+        );
+
+        let transport = Transport::new("[::]:0").await.unwrap();
+        let conn = transport.create_connection(&addr.to_string()).await;
+        conn.send(b"hello").await.unwrap();
+        let mut buf = [0u8; 16];
+        let (n, _) = tokio::time::timeout(Duration::from_secs(2), peer.recv_from(&mut buf))
+            .await
+            .expect("datagram to scoped link-local address")
+            .unwrap();
+        assert_eq!(&buf[..n], b"hello");
+    }
+
+    #[tokio::test]
+    async fn test_reuse_port() {
+        let reuse = TransportOptions { reuse_port: true, ..Default::default() };
+        let first = Transport::new_with_options("127.0.0.1:0", &reuse).await.unwrap();
+        let addr = first.local_addr().unwrap();
+        assert_ne!(addr.port(), 0);
+        // binding succeeds; which of the two sockets gets inbound datagrams is up to the
+        // kernel, so nothing is exchanged over them here
+        let second = Transport::new_with_options(&addr.to_string(), &reuse).await.unwrap();
+        assert_eq!(second.local_addr().unwrap(), addr);
+        // without reuse the port stays taken
+        assert!(Transport::new(&addr.to_string()).await.is_err());
+    }
+
+    // Two controllers talking to one device over a shared transport.
+    #[tokio::test]
+    async fn test_shared_transport_demux() {
+        let device = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let device_addr = device.local_addr().unwrap().to_string();
+        let transport = Transport::new("127.0.0.1:0").await.unwrap();
+        let local = transport.local_addr().unwrap();
+        let timeout = Duration::from_secs(5);
+
+        let first = transport.create_connection(&device_addr).await;
+        let first_session = first.alloc_session_id().unwrap();
+        let second = transport.create_connection(&device_addr).await;
+        let second_session = second.alloc_session_id().unwrap();
+        assert_ne!(first_session, second_session);
+
+        // secured messages interleaved; each reaches the owner of its session id
+        let message = |session_id: u16, payload: &[u8]| {
+            let mut ses = crate::session::Session::new();
+            ses.session_id = session_id;
+            ses.encode_message(payload).unwrap()
+        };
+        for (session_id, payload) in [
+            (second_session, b"b1"),
+            (first_session, b"a1"),
+            (second_session, b"b2"),
+            (first_session, b"a2"),
+        ] {
+            device.send_to(&message(session_id, payload), local).await.unwrap();
+        }
+        let payload = |data: Vec<u8>| crate::messages::MessageHeader::decode(&data).unwrap().1;
+        assert_eq!(payload(first.receive(timeout).await.unwrap()), b"a1");
+        assert_eq!(payload(first.receive(timeout).await.unwrap()), b"a2");
+        assert_eq!(payload(second.receive(timeout).await.unwrap()), b"b1");
+        assert_eq!(payload(second.receive(timeout).await.unwrap()), b"b2");
+
+        // unknown session id goes to the newest connection, handshakes to all
+        let unknown = (1..=u16::MAX)
+            .find(|id| ![first_session, second_session].contains(id))
+            .unwrap();
+        device.send_to(&message(unknown, b"x"), local).await.unwrap();
+        device.send_to(&message(UNSECURED_SESSION_ID, b"hs"), local).await.unwrap();
+        assert_eq!(payload(second.receive(timeout).await.unwrap()), b"x");
+        assert_eq!(payload(second.receive(timeout).await.unwrap()), b"hs");
+        assert_eq!(payload(first.receive(timeout).await.unwrap()), b"hs");
+
+        // connection without session is replaced by newer one to the same address
+        let third = transport.create_connection(&device_addr).await;
+        let fourth = transport.create_connection(&device_addr).await;
+        let err = third.receive(timeout).await.unwrap_err();
+        assert!(err.downcast_ref::<ConnectionClosed>().is_some());
+        drop(fourth);
+
+        // replaced session releases its id, the connection keeps the others
+        let rekeyed = second.alloc_session_id().unwrap();
+        second.release_session_id(second_session);
+        assert!(!transport.sessions.lock().unwrap().contains_key(&second_session));
+        assert!(transport.sessions.lock().unwrap().contains_key(&rekeyed));
+        // only the owner can release an id
+        second.release_session_id(first_session);
+        assert!(transport.sessions.lock().unwrap().contains_key(&first_session));
+
+        // session ids are released with their connection
+        drop(first);
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert!(!transport.sessions.lock().unwrap().contains_key(&first_session));
+        assert!(transport.sessions.lock().unwrap().contains_key(&rekeyed));
+    }
+
+    #[tokio::test]
+    async fn test_reauth_releases_session_id() {
+        let transport = Transport::new("127.0.0.1:0").await.unwrap();
+        let conn = transport.create_connection("127.0.0.1:5540").await;
+        let mut session = crate::session::Session::new();
+        session.my_session_id = conn.alloc_session_id().unwrap();
+        let first = session.my_session_id;
+        let active = crate::active_connection::ActiveConnection::new(conn.clone(), session);
+        for _ in 0..3 {
+            let mut session = crate::session::Session::new();
+            session.my_session_id = conn.alloc_session_id().unwrap();
+            active.reauth_with_session(session).await.unwrap();
+        }
+        let sessions = transport.sessions.lock().unwrap();
+        assert_eq!(sessions.len(), 1);
+        assert!(!sessions.contains_key(&first));
+    }
+
+    #[tokio::test]
+    async fn test_failed_pase_releases_session_id() {
+        let device = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let transport = Transport::new("127.0.0.1:0").await.unwrap();
+        let conn = transport.create_connection(&device.local_addr().unwrap().to_string()).await;
+        let pase = tokio::spawn({
+            let conn = conn.clone();
+            async move { crate::controller::auth_spake_with_params(conn.as_ref(), 20202021, None).await }
+        });
+        let mut buf = [0u8; 1024];
+        let (n, from) = device.recv_from(&mut buf).await.unwrap();
+        let req = crate::messages::Message::decode(&buf[..n]).unwrap();
+        assert_eq!(transport.sessions.lock().unwrap().len(), 1);
+
+        // device rejects PBKDF request
+        let status = crate::device_messages::status_report(
+            req.protocol_header.exchange_id,
+            1,
+            0,
+            2,
+            req.message_header.message_counter as i64,
+        )
+        .unwrap();
+        let encoded = crate::session::Session::new().encode_message(&status).unwrap();
+        device.send_to(&encoded, from).await.unwrap();
+        assert!(pase.await.unwrap().is_err());
+        assert!(transport.sessions.lock().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_alloc_session_id_exhausted() {
+        let transport = Transport::new("127.0.0.1:0").await.unwrap();
+        let conn = transport.create_connection("127.0.0.1:5540").await;
+        transport.sessions.lock().unwrap().extend((1..=u16::MAX).map(|id| (id, u64::MAX)));
+        assert!(conn.alloc_session_id().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_create_connection_addr() {
+        let peer = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let transport = Transport::new("127.0.0.1:0").await.unwrap();
+        let connection = transport.create_connection_addr(peer.local_addr().unwrap()).await;
+        connection.send(b"ping").await.unwrap();
+        let mut buf = [0u8; 16];
+        let (n, from) = peer.recv_from(&mut buf).await.unwrap();
+        assert_eq!(&buf[..n], b"ping");
+        // reply is dispatched to the connection registered under the resolved address
+        peer.send_to(b"pong", from).await.unwrap();
+        let reply = connection.receive(Duration::from_secs(5)).await.unwrap();
+        assert_eq!(reply, b"pong");
+    }
+}